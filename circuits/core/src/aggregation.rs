@@ -0,0 +1,117 @@
+use alloy_sol_types::private::FixedBytes;
+use alloy_sol_types::SolType;
+use serde::{Deserialize, Serialize};
+
+use crate::btc_light_client::RETARGET_INTERVAL;
+use crate::ProofPublicInputs;
+
+// Block proofs of consecutive header ranges, each range starts at the last block of the previous one
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AggregationInput {
+    // digest of the key the chunk proofs verify against, committed so the contract can check it
+    pub chunk_verification_key_digest: [u32; 8],
+    // ABI encoded ProofPublicInputs committed by each chunk proof, in chain order
+    pub chunk_public_inputs: Vec<Vec<u8>>,
+}
+
+pub fn verification_key_digest_to_bytes(digest: [u32; 8]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(digest) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    bytes
+}
+
+fn decode_chunk(encoded: &[u8]) -> ProofPublicInputs {
+    let chunk = ProofPublicInputs::abi_decode(encoded, true).expect("Invalid chunk public inputs");
+    // only the canonical encoding of a block proof is accepted
+    assert_eq!(
+        ProofPublicInputs::abi_encode(&chunk),
+        encoded,
+        "Invalid chunk public inputs"
+    );
+    assert!(!chunk.is_transaction_proof, "Chunk is not a block proof");
+    assert!(chunk.block_hashes.len() >= 2, "Chunk proves no blocks");
+    assert_eq!(
+        chunk.block_hashes.len(),
+        chunk.block_chainworks.len(),
+        "Chunk block hash and chainwork count mismatch"
+    );
+    chunk
+}
+
+// Stitches chunk block proofs into the public inputs of one block proof over the whole range
+pub fn aggregate_block_proofs(chunk_public_inputs: &[Vec<u8>]) -> ProofPublicInputs {
+    let chunks = chunk_public_inputs
+        .iter()
+        .map(|encoded| decode_chunk(encoded))
+        .collect::<Vec<_>>();
+    let first_chunk = chunks.first().expect("No chunks to aggregate");
+    let last_chunk = chunks.last().unwrap();
+
+    let safe_block_height = first_chunk.safe_block_height;
+    let mut block_hashes = first_chunk.block_hashes.clone();
+    let mut block_chainworks = first_chunk.block_chainworks.clone();
+
+    for chunk in &chunks[1..] {
+        // [1] verify the chunk starts at the last block proven so far
+        assert_eq!(
+            chunk.safe_block_height,
+            safe_block_height + block_hashes.len() as u64 - 1,
+            "Chunk safe block height mismatch"
+        );
+        assert_eq!(
+            chunk.block_hashes[0],
+            *block_hashes.last().unwrap(),
+            "Chunk safe block hash mismatch"
+        );
+        assert_eq!(
+            chunk.block_chainworks[0],
+            *block_chainworks.last().unwrap(),
+            "Chunk safe block chainwork mismatch"
+        );
+
+        // [2] verify the chunk's retarget block was proven by an earlier chunk, or is the initial one
+        let retarget_block_height =
+            chunk.safe_block_height - chunk.safe_block_height % RETARGET_INTERVAL;
+        let expected_retarget_block_hash = if retarget_block_height >= safe_block_height {
+            block_hashes[(retarget_block_height - safe_block_height) as usize]
+        } else {
+            first_chunk.retarget_block_hash
+        };
+        assert_eq!(
+            chunk.retarget_block_hash, expected_retarget_block_hash,
+            "Chunk retarget block hash mismatch"
+        );
+
+        // [3] verify every chunk was proven against the same network
+        assert_eq!(
+            chunk.chain_params_hash, first_chunk.chain_params_hash,
+            "Chunk chain params hash mismatch"
+        );
+
+        block_hashes.extend_from_slice(&chunk.block_hashes[1..]);
+        block_chainworks.extend_from_slice(&chunk.block_chainworks[1..]);
+    }
+
+    let confirmation_block_height_delta = last_chunk.confirmation_block_height_delta;
+    let safe_block_height_delta = (block_hashes.len() as u64 - 1)
+        .checked_sub(confirmation_block_height_delta)
+        .expect("Invalid confirmation block height delta");
+
+    ProofPublicInputs {
+        natural_txid: FixedBytes::ZERO,
+        merkle_root: FixedBytes::ZERO,
+        lp_reservation_hash: FixedBytes::ZERO,
+        order_nonce: FixedBytes::ZERO,
+        lp_count: 0,
+        retarget_block_hash: first_chunk.retarget_block_hash,
+        safe_block_height,
+        safe_block_height_delta,
+        confirmation_block_height_delta,
+        block_hashes,
+        block_chainworks,
+        is_transaction_proof: false,
+        chain_params_hash: first_chunk.chain_params_hash,
+    }
+}
@@ -1,8 +1,21 @@
 use crypto_bigint::CheckedAdd;
+use crypto_bigint::CheckedMul;
+use crypto_bigint::Encoding;
 use crypto_bigint::U256;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::chain_params::ChainParams;
+use crate::signet::{assert_signet_block_solution, SignetBlockSolution};
+
+pub const RETARGET_INTERVAL: u64 = 2016;
+// two weeks, the expected duration of a retarget period
+pub const TARGET_TIMESPAN: u64 = 14 * 24 * 60 * 60;
+// ten minutes, the expected time between blocks
+pub const TARGET_SPACING: u64 = 10 * 60;
+// number of previous blocks whose median timestamp a new block must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
 pub trait AsLittleEndianBytes {
     fn to_little_endian(self) -> Self;
 }
@@ -75,6 +88,169 @@ pub fn bits_to_target(bits: [u8; 4]) -> U256 {
     }
 }
 
+// inverse of bits_to_target, matches bitcoin core's arith_uint256::GetCompact
+pub fn target_to_bits(target: U256) -> [u8; 4] {
+    let target_bytes = target.to_be_bytes();
+    let mut size = target_bytes.iter().skip_while(|byte| **byte == 0).count() as u32;
+    let mut compact = if size <= 3 {
+        u32::from_be_bytes(target_bytes[28..].try_into().unwrap()) << (8 * (3 - size))
+    } else {
+        let start = 32 - size as usize;
+        u32::from_be_bytes([
+            0,
+            target_bytes[start],
+            target_bytes[start + 1],
+            target_bytes[start + 2],
+        ])
+    };
+    // the mantissa is signed, shift it down if the sign bit would be set
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    (compact | (size << 24)).to_le_bytes()
+}
+
+// taken from bitcoin core's CalculateNextWorkRequired
+pub fn compute_next_target(
+    previous_target: U256,
+    period_start_time: u32,
+    period_end_time: u32,
+    pow_limit: U256,
+) -> U256 {
+    let actual_timespan = (period_end_time as i64 - period_start_time as i64)
+        .clamp(TARGET_TIMESPAN as i64 / 4, TARGET_TIMESPAN as i64 * 4)
+        as u64;
+
+    let next_target = previous_target
+        .checked_mul(&U256::from_u64(actual_timespan))
+        .unwrap()
+        .checked_div(&U256::from_u64(TARGET_TIMESPAN))
+        .unwrap();
+
+    if next_target > pow_limit {
+        pow_limit
+    } else {
+        next_target
+    }
+}
+
+pub fn assert_difficulty_retarget(
+    period_start_block: &Block,
+    period_end_block: &Block,
+    retarget_block: &Block,
+    chain_params: &ChainParams,
+) {
+    // [1] verify the blocks bound the retarget period being closed
+    assert_eq!(
+        retarget_block.height % RETARGET_INTERVAL,
+        0,
+        "Retarget block is not at a retarget height"
+    );
+    assert_eq!(
+        period_start_block.height + RETARGET_INTERVAL,
+        retarget_block.height,
+        "Period start block is not the previous retarget block"
+    );
+    assert_eq!(
+        period_end_block.height + 1,
+        retarget_block.height,
+        "Period end block does not precede the retarget block"
+    );
+
+    // [2] verify the new bits match the retarget computed from the period timestamps
+    if chain_params.no_retargeting {
+        assert_eq!(
+            period_end_block.bits, retarget_block.bits,
+            "Retarget block bits do not match computed retarget"
+        );
+        return;
+    }
+
+    // bip94 retargets from the first block of the period, which is never a min difficulty block
+    let previous_bits = if chain_params.enforce_bip94 {
+        period_start_block.bits
+    } else {
+        period_end_block.bits
+    };
+    let next_target = compute_next_target(
+        bits_to_target(previous_bits),
+        u32::from_le_bytes(period_start_block.time),
+        u32::from_le_bytes(period_end_block.time),
+        U256::from_be_bytes(chain_params.pow_limit),
+    );
+    assert_eq!(
+        target_to_bits(next_target),
+        retarget_block.bits,
+        "Retarget block bits do not match computed retarget"
+    );
+
+    // [3] bip94 timewarp fix, the first block of a period can't be far behind the previous block
+    if chain_params.enforce_bip94 {
+        assert!(
+            u32::from_le_bytes(retarget_block.time) as u64 + TARGET_SPACING
+                >= u32::from_le_bytes(period_end_block.time) as u64,
+            "Retarget block timestamp too far behind previous block"
+        );
+    }
+}
+
+// Bits a non retarget block must have, matches bitcoin core's GetNextWorkRequired
+pub fn compute_expected_bits(
+    proposed_block: &Block,
+    previous_block_time: u32,
+    retarget_block: &Block,
+    chain_params: &ChainParams,
+) -> [u8; 4] {
+    // testnet rule, a block more than twice the target spacing after its parent may be mined at
+    // the pow limit, later blocks in the period go back to the retarget difficulty
+    if chain_params.allow_min_difficulty_blocks
+        && u32::from_le_bytes(proposed_block.time) as u64
+            > previous_block_time as u64 + TARGET_SPACING * 2
+    {
+        return target_to_bits(U256::from_be_bytes(chain_params.pow_limit));
+    }
+    retarget_block.bits
+}
+
+pub fn median_time_past(previous_block_times: &[u32]) -> u32 {
+    let mut sorted_times = previous_block_times.to_vec();
+    sorted_times.sort_unstable();
+    sorted_times[sorted_times.len() / 2]
+}
+
+// Asserts the ancestor blocks are the MEDIAN_TIME_SPAN - 1 blocks preceding the first block, or all
+// of them when it is closer to genesis, linked by hash. Returns the timestamps of the ancestors and
+// the first block
+fn assert_ancestor_blocks(first_block: &Block, ancestor_blocks: &[Block]) -> Vec<u32> {
+    assert_eq!(
+        ancestor_blocks.len() as u64,
+        (MEDIAN_TIME_SPAN as u64 - 1).min(first_block.height),
+        "Incorrect number of ancestor blocks"
+    );
+
+    let mut next_block = first_block;
+    for ancestor_block in ancestor_blocks.iter().rev() {
+        assert_eq!(
+            ancestor_block.height + 1,
+            next_block.height,
+            "Ancestor block height mismatch"
+        );
+        assert_eq!(
+            next_block.prev_blockhash.to_little_endian(),
+            ancestor_block.compute_block_hash(),
+            "Ancestor block hash mismatch"
+        );
+        next_block = ancestor_block;
+    }
+
+    ancestor_blocks
+        .iter()
+        .chain(std::iter::once(first_block))
+        .map(|block| u32::from_le_bytes(block.time))
+        .collect()
+}
+
 pub fn assert_pow(proposed_block_hash: &[u8; 32], proposed_block: &Block, proposed_target: U256) {
     let calculated_block_hash = proposed_block.compute_block_hash();
 
@@ -94,13 +270,34 @@ pub fn verify_block(
     proposed_block: &Block,
     retarget_block: &Block,
     previous_block_height: u64,
+    previous_block_times: &[u32],
+    chain_params: &ChainParams,
 ) {
     // [1] verify proposed target is equal to real target
     let proposed_target = bits_to_target(proposed_block.bits);
 
-    assert_eq!(
-        retarget_block.bits, proposed_block.bits,
-        "Proposed target does not match real target"
+    if proposed_block.height % RETARGET_INTERVAL == 0 {
+        // retargets are checked against the closed period by assert_difficulty_retarget
+        assert_eq!(
+            retarget_block.bits, proposed_block.bits,
+            "Proposed target does not match real target"
+        );
+    } else {
+        assert_eq!(
+            compute_expected_bits(
+                proposed_block,
+                *previous_block_times.last().unwrap(),
+                retarget_block,
+                chain_params,
+            ),
+            proposed_block.bits,
+            "Proposed target does not match real target"
+        );
+    }
+    assert!(
+        proposed_target != U256::ZERO
+            && proposed_target <= U256::from_be_bytes(chain_params.pow_limit),
+        "Proposed target out of range"
     );
 
     // [2] verify the proposed block height is one greater than previous_block_height
@@ -117,7 +314,13 @@ pub fn verify_block(
         "Proposed prev_block hash does not match real prev_block hash"
     );
 
-    // [4] verify PoW (double sha256(block_hash) <= target)
+    // [4] verify the timestamp is greater than the median time of the previous blocks
+    assert!(
+        u32::from_le_bytes(proposed_block.time) > median_time_past(previous_block_times),
+        "Block timestamp is not greater than median time past"
+    );
+
+    // [5] verify PoW (double sha256(block_hash) <= target)
     assert_pow(&proposed_block_hash, proposed_block, proposed_target);
 }
 
@@ -128,6 +331,9 @@ pub fn assert_blockchain(
     retarget_block_hash: [u8; 32],
     blocks: Vec<Block>,
     retarget_block: Block,
+    ancestor_blocks: Vec<Block>,
+    chain_params: &ChainParams,
+    signet_solutions: &[SignetBlockSolution],
 ) {
     assert_eq!(
         retarget_block.compute_block_hash(),
@@ -141,8 +347,28 @@ pub fn assert_blockchain(
         "Block count mismatch between commited block hashes and blocks provided"
     );
 
+    assert_eq!(
+        retarget_block.height,
+        safe_block_height - safe_block_height % RETARGET_INTERVAL,
+        "Initial retarget block height mismatch"
+    );
+
+    // signet blocks must also be signed by the network's block signers
+    let signet_solution_count = if chain_params.is_signet() {
+        blocks.len() - 1
+    } else {
+        0
+    };
+    assert_eq!(
+        signet_solutions.len(),
+        signet_solution_count,
+        "Signet solution count mismatch"
+    );
+
     let mut current_chainwork = *commited_block_chainworks.first().unwrap();
     let mut last_retarget_block = retarget_block;
+    // the first block is committed, so hash linked ancestors give the timestamps needed for MTP
+    let mut block_times = assert_ancestor_blocks(&blocks[0], &ancestor_blocks);
     // the first block in this array is a safe block aka known to the contract
     for i in 0..blocks.len() - 1 {
         let current_block = &blocks[i];
@@ -159,8 +385,14 @@ pub fn assert_blockchain(
             "Commited block hash mismatch"
         );
 
-        // Change retarget block if necessary
-        if next_block.height % 2016 == 0 {
+        // Verify the difficulty adjustment and change retarget block if necessary
+        if next_block.height % RETARGET_INTERVAL == 0 {
+            assert_difficulty_retarget(
+                &last_retarget_block,
+                current_block,
+                next_block,
+                chain_params,
+            );
             last_retarget_block = *next_block;
         }
 
@@ -179,7 +411,18 @@ pub fn assert_blockchain(
             next_block,
             &last_retarget_block,
             safe_block_height + i as u64,
+            // like bitcoin core, blocks near genesis take the median of every block before them
+            &block_times[block_times.len().saturating_sub(MEDIAN_TIME_SPAN)..],
+            chain_params,
         );
+        if let Some(signet_solution) = signet_solutions.get(i) {
+            assert_signet_block_solution(
+                next_block,
+                signet_solution,
+                chain_params.signet_challenge(),
+            );
+        }
+        block_times.push(u32::from_le_bytes(next_block.time));
     }
 
     assert_eq!(
@@ -0,0 +1,119 @@
+use crate::constants::MAX_SIGNET_CHALLENGE_SIZE;
+use crate::tx_hash::sha256_hash;
+use serde::{Deserialize, Serialize};
+
+// the default signet's 1-of-2 multisig block challenge
+pub const DEFAULT_SIGNET_CHALLENGE: [u8; 71] = [
+    0x51, 0x21, 0x03, 0xad, 0x5e, 0x0e, 0xda, 0xd1, 0x8c, 0xb1, 0xf0, 0xfc, 0x0d, 0x28, 0xa3, 0xd4,
+    0xf1, 0xf3, 0xe4, 0x45, 0x64, 0x03, 0x37, 0x48, 0x9a, 0xbb, 0x10, 0x40, 0x4f, 0x2d, 0x1e, 0x08,
+    0x6b, 0xe4, 0x30, 0x21, 0x03, 0x59, 0xef, 0x50, 0x21, 0x96, 0x4f, 0xe2, 0x2d, 0x6f, 0x8e, 0x05,
+    0xb2, 0x46, 0x3c, 0x95, 0x40, 0xce, 0x96, 0x88, 0x3f, 0xe3, 0xb2, 0x78, 0x76, 0x0f, 0x04, 0x8f,
+    0x51, 0x89, 0xf2, 0xe6, 0xc4, 0x52, 0xae,
+];
+
+// Consensus rules of the bitcoin network being proven, the hash of these params is committed in
+// the public values so the contract can pin the network it accepts
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainParams {
+    // big endian
+    pub pow_limit: [u8; 32],
+    // testnet rule, a block more than 20 minutes after its parent may be mined at the pow limit
+    pub allow_min_difficulty_blocks: bool,
+    pub no_retargeting: bool,
+    // testnet4 timewarp fix, retargets are computed from the first block of the period
+    pub enforce_bip94: bool,
+    // empty unless the network is a signet
+    #[serde(with = "crate::arrays")]
+    pub signet_challenge: [u8; MAX_SIGNET_CHALLENGE_SIZE],
+    pub signet_challenge_len: u64,
+}
+
+impl ChainParams {
+    pub fn mainnet() -> Self {
+        Self {
+            pow_limit: [
+                0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff, 0xff, 0xff,
+            ],
+            allow_min_difficulty_blocks: false,
+            no_retargeting: false,
+            enforce_bip94: false,
+            signet_challenge: [0u8; MAX_SIGNET_CHALLENGE_SIZE],
+            signet_challenge_len: 0,
+        }
+    }
+
+    pub fn testnet4() -> Self {
+        Self {
+            allow_min_difficulty_blocks: true,
+            enforce_bip94: true,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn signet(challenge: &[u8]) -> Self {
+        assert!(!challenge.is_empty(), "Signet challenge is empty");
+        assert!(
+            challenge.len() <= MAX_SIGNET_CHALLENGE_SIZE,
+            "Signet challenge too large"
+        );
+        let mut signet_challenge = [0u8; MAX_SIGNET_CHALLENGE_SIZE];
+        signet_challenge[..challenge.len()].copy_from_slice(challenge);
+
+        let mut pow_limit = [0u8; 32];
+        pow_limit[..5].copy_from_slice(&[0x00, 0x00, 0x03, 0x77, 0xae]);
+
+        Self {
+            pow_limit,
+            signet_challenge,
+            signet_challenge_len: challenge.len() as u64,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn default_signet() -> Self {
+        Self::signet(&DEFAULT_SIGNET_CHALLENGE)
+    }
+
+    pub fn regtest() -> Self {
+        let mut pow_limit = [0xffu8; 32];
+        pow_limit[0] = 0x7f;
+
+        Self {
+            pow_limit,
+            allow_min_difficulty_blocks: true,
+            no_retargeting: true,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn is_signet(&self) -> bool {
+        self.signet_challenge_len > 0
+    }
+
+    pub fn signet_challenge(&self) -> &[u8] {
+        &self.signet_challenge[..self.signet_challenge_len as usize]
+    }
+
+    // pow limit || allow min difficulty || no retargeting || enforce bip94 || signet challenge
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(32 + 3 + self.signet_challenge_len as usize);
+        encoded.extend_from_slice(&self.pow_limit);
+        encoded.push(self.allow_min_difficulty_blocks as u8);
+        encoded.push(self.no_retargeting as u8);
+        encoded.push(self.enforce_bip94 as u8);
+        encoded.extend_from_slice(self.signet_challenge());
+        encoded
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        sha256_hash(&self.encode())
+    }
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
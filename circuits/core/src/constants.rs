@@ -2,3 +2,5 @@ pub const MAX_BLOCKS: usize = 500;
 pub const MAX_LIQUIDITY_PROVIDERS: usize = 175;
 pub const MAX_MERKLE_PROOF_STEPS: usize = 32;
 pub const MAX_TX_SIZE: usize = 1024;
+pub const MAX_SCRIPT_PUB_KEY_SIZE: usize = 34;
+pub const MAX_SIGNET_CHALLENGE_SIZE: usize = 128;
@@ -1,15 +1,21 @@
+pub mod aggregation;
 pub mod btc_light_client;
+pub mod chain_params;
 pub mod constants;
 pub mod lp;
 pub mod payment;
 pub mod sha256_merkle;
+pub mod signet;
 pub mod tx_hash;
 
+use aggregation::AggregationInput;
 use alloy_sol_types::sol;
+use chain_params::ChainParams;
 use constants::{MAX_BLOCKS, MAX_LIQUIDITY_PROVIDERS, MAX_MERKLE_PROOF_STEPS, MAX_TX_SIZE};
 use crypto_bigint::U256;
 use serde::{Deserialize, Serialize};
 use sha256_merkle::MerkleProofStep;
+use signet::SignetBlockSolution;
 
 mod arrays {
     use std::{convert::TryInto, marker::PhantomData};
@@ -70,7 +76,7 @@ mod arrays {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CircuitPublicValues {
     pub natural_txid: [u8; 32],
     pub merkle_root: [u8; 32],
@@ -81,11 +87,11 @@ pub struct CircuitPublicValues {
     pub safe_block_height: u64,
     pub safe_block_height_delta: u64,
     pub confirmation_block_height_delta: u64,
-    #[serde(with = "arrays")]
-    pub block_hashes: [[u8; 32]; MAX_BLOCKS],
-    #[serde(with = "arrays")]
-    pub block_chainworks: [[u8; 32]; MAX_BLOCKS],
+    pub block_hashes: Vec<[u8; 32]>,
+    pub block_chainworks: Vec<[u8; 32]>,
     pub is_transaction_proof: bool,
+    // identifies the bitcoin network whose consensus rules the blocks were checked against
+    pub chain_params_hash: [u8; 32],
 }
 
 sol! {
@@ -103,6 +109,13 @@ sol! {
         bytes32[] block_hashes;
         uint256[] block_chainworks;
         bool is_transaction_proof;
+        bytes32 chain_params_hash;
+    }
+
+    /// Public values of a proof aggregating block proofs of consecutive header ranges.
+    struct AggregatedProofPublicInputs {
+        ProofPublicInputs public_inputs;
+        bytes32 chunk_verification_key_digest;
     }
 
 }
@@ -119,9 +132,10 @@ impl Default for CircuitPublicValues {
             safe_block_height: 0,
             safe_block_height_delta: 0,
             confirmation_block_height_delta: 0,
-            block_hashes: [[0u8; 32]; MAX_BLOCKS],
-            block_chainworks: [[0u8; 32]; MAX_BLOCKS],
+            block_hashes: Vec::new(),
+            block_chainworks: Vec::new(),
             is_transaction_proof: false,
+            chain_params_hash: ChainParams::default().hash(),
         }
     }
 }
@@ -140,15 +154,8 @@ impl CircuitPublicValues {
         block_hashes: Vec<[u8; 32]>,
         block_chainworks: Vec<[u8; 32]>,
         is_transaction_proof: bool,
+        chain_params_hash: [u8; 32],
     ) -> Self {
-        let mut padded_block_hashes = [[0u8; 32]; MAX_BLOCKS];
-        for (i, block_hash) in block_hashes.iter().enumerate() {
-            padded_block_hashes[i] = *block_hash;
-        }
-        let mut padded_block_chainworks = [[0u8; 32]; MAX_BLOCKS];
-        for (i, block_chainwork) in block_chainworks.iter().enumerate() {
-            padded_block_chainworks[i] = *block_chainwork;
-        }
         Self {
             natural_txid,
             merkle_root,
@@ -159,28 +166,32 @@ impl CircuitPublicValues {
             safe_block_height,
             safe_block_height_delta,
             confirmation_block_height_delta,
-            block_hashes: padded_block_hashes,
-            block_chainworks: padded_block_chainworks,
+            block_hashes,
+            block_chainworks,
             is_transaction_proof,
+            chain_params_hash,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CircuitInput {
     pub public_values: CircuitPublicValues,
-    #[serde(with = "arrays")]
-    pub txn_data_no_segwit: [u8; MAX_TX_SIZE],
-    pub utilized_txn_data_size: u64,
-    pub merkle_proof: [MerkleProofStep; MAX_MERKLE_PROOF_STEPS],
-    pub utilized_merkle_proof_steps: u64,
-    #[serde(with = "arrays")]
-    pub lp_reservation_data: [[[u8; 32]; 2]; MAX_LIQUIDITY_PROVIDERS],
-    pub utilized_lp_reservation_data: u64,
-    #[serde(with = "arrays")]
-    pub blocks: [btc_light_client::Block; MAX_BLOCKS],
-    pub utilized_blocks: u64,
+    // variable length fields are bounded by the limits in `constants`, asserted on validation
+    pub txn_data_no_segwit: Vec<u8>,
+    pub merkle_proof: Vec<MerkleProofStep>,
+    pub lp_reservation_data: Vec<[[u8; 32]; 3]>,
+    // index of the output paying each LP, in reservation order
+    pub lp_output_indices: Vec<u64>,
+    pub nonce_output_index: u64,
+    pub blocks: Vec<btc_light_client::Block>,
     pub retarget_block: btc_light_client::Block,
+    // blocks preceding the safe block, used for the median time past of the first proven blocks,
+    // MEDIAN_TIME_SPAN - 1 of them unless the safe block is closer to genesis
+    pub ancestor_blocks: Vec<btc_light_client::Block>,
+    pub chain_params: ChainParams,
+    // one per proven block after the safe block, empty unless the network is a signet
+    pub signet_solutions: Vec<SignetBlockSolution>,
 }
 
 impl CircuitInput {
@@ -188,41 +199,27 @@ impl CircuitInput {
         public_values: CircuitPublicValues,
         txn_data_no_segwit: Vec<u8>,
         merkle_proof: Vec<MerkleProofStep>,
-        lp_reservation_data: Vec<[[u8; 32]; 2]>,
+        lp_reservation_data: Vec<[[u8; 32]; 3]>,
+        lp_output_indices: Vec<u64>,
+        nonce_output_index: u64,
         blocks: Vec<btc_light_client::Block>,
         retarget_block: btc_light_client::Block,
+        ancestor_blocks: Vec<btc_light_client::Block>,
+        chain_params: ChainParams,
+        signet_solutions: Vec<SignetBlockSolution>,
     ) -> Self {
-        let mut padded_txn_data_no_segwit = [0u8; MAX_TX_SIZE];
-        for (i, byte) in txn_data_no_segwit.iter().enumerate() {
-            padded_txn_data_no_segwit[i] = *byte;
-        }
-
-        let mut padded_merkle_proof = [MerkleProofStep::default(); MAX_MERKLE_PROOF_STEPS];
-        for (i, step) in merkle_proof.iter().enumerate() {
-            padded_merkle_proof[i] = *step;
-        }
-
-        let mut padded_lp_reservation_data = [[[0u8; 32]; 2]; MAX_LIQUIDITY_PROVIDERS];
-        for (i, lp_data) in lp_reservation_data.iter().enumerate() {
-            padded_lp_reservation_data[i] = *lp_data;
-        }
-
-        let mut padded_blocks = [btc_light_client::Block::default(); MAX_BLOCKS];
-        for (i, block) in blocks.iter().enumerate() {
-            padded_blocks[i] = *block;
-        }
-
         Self {
             public_values,
-            txn_data_no_segwit: padded_txn_data_no_segwit,
-            utilized_txn_data_size: txn_data_no_segwit.len() as u64,
-            merkle_proof: padded_merkle_proof,
-            utilized_merkle_proof_steps: merkle_proof.len() as u64,
-            lp_reservation_data: padded_lp_reservation_data,
-            utilized_lp_reservation_data: lp_reservation_data.len() as u64,
-            blocks: padded_blocks,
-            utilized_blocks: blocks.len() as u64,
+            txn_data_no_segwit,
+            merkle_proof,
+            lp_reservation_data,
+            lp_output_indices,
+            nonce_output_index,
+            blocks,
             retarget_block,
+            ancestor_blocks,
+            chain_params,
+            signet_solutions,
         }
     }
 }
@@ -231,76 +228,126 @@ impl Default for CircuitInput {
     fn default() -> Self {
         Self {
             public_values: CircuitPublicValues::default(),
-            txn_data_no_segwit: [0u8; MAX_TX_SIZE],
-            utilized_txn_data_size: 0,
-            merkle_proof: [MerkleProofStep::default(); MAX_MERKLE_PROOF_STEPS],
-            utilized_merkle_proof_steps: 0,
-            lp_reservation_data: [[[0u8; 32]; 2]; MAX_LIQUIDITY_PROVIDERS],
-            utilized_lp_reservation_data: 0,
-            blocks: [btc_light_client::Block::default(); MAX_BLOCKS],
-            utilized_blocks: 0,
+            txn_data_no_segwit: Vec::new(),
+            merkle_proof: Vec::new(),
+            lp_reservation_data: Vec::new(),
+            lp_output_indices: Vec::new(),
+            nonce_output_index: 0,
+            blocks: Vec::new(),
             retarget_block: btc_light_client::Block::default(),
+            ancestor_blocks: Vec::new(),
+            chain_params: ChainParams::default(),
+            signet_solutions: Vec::new(),
         }
     }
 }
 
+// What the program proves, a rift transaction or block proof, or an aggregation of block proofs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ProgramInput {
+    Rift(Box<CircuitInput>),
+    BlockProofAggregation(AggregationInput),
+}
+
 pub fn validate_rift_transaction(circuit_input: CircuitInput) -> CircuitPublicValues {
-    let blocks = circuit_input.blocks[0..(circuit_input.utilized_blocks as usize)].to_vec();
-    let txn_data_no_segwit = circuit_input.txn_data_no_segwit
-        [0..(circuit_input.utilized_txn_data_size as usize)]
-        .to_vec();
-    let merkle_proof = circuit_input.merkle_proof
-        [0..(circuit_input.utilized_merkle_proof_steps as usize)]
-        .to_vec();
-    let lp_reservation_data = circuit_input.lp_reservation_data
-        [0..(circuit_input.utilized_lp_reservation_data as usize)]
-        .to_vec();
-    if circuit_input.public_values.is_transaction_proof {
+    // Input Bounds Verification
+    assert!(
+        circuit_input.txn_data_no_segwit.len() <= MAX_TX_SIZE,
+        "Transaction too large"
+    );
+    assert!(
+        circuit_input.merkle_proof.len() <= MAX_MERKLE_PROOF_STEPS,
+        "Too many merkle proof steps"
+    );
+    assert!(
+        circuit_input.lp_reservation_data.len() <= MAX_LIQUIDITY_PROVIDERS,
+        "Too many liquidity providers"
+    );
+    assert!(
+        circuit_input.lp_output_indices.len() <= MAX_LIQUIDITY_PROVIDERS,
+        "Too many LP output indices"
+    );
+    assert!(circuit_input.blocks.len() <= MAX_BLOCKS, "Too many blocks");
+    assert_eq!(
+        circuit_input.public_values.block_hashes.len(),
+        circuit_input.blocks.len(),
+        "Block hash count mismatch"
+    );
+    assert_eq!(
+        circuit_input.public_values.block_chainworks.len(),
+        circuit_input.blocks.len(),
+        "Block chainwork count mismatch"
+    );
+
+    let CircuitInput {
+        public_values,
+        txn_data_no_segwit,
+        merkle_proof,
+        lp_reservation_data,
+        lp_output_indices,
+        nonce_output_index,
+        blocks,
+        retarget_block,
+        ancestor_blocks,
+        chain_params,
+        signet_solutions,
+    } = circuit_input;
+
+    // Network Verification
+    assert_eq!(
+        chain_params.hash(),
+        public_values.chain_params_hash,
+        "Chain params hash mismatch"
+    );
+
+    if public_values.is_transaction_proof {
         let mut txid = tx_hash::get_natural_txid(&txn_data_no_segwit);
         txid.reverse();
 
         // Transaction Hash Verification
-        assert_eq!(
-            txid, circuit_input.public_values.natural_txid,
-            "Invalid transaction hash"
-        );
+        assert_eq!(txid, public_values.natural_txid, "Invalid transaction hash");
 
         // Transaction Inclusion Verification
         sha256_merkle::assert_merkle_proof_equality(
-            circuit_input.public_values.merkle_root,
-            circuit_input.public_values.natural_txid,
+            public_values.merkle_root,
+            public_values.natural_txid,
             &merkle_proof,
         );
 
         // LP Hash Verification
         lp::assert_lp_hash(
-            circuit_input.public_values.lp_reservation_hash,
+            public_values.lp_reservation_hash,
             &lp_reservation_data,
-            circuit_input.public_values.lp_count as u32,
+            public_values.lp_count as u32,
         );
 
         // Payment Verification
         payment::assert_bitcoin_payment(
             &txn_data_no_segwit,
             lp_reservation_data,
-            circuit_input.public_values.order_nonce,
-            circuit_input.public_values.lp_count,
+            public_values.order_nonce,
+            public_values.lp_count,
+            &lp_output_indices,
+            nonce_output_index,
         );
     }
 
     // Block Verification
     btc_light_client::assert_blockchain(
-        circuit_input.public_values.block_hashes[0..(blocks.len())].to_vec(),
-        circuit_input.public_values.block_chainworks[0..(blocks.len())]
-            .to_vec()
+        public_values.block_hashes.clone(),
+        public_values
+            .block_chainworks
             .iter()
             .map(|x| U256::from_be_slice(x))
             .collect(),
-        circuit_input.public_values.safe_block_height,
-        circuit_input.public_values.retarget_block_hash,
+        public_values.safe_block_height,
+        public_values.retarget_block_hash,
         blocks,
-        circuit_input.retarget_block,
+        retarget_block,
+        ancestor_blocks,
+        &chain_params,
+        &signet_solutions,
     );
 
-    circuit_input.public_values
+    public_values
 }
@@ -1,72 +1,108 @@
-use crate::constants::MAX_LIQUIDITY_PROVIDERS;
+use crate::constants::{MAX_LIQUIDITY_PROVIDERS, MAX_SCRIPT_PUB_KEY_SIZE};
 use sha2::{Digest, Sha256};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LiquidityReservation {
     pub expected_sats: u64,
-    pub script_pub_key: [u8; 22],
+    pub script_pub_key: Vec<u8>,
 }
 
-pub fn build_hashable_chunk(lp_data: [[u8; 32]; 2], intermediate_vault_hash: [u8; 32]) -> [u8; 96] {
-    let mut solidity_encoded_lp_data = [0u8; 96];
-    solidity_encoded_lp_data[0..64].copy_from_slice(&lp_data[0..2].concat());
+// Returns the length of a supported LP payout script, identified by its leading opcodes. The
+// exchange only accepts deposits paying out to these, see RiftExchange.isSupportedLockingScript:
+// P2WPKH: OP_0 <20 bytes>
+// P2WSH: OP_0 <32 bytes>
+// P2TR: OP_1 <32 bytes>
+// P2SH: OP_HASH160 <20 bytes> OP_EQUAL
+// P2PKH: OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+pub fn script_pub_key_len(script_pub_key: &[u8]) -> Option<usize> {
+    match script_pub_key {
+        [0x00, 0x14, ..] => Some(22),
+        [0x00, 0x20, ..] | [0x51, 0x20, ..] => Some(34),
+        [0xa9, 0x14, ..] => Some(23),
+        [0x76, 0xa9, 0x14, ..] => Some(25),
+        _ => None,
+    }
+}
+
+// Each LP is encoded as 3 words: [expected sats, script bytes 0..32, script bytes 32..64]
+// The last word is only hashed for scripts longer than 32 bytes, so scripts that fit in a single
+// word (P2WPKH, P2SH, P2PKH) hash identically to sha256(abi.encode(uint64, bytesN, bytes32)).
+// Mirrors RiftExchange.hashLiquidityProvider
+pub fn build_hashable_chunk(lp_data: [[u8; 32]; 3], intermediate_vault_hash: [u8; 32]) -> Vec<u8> {
+    let script_len = script_pub_key_len(&lp_data[1]).unwrap_or(0);
+    let script_words = if script_len > 32 { 2 } else { 1 };
+
+    let mut solidity_encoded_lp_data = Vec::with_capacity(32 * (script_words + 2));
+    solidity_encoded_lp_data.extend_from_slice(&lp_data[0..1 + script_words].concat());
     // Copy the last 32-byte chunk from the intermediate vault hash
-    solidity_encoded_lp_data[64..].copy_from_slice(&intermediate_vault_hash);
+    solidity_encoded_lp_data.extend_from_slice(&intermediate_vault_hash);
     solidity_encoded_lp_data
 }
 
 pub fn decode_liqudity_providers(
-    liquidity_providers_encoded: Vec<[[u8; 32]; 2]>,
-) -> [LiquidityReservation; MAX_LIQUIDITY_PROVIDERS] {
-    let mut liquidity_providers = [LiquidityReservation {
-        expected_sats: 0,
-        script_pub_key: [0; 22],
-    }; MAX_LIQUIDITY_PROVIDERS];
-
-    for i in 0..MAX_LIQUIDITY_PROVIDERS {
-        // Extract sats expected
-        liquidity_providers[i].expected_sats = u64::from_be_bytes(
-            liquidity_providers_encoded[i][0][32 - 8..]
-                .try_into()
-                .unwrap(),
-        );
+    liquidity_providers_encoded: &[[[u8; 32]; 3]],
+) -> Vec<LiquidityReservation> {
+    assert!(
+        liquidity_providers_encoded.len() <= MAX_LIQUIDITY_PROVIDERS,
+        "Too many liquidity providers"
+    );
 
-        // Extract script pub key
-        liquidity_providers[i]
-            .script_pub_key
-            .copy_from_slice(&liquidity_providers_encoded[i][1][0..22]);
-    }
+    liquidity_providers_encoded
+        .iter()
+        .map(|lp_data| {
+            // Extract sats expected
+            let expected_sats = u64::from_be_bytes(lp_data[0][32 - 8..].try_into().unwrap());
 
-    liquidity_providers
+            // Extract script pub key
+            let script_len =
+                script_pub_key_len(&lp_data[1]).expect("Unsupported LP script pub key");
+            let script_pub_key = [lp_data[1], lp_data[2]].concat()[..script_len].to_vec();
+
+            LiquidityReservation {
+                expected_sats,
+                script_pub_key,
+            }
+        })
+        .collect()
 }
 
 pub fn encode_liquidity_providers(
     liquidity_providers: &[LiquidityReservation],
-) -> [[[u8; 32]; 2]; MAX_LIQUIDITY_PROVIDERS] {
+) -> [[[u8; 32]; 3]; MAX_LIQUIDITY_PROVIDERS] {
     assert!(
         liquidity_providers.len() <= MAX_LIQUIDITY_PROVIDERS,
         "Too many liquidity providers"
     );
-    let mut liquidity_providers_encoded = [[[0u8; 32]; 2]; MAX_LIQUIDITY_PROVIDERS];
+    let mut liquidity_providers_encoded = [[[0u8; 32]; 3]; MAX_LIQUIDITY_PROVIDERS];
 
     for i in 0..liquidity_providers.len() {
+        let script_pub_key = &liquidity_providers[i].script_pub_key;
+        assert_eq!(
+            script_pub_key_len(script_pub_key),
+            Some(script_pub_key.len()),
+            "Unsupported LP script pub key"
+        );
+        assert!(script_pub_key.len() <= MAX_SCRIPT_PUB_KEY_SIZE);
+
         // Encode sats expected
         liquidity_providers_encoded[i][0][32 - 8..]
             .copy_from_slice(&liquidity_providers[i].expected_sats.to_be_bytes());
 
         // Encode script pub key
-        liquidity_providers_encoded[i][1][0..22]
-            .copy_from_slice(&liquidity_providers[i].script_pub_key);
+        let mut padded_script_pub_key = [0u8; 64];
+        padded_script_pub_key[..script_pub_key.len()].copy_from_slice(script_pub_key);
+        liquidity_providers_encoded[i][1].copy_from_slice(&padded_script_pub_key[..32]);
+        liquidity_providers_encoded[i][2].copy_from_slice(&padded_script_pub_key[32..]);
     }
 
     liquidity_providers_encoded[liquidity_providers.len()..]
         .iter_mut()
-        .for_each(|lp| *lp = [[0u8; 32]; 2]);
+        .for_each(|lp| *lp = [[0u8; 32]; 3]);
 
     liquidity_providers_encoded
 }
 
-pub fn compute_lp_hash(lp_reservation_data_encoded: &[[[u8; 32]; 2]], lp_count: u32) -> [u8; 32] {
+pub fn compute_lp_hash(lp_reservation_data_encoded: &[[[u8; 32]; 3]], lp_count: u32) -> [u8; 32] {
     assert!(
         lp_reservation_data_encoded.len() <= MAX_LIQUIDITY_PROVIDERS,
         "Too many liquidity providers"
@@ -83,7 +119,7 @@ pub fn compute_lp_hash(lp_reservation_data_encoded: &[[[u8; 32]; 2]], lp_count:
 
 pub fn assert_lp_hash(
     lp_reservation_hash: [u8; 32],
-    lp_reservation_data_encoded: &[[[u8; 32]; 2]],
+    lp_reservation_data_encoded: &[[[u8; 32]; 3]],
     lp_count: u32,
 ) {
     assert!(
@@ -1,11 +1,10 @@
 use crate::{
-    constants::MAX_LIQUIDITY_PROVIDERS,
+    constants::{MAX_LIQUIDITY_PROVIDERS, MAX_SCRIPT_PUB_KEY_SIZE, MAX_TX_SIZE},
     lp::{decode_liqudity_providers, LiquidityReservation},
 };
 
 // Constants
 //const MAX_SCRIPTSIG_SIZE: u64 = 22;
-const MAX_INPUT_COUNT: u64 = 1;
 //const MAX_SCRIPT_INSCRPITION_SIZE: u64 = 80;
 //const VERSION_LEN: u8 = 4;
 const TXID_LEN: u8 = 32;
@@ -15,6 +14,12 @@ const AMOUNT_LEN: u8 = 8;
 const OP_RETURN_CODE: u8 = 0x6a;
 const OP_PUSHBYTES_32: u8 = 0x20;
 //const DATA_LEN: u8 = 80;
+// smallest possible input: txid + vout + empty script sig + sequence
+const MIN_INPUT_LEN: u64 = (TXID_LEN + VOUT_LEN + 1 + SEQUENCE_LEN) as u64;
+pub const MAX_INPUT_COUNT: u64 = MAX_TX_SIZE as u64 / MIN_INPUT_LEN;
+// smallest possible output: amount + empty script pub key
+const MIN_OUTPUT_LEN: u64 = (AMOUNT_LEN + 1) as u64;
+pub const MAX_OUTPUT_COUNT: u64 = MAX_TX_SIZE as u64 / MIN_OUTPUT_LEN;
 
 // Structs
 /*
@@ -25,6 +30,12 @@ struct TxOut {
 }
 */
 
+struct TxOutPointer {
+    value: u64,
+    script_pointer: u64,
+    script_len: u64,
+}
+
 // Helper functions
 fn to_int<const N: usize>(bytes: [u8; N]) -> u64 {
     bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64)
@@ -61,66 +72,90 @@ fn extract_int_from_compint_pointer(data_pointer: u64, txn_data: &[u8]) -> (u64,
 
 fn assert_payment_utxos_exist(
     txn_data: &[u8],
-    reserved_liquidity_providers: &[LiquidityReservation; MAX_LIQUIDITY_PROVIDERS],
-    lp_count: u64,
+    reserved_liquidity_providers: &[LiquidityReservation],
     order_nonce: [u8; 32],
+    lp_output_indices: &[u64],
+    nonce_output_index: u64,
 ) {
+    let lp_count = reserved_liquidity_providers.len() as u64;
+    assert_eq!(lp_output_indices.len() as u64, lp_count);
     let mut data_pointer = 4;
     let (input_counter, input_counter_byte_len) =
         extract_int_from_compint_pointer(data_pointer, txn_data);
     data_pointer += input_counter_byte_len as u64;
-    assert_eq!(input_counter, MAX_INPUT_COUNT);
+    assert!(input_counter > 0);
+    assert!(input_counter <= MAX_INPUT_COUNT);
 
     // Skip inputs
-    for _ in 0..MAX_INPUT_COUNT {
+    for _ in 0..input_counter {
         data_pointer += (TXID_LEN + VOUT_LEN) as u64;
         let (sig_counter, sig_counter_byte_len) =
             extract_int_from_compint_pointer(data_pointer, txn_data);
         data_pointer += sig_counter + sig_counter_byte_len as u64 + SEQUENCE_LEN as u64;
+        assert!(data_pointer < txn_data.len() as u64);
     }
 
     let (output_counter, output_counter_byte_len) =
         extract_int_from_compint_pointer(data_pointer, txn_data);
-    assert!(output_counter <= MAX_LIQUIDITY_PROVIDERS as u64);
+    assert!(output_counter <= MAX_OUTPUT_COUNT);
     assert!(lp_count < output_counter);
     data_pointer += output_counter_byte_len as u64;
 
-    for (i, _lp) in reserved_liquidity_providers
+    // Locate every output, LP and nonce outputs can appear in any order
+    let mut outputs = Vec::with_capacity(output_counter as usize);
+    for _ in 0..output_counter {
+        let value = to_int::<8>(grab_bytes_le::<8>(&txn_data[data_pointer as usize..]));
+        data_pointer += AMOUNT_LEN as u64;
+        let (sig_counter, sig_counter_byte_len) =
+            extract_int_from_compint_pointer(data_pointer, txn_data);
+        data_pointer += sig_counter_byte_len as u64;
+        outputs.push(TxOutPointer {
+            value,
+            script_pointer: data_pointer,
+            script_len: sig_counter,
+        });
+        data_pointer += sig_counter;
+        assert!(data_pointer <= txn_data.len() as u64);
+    }
+
+    // Each output can only be claimed once, otherwise a single output could pay several LPs
+    let mut claimed_outputs = vec![false; output_counter as usize];
+    for output_index in lp_output_indices
         .iter()
-        .enumerate()
-        .take(MAX_LIQUIDITY_PROVIDERS)
+        .chain(std::iter::once(&nonce_output_index))
     {
-        if i < lp_count as usize {
-            let value = to_int::<8>(grab_bytes_le::<8>(&txn_data[data_pointer as usize..]));
-            data_pointer += AMOUNT_LEN as u64;
-            let (sig_counter, sig_counter_byte_len) =
-                extract_int_from_compint_pointer(data_pointer, txn_data);
-            data_pointer += sig_counter_byte_len as u64;
-
-            assert_eq!(sig_counter, 22);
+        assert!(*output_index < output_counter);
+        assert!(!claimed_outputs[*output_index as usize]);
+        claimed_outputs[*output_index as usize] = true;
+    }
 
-            let locking_script =
-                grab_bytes_be_conditional::<22>(txn_data, data_pointer, |i| i < sig_counter);
+    for (lp, output_index) in reserved_liquidity_providers
+        .iter()
+        .zip(lp_output_indices.iter())
+        .take(MAX_LIQUIDITY_PROVIDERS)
+    {
+        let output = &outputs[*output_index as usize];
 
-            let expected_sats = reserved_liquidity_providers[i].expected_sats;
+        assert_eq!(output.script_len, lp.script_pub_key.len() as u64);
 
-            assert_eq!(value, expected_sats);
+        let locking_script = grab_bytes_be_conditional::<MAX_SCRIPT_PUB_KEY_SIZE>(
+            txn_data,
+            output.script_pointer,
+            |i| i < output.script_len,
+        );
 
-            assert_eq!(
-                locking_script,
-                reserved_liquidity_providers[i].script_pub_key
-            );
+        assert_eq!(output.value, lp.expected_sats);
 
-            data_pointer += sig_counter;
-        }
+        assert_eq!(
+            &locking_script[..output.script_len as usize],
+            lp.script_pub_key.as_slice()
+        );
     }
 
-    data_pointer += AMOUNT_LEN as u64;
-    let (sig_counter, sig_counter_byte_len) =
-        extract_int_from_compint_pointer(data_pointer, txn_data);
-    data_pointer += sig_counter_byte_len as u64;
+    let nonce_output = &outputs[nonce_output_index as usize];
+    let mut data_pointer = nonce_output.script_pointer;
 
-    assert_eq!(sig_counter, 34);
+    assert_eq!(nonce_output.script_len, 34);
 
     assert_eq!(txn_data[data_pointer as usize], OP_RETURN_CODE);
     data_pointer += 1;
@@ -128,23 +163,28 @@ fn assert_payment_utxos_exist(
     data_pointer += 1;
 
     let inscribed_order_nonce =
-        grab_bytes_be_conditional::<32>(txn_data, data_pointer, |i| i < sig_counter);
+        grab_bytes_be_conditional::<32>(txn_data, data_pointer, |i| i < nonce_output.script_len);
     assert_eq!(inscribed_order_nonce, order_nonce);
 }
 
 pub fn assert_bitcoin_payment(
     txn_data_no_segwit: &[u8],
-    lp_reservation_data_encoded: Vec<[[u8; 32]; 2]>,
+    lp_reservation_data_encoded: Vec<[[u8; 32]; 3]>,
     order_nonce: [u8; 32],
     lp_count: u64,
+    lp_output_indices: &[u64],
+    nonce_output_index: u64,
 ) {
     assert!(lp_reservation_data_encoded.len() <= MAX_LIQUIDITY_PROVIDERS);
-    let liquidity_providers = decode_liqudity_providers(lp_reservation_data_encoded);
+    assert!(lp_count <= lp_reservation_data_encoded.len() as u64);
+    let liquidity_providers =
+        decode_liqudity_providers(&lp_reservation_data_encoded[..lp_count as usize]);
     assert_payment_utxos_exist(
         txn_data_no_segwit,
         &liquidity_providers,
-        lp_count,
         order_nonce,
+        &lp_output_indices[..lp_count as usize],
+        nonce_output_index,
     );
 }
 
@@ -0,0 +1,434 @@
+use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::btc_light_client::Block;
+use crate::tx_hash::get_natural_txid;
+
+const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+const MIN_WITNESS_COMMITMENT_LEN: usize = 38;
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_CHECKMULTISIG: u8 = 0xae;
+const SIGHASH_ALL: u8 = 0x01;
+
+// Private data needed to check the block solution of a signet block (BIP325)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SignetBlockSolution {
+    // coinbase transaction serialized without witness data
+    pub coinbase_txn: Vec<u8>,
+    // sibling hashes from the coinbase up to the merkle root, natural byte order
+    pub coinbase_merkle_branch: Vec<[u8; 32]>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pointer: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pointer: 0 }
+    }
+
+    fn read(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.data[self.pointer..self.pointer + len];
+        self.pointer += len;
+        bytes
+    }
+
+    fn read_compact_size(&mut self) -> u64 {
+        match self.read(1)[0] {
+            0xfd => u16::from_le_bytes(self.read(2).try_into().unwrap()) as u64,
+            0xfe => u32::from_le_bytes(self.read(4).try_into().unwrap()) as u64,
+            0xff => u64::from_le_bytes(self.read(8).try_into().unwrap()),
+            byte => byte as u64,
+        }
+    }
+
+    fn read_var_bytes(&mut self) -> &'a [u8] {
+        let len = self.read_compact_size() as usize;
+        self.read(len)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pointer == self.data.len()
+    }
+}
+
+fn write_compact_size(buffer: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => buffer.push(value as u8),
+        0xfd..=0xffff => {
+            buffer.push(0xfd);
+            buffer.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            buffer.push(0xfe);
+            buffer.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            buffer.push(0xff);
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+fn write_var_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    write_compact_size(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+// Minimal push encoding, matches bitcoin core's CScript << std::vector<unsigned char>
+fn write_push(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        0..=0x4b => script.push(data.len() as u8),
+        0x4c..=0xff => script.extend_from_slice(&[OP_PUSHDATA1, data.len() as u8]),
+        0x100..=0xffff => {
+            script.push(OP_PUSHDATA2);
+            script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        }
+        _ => {
+            script.push(OP_PUSHDATA4);
+            script.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+// Returns the opcode and any data it pushes, None at the end of the script or on a truncated push
+fn next_script_op<'a>(script: &'a [u8], pointer: &mut usize) -> Option<(u8, &'a [u8])> {
+    let opcode = *script.get(*pointer)?;
+    *pointer += 1;
+    let push_len = match opcode {
+        0x01..=0x4b => opcode as usize,
+        OP_PUSHDATA1 => {
+            let len = *script.get(*pointer)? as usize;
+            *pointer += 1;
+            len
+        }
+        OP_PUSHDATA2 => {
+            let len = u16::from_le_bytes(script.get(*pointer..*pointer + 2)?.try_into().unwrap());
+            *pointer += 2;
+            len as usize
+        }
+        OP_PUSHDATA4 => {
+            let len = u32::from_le_bytes(script.get(*pointer..*pointer + 4)?.try_into().unwrap());
+            *pointer += 4;
+            len as usize
+        }
+        _ => 0,
+    };
+    let push_data = script.get(*pointer..*pointer + push_len)?;
+    *pointer += push_len;
+    Some((opcode, push_data))
+}
+
+// Data pushed by a push only script, panics on any other opcode
+fn parse_push_only_script(script: &[u8]) -> Vec<&[u8]> {
+    let mut pointer = 0;
+    let mut pushes = Vec::new();
+    while pointer < script.len() {
+        let (opcode, push_data) =
+            next_script_op(script, &mut pointer).expect("Malformed signet solution script");
+        assert!(
+            opcode <= OP_PUSHDATA4,
+            "Signet solution script is not push only"
+        );
+        pushes.push(push_data);
+    }
+    pushes
+}
+
+struct CoinbaseTransaction<'a> {
+    // version and the single coinbase input
+    prefix: &'a [u8],
+    outputs: Vec<(&'a [u8], Vec<u8>)>,
+    lock_time: &'a [u8],
+}
+
+impl<'a> CoinbaseTransaction<'a> {
+    fn parse(txn_data: &'a [u8]) -> Self {
+        let mut reader = Reader::new(txn_data);
+        reader.read(4);
+        assert_eq!(
+            reader.read_compact_size(),
+            1,
+            "Coinbase must have one input"
+        );
+        let prevout = reader.read(36);
+        assert!(
+            prevout[..32] == [0u8; 32] && prevout[32..] == [0xff; 4],
+            "Coinbase input must spend the null outpoint"
+        );
+        reader.read_var_bytes();
+        reader.read(4);
+        let prefix = &txn_data[..reader.pointer];
+
+        let output_count = reader.read_compact_size();
+        let outputs = (0..output_count)
+            .map(|_| (reader.read(8), reader.read_var_bytes().to_vec()))
+            .collect();
+        let lock_time = reader.read(4);
+        assert!(
+            reader.is_empty(),
+            "Unexpected data after coinbase transaction"
+        );
+
+        Self {
+            prefix,
+            outputs,
+            lock_time,
+        }
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut txn_data = self.prefix.to_vec();
+        write_compact_size(&mut txn_data, self.outputs.len() as u64);
+        for (value, script_pub_key) in &self.outputs {
+            txn_data.extend_from_slice(value);
+            write_var_bytes(&mut txn_data, script_pub_key);
+        }
+        txn_data.extend_from_slice(self.lock_time);
+        txn_data
+    }
+
+    // matches bitcoin core's GetWitnessCommitmentIndex, the last matching output wins
+    fn witness_commitment_index(&self) -> Option<usize> {
+        self.outputs.iter().rposition(|(_, script_pub_key)| {
+            script_pub_key.len() >= MIN_WITNESS_COMMITMENT_LEN
+                && script_pub_key[..WITNESS_COMMITMENT_HEADER.len()] == WITNESS_COMMITMENT_HEADER
+        })
+    }
+}
+
+// Removes the signet solution from the witness commitment script, leaving the bare header push.
+// Returns the solution if one was present, matches bitcoin core's FetchAndClearCommitmentSection
+fn fetch_and_clear_signet_solution(witness_commitment: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let mut replacement = Vec::with_capacity(witness_commitment.len());
+    let mut solution = None;
+    let mut pointer = 0;
+    while let Some((opcode, push_data)) = next_script_op(witness_commitment, &mut pointer) {
+        if push_data.is_empty() {
+            replacement.push(opcode);
+        } else if solution.is_none()
+            && push_data.len() > SIGNET_HEADER.len()
+            && push_data[..SIGNET_HEADER.len()] == SIGNET_HEADER
+        {
+            solution = Some(push_data[SIGNET_HEADER.len()..].to_vec());
+            write_push(&mut replacement, &SIGNET_HEADER);
+        } else {
+            write_push(&mut replacement, push_data);
+        }
+    }
+
+    if solution.is_some() {
+        *witness_commitment = replacement;
+    }
+    solution
+}
+
+fn compute_merkle_root_from_branch(leaf: [u8; 32], branch: &[[u8; 32]]) -> [u8; 32] {
+    // the coinbase is always the leftmost leaf
+    branch.iter().fold(leaf, |node, sibling| {
+        get_natural_txid(&[node, *sibling].concat())
+    })
+}
+
+// The virtual transaction committing to the block, spent by the signet solution
+fn serialize_to_spend_txn(block_data: &[u8], challenge: &[u8]) -> Vec<u8> {
+    let mut script_sig = vec![OP_0];
+    write_push(&mut script_sig, block_data);
+
+    let mut txn_data = Vec::new();
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    write_compact_size(&mut txn_data, 1);
+    txn_data.extend_from_slice(&[0u8; 32]);
+    txn_data.extend_from_slice(&u32::MAX.to_le_bytes());
+    write_var_bytes(&mut txn_data, &script_sig);
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    write_compact_size(&mut txn_data, 1);
+    txn_data.extend_from_slice(&0u64.to_le_bytes());
+    write_var_bytes(&mut txn_data, challenge);
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    txn_data
+}
+
+// Legacy SIGHASH_ALL digest of the virtual spending transaction, with the challenge as script code
+fn compute_to_sign_sighash(to_spend_txid: [u8; 32], challenge: &[u8]) -> [u8; 32] {
+    let mut txn_data = Vec::new();
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    write_compact_size(&mut txn_data, 1);
+    txn_data.extend_from_slice(&to_spend_txid);
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    write_var_bytes(&mut txn_data, challenge);
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    write_compact_size(&mut txn_data, 1);
+    txn_data.extend_from_slice(&0u64.to_le_bytes());
+    write_var_bytes(&mut txn_data, &[OP_RETURN]);
+    txn_data.extend_from_slice(&0u32.to_le_bytes());
+    txn_data.extend_from_slice(&(SIGHASH_ALL as u32).to_le_bytes());
+    get_natural_txid(&txn_data)
+}
+
+// Only SIGHASH_ALL signatures are accepted, which is what signet miners produce
+fn verify_signature(signature: &[u8], public_key: &[u8], sighash: &[u8; 32]) -> bool {
+    let Some((hash_type, der_signature)) = signature.split_last() else {
+        return false;
+    };
+    if *hash_type != SIGHASH_ALL {
+        return false;
+    }
+    let Ok(signature) = Signature::from_der(der_signature) else {
+        return false;
+    };
+    // low s is policy, not consensus
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let Ok(verifying_key) = VerifyingKey::from_sec1_bytes(public_key) else {
+        return false;
+    };
+    verifying_key.verify_prehash(sighash, &signature).is_ok()
+}
+
+// Supports the challenge scripts signets use in practice: OP_TRUE, <pubkey> OP_CHECKSIG and bare
+// m-of-n OP_CHECKMULTISIG
+fn assert_challenge_satisfied(
+    challenge: &[u8],
+    script_sig: &[u8],
+    witness: &[Vec<u8>],
+    sighash: &[u8; 32],
+) {
+    assert!(witness.is_empty(), "Unexpected signet solution witness");
+    let script_sig_pushes = parse_push_only_script(script_sig);
+
+    if challenge == [OP_1] {
+        return;
+    }
+
+    if challenge.last() == Some(&OP_CHECKSIG) {
+        let mut pointer = 0;
+        let (_, public_key) =
+            next_script_op(challenge, &mut pointer).expect("Unsupported signet challenge");
+        assert_eq!(pointer, challenge.len() - 1, "Unsupported signet challenge");
+        assert_eq!(script_sig_pushes.len(), 1, "Invalid signet solution");
+        assert!(
+            verify_signature(script_sig_pushes[0], public_key, sighash),
+            "Invalid signet block signature"
+        );
+        return;
+    }
+
+    assert_eq!(
+        challenge.last(),
+        Some(&OP_CHECKMULTISIG),
+        "Unsupported signet challenge"
+    );
+    let mut pointer = 0;
+    let mut ops = Vec::new();
+    while pointer < challenge.len() - 1 {
+        ops.push(next_script_op(challenge, &mut pointer).expect("Unsupported signet challenge"));
+    }
+    assert!(ops.len() >= 3, "Unsupported signet challenge");
+    let (required_op, _) = ops[0];
+    let (key_count_op, _) = ops[ops.len() - 1];
+    assert!(
+        (OP_1..=OP_16).contains(&required_op) && (OP_1..=OP_16).contains(&key_count_op),
+        "Unsupported signet challenge"
+    );
+    let required_signatures = (required_op - OP_1 + 1) as usize;
+    let public_keys = ops[1..ops.len() - 1]
+        .iter()
+        .map(|(_, public_key)| *public_key)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        public_keys.len(),
+        (key_count_op - OP_1 + 1) as usize,
+        "Unsupported signet challenge"
+    );
+    assert!(
+        required_signatures <= public_keys.len(),
+        "Unsupported signet challenge"
+    );
+
+    // NULLDUMMY, the extra element consumed by OP_CHECKMULTISIG must be empty
+    assert_eq!(
+        script_sig_pushes.len(),
+        required_signatures + 1,
+        "Invalid signet solution"
+    );
+    assert!(script_sig_pushes[0].is_empty(), "Invalid signet solution");
+
+    // signatures must appear in the same order as their public keys
+    let mut key_index = 0;
+    for signature in &script_sig_pushes[1..] {
+        loop {
+            assert!(
+                key_index < public_keys.len(),
+                "Invalid signet block signature"
+            );
+            key_index += 1;
+            if verify_signature(signature, public_keys[key_index - 1], sighash) {
+                break;
+            }
+        }
+    }
+}
+
+pub fn assert_signet_block_solution(
+    block: &Block,
+    solution: &SignetBlockSolution,
+    challenge: &[u8],
+) {
+    // [1] verify the coinbase is committed to by the block
+    let coinbase_txid = get_natural_txid(&solution.coinbase_txn);
+    assert_eq!(
+        compute_merkle_root_from_branch(coinbase_txid, &solution.coinbase_merkle_branch),
+        block.merkle_root,
+        "Signet coinbase not in block"
+    );
+
+    // [2] extract the solution, the merkle root the signer committed to excludes it
+    let mut coinbase = CoinbaseTransaction::parse(&solution.coinbase_txn);
+    let commitment_index = coinbase
+        .witness_commitment_index()
+        .expect("Signet block missing witness commitment");
+    let signet_solution =
+        fetch_and_clear_signet_solution(&mut coinbase.outputs[commitment_index].1);
+
+    let mut script_sig: &[u8] = &[];
+    let mut witness = Vec::new();
+    if let Some(signet_solution) = &signet_solution {
+        let mut reader = Reader::new(signet_solution);
+        script_sig = reader.read_var_bytes();
+        let witness_len = reader.read_compact_size();
+        witness = (0..witness_len)
+            .map(|_| reader.read_var_bytes().to_vec())
+            .collect();
+        assert!(reader.is_empty(), "Unexpected data after signet solution");
+    }
+
+    let signet_merkle_root = compute_merkle_root_from_branch(
+        get_natural_txid(&coinbase.serialize()),
+        &solution.coinbase_merkle_branch,
+    );
+
+    // [3] verify the solution satisfies the challenge over the virtual signet transactions
+    let block_data = [
+        &block.version[..],
+        &block.prev_blockhash,
+        &signet_merkle_root,
+        &block.time,
+    ]
+    .concat();
+    let to_spend_txid = get_natural_txid(&serialize_to_spend_txn(&block_data, challenge));
+    let sighash = compute_to_sign_sighash(to_spend_txid, challenge);
+
+    assert_challenge_satisfied(challenge, script_sig, &witness, &sighash);
+}
@@ -1,3 +1,4 @@
+pub mod payment;
 pub mod proof;
 pub mod transaction;
 
@@ -6,8 +7,13 @@ use bitcoin::hashes::hex::FromHex;
 use bitcoin::hashes::Hash;
 use std::fmt::Write;
 
-use rift_core::btc_light_client::Block as RiftOptimizedBlock;
+use rift_core::btc_light_client::{
+    AsLittleEndianBytes, Block as RiftOptimizedBlock, RETARGET_INTERVAL,
+};
 use rift_core::sha256_merkle::{hash_pairs, MerkleProofStep};
+use rift_core::signet::SignetBlockSolution;
+
+use crate::transaction::serialize_no_segwit;
 
 pub fn load_hex_bytes(file: &str) -> Vec<u8> {
     let hex_string = std::fs::read_to_string(file).expect("Failed to read file");
@@ -23,7 +29,7 @@ pub fn to_hex_string(bytes: &[u8]) -> String {
 }
 
 pub fn get_retarget_height_from_block_height(block_height: u64) -> u64 {
-    block_height - (block_height % 2016)
+    block_height - (block_height % RETARGET_INTERVAL)
 }
 
 // Expects leaves to be in little-endian format (as shown on explorers)
@@ -86,26 +92,46 @@ pub trait AsRiftOptimizedBlock {
 
 impl AsRiftOptimizedBlock for bitcoin::Block {
     fn as_rift_optimized_block(&self, height: u64) -> RiftOptimizedBlock {
-        RiftOptimizedBlock {
-            height,
-            version: self.header.version.to_consensus().to_le_bytes(),
-            prev_blockhash: self.header.prev_blockhash.to_raw_hash().to_byte_array(),
-            merkle_root: self.header.merkle_root.to_raw_hash().to_byte_array(),
-            time: self.header.time.to_le_bytes(),
-            bits: self.header.bits.to_consensus().to_le_bytes(),
-            nonce: self.header.nonce.to_le_bytes(),
-        }
+        header_as_rift_optimized_block(&self.header, height)
     }
 
     fn as_rift_optimized_block_unsafe(&self) -> RiftOptimizedBlock {
-        RiftOptimizedBlock {
-            height: self.bip34_block_height().unwrap(),
-            version: self.header.version.to_consensus().to_le_bytes(),
-            prev_blockhash: self.header.prev_blockhash.to_raw_hash().to_byte_array(),
-            merkle_root: self.header.merkle_root.to_raw_hash().to_byte_array(),
-            time: self.header.time.to_le_bytes(),
-            bits: self.header.bits.to_consensus().to_le_bytes(),
-            nonce: self.header.nonce.to_le_bytes(),
-        }
+        header_as_rift_optimized_block(&self.header, self.bip34_block_height().unwrap())
+    }
+}
+
+pub fn header_as_rift_optimized_block(
+    header: &bitcoin::block::Header,
+    height: u64,
+) -> RiftOptimizedBlock {
+    RiftOptimizedBlock {
+        height,
+        version: header.version.to_consensus().to_le_bytes(),
+        prev_blockhash: header.prev_blockhash.to_raw_hash().to_byte_array(),
+        merkle_root: header.merkle_root.to_raw_hash().to_byte_array(),
+        time: header.time.to_le_bytes(),
+        bits: header.bits.to_consensus().to_le_bytes(),
+        nonce: header.nonce.to_le_bytes(),
+    }
+}
+
+// Coinbase and its merkle branch, which the circuit needs to check a signet block's signature
+pub fn build_signet_block_solution(block: &bitcoin::Block) -> SignetBlockSolution {
+    let coinbase = block.txdata.first().expect("Block has no coinbase");
+    let (merkle_proof, _) = generate_merkle_proof_and_root(
+        block
+            .txdata
+            .iter()
+            .map(|tx| tx.compute_txid().to_byte_array().to_little_endian())
+            .collect(),
+        coinbase.compute_txid().to_byte_array().to_little_endian(),
+    );
+
+    SignetBlockSolution {
+        coinbase_txn: serialize_no_segwit(coinbase),
+        coinbase_merkle_branch: merkle_proof
+            .iter()
+            .map(|step| step.hash.to_little_endian())
+            .collect(),
     }
 }
@@ -0,0 +1,163 @@
+use bitcoin::opcodes::all::OP_RETURN;
+use bitcoin::script::Builder;
+use bitcoin::Transaction;
+use rift_core::constants::{MAX_LIQUIDITY_PROVIDERS, MAX_TX_SIZE};
+use rift_core::lp::{script_pub_key_len, LiquidityReservation};
+use rift_core::payment::{MAX_INPUT_COUNT, MAX_OUTPUT_COUNT};
+use thiserror::Error;
+
+use crate::transaction::serialize_no_segwit;
+
+// Every way a payment can fail the checks `rift_core::payment` asserts in the circuit
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PaymentError {
+    #[error("transaction is {size} bytes without witness data, the circuit accepts at most {max}", max = MAX_TX_SIZE)]
+    TransactionTooLarge { size: usize },
+    #[error("{count} liquidity providers are reserved, the circuit accepts at most {max}", max = MAX_LIQUIDITY_PROVIDERS)]
+    TooManyLiquidityProviders { count: usize },
+    #[error("liquidity provider {lp} has an unsupported payout script")]
+    UnsupportedScriptPubKey { lp: usize },
+    #[error("transaction has no inputs")]
+    NoInputs,
+    #[error("transaction has {count} inputs, the circuit accepts at most {max}", max = MAX_INPUT_COUNT)]
+    TooManyInputs { count: usize },
+    #[error("transaction has {count} outputs, the circuit accepts at most {max}", max = MAX_OUTPUT_COUNT)]
+    TooManyOutputs { count: usize },
+    #[error("transaction has {count} outputs, too few to pay {lp_count} liquidity providers and inscribe the order nonce")]
+    TooFewOutputs { count: usize, lp_count: usize },
+    #[error("liquidity provider {lp} is paid {paid_sats} sats instead of {expected_sats}")]
+    WrongAmount {
+        lp: usize,
+        expected_sats: u64,
+        paid_sats: u64,
+    },
+    #[error("no output pays liquidity provider {lp}")]
+    MissingLpOutput { lp: usize },
+    #[error("no output inscribes the order nonce")]
+    MissingNonceOutput,
+}
+
+// The outputs the circuit is pointed at for a valid payment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentOutputs {
+    pub lp_output_indices: Vec<u64>,
+    pub nonce_output_index: u64,
+}
+
+// Checks a payment natively, in the order the circuit does, so an invalid payment is caught before
+// any time is spent proving it. A valid payment's outputs are the ones `match_payment_outputs`
+// assigns, the same the circuit is handed by `find_payment_output_indices`
+pub fn validate_payment(
+    transaction: &Transaction,
+    liquidity_providers: &[LiquidityReservation],
+    order_nonce: [u8; 32],
+) -> Result<PaymentOutputs, PaymentError> {
+    let size = serialize_no_segwit(transaction).len();
+    if size > MAX_TX_SIZE {
+        return Err(PaymentError::TransactionTooLarge { size });
+    }
+    if liquidity_providers.len() > MAX_LIQUIDITY_PROVIDERS {
+        return Err(PaymentError::TooManyLiquidityProviders {
+            count: liquidity_providers.len(),
+        });
+    }
+    if let Some(lp) = liquidity_providers
+        .iter()
+        .position(|lp| script_pub_key_len(&lp.script_pub_key) != Some(lp.script_pub_key.len()))
+    {
+        return Err(PaymentError::UnsupportedScriptPubKey { lp });
+    }
+
+    let input_count = transaction.input.len();
+    if input_count == 0 {
+        return Err(PaymentError::NoInputs);
+    }
+    if input_count as u64 > MAX_INPUT_COUNT {
+        return Err(PaymentError::TooManyInputs { count: input_count });
+    }
+    let output_count = transaction.output.len();
+    if output_count as u64 > MAX_OUTPUT_COUNT {
+        return Err(PaymentError::TooManyOutputs {
+            count: output_count,
+        });
+    }
+    if liquidity_providers.len() >= output_count {
+        return Err(PaymentError::TooFewOutputs {
+            count: output_count,
+            lp_count: liquidity_providers.len(),
+        });
+    }
+
+    match_payment_outputs(transaction, liquidity_providers, order_nonce)
+}
+
+// Assigns each LP the first unclaimed output paying its exact script and amount, then the nonce the
+// first unclaimed output inscribing it. An output can only pay LPs with an identical script and
+// amount, which are interchangeable, so taking the first candidate never leaves an LP unpaid that
+// another assignment would have paid
+pub fn match_payment_outputs(
+    transaction: &Transaction,
+    liquidity_providers: &[LiquidityReservation],
+    order_nonce: [u8; 32],
+) -> Result<PaymentOutputs, PaymentError> {
+    let mut claimed_outputs = vec![false; transaction.output.len()];
+    let mut lp_output_indices = Vec::with_capacity(liquidity_providers.len());
+    let mut unpaid_lps = Vec::new();
+    for (lp_index, lp) in liquidity_providers.iter().enumerate() {
+        let paying_output = transaction
+            .output
+            .iter()
+            .enumerate()
+            .position(|(index, output)| {
+                !claimed_outputs[index]
+                    && output.value.to_sat() == lp.expected_sats
+                    && output.script_pubkey.as_bytes() == lp.script_pub_key.as_slice()
+            });
+        match paying_output {
+            Some(index) => {
+                claimed_outputs[index] = true;
+                lp_output_indices.push(index as u64);
+            }
+            None => unpaid_lps.push(lp_index),
+        }
+    }
+
+    // diagnosed only once every LP has claimed its output, so an output paying a later LP with the
+    // same script isn't mistaken for an underpayment
+    if let Some(&lp_index) = unpaid_lps.first() {
+        let lp = &liquidity_providers[lp_index];
+        let paid_sats = transaction
+            .output
+            .iter()
+            .enumerate()
+            .find(|(index, output)| {
+                !claimed_outputs[*index]
+                    && output.script_pubkey.as_bytes() == lp.script_pub_key.as_slice()
+            })
+            .map(|(_, output)| output.value.to_sat());
+        return Err(match paid_sats {
+            Some(paid_sats) => PaymentError::WrongAmount {
+                lp: lp_index,
+                expected_sats: lp.expected_sats,
+                paid_sats,
+            },
+            None => PaymentError::MissingLpOutput { lp: lp_index },
+        });
+    }
+
+    let nonce_script = Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_slice(order_nonce)
+        .into_script();
+    let nonce_output_index = transaction
+        .output
+        .iter()
+        .enumerate()
+        .position(|(index, output)| !claimed_outputs[index] && output.script_pubkey == nonce_script)
+        .ok_or(PaymentError::MissingNonceOutput)?;
+
+    Ok(PaymentOutputs {
+        lp_output_indices,
+        nonce_output_index: nonce_output_index as u64,
+    })
+}
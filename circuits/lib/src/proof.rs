@@ -1,17 +1,34 @@
 use bitcoin::hashes::Hash;
 
+use bitcoin::block::Header;
 use bitcoin::Block;
 
 use crypto_bigint::{Encoding, U256};
 
-use rift_core::btc_light_client::AsLittleEndianBytes;
+use alloy_sol_types::private::FixedBytes;
+use alloy_sol_types::SolType;
+
+use rift_core::aggregation::{
+    aggregate_block_proofs, verification_key_digest_to_bytes, AggregationInput,
+};
+use rift_core::btc_light_client::{
+    AsLittleEndianBytes, Block as RiftOptimizedBlock, MEDIAN_TIME_SPAN,
+};
+use rift_core::chain_params::ChainParams;
 use rift_core::lp::{compute_lp_hash, encode_liquidity_providers, LiquidityReservation};
+use rift_core::signet::SignetBlockSolution;
 
-use crate::transaction::{get_chainworks, serialize_no_segwit};
-use crate::{generate_merkle_proof_and_root, AsRiftOptimizedBlock};
-use rift_core::{CircuitInput, CircuitPublicValues};
+use crate::transaction::{find_payment_output_indices, get_chainworks, serialize_no_segwit};
+use crate::{
+    build_signet_block_solution, generate_merkle_proof_and_root,
+    get_retarget_height_from_block_height, header_as_rift_optimized_block, AsRiftOptimizedBlock,
+};
+use rift_core::constants::MAX_BLOCKS;
+use rift_core::{
+    AggregatedProofPublicInputs, CircuitInput, CircuitPublicValues, ProgramInput, ProofPublicInputs,
+};
 
-use sp1_sdk::{ExecutionReport, HashableKey, ProverClient, SP1Stdin};
+use sp1_sdk::{ExecutionReport, HashableKey, ProverClient, SP1Proof, SP1Stdin};
 
 /// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
 pub const MAIN_ELF: &[u8] = include_bytes!("../../elf/riscv32im-succinct-zkvm-elf");
@@ -22,6 +39,13 @@ pub fn compute_circuit_hash() -> String {
     vk.bytes32().trim_start_matches("0x").to_string()
 }
 
+// Digest the program verifies chunk proofs against when aggregating block proofs
+pub fn compute_circuit_vkey_digest() -> [u8; 32] {
+    let client = ProverClient::new();
+    let (_, vk) = client.setup(MAIN_ELF);
+    verification_key_digest_to_bytes(vk.hash_u32())
+}
+
 pub fn build_transaction_proof_input(
     order_nonce: &[u8; 32],
     liquidity_reservations: &Vec<LiquidityReservation>,
@@ -32,6 +56,8 @@ pub fn build_transaction_proof_input(
     proposed_txid: &[u8; 32],
     retarget_block: &Block,
     retarget_block_height: u64,
+    ancestor_headers: &[Header],
+    chain_params: &ChainParams,
 ) -> CircuitInput {
     let proposed_block = &blocks[proposed_block_index];
 
@@ -57,6 +83,8 @@ pub fn build_transaction_proof_input(
     );
     let proposed_transaction = proposed_transaction.unwrap();
     let mined_transaction_serialized_no_segwit = serialize_no_segwit(proposed_transaction);
+    let (lp_output_indices, nonce_output_index) =
+        find_payment_output_indices(proposed_transaction, liquidity_reservations, *order_nonce);
 
     let (merkle_proof, calculated_merkle_root) = generate_merkle_proof_and_root(
         proposed_block
@@ -117,12 +145,18 @@ pub fn build_transaction_proof_input(
                 .collect(),
             chainworks,
             true,
+            chain_params.hash(),
         ),
         mined_transaction_serialized_no_segwit,
         merkle_proof,
         lp_reservation_data_encoded.to_vec(),
+        lp_output_indices,
+        nonce_output_index,
         rift_optimized_blocks.to_vec(),
         retarget_block.as_rift_optimized_block(retarget_block_height),
+        build_ancestor_blocks(ancestor_headers, safe_block_height),
+        *chain_params,
+        build_signet_block_solutions(blocks, chain_params),
     )
 }
 
@@ -132,6 +166,8 @@ pub fn build_block_proof_input(
     blocks: &[Block],
     retarget_block: &Block,
     retarget_block_height: u64,
+    ancestor_headers: &[Header],
+    chain_params: &ChainParams,
 ) -> CircuitInput {
     let rift_optimized_blocks = &blocks
         .iter()
@@ -165,15 +201,109 @@ pub fn build_block_proof_input(
                 .collect(),
             chainworks,
             false,
+            chain_params.hash(),
         ),
         Vec::new(),
         Vec::new(),
         Vec::new(),
+        Vec::new(),
+        0,
         rift_optimized_blocks.to_vec(),
         retarget_block.as_rift_optimized_block(retarget_block_height),
+        build_ancestor_blocks(ancestor_headers, safe_block_height),
+        *chain_params,
+        build_signet_block_solutions(blocks, chain_params),
     )
 }
 
+// Splits a header range too long for one proof into block proof inputs of at most MAX_BLOCKS blocks,
+// each starting at the last block of the previous chunk
+pub fn build_block_proof_chunk_inputs(
+    safe_chainwork: U256,
+    safe_block_height: u64,
+    blocks: &[Block],
+    retarget_block: &Block,
+    retarget_block_height: u64,
+    ancestor_headers: &[Header],
+    chain_params: &ChainParams,
+) -> Vec<CircuitInput> {
+    let chainworks = get_chainworks(
+        &blocks
+            .iter()
+            .zip(safe_block_height..)
+            .map(|(block, height)| block.as_rift_optimized_block(height))
+            .collect::<Vec<_>>(),
+        safe_chainwork,
+    );
+    let headers = ancestor_headers
+        .iter()
+        .chain(blocks.iter().map(|block| &block.header))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut chunk_inputs = Vec::new();
+    let mut start = 0;
+    while start + 1 < blocks.len() {
+        let end = (start + MAX_BLOCKS).min(blocks.len());
+        let chunk_safe_block_height = safe_block_height + start as u64;
+
+        // the chunk's retarget block is either proven by an earlier chunk or the original one
+        let chunk_retarget_block_height =
+            get_retarget_height_from_block_height(chunk_safe_block_height);
+        let chunk_retarget_block = if chunk_retarget_block_height >= safe_block_height {
+            &blocks[(chunk_retarget_block_height - safe_block_height) as usize]
+        } else {
+            assert_eq!(
+                chunk_retarget_block_height, retarget_block_height,
+                "Invalid retarget block height"
+            );
+            retarget_block
+        };
+
+        let ancestors_end = ancestor_headers.len() + start;
+        let chunk_ancestor_headers =
+            &headers[ancestors_end.saturating_sub(MEDIAN_TIME_SPAN - 1)..ancestors_end];
+
+        chunk_inputs.push(build_block_proof_input(
+            chainworks[start],
+            chunk_safe_block_height,
+            &blocks[start..end],
+            chunk_retarget_block,
+            chunk_retarget_block_height,
+            chunk_ancestor_headers,
+            chain_params,
+        ));
+        start = end - 1;
+    }
+    chunk_inputs
+}
+
+// `ancestor_headers` are the headers directly preceding the safe block, oldest first
+fn build_ancestor_blocks(
+    ancestor_headers: &[Header],
+    safe_block_height: u64,
+) -> Vec<RiftOptimizedBlock> {
+    ancestor_headers
+        .iter()
+        .zip(safe_block_height - ancestor_headers.len() as u64..)
+        .map(|(header, height)| header_as_rift_optimized_block(header, height))
+        .collect()
+}
+
+// the safe block is already known to the contract, so only the blocks after it need solutions
+fn build_signet_block_solutions(
+    blocks: &[Block],
+    chain_params: &ChainParams,
+) -> Vec<SignetBlockSolution> {
+    if !chain_params.is_signet() {
+        return Vec::new();
+    }
+    blocks[1..]
+        .iter()
+        .map(build_signet_block_solution)
+        .collect()
+}
+
 pub fn generate_plonk_proof(
     circuit_input: CircuitInput,
     verify: Option<bool>,
@@ -182,7 +312,7 @@ pub fn generate_plonk_proof(
     let client = ProverClient::new();
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
-    stdin.write(&circuit_input);
+    stdin.write(&ProgramInput::Rift(Box::new(circuit_input)));
     // Setup the program for proving.
     let (pk, vk) = client.setup(MAIN_ELF);
     // Generate the proof
@@ -203,7 +333,77 @@ pub fn generate_plonk_proof(
 pub fn execute(circuit_input: CircuitInput) -> (String, ExecutionReport) {
     let client = ProverClient::new();
     let mut stdin = SP1Stdin::new();
-    stdin.write(&circuit_input);
+    stdin.write(&ProgramInput::Rift(Box::new(circuit_input)));
     let (public_values, report) = client.execute(MAIN_ELF, stdin).run().unwrap();
     (public_values.raw(), report)
 }
+
+// Proves each chunk with a compressed proof, then proves their aggregation with a plonk proof
+pub fn generate_aggregated_block_proof(
+    chunk_inputs: Vec<CircuitInput>,
+    verify: Option<bool>,
+) -> sp1_sdk::SP1ProofWithPublicValues {
+    let client = ProverClient::new();
+    let (pk, vk) = client.setup(MAIN_ELF);
+
+    let mut chunk_public_inputs = Vec::with_capacity(chunk_inputs.len());
+    let mut chunk_proofs = Vec::with_capacity(chunk_inputs.len());
+    for chunk_input in chunk_inputs {
+        let mut chunk_stdin = SP1Stdin::new();
+        chunk_stdin.write(&ProgramInput::Rift(Box::new(chunk_input)));
+        let chunk_proof = client
+            .prove(&pk, chunk_stdin)
+            .compressed()
+            .run()
+            .expect("failed to generate chunk proof");
+        chunk_public_inputs.push(chunk_proof.public_values.to_vec());
+        let SP1Proof::Compressed(proof) = chunk_proof.proof else {
+            panic!("Chunk proof is not compressed");
+        };
+        chunk_proofs.push(proof);
+    }
+
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&ProgramInput::BlockProofAggregation(AggregationInput {
+        chunk_verification_key_digest: vk.hash_u32(),
+        chunk_public_inputs,
+    }));
+    for proof in chunk_proofs {
+        stdin.write_proof(proof, vk.vk.clone());
+    }
+
+    let proof = client
+        .prove(&pk, stdin)
+        .plonk()
+        .run()
+        .expect("failed to generate proof");
+
+    if verify.unwrap_or(true) {
+        client.verify(&proof, &vk).expect("failed to verify proof");
+    }
+
+    proof
+}
+
+// Public values an aggregation of the given chunk public values commits, without proving it
+pub fn build_aggregated_block_proof_public_values(
+    chunk_public_inputs: &[Vec<u8>],
+    chunk_verification_key_digest: [u8; 32],
+) -> Vec<u8> {
+    AggregatedProofPublicInputs::abi_encode(&AggregatedProofPublicInputs {
+        public_inputs: aggregate_block_proofs(chunk_public_inputs),
+        chunk_verification_key_digest: FixedBytes::from(chunk_verification_key_digest),
+    })
+}
+
+// Splits the public values of an aggregated block proof into the ABI encoded ProofPublicInputs of
+// the whole range and the digest its chunks were verified against
+pub fn decode_aggregated_block_proof_public_values(
+    public_values: &[u8],
+) -> Result<(Vec<u8>, [u8; 32]), alloy_sol_types::Error> {
+    let aggregated = AggregatedProofPublicInputs::abi_decode(public_values, true)?;
+    Ok((
+        ProofPublicInputs::abi_encode(&aggregated.public_inputs),
+        aggregated.chunk_verification_key_digest.0,
+    ))
+}
@@ -17,9 +17,11 @@ use bitcoin::{
 use crypto_bigint::{NonZero, U256};
 use rift_core::btc_light_client::AsLittleEndianBytes;
 use rift_core::btc_light_client::Block as RiftOptimizedBlock;
-use rift_core::lp::LiquidityReservation;
+use rift_core::lp::{script_pub_key_len, LiquidityReservation};
 use std::str::FromStr;
 
+use crate::payment::match_payment_outputs;
+
 // Assuming you have a crate named `rift_lib` with these types
 
 pub struct P2WPKHBitcoinWallet {
@@ -107,20 +109,73 @@ pub fn serialize_no_segwit(tx: &Transaction) -> Vec<u8> {
     buffer
 }
 
+// An output owned by the paying wallet, spent as an input of the payment transaction
+pub struct FundingUtxo<'a> {
+    // little endian (as shown on explorers)
+    pub txid: [u8; 32],
+    pub transaction: &'a Transaction,
+    pub vout: u32,
+}
+
+impl<'a> FundingUtxo<'a> {
+    pub fn new(txid: [u8; 32], transaction: &'a Transaction, vout: u32) -> Self {
+        Self {
+            txid,
+            transaction,
+            vout,
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.transaction.output[self.vout as usize].value.to_sat()
+    }
+}
+
+// Builds a reservation paying out to any P2WPKH, P2WSH, P2TR, P2SH or P2PKH address
+pub fn build_liquidity_reservation(
+    expected_sats: u64,
+    payout_address: &Address<NetworkChecked>,
+) -> LiquidityReservation {
+    let script_pub_key = payout_address.script_pubkey().to_bytes();
+    assert_eq!(
+        script_pub_key_len(&script_pub_key),
+        Some(script_pub_key.len()),
+        "Unsupported LP payout address"
+    );
+    LiquidityReservation {
+        expected_sats,
+        script_pub_key,
+    }
+}
+
+// Locates the output paying each LP and the OP_RETURN output inscribing the order nonce,
+// each output is matched at most once so duplicate reservations map to distinct outputs
+pub fn find_payment_output_indices(
+    transaction: &Transaction,
+    liquidity_providers: &[LiquidityReservation],
+    order_nonce: [u8; 32],
+) -> (Vec<u64>, u64) {
+    let outputs = match_payment_outputs(transaction, liquidity_providers, order_nonce)
+        .expect("Payment output not found in transaction");
+    (outputs.lp_output_indices, outputs.nonce_output_index)
+}
+
 pub fn build_rift_payment_transaction(
     order_nonce: [u8; 32],
     liquidity_providers: &[LiquidityReservation],
-    in_txid: [u8; 32],
-    transaction: &Transaction,
-    in_txvout: u32,
+    funding_utxos: &[FundingUtxo],
     wallet: &P2WPKHBitcoinWallet,
     fee_sats: u64,
 ) -> Transaction {
-    // Fetch transaction data (you'll need to implement this function)
+    assert!(
+        !funding_utxos.is_empty(),
+        "At least one funding UTXO is required"
+    );
 
     let total_lp_sum_btc: u64 = liquidity_providers.iter().map(|lp| lp.expected_sats).sum();
 
-    let vin_sats = transaction.output[in_txvout as usize].value.to_sat();
+    let input_amounts: Vec<u64> = funding_utxos.iter().map(|utxo| utxo.value()).collect();
+    let vin_sats: u64 = input_amounts.iter().sum();
 
     println!("Total LP Sum BTC: {}", total_lp_sum_btc);
     println!("Vin sats: {}", vin_sats);
@@ -154,73 +209,78 @@ pub fn build_rift_payment_transaction(
         script_pubkey: wallet.get_p2wpkh_script(),
     });
 
-    // Create input
-    let outpoint = OutPoint::new(
-        Txid::from_slice(
-            &TryInto::<[u8; 32]>::try_into((in_txid).as_slice())
-                .unwrap()
-                .to_little_endian(),
-        )
-        .unwrap(),
-        in_txvout,
-    );
-    let tx_in = TxIn {
-        previous_output: outpoint,
-        script_sig: Script::new().into(),
-        sequence: Sequence(0xFFFFFFFD),
-        witness: Witness::new(),
-    };
+    // Create inputs
+    let tx_ins = funding_utxos
+        .iter()
+        .map(|utxo| TxIn {
+            previous_output: OutPoint::new(
+                Txid::from_slice(&utxo.txid.to_little_endian()).unwrap(),
+                utxo.vout,
+            ),
+            script_sig: Script::new().into(),
+            sequence: Sequence(0xFFFFFFFD),
+            witness: Witness::new(),
+        })
+        .collect();
 
     // Create unsigned transaction
     let mut tx = Transaction {
         version: transaction::Version(1),
         lock_time: LockTime::from_consensus(0),
-        input: vec![tx_in],
+        input: tx_ins,
         output: tx_outs,
     };
 
-    sign_transaction(&mut tx, wallet, vin_sats)
+    sign_transaction(&mut tx, wallet, &input_amounts)
 }
 
 fn sign_transaction(
     tx: &mut Transaction,
     wallet: &P2WPKHBitcoinWallet,
-    input_amount: u64,
+    input_amounts: &[u64],
 ) -> Transaction {
     let secp = Secp256k1::new();
     let public_key = PublicKey::from_str(&wallet.public_key).unwrap();
 
-    // We're assuming there's only one input to sign
-    let input_index = 0;
-
-    // Create a SighashCache for efficient signature hash computation
-    let mut sighash_cache = SighashCache::new(tx.clone());
-
-    // Compute the sighash
-    let sighash = sighash_cache
-        .p2wpkh_signature_hash(
-            input_index,
-            &wallet.get_p2wpkh_script(),
-            Amount::from_sat(input_amount),
-            EcdsaSighashType::All,
-        )
-        .unwrap();
-
-    // Sign the sighash
-    let signature = secp.sign_ecdsa(
-        &secp256k1::Message::from_digest_slice(&sighash[..]).unwrap(),
-        &wallet.secret_key,
-    );
-
-    // Serialize the signature and add the sighash type
-    let mut signature_bytes = signature.serialize_der().to_vec();
-    signature_bytes.push(EcdsaSighashType::All as u8);
-
-    // Create the witness
-    let witness = Witness::from_slice(&[signature_bytes.as_slice(), &public_key.to_bytes()]);
+    // Every input is assumed to be a P2WPKH output owned by the wallet
+    let mut witnesses = Vec::with_capacity(input_amounts.len());
+    {
+        // Create a SighashCache for efficient signature hash computation
+        let mut sighash_cache = SighashCache::new(&*tx);
+
+        for (input_index, input_amount) in input_amounts.iter().enumerate() {
+            // Compute the sighash
+            let sighash = sighash_cache
+                .p2wpkh_signature_hash(
+                    input_index,
+                    &wallet.get_p2wpkh_script(),
+                    Amount::from_sat(*input_amount),
+                    EcdsaSighashType::All,
+                )
+                .unwrap();
+
+            // Sign the sighash
+            let signature = secp.sign_ecdsa(
+                &secp256k1::Message::from_digest_slice(&sighash[..]).unwrap(),
+                &wallet.secret_key,
+            );
+
+            // Serialize the signature and add the sighash type
+            let mut signature_bytes = signature.serialize_der().to_vec();
+            signature_bytes.push(EcdsaSighashType::All as u8);
+
+            // Create the witness
+            witnesses.push(Witness::from_slice(&[
+                signature_bytes.as_slice(),
+                &public_key.to_bytes(),
+            ]));
+        }
+    }
 
-    // Set the witness for the input
-    tx.input[input_index].witness = witness;
+    // Set the witness for each input
+    for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+        input.witness = witness;
+    }
 
     tx.clone()
 }
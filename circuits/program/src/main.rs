@@ -3,14 +3,28 @@ sp1_zkvm::entrypoint!(main);
 
 use alloy_sol_types::private::{FixedBytes, Uint};
 use alloy_sol_types::SolType;
-use rift_core::{validate_rift_transaction, CircuitInput, ProofPublicInputs};
+use rift_core::aggregation::{
+    aggregate_block_proofs, verification_key_digest_to_bytes, AggregationInput,
+};
+use rift_core::tx_hash::sha256_hash;
+use rift_core::{
+    validate_rift_transaction, AggregatedProofPublicInputs, CircuitInput, ProgramInput,
+    ProofPublicInputs,
+};
 
 pub fn main() {
     // Read an input to the program.
     // Behind the scenes, this compiles down to a custom system call which handles reading inputs
     // from the prover.
-    let circuit_input = sp1_zkvm::io::read::<CircuitInput>();
+    match sp1_zkvm::io::read::<ProgramInput>() {
+        ProgramInput::Rift(circuit_input) => prove_rift_transaction(*circuit_input),
+        ProgramInput::BlockProofAggregation(aggregation_input) => {
+            prove_block_proof_aggregation(aggregation_input)
+        }
+    }
+}
 
+fn prove_rift_transaction(circuit_input: CircuitInput) {
     let circuit_public_input = validate_rift_transaction(circuit_input);
 
     // Encode the public values of the program.
@@ -24,22 +38,41 @@ pub fn main() {
         safe_block_height: circuit_public_input.safe_block_height,
         safe_block_height_delta: circuit_public_input.safe_block_height_delta,
         confirmation_block_height_delta: circuit_public_input.confirmation_block_height_delta,
-        block_hashes: circuit_public_input.block_hashes
-            [0..(circuit_input.utilized_blocks as usize)]
-            .to_vec()
+        block_hashes: circuit_public_input
+            .block_hashes
             .iter()
             .map(|x| FixedBytes::from(*x))
             .collect::<Vec<_>>(),
-        block_chainworks: circuit_input.public_values.block_chainworks
-            [0..(circuit_input.utilized_blocks as usize)]
-            .to_vec()
+        block_chainworks: circuit_public_input
+            .block_chainworks
             .iter()
             .map(|chainwork| Uint::from_be_bytes(*chainwork))
             .collect(),
         is_transaction_proof: circuit_public_input.is_transaction_proof,
+        chain_params_hash: FixedBytes::from(circuit_public_input.chain_params_hash),
     });
 
     // Commit to the public values of the program. The final proof will have a commitment to all the
     // bytes that were committed to.
     sp1_zkvm::io::commit_slice(&bytes);
 }
+
+fn prove_block_proof_aggregation(aggregation_input: AggregationInput) {
+    // Verify each chunk proof against the public values it committed, the proofs themselves are
+    // supplied to the prover alongside the input
+    for chunk_public_inputs in &aggregation_input.chunk_public_inputs {
+        sp1_zkvm::lib::verify::verify_sp1_proof(
+            &aggregation_input.chunk_verification_key_digest,
+            &sha256_hash(chunk_public_inputs),
+        );
+    }
+
+    let bytes = AggregatedProofPublicInputs::abi_encode(&AggregatedProofPublicInputs {
+        public_inputs: aggregate_block_proofs(&aggregation_input.chunk_public_inputs),
+        chunk_verification_key_digest: FixedBytes::from(verification_key_digest_to_bytes(
+            aggregation_input.chunk_verification_key_digest,
+        )),
+    });
+
+    sp1_zkvm::io::commit_slice(&bytes);
+}
@@ -7,7 +7,8 @@ use hex_literal::hex;
 
 use rift_core::lp::LiquidityReservation;
 
-use rift_core::CircuitInput;
+use rift_core::chain_params::ChainParams;
+use rift_core::{CircuitInput, ProgramInput};
 use rift_lib::proof::{self, build_transaction_proof_input};
 use rift_lib::{get_retarget_height_from_block_height, load_hex_bytes, to_hex_string};
 
@@ -22,15 +23,15 @@ fn get_test_case_circuit_input() -> CircuitInput {
     let lp_reservations: Vec<LiquidityReservation> = vec![
         LiquidityReservation {
             expected_sats: 487,
-            script_pub_key: hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa"),
+            script_pub_key: hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa").to_vec(),
         },
         LiquidityReservation {
             expected_sats: 487,
-            script_pub_key: hex!("0014aa86191235be8883693452cf30daf854035b085b"),
+            script_pub_key: hex!("0014aa86191235be8883693452cf30daf854035b085b").to_vec(),
         },
         LiquidityReservation {
             expected_sats: 487,
-            script_pub_key: hex!("00146ab8f6c80b8a7dc1b90f7deb80e9b59ae16b7a5a"),
+            script_pub_key: hex!("00146ab8f6c80b8a7dc1b90f7deb80e9b59ae16b7a5a").to_vec(),
         },
     ];
 
@@ -62,6 +63,14 @@ fn get_test_case_circuit_input() -> CircuitInput {
         &mined_txid,
         &mined_retarget_block,
         mined_retarget_block.bip34_block_height().unwrap(),
+        &(854363..854373)
+            .map(|height| {
+                deserialize::<Block>(&load_hex_bytes(&format!("tests/data/block_{height}.hex")))
+                    .unwrap()
+                    .header
+            })
+            .collect::<Vec<_>>(),
+        &ChainParams::mainnet(),
     )
 }
 
@@ -90,7 +99,7 @@ fn main() {
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
 
-    stdin.write(&circuit_input);
+    stdin.write(&ProgramInput::Rift(Box::new(circuit_input.clone())));
     println!("Inputs serialized successfully.");
 
     println!(
@@ -24,4 +24,12 @@ fn main() {
 
     // Print the verification key.
     println!("Program Verification Key: {}", vk.bytes32());
+    // Chunks of aggregated block proofs are verified against this digest
+    println!(
+        "Program Verification Key Digest: 0x{}",
+        vk.hash_u32()
+            .iter()
+            .map(|word| format!("{:08x}", word))
+            .collect::<String>()
+    );
 }
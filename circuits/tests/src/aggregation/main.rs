@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use alloy_sol_types::private::{FixedBytes, Uint};
+    use alloy_sol_types::SolType;
+
+    use rift_core::aggregation::aggregate_block_proofs;
+    use rift_core::chain_params::ChainParams;
+    use rift_core::ProofPublicInputs;
+
+    fn block_hash(height: u64) -> FixedBytes<32> {
+        let mut hash = [0u8; 32];
+        hash[24..].copy_from_slice(&height.to_be_bytes());
+        FixedBytes::from(hash)
+    }
+
+    fn retarget_block_hash(height: u64) -> FixedBytes<32> {
+        block_hash(height - height % 2016)
+    }
+
+    // Public values of a block proof over the blocks from `safe_block_height` to
+    // `confirmation_block_height`, with the chainwork of each block equal to its height
+    fn build_chunk(safe_block_height: u64, confirmation_block_height: u64) -> ProofPublicInputs {
+        let heights = safe_block_height..=confirmation_block_height;
+        ProofPublicInputs {
+            natural_txid: FixedBytes::ZERO,
+            merkle_root: FixedBytes::ZERO,
+            lp_reservation_hash: FixedBytes::ZERO,
+            order_nonce: FixedBytes::ZERO,
+            lp_count: 0,
+            retarget_block_hash: retarget_block_hash(safe_block_height),
+            safe_block_height,
+            safe_block_height_delta: confirmation_block_height - safe_block_height - 1,
+            confirmation_block_height_delta: 1,
+            block_hashes: heights.clone().map(block_hash).collect(),
+            block_chainworks: heights.map(Uint::from).collect(),
+            is_transaction_proof: false,
+            chain_params_hash: FixedBytes::from(ChainParams::mainnet().hash()),
+        }
+    }
+
+    fn encode_chunks(chunks: &[ProofPublicInputs]) -> Vec<Vec<u8>> {
+        chunks.iter().map(ProofPublicInputs::abi_encode).collect()
+    }
+
+    #[test]
+    fn test_aggregate_block_proofs() {
+        // the second and third chunks start after the retarget block at 4032
+        let chunks = [
+            build_chunk(4030, 4034),
+            build_chunk(4034, 4038),
+            build_chunk(4038, 4042),
+        ];
+        let aggregated = aggregate_block_proofs(&encode_chunks(&chunks));
+
+        assert_eq!(
+            ProofPublicInputs::abi_encode(&aggregated),
+            ProofPublicInputs::abi_encode(&build_chunk(4030, 4042))
+        );
+    }
+
+    #[test]
+    fn test_aggregate_single_block_proof() {
+        let chunk = build_chunk(4030, 4034);
+        let aggregated = aggregate_block_proofs(&encode_chunks(std::slice::from_ref(&chunk)));
+
+        assert_eq!(
+            ProofPublicInputs::abi_encode(&aggregated),
+            ProofPublicInputs::abi_encode(&chunk)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk safe block height mismatch")]
+    fn test_aggregate_fails_on_gap() {
+        let chunks = [build_chunk(4030, 4034), build_chunk(4035, 4038)];
+        aggregate_block_proofs(&encode_chunks(&chunks));
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk safe block hash mismatch")]
+    fn test_aggregate_fails_on_fork() {
+        let mut forked_chunk = build_chunk(4034, 4038);
+        forked_chunk.block_hashes[0] = FixedBytes::repeat_byte(1);
+        let chunks = [build_chunk(4030, 4034), forked_chunk];
+        aggregate_block_proofs(&encode_chunks(&chunks));
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk retarget block hash mismatch")]
+    fn test_aggregate_fails_on_stale_retarget_block() {
+        let mut stale_chunk = build_chunk(4034, 4038);
+        stale_chunk.retarget_block_hash = retarget_block_hash(4030);
+        let chunks = [build_chunk(4030, 4034), stale_chunk];
+        aggregate_block_proofs(&encode_chunks(&chunks));
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk chain params hash mismatch")]
+    fn test_aggregate_fails_on_chain_params_mismatch() {
+        let mut regtest_chunk = build_chunk(4034, 4038);
+        regtest_chunk.chain_params_hash = FixedBytes::from(ChainParams::regtest().hash());
+        let chunks = [build_chunk(4030, 4034), regtest_chunk];
+        aggregate_block_proofs(&encode_chunks(&chunks));
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk is not a block proof")]
+    fn test_aggregate_fails_on_transaction_proof() {
+        let mut transaction_chunk = build_chunk(4034, 4038);
+        transaction_chunk.is_transaction_proof = true;
+        let chunks = [build_chunk(4030, 4034), transaction_chunk];
+        aggregate_block_proofs(&encode_chunks(&chunks));
+    }
+}
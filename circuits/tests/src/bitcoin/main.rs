@@ -1,18 +1,45 @@
 #[cfg(test)]
 mod tests {
+    use bitcoin::blockdata::constants::genesis_block;
     use bitcoin::consensus::encode::{deserialize, serialize};
     use bitcoin::hashes::Hash;
     use bitcoin::hex::{DisplayHex, FromHex};
-    use bitcoin::Block;
+    use bitcoin::{Block, Network};
 
     use crypto_bigint::{Encoding, U256};
     use hex_literal::hex;
     use rift_core::btc_light_client::{
-        assert_blockchain, assert_pow, bits_to_target, verify_block, AsLittleEndianBytes,
-        Block as RiftOptimizedBlock,
+        assert_blockchain, assert_difficulty_retarget, assert_pow, bits_to_target,
+        compute_expected_bits, compute_next_target, median_time_past, target_to_bits, verify_block,
+        AsLittleEndianBytes, Block as RiftOptimizedBlock, MEDIAN_TIME_SPAN,
     };
+    use rift_core::chain_params::ChainParams;
     use rift_lib::transaction::get_chainworks;
-    use rift_lib::{get_retarget_height_from_block_height, load_hex_bytes, AsRiftOptimizedBlock};
+    use rift_lib::{
+        get_retarget_height_from_block_height, header_as_rift_optimized_block, load_hex_bytes,
+        AsRiftOptimizedBlock,
+    };
+
+    fn load_rift_blocks(start_height: u64, count: u64) -> Vec<RiftOptimizedBlock> {
+        (start_height..start_height + count)
+            .map(|height| {
+                deserialize::<Block>(&load_hex_bytes(&format!("data/block_{}.hex", height)))
+                    .unwrap()
+                    .as_rift_optimized_block_unsafe()
+            })
+            .collect()
+    }
+
+    // timestamps of the MEDIAN_TIME_SPAN blocks ending at `height`
+    fn load_block_times(height: u64) -> Vec<u32> {
+        load_rift_blocks(
+            height + 1 - MEDIAN_TIME_SPAN as u64,
+            MEDIAN_TIME_SPAN as u64,
+        )
+        .iter()
+        .map(|block| u32::from_le_bytes(block.time))
+        .collect()
+    }
 
     #[test]
     fn test_rift_block_converter() {
@@ -119,6 +146,8 @@ mod tests {
             second_rift_block,
             rift_retarget_block,
             first_rift_block.height,
+            &load_block_times(first_rift_block.height),
+            &ChainParams::mainnet(),
         )
     }
 
@@ -149,6 +178,8 @@ mod tests {
             second_rift_block,
             rift_retarget_block,
             first_rift_block.height,
+            &load_block_times(first_rift_block.height),
+            &ChainParams::mainnet(),
         )
     }
 
@@ -243,6 +274,9 @@ mod tests {
             retarget_block.compute_block_hash(),
             blocks,
             *retarget_block,
+            load_rift_blocks(initial_block - 10, 10),
+            &ChainParams::mainnet(),
+            &[],
         );
     }
 
@@ -310,6 +344,345 @@ mod tests {
             retarget_block.compute_block_hash(),
             blocks,
             *retarget_block,
+            load_rift_blocks(initial_block - 10, 10),
+            &ChainParams::mainnet(),
+            &[],
+        );
+    }
+
+    fn build_retarget_period(
+        period_start_time: u32,
+        period_end_height: u64,
+        period_end_time: u32,
+        period_bits: u32,
+        retarget_bits: u32,
+    ) -> (RiftOptimizedBlock, RiftOptimizedBlock, RiftOptimizedBlock) {
+        let period_start_block = RiftOptimizedBlock {
+            height: period_end_height + 1 - 2016,
+            time: period_start_time.to_le_bytes(),
+            bits: period_bits.to_le_bytes(),
+            ..Default::default()
+        };
+        let period_end_block = RiftOptimizedBlock {
+            height: period_end_height,
+            time: period_end_time.to_le_bytes(),
+            bits: period_bits.to_le_bytes(),
+            ..Default::default()
+        };
+        let retarget_block = RiftOptimizedBlock {
+            height: period_end_height + 1,
+            bits: retarget_bits.to_le_bytes(),
+            ..Default::default()
+        };
+        (period_start_block, period_end_block, retarget_block)
+    }
+
+    #[test]
+    fn test_target_to_bits() {
+        for bits in [
+            0x1d00ffff_u32,
+            0x1d00d86a,
+            0x1c0168fd,
+            0x17034219,
+            0x1b0404cb,
+        ] {
+            assert_eq!(
+                target_to_bits(bits_to_target(bits.to_le_bytes())),
+                bits.to_le_bytes()
+            );
+        }
+    }
+
+    // vectors from bitcoin core's pow_tests.cpp
+    #[test]
+    fn test_compute_next_target() {
+        let cases = [
+            // no constraints applying, blocks 30240 -> 32255
+            (1261130161, 1262152739, 0x1d00ffff_u32, 0x1d00d86a_u32),
+            // pow limit, blocks 0 -> 2015
+            (1231006505, 1233061996, 0x1d00ffff, 0x1d00ffff),
+            // lower bound on actual timespan, blocks 66528 -> 68543
+            (1279008237, 1279297671, 0x1c05a3f4, 0x1c0168fd),
+            // upper bound on actual timespan, ending at block 46367
+            (1263163443, 1269211443, 0x1c387f6f, 0x1d00e1fd),
+        ];
+
+        for (period_start_time, period_end_time, period_bits, expected_bits) in cases {
+            let next_target = compute_next_target(
+                bits_to_target(u32::to_le_bytes(period_bits)),
+                period_start_time,
+                period_end_time,
+                U256::from_be_bytes(ChainParams::mainnet().pow_limit),
+            );
+            assert_eq!(target_to_bits(next_target), expected_bits.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_assert_difficulty_retarget() {
+        let (period_start_block, period_end_block, retarget_block) =
+            build_retarget_period(1261130161, 32255, 1262152739, 0x1d00ffff, 0x1d00d86a);
+        assert_difficulty_retarget(
+            &period_start_block,
+            &period_end_block,
+            &retarget_block,
+            &ChainParams::mainnet(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Retarget block bits do not match computed retarget")]
+    fn test_assert_difficulty_retarget_fails_on_low_difficulty() {
+        // the period was mined in 1/4 of the expected time, so difficulty can't drop
+        let (period_start_block, period_end_block, retarget_block) =
+            build_retarget_period(1279008237, 68543, 1279297671, 0x1c05a3f4, 0x1c05a3f4);
+        assert_difficulty_retarget(
+            &period_start_block,
+            &period_end_block,
+            &retarget_block,
+            &ChainParams::mainnet(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Period start block is not the previous retarget block")]
+    fn test_assert_difficulty_retarget_fails_on_wrong_period_start() {
+        let (mut period_start_block, period_end_block, retarget_block) =
+            build_retarget_period(1261130161, 32255, 1262152739, 0x1d00ffff, 0x1d00d86a);
+        period_start_block.height += 1;
+        assert_difficulty_retarget(
+            &period_start_block,
+            &period_end_block,
+            &retarget_block,
+            &ChainParams::mainnet(),
+        );
+    }
+
+    // regtest genesis followed by `length - 1` mined blocks, ten minutes apart
+    fn mine_regtest_chain(length: u64) -> Vec<RiftOptimizedBlock> {
+        let mut headers = vec![genesis_block(Network::Regtest).header];
+        while (headers.len() as u64) < length {
+            let previous = *headers.last().unwrap();
+            let mut header = previous;
+            header.prev_blockhash = previous.block_hash();
+            header.time += 600;
+            while header.validate_pow(header.target()).is_err() {
+                header.nonce += 1;
+            }
+            headers.push(header);
+        }
+        headers
+            .iter()
+            .zip(0..)
+            .map(|(header, height)| header_as_rift_optimized_block(header, height))
+            .collect()
+    }
+
+    #[test]
+    fn test_blockchain_verifies_near_genesis() {
+        let chain = mine_regtest_chain(8);
+        let safe_block_height = 3;
+        let blocks = chain[safe_block_height as usize..].to_vec();
+
+        // fewer than MEDIAN_TIME_SPAN - 1 blocks precede the safe block
+        assert_blockchain(
+            blocks
+                .iter()
+                .map(|block| block.compute_block_hash())
+                .collect(),
+            get_chainworks(&blocks, U256::from_u64(8)),
+            safe_block_height,
+            chain[0].compute_block_hash(),
+            blocks,
+            chain[0],
+            chain[..safe_block_height as usize].to_vec(),
+            &ChainParams::regtest(),
+            &[],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Incorrect number of ancestor blocks")]
+    fn test_blockchain_fails_on_missing_ancestors_near_genesis() {
+        let chain = mine_regtest_chain(8);
+        let safe_block_height = 3;
+        let blocks = chain[safe_block_height as usize..].to_vec();
+
+        assert_blockchain(
+            blocks
+                .iter()
+                .map(|block| block.compute_block_hash())
+                .collect(),
+            get_chainworks(&blocks, U256::from_u64(8)),
+            safe_block_height,
+            chain[0].compute_block_hash(),
+            blocks,
+            chain[0],
+            chain[1..safe_block_height as usize].to_vec(),
+            &ChainParams::regtest(),
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_median_time_past() {
+        let times = [8, 1, 11, 4, 6, 2, 10, 3, 9, 5, 7];
+        assert_eq!(median_time_past(&times), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "Block timestamp is not greater than median time past")]
+    fn test_verify_block_fails_on_stale_timestamp() {
+        let first_rift_block = load_rift_blocks(858564, 1)[0];
+        let mut second_rift_block = load_rift_blocks(858565, 1)[0];
+        let retarget_block = load_rift_blocks(get_retarget_height_from_block_height(858564), 1)[0];
+
+        // a timestamp equal to the median of the previous blocks is invalid
+        let previous_block_times = load_block_times(first_rift_block.height);
+        second_rift_block.time = median_time_past(&previous_block_times).to_le_bytes();
+
+        verify_block(
+            second_rift_block.compute_block_hash(),
+            first_rift_block.compute_block_hash(),
+            &second_rift_block,
+            &retarget_block,
+            first_rift_block.height,
+            &previous_block_times,
+            &ChainParams::mainnet(),
+        )
+    }
+
+    // the deploy script and contract tests initialize RiftExchange with these hashes
+    #[test]
+    fn test_chain_params_hash() {
+        assert_eq!(
+            ChainParams::mainnet().hash(),
+            hex!("65cb0f1fa5e67bf77b6795828d1ea4773b328accce41ec1d5388664811ae14ac")
+        );
+        assert_eq!(
+            ChainParams::regtest().hash(),
+            hex!("03b073d866d452236a49fb5ab9e5725f562d7f07d4bb999e8a79c9069532ed06")
+        );
+    }
+
+    #[test]
+    fn test_compute_expected_bits_min_difficulty() {
+        let retarget_block = RiftOptimizedBlock {
+            bits: 0x1c05a3f4_u32.to_le_bytes(),
+            ..Default::default()
+        };
+        let previous_block_time: u32 = 1_700_000_000;
+        let late_block = RiftOptimizedBlock {
+            time: (previous_block_time + 1201).to_le_bytes(),
+            ..Default::default()
+        };
+        let on_time_block = RiftOptimizedBlock {
+            time: (previous_block_time + 1200).to_le_bytes(),
+            ..Default::default()
+        };
+
+        // a block more than 20 minutes late may be mined at the pow limit on testnet
+        assert_eq!(
+            compute_expected_bits(
+                &late_block,
+                previous_block_time,
+                &retarget_block,
+                &ChainParams::testnet4()
+            ),
+            0x1d00ffff_u32.to_le_bytes()
+        );
+        assert_eq!(
+            compute_expected_bits(
+                &on_time_block,
+                previous_block_time,
+                &retarget_block,
+                &ChainParams::testnet4()
+            ),
+            retarget_block.bits
+        );
+        assert_eq!(
+            compute_expected_bits(
+                &late_block,
+                previous_block_time,
+                &retarget_block,
+                &ChainParams::mainnet()
+            ),
+            retarget_block.bits
+        );
+        assert_eq!(
+            compute_expected_bits(
+                &late_block,
+                previous_block_time,
+                &retarget_block,
+                &ChainParams::regtest()
+            ),
+            0x207fffff_u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_assert_difficulty_retarget_no_retargeting() {
+        let (period_start_block, period_end_block, retarget_block) =
+            build_retarget_period(1279008237, 68543, 1279297671, 0x207fffff, 0x207fffff);
+        assert_difficulty_retarget(
+            &period_start_block,
+            &period_end_block,
+            &retarget_block,
+            &ChainParams::regtest(),
+        );
+    }
+
+    #[test]
+    fn test_assert_difficulty_retarget_bip94() {
+        // the period ended on a min difficulty block, bip94 retargets from the period's first block
+        let (period_start_block, mut period_end_block, mut retarget_block) =
+            build_retarget_period(1261130161, 32255, 1262152739, 0x1d00ffff, 0x1d00d86a);
+        period_end_block.bits = 0x1c05a3f4_u32.to_le_bytes();
+        retarget_block.time = (1262152739 - 600_u32).to_le_bytes();
+        assert_difficulty_retarget(
+            &period_start_block,
+            &period_end_block,
+            &retarget_block,
+            &ChainParams::testnet4(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Retarget block timestamp too far behind previous block")]
+    fn test_assert_difficulty_retarget_bip94_timewarp() {
+        let (period_start_block, period_end_block, mut retarget_block) =
+            build_retarget_period(1261130161, 32255, 1262152739, 0x1d00ffff, 0x1d00d86a);
+        retarget_block.time = (1262152739 - 601_u32).to_le_bytes();
+        assert_difficulty_retarget(
+            &period_start_block,
+            &period_end_block,
+            &retarget_block,
+            &ChainParams::testnet4(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Signet solution count mismatch")]
+    fn test_blockchain_fails_on_missing_signet_solutions() {
+        let initial_block = 858564;
+        let blocks = load_rift_blocks(initial_block, 2);
+        let retarget_block =
+            load_rift_blocks(get_retarget_height_from_block_height(initial_block), 1)[0];
+        let chainworks = get_chainworks(&blocks, U256::ZERO);
+
+        assert_blockchain(
+            blocks
+                .iter()
+                .map(|block| block.compute_block_hash())
+                .collect(),
+            chainworks,
+            initial_block,
+            retarget_block.compute_block_hash(),
+            blocks,
+            retarget_block,
+            load_rift_blocks(initial_block - 10, 10),
+            &ChainParams::default_signet(),
+            &[],
         );
     }
 }
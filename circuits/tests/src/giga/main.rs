@@ -12,28 +12,39 @@ mod tests {
 
     use rift_core::lp::{compute_lp_hash, encode_liquidity_providers, LiquidityReservation};
 
-    use rift_core::btc_light_client::AsLittleEndianBytes;
+    use rift_core::btc_light_client::{AsLittleEndianBytes, MEDIAN_TIME_SPAN};
+    use rift_core::chain_params::ChainParams;
+    use rift_core::constants::MAX_TX_SIZE;
     use rift_core::{validate_rift_transaction, CircuitInput, CircuitPublicValues};
-    use rift_lib::transaction::serialize_no_segwit;
+    use rift_lib::transaction::{find_payment_output_indices, serialize_no_segwit};
     use rift_lib::{
         generate_merkle_proof_and_root, get_retarget_height_from_block_height, load_hex_bytes,
         AsRiftOptimizedBlock,
     };
 
+    // the blocks preceding the safe block, needed for the median time past check
+    fn load_ancestor_blocks(safe_block_height: u64) -> Vec<Block> {
+        (safe_block_height - (MEDIAN_TIME_SPAN as u64 - 1)..safe_block_height)
+            .map(|height| {
+                deserialize::<Block>(&load_hex_bytes(&format!("data/block_{height}.hex"))).unwrap()
+            })
+            .collect()
+    }
+
     fn get_test_case_circuit_input() -> CircuitInput {
         let order_nonce = hex!("f0ad57e677a89d2c2aaae4c5fd52ba20c63c0a05c916619277af96435f874c64");
         let lp_reservations: Vec<LiquidityReservation> = vec![
             LiquidityReservation {
                 expected_sats: 487,
-                script_pub_key: hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa"),
+                script_pub_key: hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa").to_vec(),
             },
             LiquidityReservation {
                 expected_sats: 487,
-                script_pub_key: hex!("0014aa86191235be8883693452cf30daf854035b085b"),
+                script_pub_key: hex!("0014aa86191235be8883693452cf30daf854035b085b").to_vec(),
             },
             LiquidityReservation {
                 expected_sats: 487,
-                script_pub_key: hex!("00146ab8f6c80b8a7dc1b90f7deb80e9b59ae16b7a5a"),
+                script_pub_key: hex!("00146ab8f6c80b8a7dc1b90f7deb80e9b59ae16b7a5a").to_vec(),
             },
         ];
 
@@ -87,6 +98,8 @@ mod tests {
         );
         let mined_transaction = mined_transaction.unwrap();
         let mined_transaction_serialized_no_segwit = serialize_no_segwit(mined_transaction);
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(mined_transaction, &lp_reservations, order_nonce);
 
         let txn = mined_transaction
             .compute_txid()
@@ -146,15 +159,24 @@ mod tests {
                     .collect(),
                 chainworks,
                 true,
+                ChainParams::mainnet().hash(),
             ),
             mined_transaction_serialized_no_segwit,
             merkle_proof,
             lp_reservation_data_encoded.to_vec(),
+            lp_output_indices,
+            nonce_output_index,
             mined_blocks
                 .iter()
                 .map(|block| block.as_rift_optimized_block_unsafe())
                 .collect(),
             mined_retarget_block.as_rift_optimized_block_unsafe(),
+            load_ancestor_blocks(854373)
+                .iter()
+                .map(|block| block.as_rift_optimized_block_unsafe())
+                .collect(),
+            ChainParams::mainnet(),
+            Vec::new(),
         )
     }
 
@@ -163,6 +185,22 @@ mod tests {
         validate_rift_transaction(get_test_case_circuit_input());
     }
 
+    #[test]
+    #[should_panic(expected = "Transaction too large")]
+    fn test_rift_txn_fails_on_oversized_transaction() {
+        let mut circuit_input = get_test_case_circuit_input();
+        circuit_input.txn_data_no_segwit = vec![0u8; MAX_TX_SIZE + 1];
+        validate_rift_transaction(circuit_input);
+    }
+
+    #[test]
+    #[should_panic(expected = "Block hash count mismatch")]
+    fn test_rift_txn_fails_on_missing_block_hash() {
+        let mut circuit_input = get_test_case_circuit_input();
+        circuit_input.public_values.block_hashes.pop();
+        validate_rift_transaction(circuit_input);
+    }
+
     #[test]
     fn test_circuit_input_serialization_functional() {
         let circuit_input = get_test_case_circuit_input();
@@ -215,6 +253,11 @@ mod tests {
             &mined_blocks,
             &retarget_block,
             retarget_block_height,
+            &load_ancestor_blocks(safe_block_height)
+                .iter()
+                .map(|block| block.header)
+                .collect::<Vec<_>>(),
+            &ChainParams::mainnet(),
         );
 
         // Verify that the circuit input is created correctly
@@ -226,7 +269,7 @@ mod tests {
             circuit_input.public_values.confirmation_block_height_delta,
             mined_blocks.len() as u64 - 1
         );
-        assert_eq!(circuit_input.utilized_blocks, mined_blocks.len() as u64);
+        assert_eq!(circuit_input.blocks.len(), mined_blocks.len());
 
         // Verify that the retarget block is set correctly
         assert_eq!(
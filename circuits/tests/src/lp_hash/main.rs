@@ -8,7 +8,7 @@ mod tests {
     fn test_assert_lp_hash() {
         let liquidity_providers = vec![LiquidityReservation {
             expected_sats: 1230,
-            script_pub_key: hex!("0014841b80d2cc75f5345c482af96294d04fdd66b2b7"),
+            script_pub_key: hex!("0014841b80d2cc75f5345c482af96294d04fdd66b2b7").to_vec(),
         }];
 
         let encoded_lps = encode_liquidity_providers(&liquidity_providers);
@@ -18,4 +18,49 @@ mod tests {
 
         assert_lp_hash(expected_vault_hash, &encoded_lps, 1);
     }
+
+    #[test]
+    fn test_assert_lp_hash_p2pkh() {
+        let liquidity_providers = vec![LiquidityReservation {
+            expected_sats: 1230,
+            script_pub_key: hex!("76a9148f4c1a3c4a1d5e6f708192a3b4c5d6e7f8091a2b88ac").to_vec(),
+        }];
+
+        let encoded_lps = encode_liquidity_providers(&liquidity_providers);
+
+        let expected_vault_hash: [u8; 32] =
+            hex!("d801599b9e301ee526fc8adccc1636e74ce3b451a0b2441fe6c4e15f99657e57");
+
+        assert_lp_hash(expected_vault_hash, &encoded_lps, 1);
+    }
+
+    #[test]
+    fn test_assert_lp_hash_p2tr() {
+        let liquidity_providers = vec![LiquidityReservation {
+            expected_sats: 1230,
+            script_pub_key: hex!(
+                "51201b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90a"
+            )
+            .to_vec(),
+        }];
+
+        let encoded_lps = encode_liquidity_providers(&liquidity_providers);
+
+        // 34 byte scripts spill into a second script word which is included in the hash
+        let expected_vault_hash: [u8; 32] =
+            hex!("b6e9981ad7eb1459bd2e1eef21eb4775512b32d04ff2af78fd8008a9a5a5431f");
+
+        assert_lp_hash(expected_vault_hash, &encoded_lps, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported LP script pub key")]
+    fn test_encode_unsupported_script_pub_key() {
+        let liquidity_providers = vec![LiquidityReservation {
+            expected_sats: 1230,
+            script_pub_key: hex!("6a0401020304").to_vec(),
+        }];
+
+        encode_liquidity_providers(&liquidity_providers);
+    }
 }
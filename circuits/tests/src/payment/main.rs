@@ -4,15 +4,19 @@ mod tests {
 
     use bitcoin::hashes::Hash;
 
-    use bitcoin::Block;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Address, Amount, Block, Network, Script, Transaction, TxIn, TxOut};
 
     use hex_literal::hex;
 
     use rift_core::btc_light_client::AsLittleEndianBytes;
     use rift_core::lp::{encode_liquidity_providers, LiquidityReservation};
     use rift_core::payment::{assert_bitcoin_payment, compint_to_u64};
+    use rift_lib::payment::{validate_payment, PaymentError, PaymentOutputs};
     use rift_lib::transaction::{
-        build_rift_payment_transaction, serialize_no_segwit, P2WPKHBitcoinWallet,
+        build_liquidity_reservation, build_rift_payment_transaction, find_payment_output_indices,
+        serialize_no_segwit, FundingUtxo, P2WPKHBitcoinWallet,
     };
     use rift_lib::{load_hex_bytes, to_hex_string};
 
@@ -23,6 +27,115 @@ mod tests {
         )
     }
 
+    fn get_test_lp_reservations() -> Vec<LiquidityReservation> {
+        vec![
+            LiquidityReservation {
+                expected_sats: 1000,
+                script_pub_key: hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa").to_vec(),
+            },
+            LiquidityReservation {
+                expected_sats: 2000,
+                script_pub_key: hex!("0014aa86191235be8883693452cf30daf854035b085b").to_vec(),
+            },
+            LiquidityReservation {
+                expected_sats: 3000,
+                script_pub_key: hex!("00146ab8f6c80b8a7dc1b90f7deb80e9b59ae16b7a5a").to_vec(),
+            },
+        ]
+    }
+
+    // builds a transaction paying each of `amounts` to the wallet, its outputs can be spent as
+    // funding utxos
+    fn build_funding_transaction(wallet: &P2WPKHBitcoinWallet, amounts: &[u64]) -> Transaction {
+        Transaction {
+            version: Version(2),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: amounts
+                .iter()
+                .map(|amount| TxOut {
+                    value: Amount::from_sat(*amount),
+                    script_pubkey: wallet.get_p2wpkh_script(),
+                })
+                .collect(),
+        }
+    }
+
+    // one of each supported payout script type
+    fn get_test_mixed_lp_reservations() -> Vec<LiquidityReservation> {
+        [
+            // P2TR
+            hex!("51201b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90a").to_vec(),
+            // P2WSH
+            hex!("00201b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90a").to_vec(),
+            // P2SH
+            hex!("a9148f4c1a3c4a1d5e6f708192a3b4c5d6e7f8091a2b87").to_vec(),
+            // P2PKH
+            hex!("76a9148f4c1a3c4a1d5e6f708192a3b4c5d6e7f8091a2b88ac").to_vec(),
+            // P2WPKH
+            hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa").to_vec(),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, script_pub_key)| {
+            let address =
+                Address::from_script(Script::from_bytes(&script_pub_key), Network::Bitcoin)
+                    .unwrap();
+            let reservation = build_liquidity_reservation(1000 * (i as u64 + 1), &address);
+            assert_eq!(reservation.script_pub_key, script_pub_key);
+            reservation
+        })
+        .collect()
+    }
+
+    fn assert_multi_input_payment(input_count: usize) {
+        assert_synthetic_payment(&get_test_lp_reservations(), input_count);
+    }
+
+    fn assert_synthetic_payment(lp_reservations: &[LiquidityReservation], input_count: usize) {
+        let wallet = get_test_wallet();
+        let order_nonce = hex!("f0ad57e677a89d2c2aaae4c5fd52ba20c63c0a05c916619277af96435f874c64");
+
+        // split the payment evenly so every input is required to cover it
+        let total_sats: u64 = lp_reservations.iter().map(|lp| lp.expected_sats).sum();
+        let fee_sats = 1100;
+        let per_input_sats = (total_sats + fee_sats) / input_count as u64 + 1;
+        let funding_transaction =
+            build_funding_transaction(&wallet, &vec![per_input_sats; input_count]);
+        let funding_txid = funding_transaction
+            .compute_txid()
+            .to_byte_array()
+            .to_little_endian();
+        let funding_utxos = (0..input_count as u32)
+            .map(|vout| FundingUtxo::new(funding_txid, &funding_transaction, vout))
+            .collect::<Vec<_>>();
+
+        let unbroadcast_txn = build_rift_payment_transaction(
+            order_nonce,
+            lp_reservations,
+            &funding_utxos,
+            &wallet,
+            fee_sats,
+        );
+        assert_eq!(unbroadcast_txn.input.len(), input_count);
+        assert!(unbroadcast_txn
+            .input
+            .iter()
+            .all(|input| input.witness.len() == 2));
+
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&unbroadcast_txn, lp_reservations, order_nonce);
+        let txn_data_no_segwit = serialize_no_segwit(&unbroadcast_txn);
+        assert_bitcoin_payment(
+            txn_data_no_segwit.as_slice(),
+            encode_liquidity_providers(lp_reservations).to_vec(),
+            order_nonce,
+            lp_reservations.len() as u64,
+            &lp_output_indices,
+            nonce_output_index,
+        )
+    }
+
     #[test]
     fn test_compint() {
         assert!(0x01 == compint_to_u64([0x01_u8]));
@@ -48,20 +161,7 @@ mod tests {
         let wallet = get_test_wallet();
 
         let order_nonce = hex!("f0ad57e677a89d2c2aaae4c5fd52ba20c63c0a05c916619277af96435f874c64");
-        let lp_reservations: Vec<LiquidityReservation> = vec![
-            LiquidityReservation {
-                expected_sats: 1000,
-                script_pub_key: hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa"),
-            },
-            LiquidityReservation {
-                expected_sats: 2000,
-                script_pub_key: hex!("0014aa86191235be8883693452cf30daf854035b085b"),
-            },
-            LiquidityReservation {
-                expected_sats: 3000,
-                script_pub_key: hex!("00146ab8f6c80b8a7dc1b90f7deb80e9b59ae16b7a5a"),
-            },
-        ];
+        let lp_reservations = get_test_lp_reservations();
 
         let utilized_block_height = 854136;
         let utilized_block_hash =
@@ -101,9 +201,11 @@ mod tests {
         let unbroadcast_txn = build_rift_payment_transaction(
             order_nonce,
             &lp_reservations,
-            utilized_txid,
-            utilized_transaction,
-            txvout,
+            &[FundingUtxo::new(
+                utilized_txid,
+                utilized_transaction,
+                txvout,
+            )],
             &wallet,
             1100,
         );
@@ -114,11 +216,317 @@ mod tests {
             to_hex_string(txn_data_no_segwit.as_slice())
         );
 
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&unbroadcast_txn, &lp_reservations, order_nonce);
         assert_bitcoin_payment(
             txn_data_no_segwit.as_slice(),
             encode_liquidity_providers(&lp_reservations).to_vec(),
             order_nonce,
             lp_reservations.len() as u64,
+            &lp_output_indices,
+            nonce_output_index,
         )
     }
+
+    #[test]
+    fn test_two_input_payment() {
+        assert_multi_input_payment(2);
+    }
+
+    #[test]
+    fn test_n_input_payment() {
+        for input_count in 3..=10 {
+            assert_multi_input_payment(input_count);
+        }
+    }
+
+    #[test]
+    fn test_mixed_script_type_payment() {
+        assert_synthetic_payment(&get_test_mixed_lp_reservations(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wrong_script_type_payment() {
+        let wallet = get_test_wallet();
+        let order_nonce = hex!("f0ad57e677a89d2c2aaae4c5fd52ba20c63c0a05c916619277af96435f874c64");
+        let lp_reservations = get_test_mixed_lp_reservations();
+
+        let funding_transaction = build_funding_transaction(&wallet, &[100_000]);
+        let funding_txid = funding_transaction
+            .compute_txid()
+            .to_byte_array()
+            .to_little_endian();
+        let unbroadcast_txn = build_rift_payment_transaction(
+            order_nonce,
+            &lp_reservations,
+            &[FundingUtxo::new(funding_txid, &funding_transaction, 0)],
+            &wallet,
+            1100,
+        );
+
+        // same hash committed as P2WSH instead of P2TR
+        let mut expected_reservations = lp_reservations.clone();
+        expected_reservations[0].script_pub_key[0] = 0x00;
+
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&unbroadcast_txn, &lp_reservations, order_nonce);
+        assert_bitcoin_payment(
+            serialize_no_segwit(&unbroadcast_txn).as_slice(),
+            encode_liquidity_providers(&expected_reservations).to_vec(),
+            order_nonce,
+            expected_reservations.len() as u64,
+            &lp_output_indices,
+            nonce_output_index,
+        )
+    }
+
+    fn build_test_payment_transaction(lp_reservations: &[LiquidityReservation]) -> Transaction {
+        let wallet = get_test_wallet();
+        let funding_transaction = build_funding_transaction(&wallet, &[100_000]);
+        let funding_txid = funding_transaction
+            .compute_txid()
+            .to_byte_array()
+            .to_little_endian();
+        build_rift_payment_transaction(
+            get_test_order_nonce(),
+            lp_reservations,
+            &[FundingUtxo::new(funding_txid, &funding_transaction, 0)],
+            &wallet,
+            1100,
+        )
+    }
+
+    fn get_test_order_nonce() -> [u8; 32] {
+        hex!("f0ad57e677a89d2c2aaae4c5fd52ba20c63c0a05c916619277af96435f874c64")
+    }
+
+    fn assert_payment_with_indices(
+        transaction: &Transaction,
+        lp_reservations: &[LiquidityReservation],
+        lp_output_indices: &[u64],
+        nonce_output_index: u64,
+    ) {
+        assert_bitcoin_payment(
+            serialize_no_segwit(transaction).as_slice(),
+            encode_liquidity_providers(lp_reservations).to_vec(),
+            get_test_order_nonce(),
+            lp_reservations.len() as u64,
+            lp_output_indices,
+            nonce_output_index,
+        )
+    }
+
+    #[test]
+    fn test_bip69_ordered_payment_with_extra_outputs() {
+        let lp_reservations = get_test_mixed_lp_reservations();
+        let mut transaction = build_test_payment_transaction(&lp_reservations);
+
+        // an unrelated output added by the wallet, then BIP69 output ordering
+        transaction.output.push(TxOut {
+            value: Amount::from_sat(2500),
+            script_pubkey: get_test_wallet().get_p2wpkh_script(),
+        });
+        transaction.output.sort_by(|a, b| {
+            (a.value, a.script_pubkey.as_bytes()).cmp(&(b.value, b.script_pubkey.as_bytes()))
+        });
+
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&transaction, &lp_reservations, get_test_order_nonce());
+        assert_eq!(nonce_output_index, 0);
+        assert_ne!(
+            lp_output_indices,
+            (0..lp_reservations.len() as u64).collect::<Vec<_>>()
+        );
+
+        assert_payment_with_indices(
+            &transaction,
+            &lp_reservations,
+            &lp_output_indices,
+            nonce_output_index,
+        );
+    }
+
+    #[test]
+    fn test_identical_lp_reservations_use_distinct_outputs() {
+        let lp_reservations = vec![get_test_lp_reservations()[0].clone(); 2];
+        let transaction = build_test_payment_transaction(&lp_reservations);
+
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&transaction, &lp_reservations, get_test_order_nonce());
+        assert_eq!(lp_output_indices, vec![0, 1]);
+
+        assert_payment_with_indices(
+            &transaction,
+            &lp_reservations,
+            &lp_output_indices,
+            nonce_output_index,
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_duplicate_lp_output_index() {
+        let lp_reservations = vec![get_test_lp_reservations()[0].clone(); 2];
+        let transaction = build_test_payment_transaction(&lp_reservations[..1]);
+
+        // both reservations claim the single matching output
+        assert_payment_with_indices(&transaction, &lp_reservations, &[0, 0], 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nonce_output_index_claimed_by_lp() {
+        let lp_reservations = get_test_lp_reservations();
+        let transaction = build_test_payment_transaction(&lp_reservations);
+
+        assert_payment_with_indices(&transaction, &lp_reservations, &[0, 1, 2], 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wrong_lp_output_index() {
+        let lp_reservations = get_test_lp_reservations();
+        let transaction = build_test_payment_transaction(&lp_reservations);
+
+        assert_payment_with_indices(&transaction, &lp_reservations, &[1, 0, 2], 3);
+    }
+
+    #[test]
+    fn test_validate_payment() {
+        let lp_reservations = get_test_mixed_lp_reservations();
+        let transaction = build_test_payment_transaction(&lp_reservations);
+
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&transaction, &lp_reservations, get_test_order_nonce());
+        assert_eq!(
+            validate_payment(&transaction, &lp_reservations, get_test_order_nonce()),
+            Ok(PaymentOutputs {
+                lp_output_indices: lp_output_indices.clone(),
+                nonce_output_index,
+            })
+        );
+        assert_payment_with_indices(
+            &transaction,
+            &lp_reservations,
+            &lp_output_indices,
+            nonce_output_index,
+        );
+    }
+
+    #[test]
+    fn test_validate_repeated_script_payment() {
+        let script_pub_key = get_test_lp_reservations()[0].script_pub_key.clone();
+        let lp_reservations = vec![
+            LiquidityReservation {
+                expected_sats: 1000,
+                script_pub_key: script_pub_key.clone(),
+            },
+            LiquidityReservation {
+                expected_sats: 2000,
+                script_pub_key,
+            },
+        ];
+        // the second LP is paid first, so the first output to the shared script isn't the first
+        // LP's
+        let mut transaction = build_test_payment_transaction(&lp_reservations);
+        transaction.output.swap(0, 1);
+
+        let (lp_output_indices, nonce_output_index) =
+            find_payment_output_indices(&transaction, &lp_reservations, get_test_order_nonce());
+        assert_eq!(lp_output_indices, vec![1, 0]);
+        assert_eq!(
+            validate_payment(&transaction, &lp_reservations, get_test_order_nonce()),
+            Ok(PaymentOutputs {
+                lp_output_indices: lp_output_indices.clone(),
+                nonce_output_index,
+            })
+        );
+        assert_payment_with_indices(
+            &transaction,
+            &lp_reservations,
+            &lp_output_indices,
+            nonce_output_index,
+        );
+
+        // the underpaid output is reported, not the one paying the second LP
+        let mut underpaid = transaction.clone();
+        underpaid.output[1].value = Amount::from_sat(999);
+        assert_eq!(
+            validate_payment(&underpaid, &lp_reservations, get_test_order_nonce()),
+            Err(PaymentError::WrongAmount {
+                lp: 0,
+                expected_sats: 1000,
+                paid_sats: 999,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_payments() {
+        let lp_reservations = get_test_lp_reservations();
+        let transaction = build_test_payment_transaction(&lp_reservations);
+        let validate = |transaction: &Transaction, lp_reservations: &[LiquidityReservation]| {
+            validate_payment(transaction, lp_reservations, get_test_order_nonce()).unwrap_err()
+        };
+
+        let mut underpaid = transaction.clone();
+        underpaid.output[1].value = Amount::from_sat(1999);
+        assert_eq!(
+            validate(&underpaid, &lp_reservations),
+            PaymentError::WrongAmount {
+                lp: 1,
+                expected_sats: 2000,
+                paid_sats: 1999,
+            }
+        );
+
+        // a single output can't pay two identical reservations
+        let duplicated = vec![lp_reservations[0].clone(); 2];
+        assert_eq!(
+            validate(
+                &build_test_payment_transaction(&duplicated[..1]),
+                &duplicated
+            ),
+            PaymentError::MissingLpOutput { lp: 1 }
+        );
+
+        let mut unsupported = lp_reservations.clone();
+        unsupported[2].script_pub_key.truncate(21);
+        assert_eq!(
+            validate(&transaction, &unsupported),
+            PaymentError::UnsupportedScriptPubKey { lp: 2 }
+        );
+
+        let mut without_nonce = transaction.clone();
+        without_nonce.output.remove(3);
+        assert_eq!(
+            validate(&without_nonce, &lp_reservations),
+            PaymentError::MissingNonceOutput
+        );
+
+        let mut too_few_outputs = transaction.clone();
+        too_few_outputs.output.truncate(3);
+        assert_eq!(
+            validate(&too_few_outputs, &lp_reservations),
+            PaymentError::TooFewOutputs {
+                count: 3,
+                lp_count: 3,
+            }
+        );
+
+        let mut without_inputs = transaction.clone();
+        without_inputs.input.clear();
+        assert_eq!(
+            validate(&without_inputs, &lp_reservations),
+            PaymentError::NoInputs
+        );
+
+        let mut too_large = transaction.clone();
+        too_large.input = vec![transaction.input[0].clone(); 25];
+        assert!(matches!(
+            validate(&too_large, &lp_reservations),
+            PaymentError::TransactionTooLarge { .. }
+        ));
+    }
 }
@@ -0,0 +1,284 @@
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::block::{Header, Version as BlockVersion};
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::hashes::Hash;
+    use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_PUSHNUM_1, OP_RETURN};
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+    use bitcoin::sighash::SighashCache;
+    use bitcoin::transaction::Version;
+    use bitcoin::{
+        Amount, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence, Transaction, TxIn,
+        TxMerkleNode, TxOut, Witness,
+    };
+
+    use hex_literal::hex;
+
+    use rift_core::chain_params::{ChainParams, DEFAULT_SIGNET_CHALLENGE};
+    use rift_core::signet::assert_signet_block_solution;
+    use rift_lib::{build_signet_block_solution, AsRiftOptimizedBlock};
+
+    const SIGNET_HEADER: [u8; 4] = hex!("ecc7daa2");
+
+    fn push_bytes(bytes: &[u8]) -> PushBytesBuf {
+        PushBytesBuf::try_from(bytes.to_vec()).unwrap()
+    }
+
+    fn get_test_secret_key() -> SecretKey {
+        SecretKey::from_slice(&hex!(
+            "ef7a6f48e45fc4af1ddfc9047af0e06f550bca661869455d5fc05812ef1a9593"
+        ))
+        .unwrap()
+    }
+
+    fn get_test_public_key(secret_key: &SecretKey) -> [u8; 33] {
+        PublicKey::from_secret_key(&Secp256k1::new(), secret_key).serialize()
+    }
+
+    fn p2pk_challenge(public_key: &[u8]) -> ScriptBuf {
+        Builder::new()
+            .push_slice(push_bytes(public_key))
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    fn one_of_two_challenge(first_key: &[u8], second_key: &[u8]) -> ScriptBuf {
+        Builder::new()
+            .push_int(1)
+            .push_slice(push_bytes(first_key))
+            .push_slice(push_bytes(second_key))
+            .push_int(2)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    fn build_coinbase(signet_push: &[u8]) -> Transaction {
+        let witness_commitment = Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(push_bytes(
+                &[hex!("aa21a9ed").as_slice(), &[7u8; 32]].concat(),
+            ))
+            .push_slice(push_bytes(signet_push))
+            .into_script();
+
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(100).into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[[0u8; 32]]),
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(5_000_000_000),
+                    script_pubkey: ScriptBuf::from_bytes(
+                        hex!("001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa").to_vec(),
+                    ),
+                },
+                TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: witness_commitment,
+                },
+            ],
+        }
+    }
+
+    fn build_block(coinbase: Transaction) -> Block {
+        let filler_transactions = (1..4).map(|i| Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_consensus(i),
+            input: vec![TxIn {
+                previous_output: OutPoint::new(bitcoin::Txid::all_zeros(), i),
+                ..Default::default()
+            }],
+            output: vec![],
+        });
+
+        let mut block = Block {
+            header: Header {
+                version: BlockVersion::from_consensus(0x20000000),
+                prev_blockhash: BlockHash::from_byte_array([3u8; 32]),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 1_700_000_000,
+                bits: CompactTarget::from_consensus(0x1e0377ae),
+                nonce: 0,
+            },
+            txdata: std::iter::once(coinbase)
+                .chain(filler_transactions)
+                .collect(),
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        block
+    }
+
+    // Legacy SIGHASH_ALL digest of BIP325's virtual spending transaction
+    fn compute_signet_sighash(block: &Block, challenge: &ScriptBuf) -> [u8; 32] {
+        let block_data = [
+            serialize(&block.header.version),
+            serialize(&block.header.prev_blockhash),
+            serialize(&block.header.merkle_root),
+            serialize(&block.header.time),
+        ]
+        .concat();
+
+        let to_spend = Transaction {
+            version: Version(0),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new()
+                    .push_int(0)
+                    .push_slice(push_bytes(&block_data))
+                    .into_script(),
+                sequence: Sequence::ZERO,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: challenge.clone(),
+            }],
+        };
+        let to_sign = Transaction {
+            version: Version(0),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ZERO,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+            }],
+        };
+
+        SighashCache::new(&to_sign)
+            .legacy_signature_hash(0, challenge, 1)
+            .unwrap()
+            .to_byte_array()
+    }
+
+    // Builds a block whose signet solution is the given secret key's signature, in the scriptSig
+    // layout `build_script_sig` produces from the signature
+    fn build_signed_block(
+        challenge: &ScriptBuf,
+        secret_key: &SecretKey,
+        build_script_sig: impl Fn(&[u8]) -> ScriptBuf,
+    ) -> Block {
+        // the signer commits to the block with the solution stripped from the coinbase
+        let unsigned_block = build_block(build_coinbase(&SIGNET_HEADER));
+        let sighash = compute_signet_sighash(&unsigned_block, challenge);
+
+        let signature = Secp256k1::new()
+            .sign_ecdsa(&Message::from_digest(sighash), secret_key)
+            .serialize_der();
+        let script_sig = build_script_sig(&[signature.as_ref(), &[0x01]].concat());
+
+        let solution = [serialize(&script_sig), serialize(&Witness::new())].concat();
+        build_block(build_coinbase(&[&SIGNET_HEADER[..], &solution].concat()))
+    }
+
+    fn assert_block_solution(block: &Block, challenge: &ScriptBuf) {
+        assert_signet_block_solution(
+            &block.as_rift_optimized_block(1),
+            &build_signet_block_solution(block),
+            challenge.as_bytes(),
+        );
+    }
+
+    #[test]
+    fn test_default_signet_challenge() {
+        let chain_params = ChainParams::default_signet();
+        assert!(chain_params.is_signet());
+        assert_eq!(chain_params.signet_challenge(), DEFAULT_SIGNET_CHALLENGE);
+        assert_ne!(chain_params.hash(), ChainParams::mainnet().hash());
+    }
+
+    #[test]
+    fn test_signet_p2pk_challenge() {
+        let secret_key = get_test_secret_key();
+        let challenge = p2pk_challenge(&get_test_public_key(&secret_key));
+        let block = build_signed_block(&challenge, &secret_key, |signature| {
+            Builder::new()
+                .push_slice(push_bytes(signature))
+                .into_script()
+        });
+        assert_block_solution(&block, &challenge);
+    }
+
+    #[test]
+    fn test_signet_multisig_challenge() {
+        let secret_key = get_test_secret_key();
+        let other_key = get_test_public_key(&SecretKey::from_slice(&[9u8; 32]).unwrap());
+        let challenge = one_of_two_challenge(&other_key, &get_test_public_key(&secret_key));
+        let block = build_signed_block(&challenge, &secret_key, |signature| {
+            Builder::new()
+                .push_int(0)
+                .push_slice(push_bytes(signature))
+                .into_script()
+        });
+        assert_block_solution(&block, &challenge);
+    }
+
+    #[test]
+    fn test_signet_op_true_challenge() {
+        let challenge = Builder::new().push_opcode(OP_PUSHNUM_1).into_script();
+        let block = build_block(build_coinbase(&[1u8]));
+        assert_block_solution(&block, &challenge);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid signet block signature")]
+    fn test_signet_fails_on_wrong_signer() {
+        let challenge = p2pk_challenge(&get_test_public_key(&get_test_secret_key()));
+        let block = build_signed_block(
+            &challenge,
+            &SecretKey::from_slice(&[9u8; 32]).unwrap(),
+            |signature| {
+                Builder::new()
+                    .push_slice(push_bytes(signature))
+                    .into_script()
+            },
+        );
+        assert_block_solution(&block, &challenge);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid signet block signature")]
+    fn test_signet_fails_on_modified_header() {
+        let secret_key = get_test_secret_key();
+        let challenge = p2pk_challenge(&get_test_public_key(&secret_key));
+        let mut block = build_signed_block(&challenge, &secret_key, |signature| {
+            Builder::new()
+                .push_slice(push_bytes(signature))
+                .into_script()
+        });
+        block.header.time += 1;
+        assert_block_solution(&block, &challenge);
+    }
+
+    #[test]
+    #[should_panic(expected = "Signet coinbase not in block")]
+    fn test_signet_fails_on_wrong_coinbase() {
+        let secret_key = get_test_secret_key();
+        let challenge = p2pk_challenge(&get_test_public_key(&secret_key));
+        let block = build_signed_block(&challenge, &secret_key, |signature| {
+            Builder::new()
+                .push_slice(push_bytes(signature))
+                .into_script()
+        });
+
+        let mut solution = build_signet_block_solution(&block);
+        solution.coinbase_merkle_branch[0] = [0u8; 32];
+        assert_signet_block_solution(
+            &block.as_rift_optimized_block(1),
+            &solution,
+            challenge.as_bytes(),
+        );
+    }
+}
@@ -146,7 +146,11 @@ contract DeployRiftExchange is Script {
         // Define the constructor arguments
         address verifierContractAddress = addresses.verifierContractAddress;
         address depositTokenAddress = addresses.depositTokenAddress;
-        bytes32 verificationKeyHash = bytes32(0x00334569e4b8059d7b1a70c011d7d92b5d3ce28f2148b32cd2396aeda3ae5af1);
+        // both printed by `cargo run --bin vkey` in circuits/script, they change with every program build
+        bytes32 verificationKeyHash = vm.envBytes32("CIRCUIT_VERIFICATION_KEY");
+        bytes32 verificationKeyDigest = vm.envBytes32("CIRCUIT_VERIFICATION_KEY_DIGEST");
+        // ChainParams::mainnet().hash(), see circuits/core/src/chain_params.rs
+        bytes32 chainParamsHash = bytes32(0x65cb0f1fa5e67bf77b6795828d1ea4773b328accce41ec1d5388664811ae14ac);
         address payable initialFeeRouterAddress = payable(address(0xfEe8d79961c529E06233fbF64F96454c2656BFEE)); // TODO: update this with the actual fee router address
 
         // Define initial permissioned hypernodes
@@ -169,7 +173,7 @@ contract DeployRiftExchange is Script {
                 verifierContractAddress,
                 depositTokenAddress,
                 initialFeeRouterAddress,
-                owner,
+                msg.sender,
                 verificationKeyHash,
                 initialPermissionedHypernodes
             )
@@ -177,6 +181,10 @@ contract DeployRiftExchange is Script {
 
         address proxy = Upgrades.deployUUPSProxy("RiftExchange.sol:RiftExchange", initializeData);
 
+        // initializeV2 is owner only, so ownership is handed over once it has run
+        RiftExchange(proxy).initializeV2(chainParamsHash, verificationKeyDigest);
+        RiftExchange(proxy).transferOwnership(owner);
+
         console.log("RiftExchange proxy deployed at:", proxy);
 
         console.log("Deployment script finished.");
@@ -23,6 +23,7 @@ error NotApprovedHypernode();
 error AmountToReserveTooLow(uint256 index);
 error TransferFailed();
 error InvalidFeeRouterAddress();
+error InvalidBtcPayoutLockingScript();
 
 interface IERC20 {
     function transferFrom(address sender, address recipient, uint256 amount) external returns (bool);
@@ -77,7 +78,7 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         uint256 unreservedBalance; // in token's smallest unit (wei, μUSDT, etc) - true balance = unreservedBalance + sum(ReservationState.Created && expired SwapReservations on this vault)
         uint256 withdrawnAmount; // in token's smallest unit (wei, μUSDT, etc)
         uint64 exchangeRate; // amount of token's smallest unit (buffered to 18 digits) per 1 sat
-        bytes22 btcPayoutLockingScript;
+        bytes22 btcPayoutLockingScript; // P2WPKH only, zero for scripts kept in btcPayoutLockingScripts
     }
 
     struct ProofPublicInputs {
@@ -93,6 +94,12 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         bytes32[] block_hashes;
         uint256[] block_chainworks;
         bool is_transaction_proof;
+        bytes32 chain_params_hash;
+    }
+
+    struct AggregatedProofPublicInputs {
+        ProofPublicInputs public_inputs;
+        bytes32 chunk_verification_key_digest;
     }
 
     // --------- CONSTANTS --------- //
@@ -115,6 +122,9 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
     SwapReservation[] public swapReservations;
     mapping(address => LiquidityProvider) liquidityProviders;
     mapping(address => bool) public permissionedHypernodes;
+    bytes32 public chainParamsHash; // sha256 of the bitcoin network params proofs are checked against
+    bytes32 public circuitVerificationKeyDigest; // recursion digest of the circuit, chunks of aggregated block proofs are checked against it
+    mapping(uint256 => bytes) btcPayoutLockingScripts; // P2WSH, P2TR, P2SH and P2PKH payout scripts by vault index, see getBtcPayoutLockingScript
 
     // --------- EVENTS --------- //
     event LiquidityDeposited(address indexed depositor, uint256 depositVaultIndex, uint256 amount, uint64 exchangeRate);
@@ -186,23 +196,37 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         }
     }
 
+    // sets the state added since the first release, existing proxies call it through upgradeToAndCall and new ones
+    // right after initialize
+    function initializeV2(
+        bytes32 initialChainParamsHash,
+        bytes32 verificationKeyDigest
+    ) public reinitializer(2) onlyOwner {
+        chainParamsHash = initialChainParamsHash;
+        circuitVerificationKeyDigest = verificationKeyDigest;
+    }
+
     //--------- WRITE FUNCTIONS ---------//
     function depositLiquidity(
         uint256 depositAmount,
         uint64 exchangeRate,
-        bytes22 btcPayoutLockingScript
+        bytes memory btcPayoutLockingScript
     ) public newDepositsNotPaused {
-        // [0] validate btc exchange rate
+        // [0] validate btc exchange rate and payout script
         if (exchangeRate == 0) {
             revert InvalidExchangeRate();
         }
+        if (!isSupportedLockingScript(btcPayoutLockingScript)) {
+            revert InvalidBtcPayoutLockingScript();
+        }
 
         // [1] create new liquidity provider if it doesn't exist
         if (liquidityProviders[msg.sender].depositVaultIndexes.length == 0) {
             liquidityProviders[msg.sender] = LiquidityProvider({depositVaultIndexes: new uint256[](0)});
         }
 
-        // [2] create new deposit vault
+        // [2] create new deposit vault, only P2WPKH scripts fit in the vault itself
+        bool isP2WPKH = btcPayoutLockingScript.length == 22;
         depositVaults.push(
             DepositVault({
                 owner: msg.sender,
@@ -211,9 +235,12 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
                 unreservedBalance: depositAmount,
                 withdrawnAmount: 0,
                 exchangeRate: exchangeRate,
-                btcPayoutLockingScript: btcPayoutLockingScript
+                btcPayoutLockingScript: isP2WPKH ? bytes22(btcPayoutLockingScript) : bytes22(0)
             })
         );
+        if (!isP2WPKH) {
+            btcPayoutLockingScripts[depositVaults.length - 1] = btcPayoutLockingScript;
+        }
 
         // [3] add deposit vault index to liquidity provider
         addDepositVaultIndexToLP(msg.sender, depositVaults.length - 1);
@@ -271,6 +298,9 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
                     btcPayoutLockingScript: vault.btcPayoutLockingScript
                 })
             );
+            if (btcPayoutLockingScripts[globalVaultIndex].length != 0) {
+                btcPayoutLockingScripts[newVaultIndex] = btcPayoutLockingScripts[globalVaultIndex];
+            }
 
             vault.withdrawnAmount += unreservedBalance;
             vault.unreservedBalance = 0;
@@ -355,12 +385,10 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         // [4] compute the aggregated vault hash && ensure there is enough liquidity in each vault
         bytes32 vaultHash;
         for (uint256 i = 0; i < vaultIndexesToReserve.length; i++) {
-            vaultHash = sha256(
-                abi.encode(
-                    expectedSatsOutputArray[i],
-                    depositVaults[vaultIndexesToReserve[i]].btcPayoutLockingScript,
-                    vaultHash
-                )
+            vaultHash = hashLiquidityProvider(
+                expectedSatsOutputArray[i],
+                getBtcPayoutLockingScript(vaultIndexesToReserve[i]),
+                vaultHash
             );
 
             if (amountsToReserve[i] > depositVaults[vaultIndexesToReserve[i]].unreservedBalance) {
@@ -422,7 +450,8 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
                 confirmation_block_height_delta: confirmationBlockHeight - proposedBlockHeight,
                 block_hashes: blockHashes,
                 block_chainworks: blockChainworks,
-                is_transaction_proof: false
+                is_transaction_proof: false,
+                chain_params_hash: chainParamsHash
             });
     }
 
@@ -451,7 +480,8 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
                 confirmation_block_height_delta: confirmationBlockHeight - proposedBlockHeight,
                 block_hashes: blockHashes,
                 block_chainworks: blockChainworks,
-                is_transaction_proof: isTransactionProof
+                is_transaction_proof: isTransactionProof,
+                chain_params_hash: chainParamsHash
             });
     }
 
@@ -574,6 +604,40 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         );
     }
 
+    // proves a header range longer than a single proof allows, by aggregating block proofs of its chunks
+    function proveBlocksAggregated(
+        uint32 safeBlockHeight,
+        uint64 confirmationBlockHeight,
+        bytes32[] memory blockHashes,
+        uint256[] memory blockChainworks,
+        bytes calldata proof
+    ) external {
+        // [0] craft public inputs
+        bytes memory publicInputs = abi.encode(
+            AggregatedProofPublicInputs({
+                public_inputs: buildBlockProofPublicInputs(
+                    safeBlockHeight,
+                    confirmationBlockHeight,
+                    blockHashes,
+                    blockChainworks
+                ),
+                chunk_verification_key_digest: circuitVerificationKeyDigest
+            })
+        );
+
+        // [1] verify proof (will revert if invalid)
+        verifierContract.verifyProof(circuitVerificationKey, publicInputs, proof);
+
+        // [2] add verified blocks to block hash storage contract
+        addBlock(
+            safeBlockHeight,
+            confirmationBlockHeight - MINIMUM_CONFIRMATION_DELTA,
+            confirmationBlockHeight,
+            blockHashes,
+            blockChainworks
+        );
+    }
+
     // --------- ONLY OWNER --------- //
     function updateNewLiquidityDepositsPaused(bool newPauseState) external onlyOwner {
         isDepositNewLiquidityPaused = newPauseState;
@@ -629,6 +693,14 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         return depositVaults[depositIndex];
     }
 
+    function getBtcPayoutLockingScript(uint256 depositIndex) public view returns (bytes memory) {
+        bytes memory script = btcPayoutLockingScripts[depositIndex];
+        if (script.length == 0) {
+            return abi.encodePacked(depositVaults[depositIndex].btcPayoutLockingScript);
+        }
+        return script;
+    }
+
     //--------- INTERNAL FUNCTIONS ---------//
 
     function cleanUpDeadSwapReservations(uint256[] memory expiredSwapReservationIndexes) internal {
@@ -669,6 +741,26 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         liquidityProviders[lpAddress].depositVaultIndexes.push(vaultIndex);
     }
 
+    // mirrors script_pub_key_len in circuits/core/src/lp.rs, the circuit can't prove payments to any other script
+    function isSupportedLockingScript(bytes memory script) internal pure returns (bool) {
+        if (script.length == 22) return script[0] == 0x00 && script[1] == 0x14; // P2WPKH
+        if (script.length == 34) return (script[0] == 0x00 || script[0] == 0x51) && script[1] == 0x20; // P2WSH, P2TR
+        if (script.length == 23) return script[0] == 0xa9 && script[1] == 0x14; // P2SH
+        if (script.length == 25) return script[0] == 0x76 && script[1] == 0xa9 && script[2] == 0x14; // P2PKH
+        return false;
+    }
+
+    // the script is right padded to one word, or two when it is longer than 32 bytes, so scripts that fit in a word
+    // hash the same as abi.encode(uint64, bytesN, bytes32), see build_hashable_chunk in circuits/core/src/lp.rs
+    function hashLiquidityProvider(
+        uint64 expectedSats,
+        bytes memory script,
+        bytes32 vaultHash
+    ) internal pure returns (bytes32) {
+        uint256 paddedLength = script.length > 32 ? 64 : 32;
+        return sha256(abi.encodePacked(uint256(expectedSats), script, new bytes(paddedLength - script.length), vaultHash));
+    }
+
     function bufferTo18Decimals(uint256 amount, uint8 decimals) internal pure returns (uint256) {
         if (decimals < 18) {
             return amount * (10 ** (18 - decimals));
@@ -680,9 +772,17 @@ contract RiftExchange is BlockHashStorageUpgradeable, OwnableUpgradeable, UUPSUp
         circuitVerificationKey = newVerificationKey;
     }
 
+    function updateCircuitVerificationKeyDigest(bytes32 newVerificationKeyDigest) public onlyOwner {
+        circuitVerificationKeyDigest = newVerificationKeyDigest;
+    }
+
     function updateVerifierContract(address newVerifierContractAddress) public onlyOwner {
         verifierContract = ISP1Verifier(newVerifierContractAddress);
     }
 
+    function updateChainParamsHash(bytes32 newChainParamsHash) public onlyOwner {
+        chainParamsHash = newChainParamsHash;
+    }
+
     function _authorizeUpgrade(address newImplementation) internal override onlyOwner {}
 }
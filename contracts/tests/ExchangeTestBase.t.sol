@@ -32,6 +32,7 @@ contract ExchangeTestBase is Test, TestBlocks {
     bytes4 constant NOT_VAULT_OWNER = bytes4(keccak256("NotVaultOwner()"));
     bytes4 constant DEPOSIT_TOO_HIGH = bytes4(keccak256("DepositTooHigh()"));
     bytes4 constant INVALID_BTC_PAYOUT_ADDRESS = bytes4(keccak256("InvalidBitcoinAddress()"));
+    bytes4 constant INVALID_BTC_PAYOUT_LOCKING_SCRIPT = bytes4(keccak256("InvalidBtcPayoutLockingScript()"));
     bytes4 constant RESERVATION_FEE_TOO_LOW = bytes4(keccak256("ReservationFeeTooLow()"));
     bytes4 constant INVALID_UPDATE_WITH_ACTIVE_RESERVATIONS =
         bytes4(keccak256("InvalidUpdateWithActiveReservations()"));
@@ -79,6 +80,10 @@ contract ExchangeTestBase is Test, TestBlocks {
 
         // Create an instance of RiftExchange pointing to the proxy contract
         riftExchange = RiftExchange(proxyAddress);
+        riftExchange.initializeV2(
+            bytes32(0x65cb0f1fa5e67bf77b6795828d1ea4773b328accce41ec1d5388664811ae14ac),
+            hex"deadbeef"
+        );
 
         riftExchange.addPermissionedHypernode(hypernode1);
     }
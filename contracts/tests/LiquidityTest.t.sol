@@ -34,7 +34,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         console.log("Starting deposit liquidity...");
         console.log("testaddress USDT balance: ", usdt.balanceOf(testAddress));
 
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 exchangeRate = 2557666;
         uint256 depositAmount = 1_000_000_000_000_000e6; // 1b USDT
 
@@ -54,6 +54,38 @@ contract LiquidityDepositTest is ExchangeTestBase {
         vm.stopPrank();
     }
 
+    function testDepositLiquidityTaprootScript() public {
+        deal(address(usdt), testAddress, 1000e6);
+        vm.startPrank(testAddress);
+        usdt.approve(address(riftExchange), 1000e6);
+
+        bytes memory btcPayoutLockingScript = hex"5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c";
+        riftExchange.depositLiquidity(1000e6, 2557666, btcPayoutLockingScript);
+
+        uint256 vaultIndex = riftExchange.getDepositVaultsLength() - 1;
+        RiftExchange.DepositVault memory deposit = riftExchange.getDepositVault(vaultIndex);
+        assertEq(deposit.btcPayoutLockingScript, bytes22(0), "Taproot script should not be stored in the vault");
+        assertEq(
+            riftExchange.getBtcPayoutLockingScript(vaultIndex),
+            btcPayoutLockingScript,
+            "BTC payout locking script mismatch"
+        );
+
+        vm.stopPrank();
+    }
+
+    function testDepositLiquidityUnsupportedScript() public {
+        deal(address(usdt), testAddress, 1000e6);
+        vm.startPrank(testAddress);
+        usdt.approve(address(riftExchange), 1000e6);
+
+        // OP_RETURN can't be paid to
+        vm.expectRevert(INVALID_BTC_PAYOUT_LOCKING_SCRIPT);
+        riftExchange.depositLiquidity(1000e6, 2557666, hex"6a14841b80d2cc75f5345c482af96294d04fdd66b2b7");
+
+        vm.stopPrank();
+    }
+
     function testDepositMultiple() public {
         uint256 totalAmount = 1_000_000_000_000_000e6; // 1 quadrillion USDT
         deal(address(usdt), testAddress, totalAmount);
@@ -64,7 +96,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         uint256 firstDepositGasCost;
         uint256 lastDepositGasCost;
 
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 exchangeRate = 2557666;
         uint256 depositAmount = 1_000_000e6; // 1 million USDT per deposit
         uint256 totalGasUsed = 0;
@@ -118,7 +150,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         vm.startPrank(testAddress);
         usdt.approve(address(riftExchange), totalAmount);
 
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 initialBtcExchangeRate = 69;
         uint256 depositAmount = 100_000_000e6; // 100 million USDT
 
@@ -156,7 +188,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         deal(address(usdt), testAddress, totalAmount);
         vm.startPrank(testAddress);
         usdt.approve(address(riftExchange), totalAmount);
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 exchangeRate = 69;
         uint192 depositAmount = 500_000_000e6; // 500 million USDT
 
@@ -220,7 +252,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         deal(address(usdt), testAddress, totalAmount);
         vm.startPrank(testAddress);
         usdt.approve(address(riftExchange), totalAmount);
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
 
         // Deposit liquidity into 3 vaults
         uint64[] memory exchangeRates = new uint64[](3);
@@ -300,7 +332,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         deal(address(usdt), testAddress, totalAmount);
         vm.startPrank(testAddress);
         usdt.approve(address(riftExchange), totalAmount);
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 exchangeRate = 69;
         uint192 depositAmount = 500_000_000e6; // 500 million USDT
 
@@ -367,7 +399,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         uint256 maxVaults = 100;
         uint192 depositAmount = 5_000_000e6; // 5 million USDT
         uint64 exchangeRate = 69;
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
 
         // create multiple vaults
         for (uint256 i = 0; i < maxVaults; i++) {
@@ -418,7 +450,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
 
         // [0] initial deposit
         uint192 depositAmount = 5_000_000e6; // 5 million USDT
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 exchangeRate = 50;
         riftExchange.depositLiquidity(depositAmount, exchangeRate, btcPayoutLockingScript);
 
@@ -464,7 +496,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
 
         // deposit liquidity
         uint192 depositAmount = 5_000_000e6; // 5 million USDT
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 initialBtcExchangeRate = 50;
         riftExchange.depositLiquidity(depositAmount, initialBtcExchangeRate, btcPayoutLockingScript);
 
@@ -529,7 +561,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         assertEq(newVault.withdrawnAmount, 0, "New vault's withdrawn amount should be 0");
         assertEq(newVault.exchangeRate, 60, "New vault's exchange rate should be the updated rate");
         assertEq(
-            newVault.btcPayoutLockingScript,
+            riftExchange.getBtcPayoutLockingScript(1),
             btcPayoutLockingScript,
             "New vault's BTC payout locking script should match the old one"
         );
@@ -549,7 +581,7 @@ contract LiquidityDepositTest is ExchangeTestBase {
         usdt.approve(address(riftExchange), totalAmount);
 
         // First, deposit liquidity
-        bytes22 btcPayoutLockingScript = 0x0014841b80d2cc75f5345c482af96294d04fdd66b2b7;
+        bytes memory btcPayoutLockingScript = hex"0014841b80d2cc75f5345c482af96294d04fdd66b2b7";
         uint64 exchangeRate = 596302900000000;
         uint256 depositAmount = 500_000e6; // 500,000 USDT
 
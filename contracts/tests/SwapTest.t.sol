@@ -17,7 +17,7 @@ contract SwapTest is ExchangeTestBase {
             // 1000 USDT
             1000e6,
             596302900000000,
-            0x001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa
+            hex"001463dff5f8da08ca226ba01f59722c62ad9b9b3eaa"
         );
         console.log("Liquidity Deposited...");
         vm.stopPrank();
@@ -0,0 +1,50 @@
+// NOT ACTUALLY USED BY THE PROTOCOL (HELPER FOR HYPERNODE)
+
+// SPDX-License-Identifier: Unlicensed
+pragma solidity ^0.8.0;
+
+// Sends several calls to the rift exchange in one transaction, a call that reverts doesn't take the
+// others down with it unless it asked to. The exchange only accepts swap proofs from approved
+// hypernodes, so the batcher itself has to be approved, which is why only its owner can call through it
+contract RiftCallBatcher {
+    struct Call {
+        bytes callData;
+        bool allowFailure;
+    }
+
+    struct CallResult {
+        bool success;
+        bytes returnData;
+    }
+
+    address public immutable owner;
+    address public immutable riftExchange;
+
+    error NotOwner();
+    error CallFailed(uint256 index, bytes returnData);
+
+    event CallReverted(uint256 index, bytes returnData);
+
+    constructor(address riftExchangeContract) {
+        owner = msg.sender;
+        riftExchange = riftExchangeContract;
+    }
+
+    function aggregate(Call[] calldata calls) external returns (CallResult[] memory results) {
+        if (msg.sender != owner) {
+            revert NotOwner();
+        }
+
+        results = new CallResult[](calls.length);
+        for (uint256 i = 0; i < calls.length; ++i) {
+            (bool success, bytes memory returnData) = riftExchange.call(calls[i].callData);
+            if (!success) {
+                if (!calls[i].allowFailure) {
+                    revert CallFailed(i, returnData);
+                }
+                emit CallReverted(i, returnData);
+            }
+            results[i] = CallResult(success, returnData);
+        }
+    }
+}
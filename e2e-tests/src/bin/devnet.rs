@@ -1,5 +1,5 @@
-use test_utils::core::RiftDevnet; 
 use eyre::Result;
+use test_utils::core::RiftDevnet;
 use tokio::signal;
 
 #[tokio::main]
@@ -10,6 +10,7 @@ use chrono;
 use hypernode::HypernodeArgs;
 use log::info;
 use rift_core::btc_light_client::AsLittleEndianBytes;
+use rift_core::chain_params::ChainParams;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::thread;
@@ -102,19 +103,63 @@ impl RiftDevnet {
             let runtime = Runtime::new().expect("Failed to create Tokio runtime");
             runtime.block_on(async {
                 hypernode::node::run(HypernodeArgs {
-                    evm_ws_rpc,
+                    evm_ws_rpc: vec![evm_ws_rpc],
                     btc_rpc,
-                    private_key,
+                    btc_source: "bitcoind".to_string(),
+                    btc_rpc_user: None,
+                    btc_rpc_password: None,
+                    btc_rpc_cookie_file: None,
+                    btc_rpc_timeout: 30,
+                    btc_rpc_retry_time: 60,
+                    btc_rpc_batch_size: 10,
+                    private_key: Some(private_key),
+                    keystore_path: None,
+                    keystore_password_file: None,
+                    remote_signer_url: None,
+                    remote_signer_address: None,
                     rift_exchange_address,
                     evm_rpc_concurrency,
                     btc_rpc_concurrency,
                     btc_polling_interval,
+                    btc_zmq_url: None,
+                    btc_mempool_polling_interval: None,
                     evm_block_time: anvil_block_time_secs,
                     btc_block_time: bitcoin_block_time_secs,
                     mock_proof,
                     proof_gen_concurrency,
                     flashbots,
                     flashbots_relay_rpc,
+                    db_path: None,
+                    btc_network: "regtest".to_string(),
+                    signet_challenge: None,
+                    metrics_address: None,
+                    admin_address: None,
+                    admin_token: None,
+                    prover_worker_address: None,
+                    prover_worker_token: None,
+                    prover_lease_duration: 60,
+                    prover_job_timeout: 3600,
+                    prover_max_attempts: 3,
+                    job_max_attempts: 5,
+                    job_retry_delay: 10,
+                    job_retry_max_delay: 600,
+                    tx_confirmations: 1,
+                    tx_bump_interval: 60,
+                    tx_fee_bump_percent: 20,
+                    tx_max_fee_per_gas_gwei: None,
+                    profitability_policy: "always".to_string(),
+                    wei_per_token_unit: None,
+                    profitability_max_delay: 3600,
+                    batcher_address: None,
+                    evm_max_lag_blocks: 3,
+                    evm_read_quorum: 1,
+                    reservation_duration_hours: 8,
+                    confirmation_height_delta: 1,
+                    checkpoint_block_interval: 72,
+                    prove_blocks_interval: 43200,
+                    header_lookback_limit: 200,
+                    config: None,
+                    profile: None,
                 })
                 .await
                 .expect("Hypernode run failed");
@@ -302,6 +347,15 @@ async fn deploy_contracts(
     .await?;
 
     let contract = RiftExchange::new(*proxy_contract.address(), provider.clone());
+    contract
+        .initializeV2(
+            ChainParams::regtest().hash().into(),
+            rift_lib::proof::compute_circuit_vkey_digest().into(),
+        )
+        .send()
+        .await?
+        .watch()
+        .await?;
 
     Ok((Arc::new(contract), Arc::new(usdt_contract)))
 }
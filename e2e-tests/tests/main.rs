@@ -7,7 +7,6 @@ mod integration_tests {
     use alloy::rpc::types::BlockTransactionsKind;
     use eyre::Result;
     use futures_util::StreamExt;
-    use hypernode::constants::CHALLENGE_PERIOD_MINUTES;
     use log::info;
     use std::sync::Once;
     use std::{collections::HashSet, sync::Arc};
@@ -95,9 +94,7 @@ mod integration_tests {
             .depositLiquidity(
                 deposit_amount,
                 exchange_rate,
-                alloy::primitives::FixedBytes::<22>(
-                    depositer_btc_locking_script.as_slice().try_into().unwrap(),
-                ),
+                depositer_btc_locking_script.into(),
             )
             .send()
             .await?
@@ -149,14 +146,14 @@ mod integration_tests {
                 .map(|(expected_sats, vault_index)| {
                     let rift_exchange_contract = devnet.rift_exchange_contract.clone();
                     async move {
-                        let vault = rift_exchange_contract
-                            .getDepositVault(vault_index)
+                        let script_pub_key = rift_exchange_contract
+                            .getBtcPayoutLockingScript(vault_index)
                             .call()
                             .await
-                            .map_err(|e| eyre!("Failed to get deposit vault: {}", e))?
+                            .map_err(|e| eyre!("Failed to get payout script: {}", e))?
                             ._0;
                         Ok(LiquidityReservation {
-                            script_pub_key: vault.btcPayoutLockingScript.0,
+                            script_pub_key: script_pub_key.to_vec(),
                             expected_sats,
                         })
                     }
@@ -202,13 +199,15 @@ mod integration_tests {
         let rift_bitcoin_transaction = rift_lib::transaction::build_rift_payment_transaction(
             swap_reservation.nonce.into(),
             &liquidity_reservations,
-            in_tx
-                .compute_txid()
-                .as_raw_hash()
-                .to_byte_array()
-                .to_little_endian(),
-            &in_tx,
-            tx_vout as u32,
+            &[rift_lib::transaction::FundingUtxo::new(
+                in_tx
+                    .compute_txid()
+                    .as_raw_hash()
+                    .to_byte_array()
+                    .to_little_endian(),
+                &in_tx,
+                tx_vout as u32,
+            )],
             &devnet.funded_btc_wallet,
             allocated_btc_fees,
         );
@@ -280,10 +279,11 @@ mod integration_tests {
                         drop(processed_logs_guard);
                         info!("ProofSubmitted w/ reservation index: {:?}", &log_data.0.swapReservationIndex);
                         let _swap_reservation_index = log_data.0.swapReservationIndex;
-                        // mine an anvil block 10 minutes into the future
+                        // mine an anvil block past the exchange's challenge period
+                        let challenge_period = devnet.rift_exchange_contract.challengePeriod().call().await?._0;
                         let current_block = devnet.rift_exchange_contract.provider().get_block(BlockId::latest(), BlockTransactionsKind::Hashes).await?.unwrap();
                         let current_timestamp = current_block.header.timestamp;
-                        let future_timestamp = current_timestamp + (CHALLENGE_PERIOD_MINUTES * 60) + 1;
+                        let future_timestamp = current_timestamp + challenge_period as u64 + 1;
                         devnet.rift_exchange_contract.provider().anvil_set_next_block_timestamp(future_timestamp).await?;
                         devnet.rift_exchange_contract.provider().anvil_mine(Some(U256::from(1)), None).await?;
                     }
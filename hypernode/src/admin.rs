@@ -0,0 +1,592 @@
+// Operator HTTP API for inspecting and steering a running hypernode. Every request carries the
+// admin token as a bearer token, 401 otherwise
+use crate::btc_indexer::ProveBlocksRequest;
+use crate::core::{ReservationMetadata, ReservationStage, ThreadSafeStore};
+use crate::error::HypernodeError;
+use crate::evm_block_trigger::EvmBlockTrigger;
+use crate::proof_broadcast::ProofBroadcastQueue;
+use crate::proof_builder::{self, ProofGenerationQueue, ProofJobStatus};
+use crate::remote_prover::require_token;
+use crate::retry::{DeadLetter, DeadLetters};
+use crate::tx_manager::{PendingTransaction, TxManager};
+use crate::{hyper_err, Result};
+use alloy::primitives::{FixedBytes, U256};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use bitcoin::hashes::Hash;
+use bitcoin::Txid;
+use log::{error, info};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct AdminState {
+    pub store: Arc<ThreadSafeStore>,
+    pub proof_gen_queue: Arc<ProofGenerationQueue>,
+    pub proof_broadcast_queue: Arc<ProofBroadcastQueue>,
+    pub trigger: Arc<EvmBlockTrigger>,
+    pub prove_blocks_request: Arc<ProveBlocksRequest>,
+    pub dead_letters: Arc<DeadLetters>,
+    pub tx_manager: Arc<TxManager>,
+}
+
+#[derive(Serialize)]
+struct ReservationSummary {
+    id: U256,
+    stage: ReservationStage,
+    proof_job: Option<ProofJobStatus>,
+    pending_payment: Option<PendingPayment>,
+    rejected_payment: Option<RejectedPayment>,
+    txid: Option<String>,
+    proposed_block_height: Option<u64>,
+    confirmation_height: Option<u64>,
+    proof_tx_hash: Option<FixedBytes<32>>,
+    liquidity_unlocked_timestamp: u64,
+}
+
+#[derive(Serialize)]
+struct PendingPayment {
+    txid: String,
+    first_seen: u64,
+    payment_error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RejectedPayment {
+    txid: String,
+    block_height: u64,
+    reason: String,
+}
+
+impl ReservationSummary {
+    fn new(id: U256, metadata: &ReservationMetadata, proof_job: Option<ProofJobStatus>) -> Self {
+        ReservationSummary {
+            id,
+            stage: metadata.stage(),
+            proof_job,
+            pending_payment: metadata.btc_pending.as_ref().map(|pending| PendingPayment {
+                txid: Txid::from_byte_array(pending.txid).to_string(),
+                first_seen: pending.first_seen,
+                payment_error: pending.payment_error.clone(),
+            }),
+            rejected_payment: metadata
+                .btc_rejected
+                .as_ref()
+                .map(|rejected| RejectedPayment {
+                    txid: Txid::from_byte_array(rejected.txid).to_string(),
+                    block_height: rejected.block_height,
+                    reason: rejected.reason.clone(),
+                }),
+            txid: metadata
+                .btc_initial
+                .as_ref()
+                .map(|initial| Txid::from_byte_array(initial.txid).to_string()),
+            proposed_block_height: metadata
+                .btc_initial
+                .as_ref()
+                .map(|initial| initial.proposed_block_height),
+            confirmation_height: metadata
+                .btc_final
+                .as_ref()
+                .map(|finalized| finalized.confirmation_height),
+            proof_tx_hash: metadata.proof_tx_hash.map(FixedBytes),
+            liquidity_unlocked_timestamp: metadata.reservation.liquidityUnlockedTimestamp,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProofGenerationJob {
+    // unset for block proofs
+    reservation_id: Option<U256>,
+    status: ProofJobStatus,
+}
+
+#[derive(Serialize)]
+struct ReleaseRequest {
+    reservation_id: U256,
+    unlock_timestamp: u64,
+}
+
+#[derive(Serialize)]
+struct Queues {
+    proof_generation: Vec<ProofGenerationJob>,
+    // reservation ids in broadcast order, unset for block proofs
+    proof_broadcast: Vec<Option<U256>>,
+    release: Vec<ReleaseRequest>,
+    // broadcast but not yet confirmed
+    transactions: Vec<PendingTransaction>,
+}
+
+struct AdminError(StatusCode, String);
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<HypernodeError> for AdminError {
+    fn from(err: HypernodeError) -> Self {
+        AdminError(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    }
+}
+
+type AdminResult<T> = std::result::Result<T, AdminError>;
+
+fn parse_reservation_id(id: &str) -> AdminResult<U256> {
+    U256::from_str(id).map_err(|e| {
+        AdminError(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid reservation id {}: {}", id, e),
+        )
+    })
+}
+
+fn reservation_not_found(id: U256) -> AdminError {
+    AdminError(
+        StatusCode::NOT_FOUND,
+        format!("Reservation not found: {}", id),
+    )
+}
+
+async fn list_reservations(State(state): State<AdminState>) -> Json<Vec<ReservationSummary>> {
+    let proof_jobs = state.proof_gen_queue.jobs();
+    let mut reservations = state
+        .store
+        .with_lock(|store| {
+            store
+                .reservations()
+                .iter()
+                .map(|(id, metadata)| {
+                    let proof_job = proof_jobs
+                        .iter()
+                        .find(|(key, _)| *key == Some(*id))
+                        .map(|(_, status)| *status);
+                    ReservationSummary::new(*id, metadata, proof_job)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+    reservations.sort_by_key(|reservation| reservation.id);
+    Json(reservations)
+}
+
+async fn get_reservation(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> AdminResult<Json<ReservationSummary>> {
+    let id = parse_reservation_id(&id)?;
+    let proof_job = state
+        .proof_gen_queue
+        .jobs()
+        .into_iter()
+        .find(|(key, _)| *key == Some(id))
+        .map(|(_, status)| status);
+    state
+        .store
+        .with_lock(|store| {
+            store
+                .get(id)
+                .map(|metadata| ReservationSummary::new(id, metadata, proof_job))
+        })
+        .await
+        .map(Json)
+        .ok_or_else(|| reservation_not_found(id))
+}
+
+// drops any existing proof and queues the reservation for proving again, cancelling a proof that
+// is still being generated
+async fn reprove_reservation(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> AdminResult<StatusCode> {
+    let id = parse_reservation_id(&id)?;
+    state
+        .store
+        .with_lock(|store| {
+            let metadata = store.get(id).ok_or_else(|| reservation_not_found(id))?;
+            match metadata.stage() {
+                ReservationStage::AwaitingPayment
+                | ReservationStage::PaymentPending
+                | ReservationStage::PaymentRejected
+                | ReservationStage::AwaitingConfirmations => Err(AdminError(
+                    StatusCode::CONFLICT,
+                    format!("Reservation {} has no confirmed payment to prove", id),
+                )),
+                ReservationStage::AwaitingRelease => Err(AdminError(
+                    StatusCode::CONFLICT,
+                    format!("Reservation {} is already proven onchain", id),
+                )),
+                ReservationStage::Proving | ReservationStage::Broadcast => {
+                    Ok(store.reset_proof(id)?)
+                }
+            }
+        })
+        .await?;
+
+    info!("Admin requested re-proving of reservation: {}", id);
+    state
+        .proof_gen_queue
+        .add(proof_builder::ProofGenerationInput::new_reservation(id))?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+// stops tracking the reservation, it is picked up again if it shows up in a later exchange event
+async fn drop_reservation(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> AdminResult<StatusCode> {
+    let id = parse_reservation_id(&id)?;
+    state
+        .store
+        .with_lock(|store| {
+            store.get(id).ok_or_else(|| reservation_not_found(id))?;
+            Ok::<_, AdminError>(store.remove(id)?)
+        })
+        .await?;
+    state.proof_gen_queue.cancel_reservation(id);
+
+    info!("Admin dropped reservation: {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_queues(State(state): State<AdminState>) -> Json<Queues> {
+    Json(Queues {
+        proof_generation: state
+            .proof_gen_queue
+            .jobs()
+            .into_iter()
+            .map(|(reservation_id, status)| ProofGenerationJob {
+                reservation_id,
+                status,
+            })
+            .collect(),
+        proof_broadcast: state.proof_broadcast_queue.pending(),
+        release: state
+            .trigger
+            .pending()
+            .await
+            .into_iter()
+            .map(|(reservation_id, unlock_timestamp)| ReleaseRequest {
+                reservation_id,
+                unlock_timestamp,
+            })
+            .collect(),
+        transactions: state.tx_manager.pending(),
+    })
+}
+
+// proveBlocks is called as soon as the bitcoin indexer is synced, regardless of how recently it
+// was last called
+async fn prove_blocks(State(state): State<AdminState>) -> StatusCode {
+    info!("Admin requested proveBlocks");
+    state.prove_blocks_request.request();
+    StatusCode::ACCEPTED
+}
+
+async fn list_dead_letters(State(state): State<AdminState>) -> Json<Vec<DeadLetter>> {
+    Json(state.dead_letters.list().await)
+}
+
+async fn clear_dead_letters(State(state): State<AdminState>) -> StatusCode {
+    info!("Admin cleared dead letters");
+    match state.dead_letters.clear().await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) => {
+            error!("Failed to clear dead letters: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+// requests have to present `token`
+pub fn router(state: AdminState, token: &str) -> Router {
+    Router::new()
+        .route("/reservations", get(list_reservations))
+        .route(
+            "/reservations/:id",
+            get(get_reservation).delete(drop_reservation),
+        )
+        .route("/reservations/:id/prove", post(reprove_reservation))
+        .route("/queues", get(get_queues))
+        .route("/prove-blocks", post(prove_blocks))
+        .route(
+            "/dead-letters",
+            get(list_dead_letters).delete(clear_dead_letters),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            require_token,
+        ))
+        .with_state(state)
+}
+
+// binds `address` and serves the admin API in the background, requests have to present `token`
+pub async fn serve(address: SocketAddr, state: AdminState, token: &str) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| hyper_err!(Config, "Failed to bind admin API to {}: {}", address, e))?;
+    let router = router(state, token);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("Admin API stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batcher::CallBatcher;
+    use crate::core::tests::test_reservation;
+    use crate::evm_endpoints::EvmEndpoints;
+    use crate::profitability::{ProfitabilityGate, ProfitabilityPolicy};
+    use crate::prover::MockProver;
+    use crate::retry::RetryPolicy;
+    use crate::tx_manager::TxManagerConfig;
+    use alloy::network::EthereumWallet;
+    use alloy::primitives::Address;
+    use alloy::signers::local::PrivateKeySigner;
+    use axum::body::Body;
+    use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+    use axum::http::{Method, Request};
+    use rift_core::chain_params::ChainParams;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "secret";
+
+    // answers health checks and block subscriptions, but never produces a block
+    async fn mock_evm_endpoint() -> String {
+        async fn answer(mut socket: WebSocket) {
+            while let Some(Ok(Message::Text(request))) = socket.recv().await {
+                let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+                let result = match request["method"].as_str() {
+                    Some("eth_blockNumber") | Some("eth_subscribe") => "0x1".into(),
+                    _ => serde_json::Value::Null,
+                };
+                let response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": result,
+                });
+                if socket
+                    .send(Message::Text(response.to_string()))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new().route(
+            "/",
+            get(|ws: WebSocketUpgrade| async { ws.on_upgrade(answer) }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("ws://{}", address)
+    }
+
+    async fn test_state() -> AdminState {
+        let store = Arc::new(ThreadSafeStore::new());
+        let dead_letters = Arc::new(DeadLetters::new(Arc::clone(&store)));
+        let retry_policy = RetryPolicy {
+            max_attempts: 1,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(1),
+        };
+        let endpoints = EvmEndpoints::connect(
+            vec![mock_evm_endpoint().await],
+            EthereumWallet::from(PrivateKeySigner::random()),
+            Address::ZERO,
+            0,
+            1,
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        let tx_manager = Arc::new(TxManager::new(
+            Arc::clone(&endpoints),
+            Arc::new(None),
+            TxManagerConfig {
+                confirmations: 1,
+                bump_interval: Duration::from_secs(60),
+                fee_bump_percent: 10,
+                max_fee_per_gas: None,
+            },
+        ));
+        let batcher = Arc::new(CallBatcher::new(
+            Arc::clone(&tx_manager),
+            Address::ZERO,
+            None,
+            Duration::from_secs(1),
+        ));
+        let profitability = Arc::new(ProfitabilityGate::new(
+            ProfitabilityPolicy::Always,
+            U256::from(1),
+            0,
+            Arc::clone(&batcher),
+            Arc::clone(&endpoints),
+        ));
+        let proof_broadcast_queue = Arc::new(ProofBroadcastQueue::new(
+            Arc::clone(&store),
+            Arc::clone(&batcher),
+            Arc::clone(&profitability),
+            Arc::clone(&endpoints),
+            retry_policy,
+            Arc::clone(&dead_letters),
+        ));
+        AdminState {
+            // without any concurrency queued jobs are never started, so they stay visible
+            proof_gen_queue: Arc::new(ProofGenerationQueue::new(
+                Arc::clone(&store),
+                Arc::clone(&proof_broadcast_queue),
+                Arc::new(MockProver),
+                0,
+                ChainParams::regtest(),
+                retry_policy,
+                Arc::clone(&dead_letters),
+            )),
+            store,
+            proof_broadcast_queue,
+            trigger: EvmBlockTrigger::new(batcher, profitability, Arc::clone(&endpoints)),
+            prove_blocks_request: Arc::new(ProveBlocksRequest::default()),
+            dead_letters,
+            tx_manager,
+        }
+    }
+
+    async fn send(router: &Router, method: Method, uri: &str) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", TOKEN))
+            .body(Body::empty())
+            .unwrap();
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_requires_token() {
+        let router = router(test_state().await, TOKEN);
+        for token in [None, Some("wrong")] {
+            let mut request = Request::builder().uri("/queues");
+            if let Some(token) = token {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+            let response = router
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(send(&router, Method::GET, "/queues").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_reprove_reservation() {
+        let state = test_state().await;
+        let router = router(state.clone(), TOKEN);
+        let (unpaid, proven) = (U256::from(1), U256::from(2));
+        state
+            .store
+            .with_lock(|store| {
+                store.insert(unpaid, test_reservation()).unwrap();
+                store.insert(proven, test_reservation()).unwrap();
+                store.update_proof_data(proven, vec![1], vec![2]).unwrap();
+            })
+            .await;
+
+        assert_eq!(
+            send(&router, Method::POST, "/reservations/3/prove").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            send(&router, Method::POST, "/reservations/1/prove").await,
+            StatusCode::CONFLICT
+        );
+        assert!(state.proof_gen_queue.jobs().is_empty());
+
+        // the proof is dropped and the reservation is queued to be proven again
+        assert_eq!(
+            send(&router, Method::POST, "/reservations/2/prove").await,
+            StatusCode::ACCEPTED
+        );
+        let proof = state
+            .store
+            .with_lock(|store| store.get(proven).unwrap().proof.clone())
+            .await;
+        assert!(proof.is_none());
+        assert_eq!(
+            state.proof_gen_queue.jobs(),
+            [(Some(proven), ProofJobStatus::Queued)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop_reservation() {
+        let state = test_state().await;
+        let router = router(state.clone(), TOKEN);
+        let id = U256::from(1);
+        state
+            .store
+            .with_lock(|store| store.insert(id, test_reservation()).unwrap())
+            .await;
+        state
+            .proof_gen_queue
+            .add(proof_builder::ProofGenerationInput::new_reservation(id))
+            .unwrap();
+
+        assert_eq!(
+            send(&router, Method::DELETE, "/reservations/2").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            send(&router, Method::DELETE, "/reservations/1").await,
+            StatusCode::NO_CONTENT
+        );
+        assert!(state.store.with_lock(|store| store.get(id).is_none()).await);
+        assert!(state.proof_gen_queue.jobs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prove_blocks() {
+        let state = test_state().await;
+        let router = router(state.clone(), TOKEN);
+        assert!(!state.prove_blocks_request.take());
+        assert_eq!(
+            send(&router, Method::POST, "/prove-blocks").await,
+            StatusCode::ACCEPTED
+        );
+        assert!(state.prove_blocks_request.take());
+    }
+
+    #[tokio::test]
+    async fn test_clear_dead_letters() {
+        let state = test_state().await;
+        let router = router(state.clone(), TOKEN);
+        let error = hyper_err!(ProofGeneration, "invalid payment");
+        state
+            .dead_letters
+            .push("proof_generation", Some(U256::from(1)), 1, &error)
+            .await;
+        assert_eq!(state.dead_letters.list().await.len(), 1);
+
+        assert_eq!(
+            send(&router, Method::DELETE, "/dead-letters").await,
+            StatusCode::NO_CONTENT
+        );
+        assert!(state.dead_letters.list().await.is_empty());
+    }
+}
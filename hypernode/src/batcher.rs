@@ -0,0 +1,218 @@
+// Groups swap proofs and releases that become ready around the same time into one RiftCallBatcher
+// transaction, so busy periods pay the base transaction cost once. A call that reverts in a batch
+// only fails its own sender. Without a batcher contract every call is sent on its own
+use crate::core::RiftCallBatcher;
+use crate::error::HypernodeError;
+use crate::telemetry::{BATCH_SIZE, TX_REVERTS};
+use crate::tx_manager::{decode_revert, TxManager};
+use crate::{hyper_err, Result};
+use alloy::primitives::{Address, Bytes, FixedBytes, U256};
+use alloy::rpc::types::TransactionReceipt;
+use alloy::sol_types::SolCall;
+use log::{error, info};
+use metrics::{counter, histogram};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+const MAX_BATCH_SIZE: usize = 16;
+
+struct BatchedCall {
+    function_name: &'static str,
+    txn_calldata: Vec<u8>,
+    // hash of the confirmed batch transaction, or why the call failed
+    result: oneshot::Sender<Result<FixedBytes<32>>>,
+}
+
+pub struct CallBatcher {
+    tx_manager: Arc<TxManager>,
+    exchange_address: Address,
+    // the batcher contract and the queue of calls waiting for the next batch, unset when calls
+    // aren't batched
+    batch: Option<(Address, mpsc::UnboundedSender<BatchedCall>)>,
+}
+
+impl CallBatcher {
+    pub fn new(
+        tx_manager: Arc<TxManager>,
+        exchange_address: Address,
+        batcher_address: Option<Address>,
+        batch_window: Duration,
+    ) -> Self {
+        let batch = batcher_address.map(|batcher_address| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(Self::batch_task(
+                receiver,
+                Arc::clone(&tx_manager),
+                batcher_address,
+                batch_window,
+            ));
+            (batcher_address, sender)
+        });
+        CallBatcher {
+            tx_manager,
+            exchange_address,
+            batch,
+        }
+    }
+
+    // sends a call to the exchange, as part of a batch when a batcher contract is configured, and
+    // returns the hash of the transaction it was confirmed in
+    pub async fn send(
+        &self,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+    ) -> Result<FixedBytes<32>> {
+        let Some((_, sender)) = &self.batch else {
+            return self.send_unbatched(function_name, txn_calldata).await;
+        };
+        let (result, receiver) = oneshot::channel();
+        sender
+            .send(BatchedCall {
+                function_name,
+                txn_calldata: txn_calldata.to_vec(),
+                result,
+            })
+            .map_err(|e| hyper_err!(Queue, "Failed to add {} to a batch: {}", function_name, e))?;
+        receiver
+            .await
+            .map_err(|e| hyper_err!(Queue, "Batch with {} was dropped: {}", function_name, e))?
+    }
+
+    // sends a call to the exchange from the hypernode's own address, for calls that don't need an
+    // approved hypernode
+    pub async fn send_unbatched(
+        &self,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+    ) -> Result<FixedBytes<32>> {
+        self.tx_manager
+            .send(self.exchange_address, function_name, txn_calldata)
+            .await
+            .map(|receipt| receipt.transaction_hash)
+    }
+
+    // gas cost of the call sent on its own, which bounds its share of a batch
+    pub async fn estimate_cost(
+        &self,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+    ) -> Result<U256> {
+        match &self.batch {
+            Some((batcher_address, _)) => {
+                self.tx_manager
+                    .estimate_cost(
+                        *batcher_address,
+                        function_name,
+                        &aggregate_calldata([txn_calldata].into_iter()),
+                    )
+                    .await
+            }
+            None => {
+                self.tx_manager
+                    .estimate_cost(self.exchange_address, function_name, txn_calldata)
+                    .await
+            }
+        }
+    }
+
+    async fn batch_task(
+        mut receiver: mpsc::UnboundedReceiver<BatchedCall>,
+        tx_manager: Arc<TxManager>,
+        batcher_address: Address,
+        batch_window: Duration,
+    ) {
+        while let Some(call) = receiver.recv().await {
+            // calls that become ready within the window of the first one join its batch
+            let mut calls = vec![call];
+            let deadline = tokio::time::Instant::now() + batch_window;
+            while calls.len() < MAX_BATCH_SIZE {
+                match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(call)) => calls.push(call),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+            // sent in the background so the next batch can form while this one confirms
+            tokio::spawn(Self::send_batch(
+                Arc::clone(&tx_manager),
+                batcher_address,
+                calls,
+            ));
+        }
+    }
+
+    async fn send_batch(
+        tx_manager: Arc<TxManager>,
+        batcher_address: Address,
+        calls: Vec<BatchedCall>,
+    ) {
+        info!(
+            "Sending batch of {} calls: {:?}",
+            calls.len(),
+            calls
+                .iter()
+                .map(|call| call.function_name)
+                .collect::<Vec<_>>()
+        );
+        histogram!(BATCH_SIZE).record(calls.len() as f64);
+        let txn_calldata =
+            aggregate_calldata(calls.iter().map(|call| call.txn_calldata.as_slice()));
+        let result = tx_manager
+            .send(batcher_address, "aggregate", &txn_calldata)
+            .await;
+        let reverted = match &result {
+            Ok(receipt) => reverted_calls(receipt, batcher_address),
+            Err(e) => {
+                error!("Batch of {} calls failed: {}", calls.len(), e);
+                HashMap::new()
+            }
+        };
+
+        for (index, call) in calls.into_iter().enumerate() {
+            let call_result = match (&result, reverted.get(&index)) {
+                (Err(e), _) => Err(e.clone()),
+                (Ok(receipt), Some(return_data)) => {
+                    counter!(TX_REVERTS, "function" => call.function_name).increment(1);
+                    Err(hyper_err!(
+                        Reverted,
+                        "{} reverted in batch {}: {}",
+                        call.function_name,
+                        receipt.transaction_hash,
+                        decode_revert(return_data)
+                    ))
+                }
+                (Ok(receipt), None) => Ok(receipt.transaction_hash),
+            };
+            // the sender may have stopped waiting
+            let _ = call.result.send(call_result);
+        }
+    }
+}
+
+fn aggregate_calldata<'a>(calls: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    RiftCallBatcher::aggregateCall {
+        calls: calls
+            .map(|txn_calldata| RiftCallBatcher::Call {
+                callData: txn_calldata.to_vec().into(),
+                allowFailure: true,
+            })
+            .collect(),
+    }
+    .abi_encode()
+}
+
+// return data of the calls in the batch that reverted, by their index in the batch
+fn reverted_calls(receipt: &TransactionReceipt, batcher_address: Address) -> HashMap<usize, Bytes> {
+    receipt
+        .inner
+        .logs()
+        .iter()
+        .filter(|log| log.address() == batcher_address)
+        .filter_map(|log| log.log_decode::<RiftCallBatcher::CallReverted>().ok())
+        .map(|log| {
+            let reverted = log.inner.data;
+            (reverted.index.to::<usize>(), reverted.returnData)
+        })
+        .collect()
+}
@@ -1,12 +1,18 @@
 use clap::Parser;
 use dotenv::dotenv;
-use hypernode::HypernodeArgs;
-use hypernode::Result;
+use hypernode::error::HypernodeError;
+use hypernode::{hyper_err, HypernodeArgs, Result};
 
-#[tokio::main]
-async fn main() -> Result<()> {
+// .env and config profile values are set as environment variables, which is only sound before the
+// runtime has started its worker threads
+fn main() -> Result<()> {
     env_logger::init();
     dotenv().ok();
+    hypernode::config::load_from_args()?;
     let args = HypernodeArgs::parse();
-    hypernode::node::run(args).await
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| hyper_err!(Unknown, "Failed to start the tokio runtime: {}", e))?
+        .block_on(hypernode::node::run(args))
 }
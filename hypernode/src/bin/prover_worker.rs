@@ -0,0 +1,46 @@
+use clap::Parser;
+use dotenv::dotenv;
+use hypernode::prover::{LocalProver, MockProver, Prover};
+use hypernode::remote_prover;
+use hypernode::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Proves jobs leased from a hypernode's prover worker API", long_about = None)]
+struct ProverWorkerArgs {
+    /// URL of the hypernode's prover worker API, e.g. http://127.0.0.1:9092
+    #[arg(long, env)]
+    coordinator_url: String,
+
+    /// Shared token the hypernode's prover worker API requires, its --prover-worker-token
+    #[arg(long, env)]
+    coordinator_token: String,
+
+    /// Execute jobs without proving and return empty proofs, for testing against a mock verifier
+    #[arg(long, env, default_value = "false")]
+    mock_proof: bool,
+
+    /// Seconds to wait before polling again when there is no job to prove
+    #[arg(long, env, default_value = "5")]
+    poll_interval: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    dotenv().ok();
+    let args = ProverWorkerArgs::parse();
+    let prover: Arc<dyn Prover> = if args.mock_proof {
+        Arc::new(MockProver)
+    } else {
+        Arc::new(LocalProver)
+    };
+    remote_prover::run_worker(
+        &args.coordinator_url,
+        &args.coordinator_token,
+        prover,
+        Duration::from_secs(args.poll_interval),
+    )
+    .await
+}
@@ -0,0 +1,463 @@
+// Reads the bitcoin chain from an Electrum server (electrs, Fulcrum, ElectrumX) over plain tcp, e.g.
+// tcp://127.0.0.1:50001. The protocol has no way to fetch a whole block, so blocks are rebuilt from
+// their transactions and checked against the header's merkle root. Chainwork is summed from the
+// headers, one difficulty period per request
+use crate::btc_source::{header_work, BitcoinDataSource, ChainworkCache};
+use crate::error::HypernodeError;
+use crate::{hyper_err, Result};
+use alloy::primitives::U256;
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, Transaction};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// requests sent in one batch when walking a block's transactions
+const TX_BATCH_SIZE: usize = 100;
+const HEADER_SIZE: usize = 80;
+
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+}
+
+pub struct ElectrumClient {
+    address: String,
+    // opened on first use and again after a failed request
+    connection: Mutex<Option<Connection>>,
+    chainwork: ChainworkCache,
+}
+
+impl ElectrumClient {
+    pub fn new(url: &str) -> Result<Self> {
+        let address = match url.split_once("://") {
+            None => url,
+            Some(("tcp", address)) => address,
+            Some((scheme, _)) => {
+                return Err(hyper_err!(
+                    Config,
+                    "Unsupported Electrum scheme {}, only tcp is supported",
+                    scheme
+                ))
+            }
+        };
+        Ok(Self {
+            address: address.trim_end_matches('/').to_string(),
+            connection: Mutex::new(None),
+            chainwork: ChainworkCache::default(),
+        })
+    }
+
+    async fn connect(&self) -> Result<Connection> {
+        let stream = TcpStream::connect(&self.address).await.map_err(|e| {
+            hyper_err!(
+                Connection,
+                "Failed to connect to Electrum server {}: {}",
+                self.address,
+                e
+            )
+        })?;
+        let (reader, writer) = stream.into_split();
+        let mut connection = Connection {
+            reader: BufReader::new(reader),
+            writer,
+            next_id: 0,
+        };
+        // servers expect the version to be negotiated before anything else
+        Self::exchange(
+            &mut connection,
+            vec![(
+                "server.version",
+                serde_json::json!(["rift-hypernode", "1.4"]),
+            )],
+        )
+        .await?
+        .remove(0)?;
+        Ok(connection)
+    }
+
+    // sends the requests as one batch, the batch fails as a whole on connection errors and each
+    // request fails on its own when the server returns an error for it
+    async fn batch(&self, requests: Vec<(&str, Value)>) -> Result<Vec<Result<Value>>> {
+        let mut connection = self.connection.lock().await;
+        let result = tokio::time::timeout(REQUEST_TIMEOUT, async {
+            if connection.is_none() {
+                *connection = Some(self.connect().await?);
+            }
+            Self::exchange(connection.as_mut().unwrap(), requests).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(hyper_err!(BitcoinRpc, "Electrum request timed out")));
+        if result.is_err() {
+            // the stream may hold a partial response
+            *connection = None;
+        }
+        result
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        self.batch(vec![(method, params)]).await?.remove(0)
+    }
+
+    async fn exchange(
+        connection: &mut Connection,
+        requests: Vec<(&str, Value)>,
+    ) -> Result<Vec<Result<Value>>> {
+        let first_id = connection.next_id;
+        connection.next_id += requests.len() as u64;
+        let batch: Vec<Value> = requests
+            .iter()
+            .zip(first_id..)
+            .map(|((method, params), id)| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params,
+                })
+            })
+            .collect();
+        let mut line = serde_json::to_string(&batch)
+            .map_err(|e| hyper_err!(BitcoinRpc, "Failed to encode Electrum request: {}", e))?;
+        line.push('\n');
+        connection
+            .writer
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| hyper_err!(BitcoinRpc, "Failed to send Electrum request: {}", e))?;
+
+        let responses = loop {
+            let mut line = String::new();
+            let read =
+                connection.reader.read_line(&mut line).await.map_err(|e| {
+                    hyper_err!(BitcoinRpc, "Failed to read Electrum response: {}", e)
+                })?;
+            if read == 0 {
+                return Err(hyper_err!(
+                    BitcoinRpc,
+                    "Electrum server closed the connection"
+                ));
+            }
+            let message: Value = serde_json::from_str(&line)
+                .map_err(|e| hyper_err!(BitcoinRpc, "Failed to parse Electrum response: {}", e))?;
+            match message {
+                Value::Array(responses) => break responses,
+                // a server that doesn't take batches answers with a single error
+                response if response.get("id").is_some() => break vec![response],
+                // anything else is a notification for a subscription
+                _ => {}
+            }
+        };
+
+        let mut results: Vec<Option<Result<Value>>> = vec![None; requests.len()];
+        for response in responses {
+            let Some(index) = response
+                .get("id")
+                .and_then(Value::as_u64)
+                .and_then(|id| id.checked_sub(first_id))
+                .filter(|index| (*index as usize) < requests.len())
+            else {
+                continue;
+            };
+            results[index as usize] = Some(match response.get("error") {
+                Some(error) if !error.is_null() => Err(hyper_err!(
+                    BitcoinRpc,
+                    "Electrum {} failed: {}",
+                    requests[index as usize].0,
+                    error
+                )),
+                _ => response
+                    .get("result")
+                    .cloned()
+                    .ok_or_else(|| hyper_err!(BitcoinRpc, "No 'result' in Electrum response")),
+            });
+        }
+        results
+            .into_iter()
+            .zip(&requests)
+            .map(|(result, (method, _))| {
+                result.ok_or_else(|| hyper_err!(BitcoinRpc, "No Electrum response for {}", method))
+            })
+            .collect()
+    }
+
+    // ids of every transaction in the block, in block order, the first position past the end
+    // makes the server return an error
+    async fn get_block_txids(&self, height: u64) -> Result<Vec<String>> {
+        let mut txids = Vec::new();
+        loop {
+            let start = txids.len();
+            let results = self
+                .batch(
+                    (start..start + TX_BATCH_SIZE)
+                        .map(|position| {
+                            (
+                                "blockchain.transaction.id_from_pos",
+                                serde_json::json!([height, position]),
+                            )
+                        })
+                        .collect(),
+                )
+                .await?;
+            for result in results {
+                match result
+                    .ok()
+                    .and_then(|txid| txid.as_str().map(str::to_string))
+                {
+                    Some(txid) => txids.push(txid),
+                    None => return Ok(txids),
+                }
+            }
+        }
+    }
+}
+
+fn decode_hex_value<T: bitcoin::consensus::Decodable>(value: &Value, name: &str) -> Result<T> {
+    let bytes = value
+        .as_str()
+        .and_then(|hexstr| hex::decode(hexstr).ok())
+        .ok_or_else(|| hyper_err!(BitcoinRpc, "Invalid {} data", name))?;
+    deserialize::<T>(&bytes).map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize {}", name))
+}
+
+#[async_trait]
+impl BitcoinDataSource for ElectrumClient {
+    async fn get_block_count(&self) -> Result<u64> {
+        self.request("blockchain.headers.subscribe", serde_json::json!([]))
+            .await?
+            .get("height")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| hyper_err!(BitcoinRpc, "Invalid block count"))
+    }
+
+    async fn get_block_hash(&self, height: u64) -> Result<[u8; 32]> {
+        let header = self.get_block_header(height).await?;
+        let mut block_hash = header.block_hash().to_byte_array();
+        block_hash.reverse();
+        Ok(block_hash)
+    }
+
+    async fn get_block(&self, height: u64) -> Result<Block> {
+        let header = self.get_block_header(height).await?;
+        let txids = self.get_block_txids(height).await?;
+        let mut txdata = Vec::with_capacity(txids.len());
+        for chunk in txids.chunks(TX_BATCH_SIZE) {
+            let results = self
+                .batch(
+                    chunk
+                        .iter()
+                        .map(|txid| ("blockchain.transaction.get", serde_json::json!([txid])))
+                        .collect(),
+                )
+                .await?;
+            for result in results {
+                txdata.push(decode_hex_value::<Transaction>(&result?, "transaction")?);
+            }
+        }
+
+        let block = Block { header, txdata };
+        if !block.check_merkle_root() {
+            return Err(hyper_err!(
+                BitcoinRpc,
+                "Transactions of block {} don't match its merkle root",
+                height
+            ));
+        }
+        Ok(block)
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header> {
+        let header = self
+            .request("blockchain.block.header", serde_json::json!([height]))
+            .await?;
+        decode_hex_value(&header, "block header")
+    }
+
+    async fn get_chainwork(&self, height: u64) -> Result<[u8; 32]> {
+        self.chainwork
+            .chainwork(height, |start, count| async move {
+                let headers = self
+                    .request(
+                        "blockchain.block.headers",
+                        serde_json::json!([start, count]),
+                    )
+                    .await?;
+                let headers = headers
+                    .get("hex")
+                    .and_then(Value::as_str)
+                    .and_then(|hexstr| hex::decode(hexstr).ok())
+                    .ok_or_else(|| hyper_err!(BitcoinRpc, "Invalid block headers data"))?;
+                if headers.len() != count as usize * HEADER_SIZE {
+                    return Err(hyper_err!(
+                        BitcoinRpc,
+                        "Expected {} headers from height {}, got {} bytes",
+                        count,
+                        start,
+                        headers.len()
+                    ));
+                }
+                headers
+                    .chunks(HEADER_SIZE)
+                    .map(|header| {
+                        deserialize::<Header>(header)
+                            .map(|header| header_work(&header))
+                            .map_err(|_| {
+                                hyper_err!(BitcoinRpc, "Failed to deserialize block header")
+                            })
+                    })
+                    .sum::<Result<U256>>()
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btc_source::tests::{display_hash, expected_chainwork, test_chain};
+    use bitcoin::consensus::serialize;
+    use bitcoin::hashes::hex::DisplayHex;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    fn electrum_response(chain: &[Block], method: &str, params: &[Value]) -> Result<Value> {
+        let param = |index: usize| params[index].as_u64().unwrap() as usize;
+        let block = |height: usize| {
+            chain
+                .get(height)
+                .ok_or_else(|| hyper_err!(BitcoinRpc, "height {} out of range", height))
+        };
+        Ok(match method {
+            "server.version" => serde_json::json!(["mock", "1.4"]),
+            "blockchain.headers.subscribe" => serde_json::json!({ "height": chain.len() - 1 }),
+            "blockchain.block.header" => serialize(&block(param(0))?.header)
+                .to_lower_hex_string()
+                .into(),
+            "blockchain.block.headers" => {
+                let end = chain.len().min(param(0) + param(1));
+                let headers: Vec<u8> = chain[param(0)..end]
+                    .iter()
+                    .flat_map(|block| serialize(&block.header))
+                    .collect();
+                serde_json::json!({ "hex": headers.to_lower_hex_string(), "count": end - param(0) })
+            }
+            "blockchain.transaction.id_from_pos" => block(param(0))?
+                .txdata
+                .get(param(1))
+                .ok_or_else(|| hyper_err!(BitcoinRpc, "no tx at position {}", param(1)))?
+                .compute_txid()
+                .to_string()
+                .into(),
+            "blockchain.transaction.get" => {
+                let txid = params[0].as_str().unwrap();
+                chain
+                    .iter()
+                    .flat_map(|block| &block.txdata)
+                    .find(|tx| tx.compute_txid().to_string() == txid)
+                    .map(|tx| serialize(tx).to_lower_hex_string().into())
+                    .ok_or_else(|| hyper_err!(BitcoinRpc, "unknown tx {}", txid))?
+            }
+            _ => return Err(hyper_err!(BitcoinRpc, "unknown method {}", method)),
+        })
+    }
+
+    // answers batches line by line, with a notification ahead of every answer
+    async fn serve_electrum(chain: Arc<Vec<Block>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let chain = chain.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let requests: Vec<Value> = serde_json::from_str(&line).unwrap();
+                        let responses: Vec<Value> = requests
+                            .iter()
+                            .map(|request| {
+                                let params = request["params"].as_array().unwrap();
+                                let method = request["method"].as_str().unwrap();
+                                match electrum_response(&chain, method, params) {
+                                    Ok(result) => {
+                                        serde_json::json!({ "id": request["id"], "result": result })
+                                    }
+                                    Err(e) => serde_json::json!({
+                                        "id": request["id"],
+                                        "error": { "code": 1, "message": e.to_string() },
+                                    }),
+                                }
+                            })
+                            .collect();
+                        let notification = serde_json::json!({
+                            "method": "blockchain.headers.subscribe",
+                            "params": [{ "height": chain.len() - 1 }],
+                        });
+                        let reply = format!("{}\n{}\n", notification, Value::Array(responses));
+                        writer.write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        format!("tcp://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_electrum_source() {
+        let mut chain = test_chain(2100);
+        // a block with more transactions than fit in one batch
+        let coinbase = chain[9].txdata[0].clone();
+        chain[9].txdata = (0..TX_BATCH_SIZE as u32 + 5)
+            .map(|lock_time| {
+                let mut tx = coinbase.clone();
+                tx.lock_time = bitcoin::absolute::LockTime::from_consensus(lock_time);
+                tx
+            })
+            .collect();
+        chain[9].header.merkle_root = chain[9].compute_merkle_root().unwrap();
+        let chain = Arc::new(chain);
+        let client = ElectrumClient::new(&serve_electrum(chain.clone()).await).unwrap();
+
+        assert_eq!(client.get_block_count().await.unwrap(), 2099);
+        assert_eq!(
+            client.get_block_hash(5).await.unwrap(),
+            display_hash(&chain[5])
+        );
+        assert_eq!(client.get_block(7).await.unwrap(), chain[7]);
+        assert_eq!(client.get_block(9).await.unwrap(), chain[9]);
+        assert_eq!(
+            client.get_chainwork(2050).await.unwrap(),
+            expected_chainwork(2050)
+        );
+        assert_eq!(
+            client.get_chainwork(3).await.unwrap(),
+            expected_chainwork(3)
+        );
+        // headers past the tip come back short
+        assert!(client.get_chainwork(2200).await.is_err());
+    }
+
+    #[test]
+    fn test_electrum_url() {
+        assert_eq!(
+            ElectrumClient::new("tcp://127.0.0.1:50001")
+                .unwrap()
+                .address,
+            "127.0.0.1:50001"
+        );
+        assert_eq!(
+            ElectrumClient::new("127.0.0.1:50001").unwrap().address,
+            "127.0.0.1:50001"
+        );
+        assert!(ElectrumClient::new("ssl://electrum.example.com:50002").is_err());
+    }
+}
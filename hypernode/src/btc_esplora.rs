@@ -0,0 +1,288 @@
+// Reads the bitcoin chain from an Esplora REST API, e.g. https://blockstream.info/api. Esplora doesn't
+// report chainwork, so it is summed from the difficulty bits of every block, which Esplora lists
+// ten blocks at a time
+use crate::btc_source::{BitcoinDataSource, ChainworkCache};
+use crate::error::HypernodeError;
+use crate::{hyper_err, Result};
+use alloy::primitives::U256;
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::consensus::deserialize;
+use bitcoin::{Block, CompactTarget, Target};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+// blocks listed per /blocks/:height page, counting down from the height
+const BLOCKS_PER_PAGE: u64 = 10;
+// pages requested at once while summing chainwork
+const CHAINWORK_CONCURRENCY: usize = 8;
+
+#[derive(Deserialize)]
+struct BlockSummary {
+    height: u64,
+    bits: u32,
+}
+
+pub struct EsploraClient {
+    client: Client,
+    url: String,
+    chainwork: ChainworkCache,
+}
+
+impl EsploraClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            chainwork: ChainworkCache::default(),
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        self.client
+            .get(format!("{}{}", self.url, path))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| hyper_err!(BitcoinRpc, "Esplora request {} failed: {}", path, e))
+    }
+
+    async fn get_text(&self, path: &str) -> Result<String> {
+        self.get(path)
+            .await?
+            .text()
+            .await
+            .map_err(|e| hyper_err!(BitcoinRpc, "Failed to read Esplora {}: {}", path, e))
+    }
+
+    // work of the `count` blocks from height `start`
+    async fn get_blocks_work(&self, start: u64, count: u64) -> Result<U256> {
+        let end = start + count;
+        let pages =
+            (0..count.div_ceil(BLOCKS_PER_PAGE)).map(|page| end - 1 - page * BLOCKS_PER_PAGE);
+        let works = stream::iter(pages)
+            .map(|top| async move {
+                let blocks = self
+                    .get(&format!("/blocks/{}", top))
+                    .await?
+                    .json::<Vec<BlockSummary>>()
+                    .await
+                    .map_err(|e| {
+                        hyper_err!(BitcoinRpc, "Invalid Esplora blocks from {}: {}", top, e)
+                    })?;
+                Ok::<_, HypernodeError>(
+                    blocks
+                        .into_iter()
+                        .filter(|block| block.height >= start && block.height <= top)
+                        .map(|block| {
+                            let target =
+                                Target::from_compact(CompactTarget::from_consensus(block.bits));
+                            U256::from_be_bytes(target.to_work().to_be_bytes())
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .buffered(CHAINWORK_CONCURRENCY)
+            .try_concat()
+            .await?;
+        if works.len() as u64 != count {
+            return Err(hyper_err!(
+                BitcoinRpc,
+                "Expected {} blocks from height {}, got {}",
+                count,
+                start,
+                works.len()
+            ));
+        }
+        Ok(works.into_iter().sum())
+    }
+
+    async fn get_block_hash_hex(&self, height: u64) -> Result<String> {
+        Ok(self
+            .get_text(&format!("/block-height/{}", height))
+            .await?
+            .trim()
+            .to_string())
+    }
+}
+
+#[async_trait]
+impl BitcoinDataSource for EsploraClient {
+    async fn get_block_count(&self) -> Result<u64> {
+        self.get_text("/blocks/tip/height")
+            .await?
+            .trim()
+            .parse()
+            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block count"))
+    }
+
+    async fn get_block_hash(&self, height: u64) -> Result<[u8; 32]> {
+        hex::decode(self.get_block_hash_hex(height).await?)
+            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block hash"))?
+            .try_into()
+            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block hash"))
+    }
+
+    async fn get_block(&self, height: u64) -> Result<Block> {
+        let block_hash = self.get_block_hash_hex(height).await?;
+        let block_bytes = self
+            .get(&format!("/block/{}/raw", block_hash))
+            .await?
+            .bytes()
+            .await
+            .map_err(|e| hyper_err!(BitcoinRpc, "Failed to read block {}: {}", block_hash, e))?;
+        deserialize::<Block>(&block_bytes)
+            .map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize block"))
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header> {
+        let block_hash = self.get_block_hash_hex(height).await?;
+        let header_hexstr = self
+            .get_text(&format!("/block/{}/header", block_hash))
+            .await?;
+        let header_bytes = hex::decode(header_hexstr.trim())
+            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block header data"))?;
+        deserialize::<Header>(&header_bytes)
+            .map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize block header"))
+    }
+
+    async fn get_chainwork(&self, height: u64) -> Result<[u8; 32]> {
+        self.chainwork
+            .chainwork(height, |start, count| self.get_blocks_work(start, count))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btc_source::header_work;
+    use crate::btc_source::tests::{display_hash, expected_chainwork, test_chain};
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use bitcoin::consensus::serialize;
+    use bitcoin::hashes::hex::DisplayHex;
+    use std::sync::Arc;
+
+    type Chain = Arc<Vec<Block>>;
+
+    fn find_block<'a>(chain: &'a Chain, hash: &str) -> Option<&'a Block> {
+        chain
+            .iter()
+            .find(|block| block.block_hash().to_string() == hash)
+    }
+
+    async fn serve_esplora(chain: Chain) -> String {
+        let app = Router::new()
+            .route(
+                "/blocks/tip/height",
+                get(|State(chain): State<Chain>| async move { (chain.len() - 1).to_string() }),
+            )
+            .route(
+                "/block-height/:height",
+                get(
+                    |State(chain): State<Chain>, Path(height): Path<usize>| async move {
+                        chain
+                            .get(height)
+                            .map(|block| block.block_hash().to_string())
+                            .ok_or(StatusCode::NOT_FOUND)
+                    },
+                ),
+            )
+            .route(
+                "/blocks/:height",
+                get(
+                    |State(chain): State<Chain>, Path(height): Path<usize>| async move {
+                        if height >= chain.len() {
+                            return Err(StatusCode::NOT_FOUND);
+                        }
+                        let blocks = (height.saturating_sub(9)..=height)
+                            .rev()
+                            .map(|height| {
+                                serde_json::json!({
+                                    "height": height,
+                                    "bits": chain[height].header.bits.to_consensus(),
+                                })
+                            })
+                            .collect::<Vec<_>>();
+                        Ok(axum::Json(blocks))
+                    },
+                ),
+            )
+            .route(
+                "/block/:hash/raw",
+                get(
+                    |State(chain): State<Chain>, Path(hash): Path<String>| async move {
+                        find_block(&chain, &hash)
+                            .map(serialize)
+                            .ok_or(StatusCode::NOT_FOUND)
+                    },
+                ),
+            )
+            .route(
+                "/block/:hash/header",
+                get(
+                    |State(chain): State<Chain>, Path(hash): Path<String>| async move {
+                        find_block(&chain, &hash)
+                            .map(|block| serialize(&block.header).to_lower_hex_string())
+                            .ok_or(StatusCode::NOT_FOUND)
+                    },
+                ),
+            )
+            .with_state(chain);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}/", address)
+    }
+
+    #[tokio::test]
+    async fn test_esplora_source() {
+        let chain = Arc::new(test_chain(2100));
+        let client = EsploraClient::new(&serve_esplora(chain.clone()).await);
+
+        assert_eq!(client.get_block_count().await.unwrap(), 2099);
+        assert_eq!(
+            client.get_block_hash(5).await.unwrap(),
+            display_hash(&chain[5])
+        );
+        assert_eq!(client.get_block(7).await.unwrap(), chain[7]);
+        assert_eq!(
+            client.get_block_header(2050).await.unwrap(),
+            chain[2050].header
+        );
+        // spans a completed difficulty period
+        assert_eq!(
+            client.get_chainwork(2050).await.unwrap(),
+            expected_chainwork(2050)
+        );
+        assert_eq!(
+            client.get_chainwork(3).await.unwrap(),
+            expected_chainwork(3)
+        );
+        // blocks past the tip aren't listed
+        assert!(client.get_chainwork(2200).await.is_err());
+        assert!(client.get_block(2100).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_esplora_chainwork_sums_every_block() {
+        let mut chain = test_chain(2100);
+        // a harder block partway through the period, which the period's first block doesn't show
+        chain[2060].header.bits = CompactTarget::from_consensus(0x203fffff);
+        let extra_work = header_work(&chain[2060].header) - header_work(&chain[2059].header);
+        let client = EsploraClient::new(&serve_esplora(Arc::new(chain)).await);
+
+        assert_eq!(
+            client.get_chainwork(2059).await.unwrap(),
+            expected_chainwork(2059)
+        );
+        assert_eq!(
+            U256::from_be_bytes(client.get_chainwork(2070).await.unwrap()),
+            U256::from_be_bytes(expected_chainwork(2070)) + extra_work
+        );
+    }
+}
@@ -1,28 +1,40 @@
 use alloy::primitives::U256;
 use futures::stream::{StreamExt, TryStreamExt};
-use futures_util::stream;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 
-use bitcoin::{hashes::Hash, hex::DisplayHex, opcodes::all::OP_RETURN, script::Builder, Block};
-use log::{debug, info};
+use bitcoin::{block::Header, hashes::Hash, opcodes::all::OP_RETURN, script::Builder, Block};
+use log::{debug, error, info, warn};
+use metrics::{counter, gauge};
+use rift_core::btc_light_client::MEDIAN_TIME_SPAN;
+use rift_lib::payment::validate_payment;
 
 use crate::{
-    btc_rpc::BitcoinRpcClient,
-    constants::{CHECKPOINT_BLOCK_INTERVAL, CONFIRMATION_HEIGHT_DELTA},
-    core::ThreadSafeStore,
+    btc_source::BitcoinDataSource,
+    constants::BTC_HEADER_WINDOW,
+    core::{BitcoinPaymentRejected, BitcoinReservationFinalized, ThreadSafeStore},
     error::HypernodeError,
-    hyper_err, proof_builder, Result,
+    hyper_err, proof_builder,
+    telemetry::{
+        BTC_INDEXED_HEIGHT, BTC_REJECTED_PAYMENTS, BTC_TIP_HEIGHT, CONTRACT_BTC_HEIGHT,
+        CONTRACT_BTC_LAG,
+    },
+    Result,
 };
 
-fn build_rift_inscription(order_nonce: [u8; 32]) -> Vec<u8> {
+pub fn build_rift_inscription(order_nonce: [u8; 32]) -> Vec<u8> {
     Builder::new()
         .push_opcode(OP_RETURN)
-        .push_slice(&order_nonce)
+        .push_slice(order_nonce)
         .into_script()
         .into_bytes()
 }
 
+// payments that wouldn't pass the circuit's checks are rejected here, with the reason recorded,
+// rather than failing once the prover runs
 async fn analyze_block_for_payments(
     height: u64,
     block: &Block,
@@ -31,11 +43,16 @@ async fn analyze_block_for_payments(
     let expected_order_inscriptions = active_reservations
         .with_lock(|reservations_guard| {
             reservations_guard
-                .reservations
+                .reservations()
                 .iter()
                 .map(|(id, metadata)| {
-                    let script = build_rift_inscription(metadata.reservation.nonce.0);
-                    (id.clone(), script)
+                    let order_nonce = metadata.reservation.nonce.0;
+                    (
+                        *id,
+                        order_nonce,
+                        build_rift_inscription(order_nonce),
+                        metadata.liquidity_reservations(),
+                    )
                 })
                 .collect::<Vec<_>>()
         })
@@ -43,25 +60,47 @@ async fn analyze_block_for_payments(
 
     for tx in block.txdata.iter() {
         for script in tx.output.iter().map(|out| out.script_pubkey.clone()) {
-            for (id, expected_script) in expected_order_inscriptions.iter() {
-                if script.as_bytes() == expected_script {
-                    info!(
-                        "Found payment for reservation: {}, txid: {} at block height: {}",
-                        id,
-                        tx.compute_txid(),
-                        height
+            for (id, order_nonce, expected_script, liquidity_reservations) in
+                expected_order_inscriptions.iter()
+            {
+                if script.as_bytes() != expected_script {
+                    continue;
+                }
+                let txid = tx.compute_txid();
+                if let Err(e) = validate_payment(tx, liquidity_reservations, *order_nonce) {
+                    warn!(
+                        "Rejected payment for reservation: {}, txid: {} at block height: {}: {}",
+                        id, txid, height, e
                     );
+                    counter!(BTC_REJECTED_PAYMENTS).increment(1);
                     active_reservations
                         .with_lock(|reservations_guard| {
-                            reservations_guard.update_btc_reservation_initial(
+                            reservations_guard.update_btc_payment_rejected(
                                 *id,
-                                height,
-                                *block.block_hash().as_raw_hash().as_byte_array(),
-                                *tx.compute_txid().as_byte_array(),
-                            );
+                                BitcoinPaymentRejected {
+                                    txid: *txid.as_byte_array(),
+                                    block_height: height,
+                                    reason: e.to_string(),
+                                },
+                            )
                         })
-                        .await;
+                        .await?;
+                    continue;
                 }
+                info!(
+                    "Found payment for reservation: {}, txid: {} at block height: {}",
+                    id, txid, height
+                );
+                active_reservations
+                    .with_lock(|reservations_guard| {
+                        reservations_guard.update_btc_reservation_initial(
+                            *id,
+                            height,
+                            *block.block_hash().as_raw_hash().as_byte_array(),
+                            *txid.as_byte_array(),
+                        )
+                    })
+                    .await?;
             }
         }
     }
@@ -73,26 +112,25 @@ async fn analyze_reservations_for_sufficient_confirmations(
     height: u64,
     block: &Block,
     active_reservations: Arc<ThreadSafeStore>,
-    rpc_client: &BitcoinRpcClient,
+    rpc_client: &dyn BitcoinDataSource,
     proof_gen_queue: Arc<proof_builder::ProofGenerationQueue>,
+    config: &BlockListenerConfig,
 ) -> Result<()> {
     let pending_confirmation_reservations = active_reservations
         .with_lock(|reservations_guard| {
             reservations_guard
-                .reservations
+                .reservations()
                 .iter()
-                .filter_map(|(id, metadata)| {
-                    (metadata.btc_initial.as_ref().is_some()
-                        && metadata.btc_final.as_ref().is_none())
-                    .then(|| Some((id.clone(), metadata.clone())))
-                    .unwrap_or(None)
+                .filter(|(_, metadata)| {
+                    metadata.btc_initial.is_some() && metadata.btc_final.is_none()
                 })
+                .map(|(id, metadata)| (*id, metadata.clone()))
                 .collect::<Vec<_>>()
         })
         .await;
 
     let header_contract_btc_block_hashes = active_reservations
-        .with_lock(|reservations_guard| reservations_guard.safe_contract_block_hashes.clone())
+        .with_lock(|reservations_guard| reservations_guard.safe_contract_block_hashes().clone())
         .await;
 
     let mut available_btc_heights = header_contract_btc_block_hashes.keys().collect::<Vec<_>>();
@@ -100,18 +138,19 @@ async fn analyze_reservations_for_sufficient_confirmations(
 
     for (id, reservation_metadata) in pending_confirmation_reservations {
         let btc_initial_metadata = reservation_metadata.btc_initial.unwrap();
-        if btc_initial_metadata.proposed_block_height + CONFIRMATION_HEIGHT_DELTA > height {
+        if btc_initial_metadata.proposed_block_height + config.confirmation_height_delta > height {
             info!(
                 "Reservation: {} not confirmed yet, need {} more confirmations",
                 id,
-                btc_initial_metadata.proposed_block_height + CONFIRMATION_HEIGHT_DELTA - height
+                btc_initial_metadata.proposed_block_height + config.confirmation_height_delta
+                    - height
             );
             // not enough confirmations yet
             continue;
         }
         // If here, this reservation can be considered confirmed, it's confirmation height may be
         // behind the current tip so in that case, we need to find the closest available block to
-        // the right of the proposed block height + CONFIRMATION_HEIGHT_DELTA (min of 5)
+        // the right of the proposed block height + confirmation_height_delta (min of 5)
         // use binary search to find an available block height that is greater than or equal to the proposed block height
         let safe_height_index = match available_btc_heights
             .binary_search(&&btc_initial_metadata.proposed_block_height)
@@ -122,10 +161,10 @@ async fn analyze_reservations_for_sufficient_confirmations(
         let safe_height = available_btc_heights[safe_height_index];
 
         let min_confirmation_height =
-            btc_initial_metadata.proposed_block_height + CONFIRMATION_HEIGHT_DELTA;
+            btc_initial_metadata.proposed_block_height + config.confirmation_height_delta;
 
         // it's possible that the actual btc chain has enough confirmations but the contract chain
-        // does not, in that case, we provide the actual + CONFIRMATION_HEIGHT_DELTA block as the
+        // does not, in that case, we provide the actual + confirmation_height_delta block as the
         // confirmation height b/c the contract chain doesn't have enough blocks yet
         let confirmation_height =
             match available_btc_heights.binary_search(&&min_confirmation_height) {
@@ -151,55 +190,32 @@ async fn analyze_reservations_for_sufficient_confirmations(
         );
 
         let btc_rpc = rpc_client;
-        let blocks: Vec<Block> = stream::iter(*safe_height..*confirmation_height + 1)
-            .map(move |height| async move {
-                let block_hash = btc_rpc.get_block_hash(height).await.map_err(|e| {
-                    hyper_err!(
-                        RpcError,
-                        "Failed to get block hash for height {}: {}",
-                        height,
-                        e
-                    )
-                })?;
-
-                let block = btc_rpc.get_block(&block_hash).await.map_err(|e| {
-                    hyper_err!(
-                        RpcError,
-                        "Failed to get block for hash {}: {}",
-                        block_hash.to_hex_string(bitcoin::hex::Case::Lower),
-                        e
-                    )
-                })?;
-                Ok(block)
-            })
-            .buffered(10)
-            .collect::<Vec<Result<Block>>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>>>()?;
-
-        let blocks = blocks.into_iter().collect::<Vec<_>>();
+        let blocks: Vec<Block> = download_blocks(
+            btc_rpc,
+            *safe_height,
+            *confirmation_height,
+            config.block_batch_size,
+            config.max_concurrent_requests,
+        )
+        .await?
+        .into_iter()
+        .map(|(_, block)| block)
+        .collect();
 
-        let block_hash = btc_rpc
-            .get_block_hash(*safe_height)
-            .await
-            .map_err(|e| hyper_err!(RpcError, "Failed to get block hash: {}", e))?;
         let safe_chainwork = btc_rpc
-            .get_chainwork(&block_hash)
+            .get_chainwork(*safe_height)
             .await
             .map_err(|e| hyper_err!(RpcError, "Failed to get chainwork: {}", e))?;
 
         let retarget_height = safe_height - (safe_height % 2016);
 
-        let retarget_block_hash = btc_rpc
-            .get_block_hash(retarget_height)
-            .await
-            .map_err(|e| hyper_err!(RpcError, "Failed to get retarget block hash: {}", e))?;
         let retarget_block = btc_rpc
-            .get_block(&retarget_block_hash)
+            .get_block(retarget_height)
             .await
             .map_err(|e| hyper_err!(RpcError, "Failed to get retarget block: {}", e))?;
 
+        let ancestor_headers = download_ancestor_headers(btc_rpc, *safe_height).await?;
+
         debug!(
             "Retarget block height: {}, reservation: {}",
             retarget_height, id
@@ -218,48 +234,43 @@ async fn analyze_reservations_for_sufficient_confirmations(
         active_reservations
             .with_lock(|reservations_guard| {
                 reservations_guard.update_btc_reservation_final(
-                    id.clone(),
-                    *confirmation_height,
-                    *block.block_hash().as_raw_hash().as_byte_array(),
-                    *safe_height,
-                    safe_chainwork,
-                    blocks,
-                    retarget_block,
-                    retarget_height,
-                );
+                    id,
+                    BitcoinReservationFinalized {
+                        confirmation_height: *confirmation_height,
+                        confirmation_block_hash: *block.block_hash().as_raw_hash().as_byte_array(),
+                        safe_block_height: *safe_height,
+                        safe_block_chainwork: safe_chainwork,
+                        blocks,
+                        retarget_block,
+                        retarget_block_height: retarget_height,
+                        ancestor_headers,
+                    },
+                )
             })
-            .await;
+            .await?;
 
         // add it the proof gen queue
         info!("Adding reservation: {} to proof generation queue", id);
-        proof_gen_queue.add(proof_builder::ProofGenerationInput::new_reservation(
-            id.clone(),
-        ))?;
+        proof_gen_queue.add(proof_builder::ProofGenerationInput::new_reservation(id))?;
     }
 
     Ok(())
 }
 
 pub async fn find_block_height_from_time(
-    rpc_url: &str,
+    rpc: &dyn BitcoinDataSource,
     hours: u64,
     average_seconds_between_bitcoin_blocks: u64,
 ) -> Result<u64> {
-    let rpc = BitcoinRpcClient::new(rpc_url);
     let time = Instant::now();
     let current_block_height = rpc
         .get_block_count()
         .await
         .map_err(|e| hyper_err!(RpcError, "Failed to get block count: {}", e))?;
-    let current_block_hash = rpc
-        .get_block_hash(current_block_height)
-        .await
-        .map_err(|e| hyper_err!(RpcError, "Failed to get block hash: {}", e))?;
     let current_block_timestamp = rpc
-        .get_block(&current_block_hash)
+        .get_block_header(current_block_height)
         .await
-        .map_err(|e| hyper_err!(RpcError, "Failed to get block: {}", e))?
-        .header
+        .map_err(|e| hyper_err!(RpcError, "Failed to get block header: {}", e))?
         .time as u64;
     let target_timestamp = current_block_timestamp - hours * 3600;
     let blocks_per_hour = 3600 / average_seconds_between_bitcoin_blocks;
@@ -269,22 +280,17 @@ pub async fn find_block_height_from_time(
 
     while check_block > 0 && check_block != previous_check_block {
         previous_check_block = check_block;
-        let block_hash = rpc
-            .get_block_hash(check_block)
-            .await
-            .map_err(|e| hyper_err!(RpcError, "Failed to get block hash: {}", e))?;
         let block_timestamp = rpc
-            .get_block(&block_hash)
+            .get_block_header(check_block)
             .await
-            .map_err(|e| hyper_err!(RpcError, "Failed to get block: {}", e))?
-            .header
+            .map_err(|e| hyper_err!(RpcError, "Failed to get block header: {}", e))?
             .time as u64;
 
         if block_timestamp <= target_timestamp {
             info!(
                 "Found Bitcoin block height: {}, {:.2} hours from tip in {:?}",
                 check_block,
-                (current_block_timestamp - block_timestamp) as f64 / 3600 as f64,
+                (current_block_timestamp - block_timestamp) as f64 / 3600.0,
                 time.elapsed()
             );
             return Ok(check_block);
@@ -301,59 +307,168 @@ pub async fn find_block_height_from_time(
     Ok(check_block)
 }
 
+// headers of the MEDIAN_TIME_SPAN - 1 blocks preceding the safe block, oldest first. A safe block
+// closer to genesis, as on a fresh regtest chain, has every block before it as ancestors
+async fn download_ancestor_headers(
+    rpc: &dyn BitcoinDataSource,
+    safe_height: u64,
+) -> Result<Vec<Header>> {
+    let mut ancestor_headers = Vec::with_capacity(MEDIAN_TIME_SPAN - 1);
+    for height in safe_height.saturating_sub(MEDIAN_TIME_SPAN as u64 - 1)..safe_height {
+        let header = rpc.get_block_header(height).await.map_err(|e| {
+            hyper_err!(
+                RpcError,
+                "Failed to get block header for height {}: {}",
+                height,
+                e
+            )
+        })?;
+        ancestor_headers.push(header);
+    }
+    Ok(ancestor_headers)
+}
+
+// fetches the range in batches of batch_size blocks, up to max_concurrent_requests batches at a time
 async fn download_blocks(
-    rpc: &BitcoinRpcClient,
+    rpc: &dyn BitcoinDataSource,
     start_height: u64,
     end_height: u64,
+    batch_size: u64,
     max_concurrent_requests: usize,
 ) -> Result<Vec<(u64, Block)>> {
-    let heights_to_download = start_height..=end_height;
-
     info!(
         "Downloading bitcoin blocks: {} -> {}",
         start_height, end_height
     );
 
-    let blocks_with_heights: Result<Vec<(u64, Block)>> = futures::stream::iter(heights_to_download)
-        .map(|height| async move {
-            let block_hash = rpc.get_block_hash(height).await.map_err(|e| {
+    let batches = (start_height..=end_height)
+        .step_by(batch_size as usize)
+        .map(|batch_start| (batch_start, end_height.min(batch_start + batch_size - 1)));
+    let batches: Vec<Vec<(u64, Block)>> = futures::stream::iter(batches)
+        .map(|(batch_start, batch_end)| async move {
+            let blocks = rpc.get_blocks(batch_start, batch_end).await.map_err(|e| {
                 hyper_err!(
                     RpcError,
-                    "Failed to get block hash for height {}: {}",
-                    height,
+                    "Failed to get blocks for heights {} -> {}: {}",
+                    batch_start,
+                    batch_end,
                     e
                 )
             })?;
-            let block = rpc.get_block(&block_hash).await.map_err(|e| {
-                hyper_err!(
-                    RpcError,
-                    "Failed to get block for hash {}: {}",
-                    block_hash.to_hex_string(bitcoin::hex::Case::Lower),
-                    e
-                )
-            })?;
-            Ok((height, block))
+            Ok::<_, HypernodeError>((batch_start..=batch_end).zip(blocks).collect())
         })
-        .buffer_unordered(max_concurrent_requests)
+        .buffered(max_concurrent_requests)
         .try_collect()
+        .await?;
+
+    Ok(batches.into_iter().flatten().collect())
+}
+
+// rolls back everything that was derived from the orphaned blocks and cancels the affected proof
+// jobs, returns the height the block listener resumes scanning after
+async fn handle_reorg(
+    rpc: &dyn BitcoinDataSource,
+    store: &Arc<ThreadSafeStore>,
+    proof_gen_queue: &Arc<proof_builder::ProofGenerationQueue>,
+    mismatch_height: u64,
+) -> Result<u64> {
+    let block_hashes = store
+        .with_lock(|store| store.btc_block_hashes().clone())
         .await;
+    let fork_height = find_fork_height(rpc, &block_hashes, mismatch_height).await?;
 
-    let mut blocks_with_heights = blocks_with_heights?;
-    blocks_with_heights.sort_by_key(|(height, _)| *height);
+    warn!(
+        "Bitcoin reorg detected at height {}, rolling back to height {}",
+        mismatch_height, fork_height
+    );
+
+    let affected_reservations = store
+        .with_lock(|store| store.rollback_btc_blocks(fork_height))
+        .await?;
+    for id in affected_reservations {
+        proof_gen_queue.cancel_reservation(id);
+    }
+    proof_gen_queue.cancel_block_proofs();
 
-    Ok(blocks_with_heights)
+    Ok(fork_height)
+}
+
+// walks back through the stored header window until it finds the last block that is still part of
+// the best chain. When none of them are, the reorg is deeper than the window and the whole window is
+// treated as orphaned, so it is scanned again from just below it
+async fn find_fork_height(
+    rpc: &dyn BitcoinDataSource,
+    block_hashes: &BTreeMap<u64, [u8; 32]>,
+    mismatch_height: u64,
+) -> Result<u64> {
+    for (height, block_hash) in block_hashes.range(..=mismatch_height).rev() {
+        let mut best_chain_hash = rpc
+            .get_block_hash(*height)
+            .await
+            .map_err(|e| hyper_err!(RpcError, "Failed to get block hash: {}", e))?;
+        // rpc returns the hash in display order
+        best_chain_hash.reverse();
+        if best_chain_hash == *block_hash {
+            return Ok(*height);
+        }
+    }
+
+    let resync_height = block_hashes
+        .keys()
+        .next()
+        .map_or(mismatch_height, |height| height.saturating_sub(1));
+    error!(
+        "Bitcoin reorg at height {} is deeper than the {} block header window, resyncing from height {}",
+        mismatch_height, BTC_HEADER_WINDOW, resync_height
+    );
+    Ok(resync_height)
+}
+
+pub struct BlockListenerConfig {
+    // seconds between polls for new blocks once synced
+    pub polling_interval: u64,
+    // seconds a reservation stays open for
+    pub reservation_duration: u64,
+    pub confirmation_height_delta: u64,
+    // blocks the contract can fall behind the tip before proveBlocks is called
+    pub checkpoint_block_interval: u64,
+    pub prove_blocks_interval: Duration,
+    // blocks requested per batch when downloading block ranges
+    pub block_batch_size: u64,
+    // batches of blocks downloaded at once
+    pub max_concurrent_requests: usize,
+}
+
+// Lets an operator ask for a proveBlocks as soon as the indexer is synced, instead of waiting for
+// the contract to fall checkpoint_block_interval blocks behind and prove_blocks_interval to pass
+#[derive(Default)]
+pub struct ProveBlocksRequest {
+    requested: AtomicBool,
+    notify: Notify,
+}
+
+impl ProveBlocksRequest {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    pub(crate) fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
 }
 
 // analyzes every btc block in the range [start_block_height, current_height] for reservation
 // payments, once it's fully sync'd to the current tip, it will poll for new blocks every
-// polling_interval seconds
+// polling_interval seconds or as soon as new_block is notified
 pub async fn block_listener(
-    btc_rpc: Arc<BitcoinRpcClient>,
+    btc_rpc: Arc<dyn BitcoinDataSource>,
     start_block_height: u64,
-    polling_interval: u64,
     store: Arc<ThreadSafeStore>,
     proof_gen_queue: Arc<proof_builder::ProofGenerationQueue>,
-    max_concurrent_requests: usize,
+    prove_blocks_request: Arc<ProveBlocksRequest>,
+    new_block: Arc<Notify>,
+    config: BlockListenerConfig,
 ) -> Result<()> {
     let rpc = btc_rpc;
     let mut current_height = rpc
@@ -364,26 +479,59 @@ pub async fn block_listener(
     let mut total_blocks_to_sync = current_height.saturating_sub(start_block_height);
     let mut fully_synced_logged = false;
 
-    const PROVE_BLOCKS_INTERVAL: Duration = Duration::from_secs(12 * 3600); // 12 hours
-    let mut last_prove_blocks_time = Instant::now() - PROVE_BLOCKS_INTERVAL;
+    // unset until the first proveBlocks, an interval longer than the uptime can't be subtracted
+    let mut last_prove_blocks_time: Option<Instant> = None;
 
     loop {
+        gauge!(BTC_TIP_HEIGHT).set(current_height as f64);
         if current_height > analyzed_height {
             let blocks_with_heights = download_blocks(
-                &rpc,
+                rpc.as_ref(),
                 analyzed_height + 1,
                 current_height,
-                max_concurrent_requests,
+                config.block_batch_size,
+                config.max_concurrent_requests,
             )
             .await?;
 
             for (height, block) in blocks_with_heights {
+                let previous_block_hash = store
+                    .with_lock(|store| {
+                        store
+                            .btc_block_hashes()
+                            .get(&height.saturating_sub(1))
+                            .cloned()
+                    })
+                    .await;
+                if previous_block_hash.is_some_and(|previous_block_hash| {
+                    previous_block_hash != block.header.prev_blockhash.to_byte_array()
+                }) {
+                    analyzed_height = handle_reorg(
+                        rpc.as_ref(),
+                        &store,
+                        &proof_gen_queue,
+                        height.saturating_sub(1),
+                    )
+                    .await?;
+                    current_height = rpc
+                        .get_block_count()
+                        .await
+                        .map_err(|e| hyper_err!(RpcError, "Failed to get block count: {}", e))?;
+                    // re-scan the new best chain from the fork point
+                    break;
+                }
+
                 analyzed_height = height;
                 let current_timestamp = chrono::Utc::now().timestamp() as u64;
 
                 store
-                    .with_lock(|store| store.drop_expired_reservations(current_timestamp))
-                    .await;
+                    .with_lock(|store| {
+                        store.drop_expired_reservations(
+                            current_timestamp,
+                            config.reservation_duration,
+                        )
+                    })
+                    .await?;
 
                 let sift_start = Instant::now();
                 analyze_block_for_payments(analyzed_height, &block, Arc::clone(&store)).await?;
@@ -397,8 +545,9 @@ pub async fn block_listener(
                     analyzed_height,
                     &block,
                     Arc::clone(&store),
-                    &rpc,
+                    rpc.as_ref(),
                     Arc::clone(&proof_gen_queue),
+                    &config,
                 )
                 .await?;
 
@@ -408,7 +557,17 @@ pub async fn block_listener(
                     sift_start.elapsed()
                 );
 
-                let blocks_synced = analyzed_height - start_block_height + 1;
+                store
+                    .with_lock(|store| {
+                        store.update_btc_analyzed_block(
+                            analyzed_height,
+                            block.block_hash().to_byte_array(),
+                        )
+                    })
+                    .await?;
+                gauge!(BTC_INDEXED_HEIGHT).set(analyzed_height as f64);
+
+                let blocks_synced = (analyzed_height + 1).saturating_sub(start_block_height);
                 let progress_percentage = ((blocks_synced as f64 / total_blocks_to_sync as f64)
                     * 100.0)
                     .clamp(0.0, 100.0);
@@ -419,7 +578,29 @@ pub async fn block_listener(
                 fully_synced_logged = false;
             }
         } else {
-            // We've caught up, check for new blocks
+            // We've caught up, make sure the tip we analyzed hasn't been replaced
+            let analyzed_tip_hash = store
+                .with_lock(|store| store.btc_block_hashes().get(&analyzed_height).cloned())
+                .await;
+            if let Some(analyzed_tip_hash) = analyzed_tip_hash {
+                let mut best_chain_hash = rpc
+                    .get_block_hash(analyzed_height)
+                    .await
+                    .map_err(|e| hyper_err!(RpcError, "Failed to get block hash: {}", e))?;
+                best_chain_hash.reverse();
+                if best_chain_hash != analyzed_tip_hash {
+                    analyzed_height =
+                        handle_reorg(rpc.as_ref(), &store, &proof_gen_queue, analyzed_height)
+                            .await?;
+                    current_height = rpc
+                        .get_block_count()
+                        .await
+                        .map_err(|e| hyper_err!(RpcError, "Failed to get block count: {}", e))?;
+                    continue;
+                }
+            }
+
+            // check for new blocks
             let new_height = rpc
                 .get_block_count()
                 .await
@@ -443,7 +624,7 @@ pub async fn block_listener(
             let latest_contract_block_height = store
                 .with_lock(|store| {
                     store
-                        .safe_contract_block_hashes
+                        .safe_contract_block_hashes()
                         .keys()
                         .max()
                         .cloned()
@@ -451,12 +632,23 @@ pub async fn block_listener(
                 })
                 .await;
             let latest_btc_block_height = rpc.get_block_count().await?;
+            let contract_lag = latest_btc_block_height.saturating_sub(latest_contract_block_height);
+            gauge!(CONTRACT_BTC_HEIGHT).set(latest_contract_block_height as f64);
+            gauge!(CONTRACT_BTC_LAG).set(contract_lag as f64);
+            let prove_blocks_requested = prove_blocks_request.take();
+            if prove_blocks_requested && contract_lag == 0 {
+                info!("proveBlocks requested but the contract is already at the bitcoin tip");
+            }
             // TODO: This is a rough heuristic, better solution would be to use something evm block based, not time based
-            if latest_btc_block_height.saturating_sub(latest_contract_block_height)
-                > CHECKPOINT_BLOCK_INTERVAL
+            if contract_lag > config.checkpoint_block_interval
+                || (prove_blocks_requested && contract_lag > 0)
             {
                 let now = Instant::now();
-                if now.duration_since(last_prove_blocks_time) >= PROVE_BLOCKS_INTERVAL {
+                if prove_blocks_requested
+                    || !last_prove_blocks_time.is_some_and(|last_prove_blocks_time| {
+                        now.duration_since(last_prove_blocks_time) < config.prove_blocks_interval
+                    })
+                {
                     info!(
                         "Calling proveBlocks at height {}",
                         latest_contract_block_height
@@ -465,10 +657,11 @@ pub async fn block_listener(
                     let confirmation_height = latest_btc_block_height;
 
                     let blocks_with_heights = download_blocks(
-                        &rpc,
+                        rpc.as_ref(),
                         safe_height,
                         confirmation_height,
-                        max_concurrent_requests,
+                        config.block_batch_size,
+                        config.max_concurrent_requests,
                     )
                     .await?;
 
@@ -478,46 +671,160 @@ pub async fn block_listener(
                         .map(|(_, block)| block)
                         .collect();
 
-                    let block_hash = rpc
-                        .get_block_hash(safe_height)
-                        .await
-                        .map_err(|e| hyper_err!(RpcError, "Failed to get block hash: {}", e))?;
-
                     let safe_chainwork = rpc
-                        .get_chainwork(&block_hash)
+                        .get_chainwork(safe_height)
                         .await
                         .map_err(|e| hyper_err!(RpcError, "Failed to get chainwork: {}", e))?;
 
                     let retarget_height = safe_height - (safe_height % 2016);
 
-                    let retarget_block_hash =
-                        rpc.get_block_hash(retarget_height).await.map_err(|e| {
-                            hyper_err!(RpcError, "Failed to get retarget block hash: {}", e)
-                        })?;
                     let retarget_block = rpc
-                        .get_block(&retarget_block_hash)
+                        .get_block(retarget_height)
                         .await
                         .map_err(|e| hyper_err!(RpcError, "Failed to get retarget block: {}", e))?;
 
+                    let ancestor_headers =
+                        download_ancestor_headers(rpc.as_ref(), safe_height).await?;
+
                     info!(
                         "Contract database is out of sync by {} blocks. Proving blocks...",
-                        latest_btc_block_height.saturating_sub(latest_contract_block_height)
+                        contract_lag
                     );
 
                     proof_gen_queue.add(proof_builder::ProofGenerationInput::new_block_proof(
-                        U256::from_be_slice(&safe_chainwork),
-                        safe_height,
-                        blocks,
-                        retarget_block,
-                        retarget_height,
+                        proof_builder::BlockProofInput {
+                            safe_chainwork: U256::from_be_slice(&safe_chainwork),
+                            safe_block_height: safe_height,
+                            blocks,
+                            retarget_block,
+                            retarget_block_height: retarget_height,
+                            ancestor_headers,
+                        },
                     ))?;
 
-                    last_prove_blocks_time = now;
+                    last_prove_blocks_time = Some(now);
                 }
             }
 
-            // Sleep and try again
-            tokio::time::sleep(tokio::time::Duration::from_secs(polling_interval)).await;
+            // Sleep and try again, a proveBlocks request or a new block notification cuts the wait
+            // short
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(config.polling_interval)) => {}
+                _ = prove_blocks_request.notify.notified() => {}
+                _ = new_block.notified() => {}
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btc_source::tests::test_chain;
+    use crate::core::Store;
+    use async_trait::async_trait;
+
+    // serves a chain held in memory
+    struct MockChain(Vec<Block>);
+
+    #[async_trait]
+    impl BitcoinDataSource for MockChain {
+        async fn get_block_count(&self) -> Result<u64> {
+            Ok(self.0.len() as u64 - 1)
+        }
+
+        async fn get_block_hash(&self, height: u64) -> Result<[u8; 32]> {
+            let mut block_hash = self.get_block(height).await?.block_hash().to_byte_array();
+            block_hash.reverse();
+            Ok(block_hash)
+        }
+
+        async fn get_block(&self, height: u64) -> Result<Block> {
+            self.0
+                .get(height as usize)
+                .cloned()
+                .ok_or_else(|| hyper_err!(RpcError, "No block at height {}", height))
+        }
+
+        async fn get_block_header(&self, height: u64) -> Result<Header> {
+            Ok(self.get_block(height).await?.header)
+        }
+
+        async fn get_chainwork(&self, height: u64) -> Result<[u8; 32]> {
+            Ok(U256::from(2 * (height + 1)).to_be_bytes::<32>())
+        }
+    }
+
+    // hashes of the blocks at `heights`, as the block listener stores them
+    fn stored_hashes(chain: &[Block], heights: std::ops::Range<u64>) -> BTreeMap<u64, [u8; 32]> {
+        heights
+            .map(|height| (height, chain[height as usize].block_hash().to_byte_array()))
+            .collect()
+    }
+
+    // the chain with every block from `height` replaced by a competing one
+    fn fork_chain(chain: &[Block], height: usize) -> Vec<Block> {
+        let mut fork = chain[..height].to_vec();
+        for block in &chain[height..] {
+            let mut block = block.clone();
+            block.header.prev_blockhash = fork.last().unwrap().block_hash();
+            block.header.time += 1;
+            fork.push(block);
+        }
+        fork
+    }
+
+    #[tokio::test]
+    async fn test_find_fork_height() {
+        let chain = test_chain(30);
+        let source = MockChain(fork_chain(&chain, 25));
+
+        let fork_height = find_fork_height(&source, &stored_hashes(&chain, 10..28), 27)
+            .await
+            .unwrap();
+        assert_eq!(fork_height, 24);
+    }
+
+    #[tokio::test]
+    async fn test_find_fork_height_deeper_than_window() {
+        let chain = test_chain(30);
+        let source = MockChain(fork_chain(&chain, 5));
+        let mut store = Store::new();
+        for (height, block_hash) in stored_hashes(&chain, 10..28) {
+            store.update_btc_analyzed_block(height, block_hash).unwrap();
+        }
+
+        // every stored block is orphaned, so the window is dropped and scanned again from below it
+        let fork_height = find_fork_height(&source, store.btc_block_hashes(), 27)
+            .await
+            .unwrap();
+        assert_eq!(fork_height, 9);
+        store.rollback_btc_blocks(fork_height).unwrap();
+        assert!(store.btc_block_hashes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_download_ancestor_headers() {
+        let chain = test_chain(20);
+        let source = MockChain(chain.clone());
+
+        let headers = download_ancestor_headers(&source, 15).await.unwrap();
+        assert_eq!(headers.len(), MEDIAN_TIME_SPAN - 1);
+        assert_eq!(headers[0], chain[5].header);
+        assert_eq!(*headers.last().unwrap(), chain[14].header);
+
+        // near genesis every earlier block is an ancestor
+        let headers = download_ancestor_headers(&source, 4).await.unwrap();
+        assert_eq!(
+            headers,
+            chain[..4]
+                .iter()
+                .map(|block| block.header)
+                .collect::<Vec<_>>()
+        );
+        assert!(download_ancestor_headers(&source, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+// Watches bitcoind's mempool for payments to reservations that are still waiting for one, so
+// operators can see a payment, and whether it pays the reserved vaults, before it is mined. A
+// pending payment is only informational, reservations move on once their payment is mined
+use crate::btc_indexer::build_rift_inscription;
+use crate::btc_rpc::BitcoinRpcClient;
+use crate::core::{BitcoinPaymentPending, ThreadSafeStore};
+use crate::telemetry::BTC_PENDING_PAYMENTS;
+use crate::Result;
+use alloy::primitives::U256;
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::all::{OP_PUSHBYTES_32, OP_RETURN};
+use bitcoin::{Script, Transaction, Txid};
+use log::{info, warn};
+use metrics::gauge;
+use rift_lib::payment::validate_payment;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+// transactions fetched per getrawtransaction batch
+const MEMPOOL_BATCH_SIZE: usize = 100;
+
+// mempool transactions already fetched, only the ones inscribing something that could be an order
+// nonce are kept around
+#[derive(Default)]
+struct MempoolCache {
+    seen: HashSet<Txid>,
+    inscribed: HashMap<Txid, Transaction>,
+}
+
+pub async fn mempool_listener(
+    rpc: Arc<BitcoinRpcClient>,
+    store: Arc<ThreadSafeStore>,
+    polling_interval: u64,
+) {
+    let mut cache = MempoolCache::default();
+    loop {
+        if let Err(e) = scan_mempool(&rpc, &store, &mut cache).await {
+            warn!("Failed to scan the bitcoin mempool: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(polling_interval)).await;
+    }
+}
+
+async fn scan_mempool(
+    rpc: &BitcoinRpcClient,
+    store: &ThreadSafeStore,
+    cache: &mut MempoolCache,
+) -> Result<()> {
+    let mempool: HashSet<Txid> = rpc.get_raw_mempool().await?.into_iter().collect();
+    cache.seen.retain(|txid| mempool.contains(txid));
+    cache.inscribed.retain(|txid, _| mempool.contains(txid));
+
+    let new_txids: Vec<Txid> = mempool.difference(&cache.seen).copied().collect();
+    for txids in new_txids.chunks(MEMPOOL_BATCH_SIZE) {
+        for (txid, tx) in txids.iter().zip(rpc.get_raw_transactions(txids).await?) {
+            // mined or evicted since the mempool was listed
+            let Ok(tx) = tx else {
+                continue;
+            };
+            if tx
+                .output
+                .iter()
+                .any(|output| inscribes_nonce(&output.script_pubkey))
+            {
+                cache.inscribed.insert(*txid, tx);
+            }
+        }
+        cache.seen.extend(txids);
+    }
+
+    // reservations with a confirmed payment are left to the block listener
+    let awaiting_payment = store
+        .with_lock(|store| {
+            store
+                .reservations()
+                .iter()
+                .filter(|(_, metadata)| metadata.btc_initial.is_none())
+                .map(|(id, metadata)| {
+                    (
+                        *id,
+                        metadata.reservation.nonce.0,
+                        metadata.liquidity_reservations(),
+                        metadata.btc_pending.as_ref().map(|pending| pending.txid),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+
+    let mut pending_payments = 0;
+    for (id, order_nonce, liquidity_reservations, pending_txid) in awaiting_payment {
+        let inscription = build_rift_inscription(order_nonce);
+        let payment = cache.inscribed.iter().find(|(_, tx)| {
+            tx.output
+                .iter()
+                .any(|output| output.script_pubkey.as_bytes() == inscription)
+        });
+        let pending = match (payment, pending_txid) {
+            (Some((txid, _)), Some(pending_txid)) if *txid.as_byte_array() == pending_txid => {
+                pending_payments += 1;
+                continue;
+            }
+            (Some((txid, tx)), _) => {
+                pending_payments += 1;
+                let payment_error = match validate_payment(tx, &liquidity_reservations, order_nonce)
+                {
+                    Ok(_) => {
+                        info!(
+                            "Found pending payment for reservation: {}, txid: {}",
+                            id, txid
+                        );
+                        None
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Pending payment for reservation: {}, txid: {} can't be proven: {}",
+                            id, txid, e
+                        );
+                        Some(e.to_string())
+                    }
+                };
+                Some(BitcoinPaymentPending {
+                    txid: *txid.as_byte_array(),
+                    first_seen: chrono::Utc::now().timestamp() as u64,
+                    payment_error,
+                })
+            }
+            (None, Some(pending_txid)) => {
+                info!(
+                    "Pending payment for reservation: {}, txid: {} left the mempool",
+                    id,
+                    Txid::from_byte_array(pending_txid)
+                );
+                None
+            }
+            (None, None) => continue,
+        };
+        update_pending(store, id, pending).await?;
+    }
+    gauge!(BTC_PENDING_PAYMENTS).set(pending_payments as f64);
+    Ok(())
+}
+
+// the block listener may have found the payment, or the reservation expired, since the store
+// was read
+async fn update_pending(
+    store: &ThreadSafeStore,
+    id: U256,
+    pending: Option<BitcoinPaymentPending>,
+) -> Result<()> {
+    store
+        .with_lock(|store| {
+            if store
+                .get(id)
+                .is_some_and(|metadata| metadata.btc_initial.is_none())
+            {
+                store.update_btc_payment_pending(id, pending)?;
+            }
+            Ok(())
+        })
+        .await
+}
+
+fn inscribes_nonce(script: &Script) -> bool {
+    matches!(
+        script.as_bytes(),
+        [op_return, push, ..] if script.len() == 34
+            && *op_return == OP_RETURN.to_u8()
+            && *push == OP_PUSHBYTES_32.to_u8()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::ScriptBuf;
+
+    #[test]
+    fn test_inscribes_nonce() {
+        let inscription = ScriptBuf::from_bytes(build_rift_inscription([3; 32]));
+        assert!(inscribes_nonce(&inscription));
+        // a 20 byte push isn't a nonce
+        assert!(!inscribes_nonce(&ScriptBuf::new_op_return([3; 20])));
+    }
+}
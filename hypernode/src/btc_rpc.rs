@@ -1,64 +1,194 @@
+use crate::btc_source::BitcoinDataSource;
 use crate::error::HypernodeError;
 use crate::{hyper_err, Result};
+use async_trait::async_trait;
+use backoff::ExponentialBackoff;
+use bitcoin::block::Header;
 use bitcoin::consensus::deserialize;
-use bitcoin::Block;
-use reqwest::Client;
+use bitcoin::{Block, Transaction, Txid};
+use reqwest::{Client, StatusCode};
 use serde_json::Value;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// bitcoind answers with this while it loads the chain after starting
+const RPC_IN_WARMUP: i64 = -28;
+
+// credentials embedded in the rpc url are sent as they are, without any of these
+#[derive(Clone, Debug, Default)]
+pub enum BitcoinRpcAuth {
+    #[default]
+    None,
+    UserPass {
+        user: String,
+        password: String,
+    },
+    // read on every request as bitcoind writes a new cookie each time it starts
+    CookieFile(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct BitcoinRpcConfig {
+    pub auth: BitcoinRpcAuth,
+    // time a single attempt at a request can take
+    pub timeout: Duration,
+    // time a request is retried for after transient failures
+    pub max_retry_time: Duration,
+}
+
+impl Default for BitcoinRpcConfig {
+    fn default() -> Self {
+        Self {
+            auth: BitcoinRpcAuth::None,
+            timeout: Duration::from_secs(30),
+            max_retry_time: Duration::from_secs(60),
+        }
+    }
+}
+
+struct RpcResponse {
+    status: StatusCode,
+    text: String,
+    json: Value,
+}
 
 pub struct BitcoinRpcClient {
     client: Client,
     id_counter: Arc<Mutex<u64>>,
     rpc_url: String,
+    config: BitcoinRpcConfig,
 }
 
 impl BitcoinRpcClient {
     pub fn new(rpc_url: &str) -> Self {
+        Self::with_config(rpc_url, BitcoinRpcConfig::default())
+    }
+
+    pub fn with_config(rpc_url: &str, config: BitcoinRpcConfig) -> Self {
         Self {
             client: Client::new(),
             id_counter: Arc::new(Mutex::new(0)),
             rpc_url: rpc_url.to_string(),
+            config,
         }
     }
 
-    async fn send_request(&self, method: &str, params: Value) -> Result<Value> {
-        let id = {
-            let mut counter = self.id_counter.lock().unwrap();
-            *counter += 1;
-            *counter
+    // reserves `count` consecutive request ids and returns the first
+    fn next_ids(&self, count: u64) -> u64 {
+        let mut counter = self.id_counter.lock().unwrap();
+        let first = *counter + 1;
+        *counter += count;
+        first
+    }
+
+    // posts the body, retrying failures to reach bitcoind and errors it returns while warming up.
+    // Any other rpc error is left in the response for the caller
+    async fn post(&self, body: &Value) -> Result<RpcResponse> {
+        let backoff = ExponentialBackoff {
+            max_elapsed_time: Some(self.config.max_retry_time),
+            ..Default::default()
         };
+        backoff::future::retry(backoff, || self.post_once(body)).await
+    }
 
-        let response = self
+    async fn post_once(
+        &self,
+        body: &Value,
+    ) -> std::result::Result<RpcResponse, backoff::Error<HypernodeError>> {
+        let mut request = self
             .client
             .post(&self.rpc_url)
-            .json(&serde_json::json!({
-                "jsonrpc": "1.0",
-                "id": id,
-                "method": method,
-                "params": params
-            }))
-            .send()
-            .await
-            .map_err(|e| hyper_err!(BitcoinRpc, "Failed to send request: {}", e))?;
+            .timeout(self.config.timeout)
+            .json(body);
+        match &self.config.auth {
+            BitcoinRpcAuth::None => {}
+            BitcoinRpcAuth::UserPass { user, password } => {
+                request = request.basic_auth(user, Some(password));
+            }
+            BitcoinRpcAuth::CookieFile(path) => {
+                // missing while bitcoind restarts
+                let cookie = tokio::fs::read_to_string(path).await.map_err(|e| {
+                    backoff::Error::transient(hyper_err!(
+                        BitcoinRpc,
+                        "Failed to read cookie file {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                let (user, password) = cookie.trim().split_once(':').ok_or_else(|| {
+                    backoff::Error::permanent(hyper_err!(
+                        Config,
+                        "Invalid cookie file {}",
+                        path.display()
+                    ))
+                })?;
+                request = request.basic_auth(user, Some(password));
+            }
+        }
+
+        let response = request.send().await.map_err(|e| {
+            backoff::Error::transient(hyper_err!(BitcoinRpc, "Failed to send request: {}", e))
+        })?;
 
         let status = response.status();
-        let text = response
-            .text()
-            .await
-            .map_err(|e| hyper_err!(BitcoinRpc, "Failed to get response body: {}", e))?;
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(backoff::Error::permanent(hyper_err!(
+                BitcoinRpc,
+                "Bitcoin RPC rejected the credentials, status: {}",
+                status
+            )));
+        }
+        let text = response.text().await.map_err(|e| {
+            backoff::Error::transient(hyper_err!(BitcoinRpc, "Failed to get response body: {}", e))
+        })?;
 
         let json: Value = serde_json::from_str(&text).map_err(|e| {
-            hyper_err!(
+            let error = hyper_err!(
                 BitcoinRpc,
-                "Failed to parse JSON. Method: {}, Params: {:?}, Status: {}, Body: {}, Error: {}",
-                method,
-                params,
+                "Failed to parse JSON. Status: {}, Body: {}, Error: {}",
                 status,
                 text,
                 e
-            )
+            );
+            // bitcoind answers with plain text when its work queue is full
+            if status.is_server_error() {
+                backoff::Error::transient(error)
+            } else {
+                backoff::Error::permanent(error)
+            }
         })?;
 
+        let responses = match &json {
+            Value::Array(responses) => responses.iter().collect(),
+            response => vec![response],
+        };
+        if responses
+            .iter()
+            .any(|response| response["error"]["code"].as_i64() == Some(RPC_IN_WARMUP))
+        {
+            return Err(backoff::Error::transient(hyper_err!(
+                BitcoinRpc,
+                "Bitcoin node is warming up: {}",
+                text
+            )));
+        }
+
+        Ok(RpcResponse { status, text, json })
+    }
+
+    async fn send_request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_ids(1);
+        let RpcResponse { status, text, json } = self
+            .post(&serde_json::json!({
+                "jsonrpc": "1.0",
+                "id": id,
+                "method": method,
+                "params": params
+            }))
+            .await?;
+
         if let Some(error) = json.get("error") {
             if !error.is_null() {
                 return Err(hyper_err!(
@@ -84,6 +214,69 @@ impl BitcoinRpcClient {
         })
     }
 
+    // sends the calls as one JSON-RPC batch, the batch fails as a whole when bitcoind can't be
+    // reached and each call fails on its own when bitcoind returns an error for it
+    async fn send_batch(&self, calls: Vec<(&str, Value)>) -> Result<Vec<Result<Value>>> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        let first_id = self.next_ids(calls.len() as u64);
+        let batch: Vec<Value> = calls
+            .iter()
+            .zip(first_id..)
+            .map(|((method, params), id)| {
+                serde_json::json!({
+                    "jsonrpc": "1.0",
+                    "id": id,
+                    "method": method,
+                    "params": params
+                })
+            })
+            .collect();
+        let RpcResponse { status, json, .. } = self.post(&Value::Array(batch)).await?;
+        let Value::Array(responses) = json else {
+            return Err(hyper_err!(
+                BitcoinRpc,
+                "Expected a batch response, status: {}, got: {}",
+                status,
+                json
+            ));
+        };
+
+        let mut results: Vec<Option<Result<Value>>> = vec![None; calls.len()];
+        for response in responses {
+            let Some(index) = response
+                .get("id")
+                .and_then(Value::as_u64)
+                .and_then(|id| id.checked_sub(first_id))
+                .map(|index| index as usize)
+                .filter(|index| *index < calls.len())
+            else {
+                continue;
+            };
+            results[index] = Some(match response.get("error") {
+                Some(error) if !error.is_null() => Err(hyper_err!(
+                    BitcoinRpc,
+                    "RPC error. Method: {}, Params: {:?}, Error: {}",
+                    calls[index].0,
+                    calls[index].1,
+                    error
+                )),
+                _ => response
+                    .get("result")
+                    .cloned()
+                    .ok_or_else(|| hyper_err!(BitcoinRpc, "No 'result' in response")),
+            });
+        }
+        results
+            .into_iter()
+            .zip(&calls)
+            .map(|(result, (method, _))| {
+                result.ok_or_else(|| hyper_err!(BitcoinRpc, "No response for {} in batch", method))
+            })
+            .collect()
+    }
+
     pub async fn get_block_count(&self) -> Result<u64> {
         let result = self
             .send_request("getblockcount", Value::Array(vec![]))
@@ -97,14 +290,24 @@ impl BitcoinRpcClient {
         let result = self
             .send_request("getblockhash", Value::Array(vec![block_height.into()]))
             .await?;
-        let block_hexstr = result
-            .as_str()
-            .ok_or_else(|| hyper_err!(BitcoinRpc, "Block hash doesn't exist"))?;
-        let block_hash: [u8; 32] = hex::decode(block_hexstr)
-            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block hash"))?
-            .try_into()
-            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block hash"))?;
-        Ok(block_hash)
+        parse_block_hash(&result)
+    }
+
+    // hashes of the blocks at heights start_height..=end_height, fetched in one batch
+    pub async fn get_block_hashes(
+        &self,
+        start_height: u64,
+        end_height: u64,
+    ) -> Result<Vec<[u8; 32]>> {
+        self.send_batch(
+            (start_height..=end_height)
+                .map(|height| ("getblockhash", Value::Array(vec![height.into()])))
+                .collect(),
+        )
+        .await?
+        .into_iter()
+        .map(|result| parse_block_hash(&result?))
+        .collect()
     }
 
     pub async fn get_block(&self, block_hash: &[u8; 32]) -> Result<Block> {
@@ -114,13 +317,79 @@ impl BitcoinRpcClient {
                 Value::Array(vec![hex::encode(block_hash).into(), 0.into()]),
             )
             .await?;
-        let block_hexstr = result
+        parse_block(&result)
+    }
+
+    // blocks with the given hashes, fetched in one batch
+    pub async fn get_blocks(&self, block_hashes: &[[u8; 32]]) -> Result<Vec<Block>> {
+        self.send_batch(
+            block_hashes
+                .iter()
+                .map(|block_hash| {
+                    (
+                        "getblock",
+                        Value::Array(vec![hex::encode(block_hash).into(), 0.into()]),
+                    )
+                })
+                .collect(),
+        )
+        .await?
+        .into_iter()
+        .map(|result| parse_block(&result?))
+        .collect()
+    }
+
+    pub async fn get_raw_mempool(&self) -> Result<Vec<Txid>> {
+        let result = self
+            .send_request("getrawmempool", Value::Array(vec![]))
+            .await?;
+        result
+            .as_array()
+            .ok_or_else(|| hyper_err!(BitcoinRpc, "Invalid mempool"))?
+            .iter()
+            .map(|txid| {
+                txid.as_str()
+                    .and_then(|txid| Txid::from_str(txid).ok())
+                    .ok_or_else(|| hyper_err!(BitcoinRpc, "Invalid mempool txid"))
+            })
+            .collect()
+    }
+
+    // mempool transactions with the given txids, fetched in one batch. A transaction that left the
+    // mempool since it was listed fails on its own
+    pub async fn get_raw_transactions(&self, txids: &[Txid]) -> Result<Vec<Result<Transaction>>> {
+        Ok(self
+            .send_batch(
+                txids
+                    .iter()
+                    .map(|txid| {
+                        (
+                            "getrawtransaction",
+                            Value::Array(vec![txid.to_string().into()]),
+                        )
+                    })
+                    .collect(),
+            )
+            .await?
+            .into_iter()
+            .map(|result| parse_transaction(&result?))
+            .collect())
+    }
+
+    pub async fn get_block_header(&self, block_hash: &[u8; 32]) -> Result<Header> {
+        let result = self
+            .send_request(
+                "getblockheader",
+                Value::Array(vec![hex::encode(block_hash).into(), false.into()]),
+            )
+            .await?;
+        let header_hexstr = result
             .as_str()
-            .ok_or_else(|| hyper_err!(BitcoinRpc, "Block doesn't exist"))?;
-        let block_bytes =
-            hex::decode(block_hexstr).map_err(|_| hyper_err!(BitcoinRpc, "Invalid block data"))?;
-        deserialize::<Block>(&block_bytes)
-            .map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize block"))
+            .ok_or_else(|| hyper_err!(BitcoinRpc, "Block header doesn't exist"))?;
+        let header_bytes = hex::decode(header_hexstr)
+            .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block header data"))?;
+        deserialize::<Header>(&header_bytes)
+            .map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize block header"))
     }
 
     pub async fn get_chainwork(&self, block_hash: &[u8; 32]) -> Result<[u8; 32]> {
@@ -143,10 +412,75 @@ impl BitcoinRpcClient {
     }
 }
 
+fn parse_block_hash(result: &Value) -> Result<[u8; 32]> {
+    let block_hexstr = result
+        .as_str()
+        .ok_or_else(|| hyper_err!(BitcoinRpc, "Block hash doesn't exist"))?;
+    hex::decode(block_hexstr)
+        .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block hash"))?
+        .try_into()
+        .map_err(|_| hyper_err!(BitcoinRpc, "Invalid block hash"))
+}
+
+fn parse_block(result: &Value) -> Result<Block> {
+    let block_hexstr = result
+        .as_str()
+        .ok_or_else(|| hyper_err!(BitcoinRpc, "Block doesn't exist"))?;
+    let block_bytes =
+        hex::decode(block_hexstr).map_err(|_| hyper_err!(BitcoinRpc, "Invalid block data"))?;
+    deserialize::<Block>(&block_bytes)
+        .map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize block"))
+}
+
+fn parse_transaction(result: &Value) -> Result<Transaction> {
+    let tx_hexstr = result
+        .as_str()
+        .ok_or_else(|| hyper_err!(BitcoinRpc, "Transaction doesn't exist"))?;
+    let tx_bytes =
+        hex::decode(tx_hexstr).map_err(|_| hyper_err!(BitcoinRpc, "Invalid transaction data"))?;
+    deserialize::<Transaction>(&tx_bytes)
+        .map_err(|_| hyper_err!(BitcoinRpc, "Failed to deserialize transaction"))
+}
+
+// the inherent methods take bitcoind's block hashes, the data source looks them up by height first
+#[async_trait]
+impl BitcoinDataSource for BitcoinRpcClient {
+    async fn get_block_count(&self) -> Result<u64> {
+        BitcoinRpcClient::get_block_count(self).await
+    }
+
+    async fn get_block_hash(&self, height: u64) -> Result<[u8; 32]> {
+        BitcoinRpcClient::get_block_hash(self, height).await
+    }
+
+    async fn get_block(&self, height: u64) -> Result<Block> {
+        let block_hash = BitcoinRpcClient::get_block_hash(self, height).await?;
+        BitcoinRpcClient::get_block(self, &block_hash).await
+    }
+
+    async fn get_blocks(&self, start_height: u64, end_height: u64) -> Result<Vec<Block>> {
+        let block_hashes =
+            BitcoinRpcClient::get_block_hashes(self, start_height, end_height).await?;
+        BitcoinRpcClient::get_blocks(self, &block_hashes).await
+    }
+
+    async fn get_block_header(&self, height: u64) -> Result<Header> {
+        let block_hash = BitcoinRpcClient::get_block_hash(self, height).await?;
+        BitcoinRpcClient::get_block_header(self, &block_hash).await
+    }
+
+    async fn get_chainwork(&self, height: u64) -> Result<[u8; 32]> {
+        let block_hash = BitcoinRpcClient::get_block_hash(self, height).await?;
+        BitcoinRpcClient::get_chainwork(self, &block_hash).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::btc_source::tests::{display_hash, expected_chainwork, test_chain};
     use bitcoin::hashes::Hash;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[tokio::test]
     async fn test_get_block_count() {
@@ -177,6 +511,20 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_block_header() {
+        let client = BitcoinRpcClient::new("https://bitcoin-mainnet.public.blastapi.io");
+        let block_height = 859812;
+        let mut block_hash = client.get_block_hash(block_height).await.unwrap();
+        let header = client.get_block_header(&block_hash).await.unwrap();
+        // reverse so it matches the native byte order
+        block_hash.reverse();
+        assert_eq!(
+            *header.block_hash().as_raw_hash().as_byte_array(),
+            block_hash
+        );
+    }
+
     #[tokio::test]
     async fn test_get_chainwork() {
         let client = BitcoinRpcClient::new("https://bitcoin-mainnet.public.blastapi.io");
@@ -185,4 +533,242 @@ mod tests {
         let chainwork = client.get_chainwork(&block_hash).await.unwrap();
         assert_eq!(chainwork.len(), 32);
     }
+
+    struct MockBitcoind {
+        chain: Vec<Block>,
+        // authorization header every request has to carry
+        authorization: Option<&'static str>,
+        // requests turned away as if the work queue was full before any are answered
+        busy_requests: AtomicUsize,
+        requests: AtomicUsize,
+    }
+
+    impl MockBitcoind {
+        fn new(chain: Vec<Block>) -> Self {
+            Self {
+                chain,
+                authorization: None,
+                busy_requests: AtomicUsize::new(0),
+                requests: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    fn bitcoind_response(chain: &[Block], request: &Value) -> Value {
+        use bitcoin::consensus::serialize;
+
+        let params = request["params"].as_array().unwrap();
+        let find_block = |hash: &Value| {
+            chain
+                .iter()
+                .position(|block| block.block_hash().to_string() == hash.as_str().unwrap())
+                .unwrap()
+        };
+        let result: Value = match request["method"].as_str().unwrap() {
+            "getblockcount" => (chain.len() - 1).into(),
+            "getblockhash" => match chain.get(params[0].as_u64().unwrap() as usize) {
+                Some(block) => block.block_hash().to_string().into(),
+                None => {
+                    return serde_json::json!({
+                        "id": request["id"],
+                        "result": null,
+                        "error": { "code": -8, "message": "Block height out of range" },
+                    })
+                }
+            },
+            "getblock" => hex::encode(serialize(&chain[find_block(&params[0])])).into(),
+            // verbose headers carry the chainwork
+            "getblockheader" if params.len() == 1 => serde_json::json!({
+                "chainwork": format!("{:064x}", 2 * (find_block(&params[0]) + 1)),
+            }),
+            "getblockheader" => {
+                hex::encode(serialize(&chain[find_block(&params[0])].header)).into()
+            }
+            // the genesis coinbase stands in for the mempool
+            "getrawmempool" => serde_json::json!([chain[0].txdata[0].compute_txid().to_string()]),
+            "getrawtransaction" => match chain[0]
+                .txdata
+                .iter()
+                .find(|tx| tx.compute_txid().to_string() == params[0].as_str().unwrap())
+            {
+                Some(tx) => hex::encode(serialize(tx)).into(),
+                None => {
+                    return serde_json::json!({
+                        "id": request["id"],
+                        "result": null,
+                        "error": { "code": -5, "message": "No such mempool transaction" },
+                    })
+                }
+            },
+            method => panic!("unexpected method {}", method),
+        };
+        serde_json::json!({ "id": request["id"], "result": result, "error": null })
+    }
+
+    async fn serve_bitcoind(mock: Arc<MockBitcoind>) -> String {
+        use axum::extract::State;
+        use axum::http::{header::AUTHORIZATION, HeaderMap};
+        use axum::routing::post;
+        use axum::{Json, Router};
+
+        async fn handle(
+            State(mock): State<Arc<MockBitcoind>>,
+            headers: HeaderMap,
+            Json(request): Json<Value>,
+        ) -> (StatusCode, String) {
+            mock.requests.fetch_add(1, Ordering::SeqCst);
+            if let Some(authorization) = mock.authorization {
+                if headers
+                    .get(AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    != Some(authorization)
+                {
+                    return (StatusCode::UNAUTHORIZED, String::new());
+                }
+            }
+            let busy = mock
+                .busy_requests
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |busy| {
+                    busy.checked_sub(1)
+                })
+                .is_ok();
+            if busy {
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Work queue depth exceeded".to_string(),
+                );
+            }
+            match request {
+                Value::Array(requests) => {
+                    let responses: Vec<Value> = requests
+                        .iter()
+                        .map(|request| bitcoind_response(&mock.chain, request))
+                        .collect();
+                    (StatusCode::OK, Value::Array(responses).to_string())
+                }
+                // like bitcoind, a single failed call is answered with a server error
+                request => {
+                    let response = bitcoind_response(&mock.chain, &request);
+                    let status = if response["error"].is_null() {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    };
+                    (status, response.to_string())
+                }
+            }
+        }
+
+        let app = Router::new().route("/", post(handle)).with_state(mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_bitcoind_source() {
+        let mock = Arc::new(MockBitcoind::new(test_chain(10)));
+        let client = BitcoinRpcClient::new(&serve_bitcoind(mock.clone()).await);
+        let source: &dyn BitcoinDataSource = &client;
+        let chain = &mock.chain;
+
+        assert_eq!(source.get_block_count().await.unwrap(), 9);
+        assert_eq!(
+            source.get_block_hash(5).await.unwrap(),
+            display_hash(&chain[5])
+        );
+        assert_eq!(source.get_block(7).await.unwrap(), chain[7]);
+        assert_eq!(source.get_block_header(8).await.unwrap(), chain[8].header);
+        assert_eq!(
+            source.get_chainwork(6).await.unwrap(),
+            expected_chainwork(6)
+        );
+
+        // one batch for the hashes and one for the blocks
+        let requests = mock.requests.load(Ordering::SeqCst);
+        assert_eq!(source.get_blocks(2, 8).await.unwrap(), chain[2..=8]);
+        assert_eq!(mock.requests.load(Ordering::SeqCst), requests + 2);
+        assert!(source.get_blocks(8, 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bitcoind_mempool() {
+        let mock = Arc::new(MockBitcoind::new(test_chain(1)));
+        let client = BitcoinRpcClient::new(&serve_bitcoind(mock.clone()).await);
+        let tx = &mock.chain[0].txdata[0];
+
+        let mempool = client.get_raw_mempool().await.unwrap();
+        assert_eq!(mempool, vec![tx.compute_txid()]);
+        let txs = client
+            .get_raw_transactions(&[mempool[0], Txid::all_zeros()])
+            .await
+            .unwrap();
+        assert_eq!(txs[0].as_ref().unwrap(), tx);
+        assert!(txs[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bitcoind_auth() {
+        let mock = Arc::new(MockBitcoind {
+            authorization: Some("Basic X19jb29raWVfXzpzZWNyZXQ="),
+            ..MockBitcoind::new(test_chain(1))
+        });
+        let url = serve_bitcoind(mock.clone()).await;
+        let cookie_file =
+            std::env::temp_dir().join(format!("hypernode-test-{}.cookie", std::process::id()));
+        std::fs::write(&cookie_file, "__cookie__:secret\n").unwrap();
+
+        let client = BitcoinRpcClient::with_config(
+            &url,
+            BitcoinRpcConfig {
+                auth: BitcoinRpcAuth::CookieFile(cookie_file.clone()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(client.get_block_count().await.unwrap(), 0);
+        std::fs::remove_file(&cookie_file).unwrap();
+
+        // rejected credentials aren't retried
+        let client = BitcoinRpcClient::with_config(
+            &url,
+            BitcoinRpcConfig {
+                auth: BitcoinRpcAuth::UserPass {
+                    user: "__cookie__".to_string(),
+                    password: "wrong".to_string(),
+                },
+                ..Default::default()
+            },
+        );
+        let requests = mock.requests.load(Ordering::SeqCst);
+        assert!(client.get_block_count().await.is_err());
+        assert_eq!(mock.requests.load(Ordering::SeqCst), requests + 1);
+    }
+
+    #[tokio::test]
+    async fn test_bitcoind_retries() {
+        let mock = Arc::new(MockBitcoind {
+            busy_requests: AtomicUsize::new(2),
+            ..MockBitcoind::new(test_chain(1))
+        });
+        let client = BitcoinRpcClient::new(&serve_bitcoind(mock.clone()).await);
+
+        assert_eq!(client.get_block_count().await.unwrap(), 0);
+        assert_eq!(mock.requests.load(Ordering::SeqCst), 3);
+
+        // errors returned by bitcoind fail right away
+        assert!(client.get_block_hash(5).await.is_err());
+        assert_eq!(mock.requests.load(Ordering::SeqCst), 4);
+
+        // as do requests that never get an answer, once they have been retried for long enough
+        let client = BitcoinRpcClient::with_config(
+            "http://127.0.0.1:1",
+            BitcoinRpcConfig {
+                timeout: Duration::from_secs(1),
+                max_retry_time: Duration::from_secs(1),
+                ..Default::default()
+            },
+        );
+        assert!(client.get_block_count().await.is_err());
+    }
 }
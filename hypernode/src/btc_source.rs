@@ -0,0 +1,148 @@
+// Where the indexer reads the bitcoin chain from: a bitcoind RPC, an Esplora REST API or an Electrum
+// server. Blocks are looked up by height and hashes are in display order, as bitcoind returns them
+use crate::btc_electrum::ElectrumClient;
+use crate::btc_esplora::EsploraClient;
+use crate::btc_rpc::{BitcoinRpcAuth, BitcoinRpcClient, BitcoinRpcConfig};
+use crate::error::HypernodeError;
+use crate::{hyper_err, Result};
+use alloy::primitives::U256;
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::Block;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+// blocks in a difficulty period share a target outside of testnet's min difficulty blocks
+pub const DIFFICULTY_PERIOD: u64 = 2016;
+
+#[async_trait]
+pub trait BitcoinDataSource: Send + Sync {
+    async fn get_block_count(&self) -> Result<u64>;
+    async fn get_block_hash(&self, height: u64) -> Result<[u8; 32]>;
+    async fn get_block(&self, height: u64) -> Result<Block>;
+    // blocks at heights start_height..=end_height in order, backends without batch requests fetch
+    // them one at a time
+    async fn get_blocks(&self, start_height: u64, end_height: u64) -> Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        for height in start_height..=end_height {
+            blocks.push(self.get_block(height).await?);
+        }
+        Ok(blocks)
+    }
+    async fn get_block_header(&self, height: u64) -> Result<Header>;
+    // total work of the chain up to and including the block at `height`, big endian
+    async fn get_chainwork(&self, height: u64) -> Result<[u8; 32]>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitcoinSourceKind {
+    Bitcoind,
+    Esplora,
+    Electrum,
+}
+
+impl FromStr for BitcoinSourceKind {
+    type Err = HypernodeError;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "bitcoind" => Ok(BitcoinSourceKind::Bitcoind),
+            "esplora" => Ok(BitcoinSourceKind::Esplora),
+            "electrum" => Ok(BitcoinSourceKind::Electrum),
+            _ => Err(hyper_err!(
+                Config,
+                "Unknown bitcoin source {}, expected bitcoind, esplora or electrum",
+                kind
+            )),
+        }
+    }
+}
+
+// `rpc_config` only applies to bitcoind
+pub fn connect(
+    kind: BitcoinSourceKind,
+    url: &str,
+    rpc_config: BitcoinRpcConfig,
+) -> Result<Arc<dyn BitcoinDataSource>> {
+    if kind != BitcoinSourceKind::Bitcoind && !matches!(rpc_config.auth, BitcoinRpcAuth::None) {
+        return Err(hyper_err!(
+            Config,
+            "Bitcoin RPC credentials can only be used with the bitcoind source"
+        ));
+    }
+    Ok(match kind {
+        BitcoinSourceKind::Bitcoind => Arc::new(BitcoinRpcClient::with_config(url, rpc_config)),
+        BitcoinSourceKind::Esplora => Arc::new(EsploraClient::new(url)),
+        BitcoinSourceKind::Electrum => Arc::new(ElectrumClient::new(url)?),
+    })
+}
+
+// Chainwork for backends that don't report it, summed from genesis one difficulty period at a time.
+// The totals of completed periods are kept so only the latest period is summed again
+#[derive(Default)]
+pub struct ChainworkCache {
+    // chainwork of every block before the start of the period at each index
+    period_starts: Mutex<Vec<U256>>,
+}
+
+impl ChainworkCache {
+    // `period_work(start, count)` is the work of the `count` blocks from height `start`
+    pub async fn chainwork<F, Fut>(&self, height: u64, period_work: F) -> Result<[u8; 32]>
+    where
+        F: Fn(u64, u64) -> Fut,
+        Fut: Future<Output = Result<U256>>,
+    {
+        let period = (height / DIFFICULTY_PERIOD) as usize;
+        let mut period_starts = self.period_starts.lock().await;
+        if period_starts.is_empty() {
+            period_starts.push(U256::ZERO);
+        }
+        while period_starts.len() <= period {
+            let start = (period_starts.len() as u64 - 1) * DIFFICULTY_PERIOD;
+            let work = period_work(start, DIFFICULTY_PERIOD).await?;
+            let total = *period_starts.last().unwrap() + work;
+            period_starts.push(total);
+        }
+
+        let start = period as u64 * DIFFICULTY_PERIOD;
+        let work = period_work(start, height - start + 1).await?;
+        Ok((period_starts[period] + work).to_be_bytes::<32>())
+    }
+}
+
+pub fn header_work(header: &Header) -> U256 {
+    U256::from_be_bytes(header.work().to_be_bytes())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{Block, Network};
+
+    // regtest genesis followed by blocks reusing its coinbase, each worth 2 units of work
+    pub fn test_chain(length: usize) -> Vec<Block> {
+        let mut chain = vec![genesis_block(Network::Regtest)];
+        while chain.len() < length {
+            let previous = chain.last().unwrap();
+            let mut block = previous.clone();
+            block.header.prev_blockhash = previous.block_hash();
+            block.header.time += 600;
+            chain.push(block);
+        }
+        chain
+    }
+
+    // block hash in display order, as the data sources return it
+    pub fn display_hash(block: &Block) -> [u8; 32] {
+        let mut block_hash = block.block_hash().to_byte_array();
+        block_hash.reverse();
+        block_hash
+    }
+
+    pub fn expected_chainwork(height: u64) -> [u8; 32] {
+        alloy::primitives::U256::from(2 * (height + 1)).to_be_bytes::<32>()
+    }
+}
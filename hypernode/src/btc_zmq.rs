@@ -0,0 +1,238 @@
+// Wakes the block listener as soon as bitcoind announces a block on its zmqpubhashblock or
+// zmqpubrawblock endpoint, e.g. started with -zmqpubhashblock=tcp://127.0.0.1:28332. Only enough of
+// ZMTP 3.0 to be a SUB socket is spoken, and the notifications carry no data the listener relies
+// on, blocks are still read through the data source so a missed notification only costs latency
+use crate::error::HypernodeError;
+use crate::telemetry::BTC_BLOCK_NOTIFICATIONS;
+use crate::{hyper_err, Result};
+use log::{debug, info, warn};
+use metrics::counter;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+
+const BLOCK_TOPICS: [&[u8]; 2] = [b"hashblock", b"rawblock"];
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// serialized blocks stay under 4MB
+const MAX_FRAME_SIZE: u64 = 8 * 1024 * 1024;
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+// subscribes to the zmq endpoint in the background, reconnecting whenever the connection drops, and
+// notifies the returned Notify for every block announced
+pub fn spawn_block_notifier(url: &str) -> Result<Arc<Notify>> {
+    let address = match url.split_once("://") {
+        Some(("tcp", address)) => address.to_string(),
+        _ => {
+            return Err(hyper_err!(
+                Config,
+                "Unsupported zmq address {}, expected tcp://host:port",
+                url
+            ))
+        }
+    };
+    let notify = Arc::new(Notify::new());
+    let block_notify = Arc::clone(&notify);
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = subscribe(&address, &block_notify).await {
+                warn!(
+                    "Bitcoin zmq subscription to {} failed, falling back to polling until it \
+                     reconnects: {}",
+                    address, e
+                );
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+    Ok(notify)
+}
+
+async fn subscribe(address: &str, notify: &Notify) -> Result<()> {
+    let mut stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        let mut stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| hyper_err!(Connection, "Failed to connect: {}", e))?;
+        handshake(&mut stream, b"SUB").await?;
+        for topic in BLOCK_TOPICS {
+            // ZMTP 3.0 subscriptions are messages starting with 1
+            write_frame(&mut stream, 0, &[&[1], topic].concat()).await?;
+        }
+        Ok::<_, HypernodeError>(stream)
+    })
+    .await
+    .map_err(|_| hyper_err!(Connection, "zmq handshake timed out"))??;
+    info!("Subscribed to bitcoin block notifications at {}", address);
+
+    loop {
+        let message = read_message(&mut stream).await?;
+        // topic, body and sequence number
+        let Some(topic) = message.first() else {
+            continue;
+        };
+        if BLOCK_TOPICS.contains(&topic.as_slice()) {
+            debug!("Received {} notification", String::from_utf8_lossy(topic));
+            counter!(BTC_BLOCK_NOTIFICATIONS).increment(1);
+            notify.notify_one();
+        }
+    }
+}
+
+// exchanges greetings for the NULL mechanism and READY commands announcing the socket types
+async fn handshake(stream: &mut TcpStream, socket_type: &[u8]) -> Result<()> {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    write_all(stream, &greeting).await?;
+
+    let mut peer_greeting = [0u8; 64];
+    stream
+        .read_exact(&mut peer_greeting)
+        .await
+        .map_err(|e| hyper_err!(Connection, "Failed to read zmq greeting: {}", e))?;
+    if peer_greeting[0] != 0xff
+        || peer_greeting[9] & 0x01 == 0
+        || peer_greeting[10] < 3
+        || &peer_greeting[12..17] != b"NULL\0"
+    {
+        return Err(hyper_err!(
+            Connection,
+            "Peer is not a ZMTP 3 endpoint with the NULL mechanism"
+        ));
+    }
+
+    let mut ready = vec![5];
+    ready.extend_from_slice(b"READY");
+    ready.push(11);
+    ready.extend_from_slice(b"Socket-Type");
+    ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    ready.extend_from_slice(socket_type);
+    write_frame(stream, FLAG_COMMAND, &ready).await?;
+
+    let (flags, command) = read_frame(stream).await?;
+    if flags & FLAG_COMMAND == 0 || !command.starts_with(b"\x05READY") {
+        return Err(hyper_err!(
+            Connection,
+            "Expected a READY command from the peer"
+        ));
+    }
+    Ok(())
+}
+
+// frames of the next message, commands in between are skipped
+async fn read_message(stream: &mut TcpStream) -> Result<Vec<Vec<u8>>> {
+    let mut frames = Vec::new();
+    loop {
+        let (flags, body) = read_frame(stream).await?;
+        if flags & FLAG_COMMAND != 0 {
+            continue;
+        }
+        frames.push(body);
+        if flags & FLAG_MORE == 0 {
+            return Ok(frames);
+        }
+    }
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+    let read_error = |e: std::io::Error| hyper_err!(Connection, "Failed to read zmq frame: {}", e);
+    let flags = stream.read_u8().await.map_err(read_error)?;
+    let size = if flags & FLAG_LONG != 0 {
+        stream.read_u64().await.map_err(read_error)?
+    } else {
+        stream.read_u8().await.map_err(read_error)? as u64
+    };
+    if size > MAX_FRAME_SIZE {
+        return Err(hyper_err!(
+            Connection,
+            "zmq frame of {} bytes is too large",
+            size
+        ));
+    }
+    let mut body = vec![0; size as usize];
+    stream.read_exact(&mut body).await.map_err(read_error)?;
+    Ok((flags, body))
+}
+
+async fn write_frame(stream: &mut TcpStream, flags: u8, body: &[u8]) -> Result<()> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+    if body.len() > u8::MAX as usize {
+        frame.push(flags | FLAG_LONG);
+        frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend_from_slice(body);
+    write_all(stream, &frame).await
+}
+
+async fn write_all(stream: &mut TcpStream, bytes: &[u8]) -> Result<()> {
+    stream
+        .write_all(bytes)
+        .await
+        .map_err(|e| hyper_err!(Connection, "Failed to write to zmq endpoint: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    // publishes a hashblock notification for every connection once it subscribed, then hangs up
+    async fn serve_publisher(connections: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for sequence in 0..connections as u32 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                handshake(&mut stream, b"PUB").await.unwrap();
+                let mut subscriptions = Vec::new();
+                for _ in BLOCK_TOPICS {
+                    subscriptions.extend(read_message(&mut stream).await.unwrap());
+                }
+                assert_eq!(
+                    subscriptions,
+                    vec![b"\x01hashblock".to_vec(), b"\x01rawblock".to_vec()]
+                );
+
+                // a topic that isn't subscribed to is ignored
+                write_frame(&mut stream, FLAG_MORE, b"hashtx")
+                    .await
+                    .unwrap();
+                write_frame(&mut stream, FLAG_MORE, &[0; 32]).await.unwrap();
+                write_frame(&mut stream, 0, &sequence.to_le_bytes())
+                    .await
+                    .unwrap();
+                write_frame(&mut stream, FLAG_MORE, b"hashblock")
+                    .await
+                    .unwrap();
+                write_frame(&mut stream, FLAG_MORE, &[0; 32]).await.unwrap();
+                write_frame(&mut stream, 0, &sequence.to_le_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        format!("tcp://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_block_notifications() {
+        let notify = spawn_block_notifier(&serve_publisher(2).await).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), notify.notified())
+            .await
+            .unwrap();
+        // announced again on the next connection once the publisher went away
+        tokio::time::timeout(RECONNECT_DELAY * 2, notify.notified())
+            .await
+            .unwrap();
+        assert!(spawn_block_notifier("ipc:///tmp/bitcoind.sock").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+// Config files hold named profiles of flag values, keyed by the flag's long name in snake case:
+//
+// [testnet]
+// btc_network = "testnet4"
+// checkpoint_block_interval = 144
+//
+// The selected profile's values are set as environment variables unless they are already set, so
+// like a .env file they only fill in what the command line and environment leave out. That has to
+// happen before the tokio runtime starts any threads
+use crate::error::HypernodeError;
+use crate::{hyper_err, HypernodeArgs, Result};
+use clap::CommandFactory;
+use std::path::{Path, PathBuf};
+
+// the config file and profile have to be known before the rest of the flags are parsed, as the
+// profile can provide required ones
+pub fn load_from_args() -> Result<()> {
+    let matches = HypernodeArgs::command().ignore_errors(true).get_matches();
+    match (
+        matches.get_one::<PathBuf>("config"),
+        matches.get_one::<String>("profile"),
+    ) {
+        (Some(path), Some(profile)) => load_profile(path, profile),
+        // a config without a profile, or the other way around, is rejected once the flags are
+        // parsed
+        _ => Ok(()),
+    }
+}
+
+pub fn load_profile(path: &Path, profile: &str) -> Result<()> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        hyper_err!(
+            Config,
+            "Failed to read config file {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    let profiles: toml::Table = contents.parse().map_err(|e| {
+        hyper_err!(
+            Config,
+            "Failed to parse config file {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    let Some(toml::Value::Table(values)) = profiles.get(profile) else {
+        return Err(hyper_err!(
+            Config,
+            "Profile {} not found in {}, available profiles: {}",
+            profile,
+            path.display(),
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    };
+
+    let command = HypernodeArgs::command();
+    for (key, value) in values {
+        let env = command
+            .get_arguments()
+            .filter(|arg| !matches!(arg.get_id().as_str(), "config" | "profile"))
+            .find(|arg| arg.get_id().as_str() == key)
+            .and_then(|arg| arg.get_env())
+            .ok_or_else(|| hyper_err!(Config, "Unknown key {} in profile {}", key, profile))?;
+        let value = match value {
+            toml::Value::String(value) => value.clone(),
+            toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                value.to_string()
+            }
+            _ => {
+                return Err(hyper_err!(
+                    Config,
+                    "{} in profile {} must be a string, number or boolean",
+                    key,
+                    profile
+                ))
+            }
+        };
+        if std::env::var_os(env).is_none() {
+            std::env::set_var(env, value);
+        }
+    }
+    Ok(())
+}
+
+// rejects values the hypernode can't run with that clap can't catch on its own
+pub fn validate(args: &HypernodeArgs) -> Result<()> {
+    for (name, value) in [
+        (
+            "reservation_duration_hours",
+            args.reservation_duration_hours,
+        ),
+        ("confirmation_height_delta", args.confirmation_height_delta),
+        ("checkpoint_block_interval", args.checkpoint_block_interval),
+        ("prove_blocks_interval", args.prove_blocks_interval),
+        ("header_lookback_limit", args.header_lookback_limit),
+        ("btc_rpc_timeout", args.btc_rpc_timeout),
+        ("btc_rpc_batch_size", args.btc_rpc_batch_size),
+    ] {
+        if value == 0 {
+            return Err(hyper_err!(Config, "{} must be greater than 0", name));
+        }
+    }
+
+    if let Some(polling_interval) = args.btc_mempool_polling_interval {
+        if polling_interval == 0 {
+            return Err(hyper_err!(
+                Config,
+                "btc_mempool_polling_interval must be greater than 0"
+            ));
+        }
+        if args.btc_source != "bitcoind" {
+            return Err(hyper_err!(
+                Config,
+                "The mempool can only be watched with the bitcoind source"
+            ));
+        }
+    }
+
+    if args
+        .prover_worker_token
+        .as_ref()
+        .is_some_and(|token| token.is_empty())
+    {
+        return Err(hyper_err!(Config, "The prover worker token can't be empty"));
+    }
+
+    // a delayed swap proof still has to land before its reservation expires
+    if args.profitability_max_delay >= args.reservation_duration_hours * 3600 {
+        return Err(hyper_err!(
+            Config,
+            "Profitability max delay must be shorter than the {} hour reservation duration",
+            args.reservation_duration_hours
+        ));
+    }
+    Ok(())
+}
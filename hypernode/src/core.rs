@@ -12,14 +12,23 @@ use alloy::sol;
 use alloy::transports::http::Http;
 use alloy::transports::{impl_future, TransportResult};
 use backoff::ExponentialBackoff;
+use bitcoin::block::Header;
 use bitcoin::Block;
 use log::info;
+use redb::{Database, ReadableTable, TableDefinition, WriteTransaction};
 use reqwest::Client;
-use std::collections::HashMap;
+use rift_core::lp::LiquidityReservation;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
-use crate::constants::RESERVATION_DURATION_HOURS;
+use crate::constants::BTC_HEADER_WINDOW;
+use crate::error::HypernodeError;
+use crate::retry::DeadLetter;
+use crate::{hyper_err, telemetry, Result};
 
 sol!(
     #[allow(missing_docs)]
@@ -41,9 +50,42 @@ sol! {
     }
 }
 
-sol!(
+// see ISP1Verifier in sp1-contracts, the exchange's verifierContract implements it
+sol! {
     #[allow(missing_docs)]
     #[sol(rpc)]
+    interface ISP1Verifier {
+        function verifyProof(
+            bytes32 programVKey,
+            bytes calldata publicValues,
+            bytes calldata proofBytes
+        ) external view;
+    }
+}
+
+// see data-aggregation-contracts/src/RiftCallBatcher.sol
+sol! {
+    #[allow(missing_docs)]
+    contract RiftCallBatcher {
+        struct Call {
+            bytes callData;
+            bool allowFailure;
+        }
+
+        struct CallResult {
+            bool success;
+            bytes returnData;
+        }
+
+        event CallReverted(uint256 index, bytes returnData);
+
+        function aggregate(Call[] calldata calls) external returns (CallResult[] memory results);
+    }
+}
+
+sol!(
+    #[allow(missing_docs, clippy::too_many_arguments)]
+    #[sol(rpc)]
     #[derive(serde::Serialize, serde::Deserialize)]
     RiftExchange,
     "artifacts/RiftExchange.json"
@@ -88,37 +130,96 @@ pub type RiftExchangeHttp = RiftExchange::RiftExchangeInstance<Http<Client>, Arc
 
 /// Retrying websocket connection using exponential backoff
 #[derive(Clone, Debug)]
-pub struct RetryWsConnect(pub WsConnect);
+pub struct RetryWsConnect {
+    pub ws: WsConnect,
+    // reconnects are given up after this long, the default backoff keeps a dead endpoint's calls
+    // hanging for 15 minutes
+    pub max_reconnect_time: Duration,
+}
 
 impl PubSubConnect for RetryWsConnect {
     fn is_local(&self) -> bool {
-        self.0.is_local()
+        self.ws.is_local()
     }
 
     fn connect(&self) -> impl_future!(<Output = TransportResult<ConnectionHandle>>) {
-        self.0.connect()
+        self.ws.connect()
     }
 
     async fn try_reconnect(&self) -> TransportResult<ConnectionHandle> {
-        backoff::future::retry(ExponentialBackoff::default(), || async {
-            Ok(self.0.try_reconnect().await?)
-        })
-        .await
+        let backoff = ExponentialBackoff {
+            max_elapsed_time: Some(self.max_reconnect_time),
+            ..Default::default()
+        };
+        backoff::future::retry(backoff, || async { Ok(self.ws.try_reconnect().await?) }).await
     }
 }
 
-#[derive(Clone)]
+// bitcoin blocks are persisted consensus encoded, which is far more compact than their serde form
+mod consensus_hex {
+    use bitcoin::consensus::{Decodable, Encodable};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, T: Encodable>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bitcoin::consensus::serialize(value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Decodable>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let bytes = hex::decode(String::deserialize(deserializer)?).map_err(D::Error::custom)?;
+        bitcoin::consensus::deserialize(&bytes).map_err(D::Error::custom)
+    }
+
+    pub mod vec {
+        use super::*;
+        use serde::ser::SerializeSeq;
+
+        pub fn serialize<S: Serializer, T: Encodable>(
+            values: &[T],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(values.len()))?;
+            for value in values {
+                seq.serialize_element(&hex::encode(bitcoin::consensus::serialize(value)))?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: Decodable>(
+            deserializer: D,
+        ) -> Result<Vec<T>, D::Error> {
+            Vec::<String>::deserialize(deserializer)?
+                .into_iter()
+                .map(|value_hex| {
+                    let bytes = hex::decode(value_hex).map_err(D::Error::custom)?;
+                    bitcoin::consensus::deserialize(&bytes).map_err(D::Error::custom)
+                })
+                .collect()
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BitcoinReservationFinalized {
     pub confirmation_height: u64,
     pub confirmation_block_hash: [u8; 32],
     pub safe_block_height: u64,
     pub safe_block_chainwork: [u8; 32],
+    #[serde(with = "consensus_hex::vec")]
     pub blocks: Vec<Block>,
+    #[serde(with = "consensus_hex")]
     pub retarget_block: Block,
     pub retarget_block_height: u64,
+    // headers preceding the safe block, needed for the median time past check
+    #[serde(with = "consensus_hex::vec")]
+    pub ancestor_headers: Vec<Header>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BitcoinReservationInProgress {
     pub proposed_block_height: u64,
     pub proposed_block_hash: [u8; 32],
@@ -135,38 +236,178 @@ impl BitcoinReservationInProgress {
     }
 }
 
+// a payment broadcast to the mempool that hasn't been mined yet
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BitcoinPaymentPending {
+    pub txid: [u8; 32],
+    // unix timestamp the transaction was first seen in the mempool
+    pub first_seen: u64,
+    // why the payment can't be proven, if it can't
+    pub payment_error: Option<String>,
+}
+
+// a mined payment that can't be proven, kept so operators can see why the reservation wasn't
+// picked up
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BitcoinPaymentRejected {
+    pub txid: [u8; 32],
+    pub block_height: u64,
+    pub reason: String,
+}
+
+// where a reservation is in the hypernode's pipeline, derived from its metadata
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservationStage {
+    AwaitingPayment,
+    PaymentPending,
+    PaymentRejected,
+    AwaitingConfirmations,
+    Proving,
+    Broadcast,
+    AwaitingRelease,
+}
+
+// mirrors RiftExchange.ReservationState
+const RESERVATION_STATE_PROVED: u8 = 2;
+
 // stores data about the current state of a reservation, as well as the reservation itself
 // metadata is used within the indexer to determine what to do with a reservation
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ReservationMetadata {
     pub reservation: RiftExchange::SwapReservation,
     pub reserved_vaults: Vec<RiftExchange::DepositVault>,
+    // payout scripts of the reserved vaults, vaults only hold P2WPKH scripts themselves
+    #[serde(default)]
+    pub payout_scripts: Vec<Vec<u8>>,
+    // only tracked while the payment is unconfirmed
+    pub btc_pending: Option<BitcoinPaymentPending>,
+    // the last mined payment that failed validation
+    pub btc_rejected: Option<BitcoinPaymentRejected>,
     pub btc_initial: Option<BitcoinReservationInProgress>,
     pub btc_final: Option<BitcoinReservationFinalized>,
     pub proof: Option<Vec<u8>>,
     pub public_inputs: Option<Vec<u8>>,
+    // set once the proof has been broadcast, so a restarted node doesn't submit it twice
+    pub proof_tx_hash: Option<[u8; 32]>,
 }
 
 impl ReservationMetadata {
     pub fn new(
         reservation: RiftExchange::SwapReservation,
         reserved_vaults: Vec<RiftExchange::DepositVault>,
+        payout_scripts: Vec<Vec<u8>>,
     ) -> Self {
         ReservationMetadata {
             reservation,
             reserved_vaults,
+            payout_scripts,
+            btc_pending: None,
+            btc_rejected: None,
             btc_initial: None,
             btc_final: None,
             proof: None,
             public_inputs: None,
+            proof_tx_hash: None,
+        }
+    }
+
+    pub fn stage(&self) -> ReservationStage {
+        if self.reservation.state == RESERVATION_STATE_PROVED {
+            ReservationStage::AwaitingRelease
+        } else if self.proof.is_some() {
+            ReservationStage::Broadcast
+        } else if self.btc_final.is_some() {
+            ReservationStage::Proving
+        } else if self.btc_initial.is_some() {
+            ReservationStage::AwaitingConfirmations
+        } else if self.btc_pending.is_some() {
+            ReservationStage::PaymentPending
+        } else if self.btc_rejected.is_some() {
+            ReservationStage::PaymentRejected
+        } else {
+            ReservationStage::AwaitingPayment
         }
     }
+
+    // the outputs the payment has to contain, one per reserved vault
+    pub fn liquidity_reservations(&self) -> Vec<LiquidityReservation> {
+        self.reserved_vaults
+            .iter()
+            .zip(self.reservation.expectedSatsOutput.iter())
+            .enumerate()
+            .map(|(i, (vault, sats))| LiquidityReservation {
+                expected_sats: *sats,
+                // reservations stored before payout scripts were tracked only reserved P2WPKH vaults
+                script_pub_key: self
+                    .payout_scripts
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| vault.btcPayoutLockingScript.to_vec()),
+            })
+            .collect()
+    }
+
+    // forgets everything derived from bitcoin blocks above `fork_height`, returns true if the
+    // reservation was affected
+    pub fn rollback_to(&mut self, fork_height: u64) -> bool {
+        let payment_orphaned = self
+            .btc_initial
+            .as_ref()
+            .is_some_and(|initial| initial.proposed_block_height > fork_height);
+        let confirmation_orphaned = self
+            .btc_final
+            .as_ref()
+            .is_some_and(|finalized| finalized.confirmation_height > fork_height);
+        let rejection_orphaned = self
+            .btc_rejected
+            .as_ref()
+            .is_some_and(|rejected| rejected.block_height > fork_height);
+
+        if rejection_orphaned {
+            self.btc_rejected = None;
+        }
+        if payment_orphaned {
+            self.btc_initial = None;
+        }
+        if payment_orphaned || confirmation_orphaned {
+            self.btc_final = None;
+            self.proof = None;
+            self.public_inputs = None;
+            self.proof_tx_hash = None;
+        }
+        payment_orphaned || confirmation_orphaned || rejection_orphaned
+    }
 }
 
+const RESERVATIONS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("reservations");
+const SAFE_BLOCK_HASHES_TABLE: TableDefinition<u64, &[u8]> =
+    TableDefinition::new("safe_contract_block_hashes");
+const BTC_BLOCK_HASHES_TABLE: TableDefinition<u64, &[u8]> =
+    TableDefinition::new("btc_block_hashes");
+const INDEXER_STATE_TABLE: TableDefinition<&str, u64> = TableDefinition::new("indexer_state");
+const DEAD_LETTERS_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("dead_letters");
+const BTC_ANALYZED_HEIGHT_KEY: &str = "btc_analyzed_height";
+
+// Reads are served from the in memory maps, mutations must go through the methods below so they
+// are written through to the database (when one is configured)
 pub struct Store {
-    pub reservations: HashMap<U256, ReservationMetadata>,
+    reservations: HashMap<U256, ReservationMetadata>,
     // Cache available block hashes for building safe -> proposed -> confirmation chains
-    pub safe_contract_block_hashes: HashMap<u64, [u8; 32]>,
+    safe_contract_block_hashes: HashMap<u64, [u8; 32]>,
+    // Last bitcoin block the indexer has fully analyzed
+    btc_analyzed_height: Option<u64>,
+    // Hashes of the last BTC_HEADER_WINDOW analyzed bitcoin blocks, used to detect reorgs
+    btc_block_hashes: BTreeMap<u64, [u8; 32]>,
+    // Jobs that failed for good, kept until an operator clears them
+    dead_letters: Vec<DeadLetter>,
+    db: Option<Database>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Store {
@@ -174,30 +415,266 @@ impl Store {
         Store {
             reservations: HashMap::new(),
             safe_contract_block_hashes: HashMap::new(),
+            btc_analyzed_height: None,
+            btc_block_hashes: BTreeMap::new(),
+            dead_letters: Vec::new(),
+            db: None,
+        }
+    }
+
+    // opens (or creates) the database at `path` and loads any state persisted by a previous run
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = Database::create(path)
+            .map_err(|e| hyper_err!(Store, "Failed to open database: {}", e))?;
+
+        // create the tables up front so reads below never hit a missing table
+        let write_txn = db
+            .begin_write()
+            .map_err(|e| hyper_err!(Store, "Failed to begin write transaction: {}", e))?;
+        {
+            write_txn
+                .open_table(RESERVATIONS_TABLE)
+                .map_err(|e| hyper_err!(Store, "Failed to open reservations table: {}", e))?;
+            write_txn
+                .open_table(SAFE_BLOCK_HASHES_TABLE)
+                .map_err(|e| hyper_err!(Store, "Failed to open block hashes table: {}", e))?;
+            write_txn
+                .open_table(BTC_BLOCK_HASHES_TABLE)
+                .map_err(|e| hyper_err!(Store, "Failed to open block hashes table: {}", e))?;
+            write_txn
+                .open_table(INDEXER_STATE_TABLE)
+                .map_err(|e| hyper_err!(Store, "Failed to open indexer state table: {}", e))?;
+            write_txn
+                .open_table(DEAD_LETTERS_TABLE)
+                .map_err(|e| hyper_err!(Store, "Failed to open dead letters table: {}", e))?;
+        }
+        write_txn
+            .commit()
+            .map_err(|e| hyper_err!(Store, "Failed to commit write transaction: {}", e))?;
+
+        let mut store = Store::new();
+        let read_txn = db
+            .begin_read()
+            .map_err(|e| hyper_err!(Store, "Failed to begin read transaction: {}", e))?;
+
+        let reservations = read_txn
+            .open_table(RESERVATIONS_TABLE)
+            .map_err(|e| hyper_err!(Store, "Failed to open reservations table: {}", e))?;
+        for entry in reservations
+            .iter()
+            .map_err(|e| hyper_err!(Store, "Failed to read reservations: {}", e))?
+        {
+            let (id, metadata) =
+                entry.map_err(|e| hyper_err!(Store, "Failed to read reservation: {}", e))?;
+            let metadata: ReservationMetadata = serde_json::from_slice(metadata.value())
+                .map_err(|e| hyper_err!(Decode, "Failed to decode stored reservation: {}", e))?;
+            store
+                .reservations
+                .insert(U256::from_be_slice(id.value()), metadata);
+        }
+
+        let block_hashes = read_txn
+            .open_table(SAFE_BLOCK_HASHES_TABLE)
+            .map_err(|e| hyper_err!(Store, "Failed to open block hashes table: {}", e))?;
+        for entry in block_hashes
+            .iter()
+            .map_err(|e| hyper_err!(Store, "Failed to read block hashes: {}", e))?
+        {
+            let (height, hash) =
+                entry.map_err(|e| hyper_err!(Store, "Failed to read block hash: {}", e))?;
+            let hash: [u8; 32] = hash
+                .value()
+                .try_into()
+                .map_err(|_| hyper_err!(Decode, "Invalid stored block hash"))?;
+            store
+                .safe_contract_block_hashes
+                .insert(height.value(), hash);
+        }
+
+        let btc_block_hashes = read_txn
+            .open_table(BTC_BLOCK_HASHES_TABLE)
+            .map_err(|e| hyper_err!(Store, "Failed to open block hashes table: {}", e))?;
+        for entry in btc_block_hashes
+            .iter()
+            .map_err(|e| hyper_err!(Store, "Failed to read block hashes: {}", e))?
+        {
+            let (height, hash) =
+                entry.map_err(|e| hyper_err!(Store, "Failed to read block hash: {}", e))?;
+            let hash: [u8; 32] = hash
+                .value()
+                .try_into()
+                .map_err(|_| hyper_err!(Decode, "Invalid stored block hash"))?;
+            store.btc_block_hashes.insert(height.value(), hash);
+        }
+
+        let indexer_state = read_txn
+            .open_table(INDEXER_STATE_TABLE)
+            .map_err(|e| hyper_err!(Store, "Failed to open indexer state table: {}", e))?;
+        store.btc_analyzed_height = indexer_state
+            .get(BTC_ANALYZED_HEIGHT_KEY)
+            .map_err(|e| hyper_err!(Store, "Failed to read analyzed height: {}", e))?
+            .map(|height| height.value());
+
+        let dead_letters = read_txn
+            .open_table(DEAD_LETTERS_TABLE)
+            .map_err(|e| hyper_err!(Store, "Failed to open dead letters table: {}", e))?;
+        for entry in dead_letters
+            .iter()
+            .map_err(|e| hyper_err!(Store, "Failed to read dead letters: {}", e))?
+        {
+            let (_, dead_letter) =
+                entry.map_err(|e| hyper_err!(Store, "Failed to read dead letter: {}", e))?;
+            let dead_letter: DeadLetter = serde_json::from_slice(dead_letter.value())
+                .map_err(|e| hyper_err!(Decode, "Failed to decode stored dead letter: {}", e))?;
+            store.dead_letters.push(dead_letter);
+        }
+
+        drop(read_txn);
+        info!(
+            "Loaded {} reservations and {} block hashes from database",
+            store.reservations.len(),
+            store.safe_contract_block_hashes.len()
+        );
+        store.db = Some(db);
+        Ok(store)
+    }
+
+    pub fn reservations(&self) -> &HashMap<U256, ReservationMetadata> {
+        &self.reservations
+    }
+
+    pub fn safe_contract_block_hashes(&self) -> &HashMap<u64, [u8; 32]> {
+        &self.safe_contract_block_hashes
+    }
+
+    pub fn btc_analyzed_height(&self) -> Option<u64> {
+        self.btc_analyzed_height
+    }
+
+    pub fn btc_block_hashes(&self) -> &BTreeMap<u64, [u8; 32]> {
+        &self.btc_block_hashes
+    }
+
+    pub fn dead_letters(&self) -> &[DeadLetter] {
+        &self.dead_letters
+    }
+
+    fn write<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&WriteTransaction) -> Result<()>,
+    {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let write_txn = db
+            .begin_write()
+            .map_err(|e| hyper_err!(Store, "Failed to begin write transaction: {}", e))?;
+        f(&write_txn)?;
+        write_txn
+            .commit()
+            .map_err(|e| hyper_err!(Store, "Failed to commit write transaction: {}", e))
+    }
+
+    fn persist_reservation(&self, id: U256) -> Result<()> {
+        if self.db.is_none() {
+            return Ok(());
         }
+        let metadata = self
+            .reservations
+            .get(&id)
+            .ok_or_else(|| hyper_err!(Store, "Reservation not found: {}", id))?;
+        let encoded = serde_json::to_vec(metadata)
+            .map_err(|e| hyper_err!(Store, "Failed to encode reservation {}: {}", id, e))?;
+        self.write(|txn| {
+            txn.open_table(RESERVATIONS_TABLE)?
+                .insert(id.to_be_bytes::<32>().as_slice(), encoded.as_slice())?;
+            Ok(())
+        })
     }
 
-    pub fn drop_expired_reservations(&mut self, current_timestamp: u64) {
+    fn delete_reservations(&self, ids: &[U256]) -> Result<()> {
+        self.write(|txn| {
+            let mut table = txn.open_table(RESERVATIONS_TABLE)?;
+            for id in ids {
+                table.remove(id.to_be_bytes::<32>().as_slice())?;
+            }
+            Ok(())
+        })
+    }
+
+    fn reservation_mut(&mut self, id: U256) -> Result<&mut ReservationMetadata> {
+        self.reservations
+            .get_mut(&id)
+            .ok_or_else(|| hyper_err!(Store, "Reservation not found: {}", id))
+    }
+
+    // `reservation_duration` is in seconds
+    pub fn drop_expired_reservations(
+        &mut self,
+        current_timestamp: u64,
+        reservation_duration: u64,
+    ) -> Result<()> {
         let stale_ids: Vec<U256> = self
             .reservations
             .iter()
             .filter(|&(_, metadata)| {
-                (metadata.reservation.reservationTimestamp + (RESERVATION_DURATION_HOURS * 3600))
+                (metadata.reservation.reservationTimestamp + reservation_duration)
                     < current_timestamp
             })
             .map(|(&id, _)| id)
             .collect();
 
-        for id in stale_ids {
+        for id in &stale_ids {
             info!("Dropping stale reservation: {:?}", id);
-            self.reservations.remove(&id);
+            self.reservations.remove(id);
         }
+        self.delete_reservations(&stale_ids)
     }
 
-    pub fn update_proof_data(&mut self, id: U256, proof: Vec<u8>, public_inputs: Vec<u8>) {
-        let metadata = self.reservations.get_mut(&id).unwrap();
+    pub fn update_proof_data(
+        &mut self,
+        id: U256,
+        proof: Vec<u8>,
+        public_inputs: Vec<u8>,
+    ) -> Result<()> {
+        let metadata = self.reservation_mut(id)?;
         metadata.public_inputs = Some(public_inputs);
         metadata.proof = Some(proof);
+        self.persist_reservation(id)
+    }
+
+    pub fn update_proof_tx_hash(&mut self, id: U256, tx_hash: [u8; 32]) -> Result<()> {
+        self.reservation_mut(id)?.proof_tx_hash = Some(tx_hash);
+        self.persist_reservation(id)
+    }
+
+    // forgets a generated (and possibly broadcast) proof so the reservation is proven again
+    pub fn reset_proof(&mut self, id: U256) -> Result<()> {
+        let metadata = self.reservation_mut(id)?;
+        metadata.proof = None;
+        metadata.public_inputs = None;
+        metadata.proof_tx_hash = None;
+        self.persist_reservation(id)
+    }
+
+    pub fn update_btc_payment_pending(
+        &mut self,
+        id: U256,
+        pending: Option<BitcoinPaymentPending>,
+    ) -> Result<()> {
+        self.reservation_mut(id)?.btc_pending = pending;
+        self.persist_reservation(id)
+    }
+
+    pub fn update_btc_payment_rejected(
+        &mut self,
+        id: U256,
+        rejected: BitcoinPaymentRejected,
+    ) -> Result<()> {
+        let metadata = self.reservation_mut(id)?;
+        metadata.btc_pending = None;
+        metadata.btc_rejected = Some(rejected);
+        self.persist_reservation(id)
     }
 
     pub fn update_btc_reservation_initial(
@@ -206,50 +683,168 @@ impl Store {
         proposed_block_height: u64,
         proposed_block_hash: [u8; 32],
         txid: [u8; 32],
-    ) {
-        let metadata = self.reservations.get_mut(&id).unwrap();
+    ) -> Result<()> {
+        let metadata = self.reservation_mut(id)?;
+        metadata.btc_pending = None;
         metadata.btc_initial = Some(BitcoinReservationInProgress::new(
             proposed_block_height,
             proposed_block_hash,
             txid,
         ));
+        self.persist_reservation(id)
     }
 
     pub fn update_btc_reservation_final(
         &mut self,
         id: U256,
-        confirmation_height: u64,
-        confirmation_block_hash: [u8; 32],
-        safe_block_height: u64,
-        safe_block_chainwork: [u8; 32],
-        blocks: Vec<Block>,
-        retarget_block: Block,
-        retarget_block_height: u64,
-    ) {
-        let metadata = self.reservations.get_mut(&id).unwrap();
-        metadata.btc_final = Some(BitcoinReservationFinalized {
-            confirmation_height,
-            confirmation_block_hash,
-            safe_block_height,
-            safe_block_chainwork,
-            blocks,
-            retarget_block,
-            retarget_block_height,
-        });
-    }
-
-    pub fn insert(&mut self, swap_reservation_index: U256, reservation: ReservationMetadata) {
+        btc_final: BitcoinReservationFinalized,
+    ) -> Result<()> {
+        let metadata = self.reservation_mut(id)?;
+        metadata.btc_final = Some(btc_final);
+        self.persist_reservation(id)
+    }
+
+    pub fn insert(
+        &mut self,
+        swap_reservation_index: U256,
+        reservation: ReservationMetadata,
+    ) -> Result<()> {
         self.reservations
             .insert(swap_reservation_index, reservation);
+        self.persist_reservation(swap_reservation_index)
+    }
+
+    // refreshes the onchain data of a reservation while keeping any bitcoin/proof progress that
+    // was already made locally
+    pub fn upsert(
+        &mut self,
+        swap_reservation_index: U256,
+        reservation: ReservationMetadata,
+    ) -> Result<()> {
+        match self.reservations.get_mut(&swap_reservation_index) {
+            Some(metadata) => {
+                metadata.reservation = reservation.reservation;
+                metadata.reserved_vaults = reservation.reserved_vaults;
+                metadata.payout_scripts = reservation.payout_scripts;
+                self.persist_reservation(swap_reservation_index)
+            }
+            None => self.insert(swap_reservation_index, reservation),
+        }
     }
 
-    pub fn remove(&mut self, id: U256) {
+    pub fn remove(&mut self, id: U256) -> Result<()> {
         self.reservations.remove(&id);
+        self.delete_reservations(&[id])
+    }
+
+    // drops every reservation that isn't in `ids`
+    pub fn retain(&mut self, ids: &[U256]) -> Result<()> {
+        let removed_ids: Vec<U256> = self
+            .reservations
+            .keys()
+            .filter(|id| !ids.contains(id))
+            .cloned()
+            .collect();
+        for id in &removed_ids {
+            self.reservations.remove(id);
+        }
+        self.delete_reservations(&removed_ids)
     }
 
     pub fn get(&self, id: U256) -> Option<&ReservationMetadata> {
         self.reservations.get(&id)
     }
+
+    pub fn insert_safe_contract_block_hash(&mut self, height: u64, hash: [u8; 32]) -> Result<()> {
+        self.safe_contract_block_hashes.insert(height, hash);
+        self.write(|txn| {
+            txn.open_table(SAFE_BLOCK_HASHES_TABLE)?
+                .insert(height, hash.as_slice())?;
+            Ok(())
+        })
+    }
+
+    // records `block_hash` (natural byte order) as the block analyzed at `height`, only the last
+    // BTC_HEADER_WINDOW hashes are kept
+    pub fn update_btc_analyzed_block(&mut self, height: u64, block_hash: [u8; 32]) -> Result<()> {
+        self.btc_analyzed_height = Some(height);
+        self.btc_block_hashes.insert(height, block_hash);
+        let pruned_heights: Vec<u64> = self
+            .btc_block_hashes
+            .range(..height.saturating_sub(BTC_HEADER_WINDOW))
+            .map(|(height, _)| *height)
+            .collect();
+        for pruned_height in &pruned_heights {
+            self.btc_block_hashes.remove(pruned_height);
+        }
+
+        self.write(|txn| {
+            let mut block_hashes = txn.open_table(BTC_BLOCK_HASHES_TABLE)?;
+            block_hashes.insert(height, block_hash.as_slice())?;
+            for pruned_height in &pruned_heights {
+                block_hashes.remove(pruned_height)?;
+            }
+            txn.open_table(INDEXER_STATE_TABLE)?
+                .insert(BTC_ANALYZED_HEIGHT_KEY, height)?;
+            Ok(())
+        })
+    }
+
+    // rewinds the indexer to `fork_height` after a reorg, dropping the orphaned block hashes and
+    // any reservation progress built on them. Returns the ids of the affected reservations
+    pub fn rollback_btc_blocks(&mut self, fork_height: u64) -> Result<Vec<U256>> {
+        let orphaned_heights: Vec<u64> = self
+            .btc_block_hashes
+            .range(fork_height + 1..)
+            .map(|(height, _)| *height)
+            .collect();
+        for orphaned_height in &orphaned_heights {
+            self.btc_block_hashes.remove(orphaned_height);
+        }
+        self.btc_analyzed_height = Some(fork_height);
+
+        self.write(|txn| {
+            let mut block_hashes = txn.open_table(BTC_BLOCK_HASHES_TABLE)?;
+            for orphaned_height in &orphaned_heights {
+                block_hashes.remove(orphaned_height)?;
+            }
+            txn.open_table(INDEXER_STATE_TABLE)?
+                .insert(BTC_ANALYZED_HEIGHT_KEY, fork_height)?;
+            Ok(())
+        })?;
+
+        let affected_ids: Vec<U256> = self
+            .reservations
+            .iter_mut()
+            .filter_map(|(id, metadata)| metadata.rollback_to(fork_height).then_some(*id))
+            .collect();
+        for id in &affected_ids {
+            info!("Rolled back reservation: {} to height {}", id, fork_height);
+            self.persist_reservation(*id)?;
+        }
+        Ok(affected_ids)
+    }
+
+    pub fn push_dead_letter(&mut self, dead_letter: DeadLetter) -> Result<()> {
+        let encoded = serde_json::to_vec(&dead_letter)
+            .map_err(|e| hyper_err!(Store, "Failed to encode dead letter: {}", e))?;
+        // letters are keyed by their position, which clearing resets along with the table
+        let key = self.dead_letters.len() as u64;
+        self.dead_letters.push(dead_letter);
+        self.write(|txn| {
+            txn.open_table(DEAD_LETTERS_TABLE)?
+                .insert(key, encoded.as_slice())?;
+            Ok(())
+        })
+    }
+
+    pub fn clear_dead_letters(&mut self) -> Result<()> {
+        self.dead_letters.clear();
+        self.write(|txn| {
+            txn.open_table(DEAD_LETTERS_TABLE)?.retain(|_, _| false)?;
+            Ok(())
+        })
+    }
 }
 
 pub struct StoreGuard<'a> {
@@ -272,17 +867,141 @@ impl<'a> std::ops::DerefMut for StoreGuard<'a> {
 
 pub struct ThreadSafeStore(Arc<Mutex<Store>>);
 
+impl Default for ThreadSafeStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ThreadSafeStore {
     pub fn new() -> Self {
         ThreadSafeStore(Arc::new(Mutex::new(Store::new())))
     }
 
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(ThreadSafeStore(Arc::new(Mutex::new(Store::open(path)?))))
+    }
+
     pub async fn with_lock<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&mut StoreGuard<'_>) -> R,
     {
         let guard = self.0.lock().await;
         let mut reservations_guard = StoreGuard { guard };
-        f(&mut reservations_guard)
+        let result = f(&mut reservations_guard);
+        telemetry::record_store_sizes(&reservations_guard);
+        result
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn test_reservation() -> ReservationMetadata {
+        let reservation = RiftExchange::SwapReservation {
+            owner: Default::default(),
+            confirmationBlockHeight: 0,
+            reservationTimestamp: 0,
+            liquidityUnlockedTimestamp: 0,
+            state: 0,
+            ethPayoutAddress: Default::default(),
+            lpReservationHash: Default::default(),
+            nonce: Default::default(),
+            totalSatsInputIncludingProxyFee: U256::ZERO,
+            totalSwapOutputAmount: U256::ZERO,
+            proposedBlockHeight: 0,
+            proposedBlockHash: Default::default(),
+            vaultIndexes: vec![],
+            amountsToReserve: vec![],
+            expectedSatsOutput: vec![],
+        };
+        ReservationMetadata::new(reservation, vec![], vec![])
+    }
+
+    #[test]
+    fn test_store_persistence() {
+        let path = std::env::temp_dir().join(format!("hypernode-test-{}.redb", std::process::id()));
+        let id = U256::from(1);
+        {
+            let mut store = Store::open(&path).unwrap();
+            store.insert(id, test_reservation()).unwrap();
+            store
+                .update_btc_reservation_initial(id, 100, [1; 32], [2; 32])
+                .unwrap();
+            store.insert_safe_contract_block_hash(99, [3; 32]).unwrap();
+            store.update_btc_analyzed_block(100, [1; 32]).unwrap();
+        }
+
+        let store = Store::open(&path).unwrap();
+        let initial = store.get(id).unwrap().btc_initial.as_ref().unwrap();
+        assert_eq!(initial.proposed_block_height, 100);
+        assert_eq!(initial.txid, [2; 32]);
+        assert_eq!(store.safe_contract_block_hashes().get(&99), Some(&[3; 32]));
+        assert_eq!(store.btc_analyzed_height(), Some(100));
+        assert_eq!(store.btc_block_hashes().get(&100), Some(&[1; 32]));
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dead_letter_persistence() {
+        let path = std::env::temp_dir().join(format!(
+            "hypernode-dead-letters-test-{}.redb",
+            std::process::id()
+        ));
+        let dead_letter = |attempts| DeadLetter {
+            queue: "proof_generation".to_string(),
+            reservation_id: Some(U256::from(1)),
+            attempts,
+            error: "Proof generation error: invalid payment".to_string(),
+            failed_at: 0,
+        };
+        {
+            let mut store = Store::open(&path).unwrap();
+            store.push_dead_letter(dead_letter(1)).unwrap();
+            store.push_dead_letter(dead_letter(2)).unwrap();
+        }
+
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.dead_letters(), [dead_letter(1), dead_letter(2)]);
+        store.clear_dead_letters().unwrap();
+        store.push_dead_letter(dead_letter(3)).unwrap();
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.dead_letters(), [dead_letter(3)]);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rollback_btc_blocks() {
+        let mut store = Store::new();
+        for height in 100..=110 {
+            store
+                .update_btc_analyzed_block(height, [height as u8; 32])
+                .unwrap();
+        }
+        let (orphaned, kept) = (U256::from(1), U256::from(2));
+        store.insert(orphaned, test_reservation()).unwrap();
+        store
+            .update_btc_reservation_initial(orphaned, 105, [105; 32], [1; 32])
+            .unwrap();
+        store.insert(kept, test_reservation()).unwrap();
+        store
+            .update_btc_reservation_initial(kept, 101, [101; 32], [2; 32])
+            .unwrap();
+
+        assert_eq!(store.rollback_btc_blocks(102).unwrap(), vec![orphaned]);
+        assert_eq!(store.btc_analyzed_height(), Some(102));
+        assert_eq!(
+            store.btc_block_hashes().keys().copied().collect::<Vec<_>>(),
+            (100..=102).collect::<Vec<_>>()
+        );
+        assert!(store.get(orphaned).unwrap().btc_initial.is_none());
+        assert!(store.get(kept).unwrap().btc_initial.is_some());
+        // nothing is left above the fork
+        assert!(store.rollback_btc_blocks(102).unwrap().is_empty());
     }
 }
@@ -2,7 +2,7 @@ use crate::Result;
 use eyre::eyre;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum HypernodeError {
     #[error("Rpc error: {0}")]
     RpcError(String),
@@ -10,6 +10,8 @@ pub enum HypernodeError {
     BitcoinRpc(String),
     #[error("Evm error: {0}")]
     Evm(String),
+    #[error("Transaction reverted: {0}")]
+    Reverted(String),
     #[error("Decoding error: {0}")]
     Decode(String),
     #[error("Internal storage error: {0}")]
@@ -36,12 +38,38 @@ pub enum HypernodeError {
     Unknown(String),
 }
 
+impl HypernodeError {
+    // errors talking to other services may go away on retry, the rest would fail the same way
+    // again. Unknown errors are not retried as nothing says where they came from
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            HypernodeError::RpcError(_)
+                | HypernodeError::BitcoinRpc(_)
+                | HypernodeError::Evm(_)
+                | HypernodeError::Connection(_)
+        )
+    }
+}
+
 impl From<eyre::Report> for HypernodeError {
     fn from(err: eyre::Report) -> Self {
         HypernodeError::Unknown(err.to_string())
     }
 }
 
+impl From<redb::TableError> for HypernodeError {
+    fn from(err: redb::TableError) -> Self {
+        HypernodeError::Store(format!("Failed to open table: {}", err))
+    }
+}
+
+impl From<redb::StorageError> for HypernodeError {
+    fn from(err: redb::StorageError) -> Self {
+        HypernodeError::Store(format!("Failed to write to database: {}", err))
+    }
+}
+
 #[macro_export]
 macro_rules! hyper_err {
     ($variant:ident, $msg:expr) => {
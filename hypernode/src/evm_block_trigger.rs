@@ -1,6 +1,8 @@
 // Calls releaseLiquidity once enough evm blocks have passed
-use crate::core::{EvmHttpProvider, RiftExchangeWebsocket};
-use crate::evm_indexer::{broadcast_transaction, broadcast_transaction_via_flashbots};
+use crate::batcher::CallBatcher;
+use crate::evm_endpoints::EvmEndpoints;
+use crate::profitability::{ProfitabilityDecision, ProfitabilityGate};
+use crate::telemetry::{BROADCASTS, BROADCAST_FAILURES, EVM_BLOCK_TRIGGER_QUEUE_DEPTH};
 use crate::Result;
 use alloy::primitives::U256;
 use alloy::providers::Provider;
@@ -8,42 +10,54 @@ use alloy::rpc::types::{Block, Transaction};
 use futures::lock::Mutex;
 use futures::StreamExt;
 use log::{debug, info};
+use metrics::{counter, gauge};
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::Duration;
+
+// wait before subscribing again after a block subscription failed or ended
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct EvmBlockTriggerRequestInput {
     reservation_id: U256,
     unlock_timestamp: u64,
+    // the protocol fee is taken from it, see ProfitabilityGate
+    total_swap_output_amount: U256,
 }
 
 impl EvmBlockTriggerRequestInput {
-    pub fn new(reservation_id: U256, unlock_timestamp: u64) -> Self {
+    pub fn new(
+        reservation_id: U256,
+        unlock_timestamp: u64,
+        total_swap_output_amount: U256,
+    ) -> Self {
         EvmBlockTriggerRequestInput {
             reservation_id,
             unlock_timestamp,
+            total_swap_output_amount,
         }
     }
 }
 
 pub struct EvmBlockTrigger {
     trigger_queue: Arc<Mutex<Vec<EvmBlockTriggerRequestInput>>>,
-    flashbots_provider: Arc<Option<EvmHttpProvider>>,
-    contract: Arc<RiftExchangeWebsocket>,
-    debug_url: String,
+    batcher: Arc<CallBatcher>,
+    profitability: Arc<ProfitabilityGate>,
+    endpoints: Arc<EvmEndpoints>,
 }
 
 impl EvmBlockTrigger {
     pub fn new(
-        flashbots_provider: Arc<Option<EvmHttpProvider>>,
-        contract: Arc<RiftExchangeWebsocket>,
-        debug_url: &str,
+        batcher: Arc<CallBatcher>,
+        profitability: Arc<ProfitabilityGate>,
+        endpoints: Arc<EvmEndpoints>,
     ) -> Arc<Self> {
         let trigger = Arc::new(Self {
             trigger_queue: Arc::new(Mutex::new(Vec::new())),
-            flashbots_provider,
-            contract,
-            debug_url: debug_url.to_string(),
+            batcher,
+            profitability,
+            endpoints,
         });
 
         EvmBlockTrigger::trigger_on_blocks(Arc::clone(&trigger)).unwrap();
@@ -62,6 +76,7 @@ impl EvmBlockTrigger {
                 &req.reservation_id
             );
             trigger_queue_handle.push(req);
+            gauge!(EVM_BLOCK_TRIGGER_QUEUE_DEPTH).set(trigger_queue_handle.len() as f64);
         } else {
             info!(
                 "Release request for reservation ID: {} already exists in the queue",
@@ -71,17 +86,48 @@ impl EvmBlockTrigger {
         Ok(())
     }
 
+    // reservation ids waiting to be released, with their unlock timestamps
+    pub async fn pending(&self) -> Vec<(U256, u64)> {
+        self.trigger_queue
+            .lock()
+            .await
+            .iter()
+            .map(|req| (req.reservation_id, req.unlock_timestamp))
+            .collect()
+    }
+
     fn trigger_on_blocks(trigger: Arc<Self>) -> Result<()> {
         tokio::spawn(async move {
-            let provider = trigger.contract.provider();
-            let sub = provider.subscribe_blocks().await.unwrap();
-
-            let mut stream = sub.into_stream();
-            while let Some(block) = stream.next().await {
-                match trigger.process_queue(block).await {
-                    Ok(_) => {}
+            let mut active = trigger.endpoints.watch_active();
+            // blocks are subscribed to again on the new endpoint after a failover
+            loop {
+                active.mark_unchanged();
+                let contract = trigger.endpoints.contract();
+                let sub = match contract.provider().subscribe_blocks().await {
+                    Ok(sub) => sub,
                     Err(e) => {
-                        log::error!("Error processing queue: {:?}", e);
+                        log::error!("Failed to subscribe to blocks: {}", e);
+                        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                        continue;
+                    }
+                };
+
+                let mut stream = sub.into_stream();
+                loop {
+                    tokio::select! {
+                        block = stream.next() => {
+                            let Some(block) = block else {
+                                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                                break;
+                            };
+                            match trigger.process_queue(block).await {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    log::error!("Error processing queue: {:?}", e);
+                                }
+                            }
+                        }
+                        _ = active.changed() => break,
                     }
                 }
             }
@@ -89,63 +135,88 @@ impl EvmBlockTrigger {
         Ok(())
     }
 
-    async fn release_liquidity(&self, reservation_id: U256) -> Result<()> {
+    async fn release_liquidity(
+        &self,
+        req: &EvmBlockTriggerRequestInput,
+    ) -> Result<ProfitabilityDecision> {
+        let reservation_id = req.reservation_id;
         let txn_calldata = self
-            .contract
+            .endpoints
+            .contract()
             .releaseLiquidity(reservation_id)
             .calldata()
             .to_owned();
 
-        let tx_hash = if let Some(flashbots_provider) = self.flashbots_provider.as_ref() {
-            info!(
-                "Broadcasting release for reservation index: {} via Flashbots",
-                reservation_id
-            );
-            broadcast_transaction_via_flashbots(&self.contract, flashbots_provider, &txn_calldata)
-                .await?
-        } else {
-            broadcast_transaction(&self.contract, &txn_calldata, &self.debug_url).await?
-        };
+        let decision = self
+            .profitability
+            .check(
+                "releaseLiquidity",
+                &txn_calldata,
+                req.total_swap_output_amount,
+                req.unlock_timestamp,
+            )
+            .await?;
+        if decision != ProfitabilityDecision::Submit {
+            return Ok(decision);
+        }
+
+        let result = self.batcher.send("releaseLiquidity", &txn_calldata).await;
+        counter!(BROADCASTS, "function" => "releaseLiquidity").increment(1);
+        let tx_hash = result.inspect_err(|_| {
+            counter!(BROADCAST_FAILURES, "function" => "releaseLiquidity").increment(1);
+        })?;
         info!("Liquidity released with evm tx hash: {}", tx_hash);
-        Ok(())
+        Ok(decision)
     }
 
     async fn process_queue(&self, block: Block<Transaction>) -> Result<()> {
         let current_timestamp = block.header.timestamp;
-        let mut queue = self.trigger_queue.lock().await;
-
-        // Separate ready and not ready items
-        let (ready, not_ready): (Vec<_>, Vec<_>) = queue
-            .drain(..)
-            .partition(|req| current_timestamp > req.unlock_timestamp);
-
-        if !ready.is_empty() {
-            debug!("Processing queue at block timestamp: {}", current_timestamp);
-            debug!(
-                "Ready releaser items: {:?}",
-                ready.iter().map(|r| r).collect::<Vec<_>>()
-            );
-        }
-        if !not_ready.is_empty() {
-            debug!("Processing queue at block timestamp: {}", current_timestamp);
-            debug!(
-                "Not ready releaser indexes: {:?}",
-                not_ready.iter().map(|r| r).collect::<Vec<_>>()
-            );
-        }
+        // ready items are taken out up front so the queue isn't locked while their releases
+        // confirm
+        let ready = {
+            let mut queue = self.trigger_queue.lock().await;
+
+            // Separate ready and not ready items
+            let (ready, not_ready): (Vec<_>, Vec<_>) = queue
+                .drain(..)
+                .partition(|req| current_timestamp > req.unlock_timestamp);
+
+            if !ready.is_empty() {
+                debug!("Processing queue at block timestamp: {}", current_timestamp);
+                debug!("Ready releaser items: {:?}", ready);
+            }
+            if !not_ready.is_empty() {
+                debug!("Processing queue at block timestamp: {}", current_timestamp);
+                debug!("Not ready releaser indexes: {:?}", not_ready);
+            }
+
+            // Update the queue with remaining items
+            *queue = not_ready;
+            gauge!(EVM_BLOCK_TRIGGER_QUEUE_DEPTH).set(queue.len() as f64);
+            ready
+        };
 
         // Process all ready items concurrently
         let release_futures = ready.into_iter().map(|req| {
             let reservation_id = req.reservation_id;
             async move {
-                match self.release_liquidity(reservation_id).await {
-                    Ok(_) => {
+                match self.release_liquidity(&req).await {
+                    Ok(ProfitabilityDecision::Submit) => {
                         info!(
                             "Successfully released liquidity for reservation ID: {}",
                             reservation_id
                         );
-                        Ok(())
+                        Ok(None)
+                    }
+                    Ok(ProfitabilityDecision::Skip) => {
+                        info!(
+                            "Skipping unprofitable release for reservation ID: {}",
+                            reservation_id
+                        );
+                        Ok(None)
                     }
+                    // checked again on the next block
+                    Ok(ProfitabilityDecision::Delay) => Ok(Some(req)),
                     Err(e) => {
                         log::error!(
                             "Failed to release liquidity for reservation ID: {}, Error: {:?}",
@@ -159,17 +230,24 @@ impl EvmBlockTrigger {
         });
 
         // Wait for all release operations to complete
-        let results: Vec<Result<()>> = futures::future::join_all(release_futures).await;
+        let results: Vec<Result<Option<EvmBlockTriggerRequestInput>>> =
+            futures::future::join_all(release_futures).await;
 
         // Log any errors that occurred during processing
+        let mut held = Vec::new();
         for result in results {
-            if let Err(e) = result {
-                log::error!("Error during batch processing: {:?}", e);
+            match result {
+                Ok(Some(req)) => held.push(req),
+                Ok(None) => {}
+                Err(e) => log::error!("Error during batch processing: {:?}", e),
             }
         }
 
-        // Update the queue with remaining items
-        *queue = not_ready;
+        if !held.is_empty() {
+            let mut queue = self.trigger_queue.lock().await;
+            queue.extend(held);
+            gauge!(EVM_BLOCK_TRIGGER_QUEUE_DEPTH).set(queue.len() as f64);
+        }
 
         Ok(())
     }
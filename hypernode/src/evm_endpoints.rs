@@ -0,0 +1,347 @@
+// Spreads the hypernode's EVM traffic over several websocket endpoints. Calls go to one active
+// endpoint, which is swapped for the first healthy one in the configured order when it stops
+// answering or falls more than max_lag blocks behind the others. Critical reads can also require
+// `quorum` endpoints to return the same result, other reads are replayed on the next healthy
+// endpoint when they fail. Endpoints are logged by their index as urls often carry api keys
+use crate::core::{RetryWsConnect, RiftExchange, RiftExchangeWebsocket};
+use crate::error::HypernodeError;
+use crate::telemetry::{EVM_ENDPOINT_FAILOVERS, EVM_ENDPOINT_HEIGHT};
+use crate::{hyper_err, Result};
+use alloy::network::EthereumWallet;
+use alloy::primitives::{Address, Bytes};
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
+use alloy::rpc::client::ClientBuilder;
+use alloy::rpc::types::TransactionRequest;
+use log::{info, warn};
+use metrics::{counter, gauge};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+struct Endpoint {
+    url: String,
+    // unset until a connection has been made
+    contract: Mutex<Option<Arc<RiftExchangeWebsocket>>>,
+    // whether the endpoint passed the last health check
+    healthy: AtomicBool,
+}
+
+pub struct EvmEndpoints {
+    endpoints: Vec<Endpoint>,
+    // index of the endpoint calls go to
+    active: watch::Sender<usize>,
+    wallet: EthereumWallet,
+    rift_exchange_address: Address,
+    max_lag: u64,
+    quorum: usize,
+    // bounds every health check and reconnect, so one dead endpoint can't hold up the others
+    check_interval: Duration,
+}
+
+impl EvmEndpoints {
+    // fails unless one endpoint can be reached, the others are connected by the health checks once
+    // they come up
+    pub async fn connect(
+        urls: Vec<String>,
+        wallet: EthereumWallet,
+        rift_exchange_address: Address,
+        max_lag: u64,
+        quorum: usize,
+        check_interval: Duration,
+    ) -> Result<Arc<Self>> {
+        if urls.is_empty() {
+            return Err(hyper_err!(Config, "At least one EVM endpoint is required"));
+        }
+        if quorum == 0 || quorum > urls.len() {
+            return Err(hyper_err!(
+                Config,
+                "EVM read quorum must be between 1 and the {} configured endpoints",
+                urls.len()
+            ));
+        }
+
+        let endpoints = Arc::new(EvmEndpoints {
+            endpoints: urls
+                .into_iter()
+                .map(|url| Endpoint {
+                    url,
+                    contract: Mutex::new(None),
+                    healthy: AtomicBool::new(false),
+                })
+                .collect(),
+            active: watch::Sender::new(0),
+            wallet,
+            rift_exchange_address,
+            max_lag,
+            quorum,
+            check_interval,
+        });
+        endpoints.check_health().await;
+        // calls only ever move to endpoints that have connected
+        let active = *endpoints.active.borrow();
+        if endpoints.endpoints[active]
+            .contract
+            .lock()
+            .unwrap()
+            .is_none()
+        {
+            return Err(hyper_err!(
+                Connection,
+                "None of the EVM endpoints are reachable"
+            ));
+        }
+        tokio::spawn(Arc::clone(&endpoints).health_task());
+        Ok(endpoints)
+    }
+
+    // contract bound to the active endpoint, fetch it again for every operation so failovers are
+    // picked up. reads should go through `call` instead so they survive the endpoint failing
+    pub fn contract(&self) -> Arc<RiftExchangeWebsocket> {
+        self.endpoints[*self.active.borrow()]
+            .contract
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("only connected endpoints are made active")
+    }
+
+    pub fn active_url(&self) -> String {
+        self.endpoints[*self.active.borrow()].url.clone()
+    }
+
+    // notified whenever calls move to another endpoint, for subscriptions and filters to be
+    // recreated on it
+    pub fn watch_active(&self) -> watch::Receiver<usize> {
+        self.active.subscribe()
+    }
+
+    // runs the call against every connected endpoint and returns the output at least `quorum` of
+    // them agree on
+    pub async fn quorum_call(
+        &self,
+        function_name: &str,
+        request: TransactionRequest,
+    ) -> Result<Bytes> {
+        if self.quorum == 1 {
+            return self
+                .call(function_name, |contract| {
+                    let request = request.clone();
+                    async move {
+                        contract
+                            .provider()
+                            .call(&request)
+                            .await
+                            .map_err(|e| hyper_err!(Evm, "Failed to call {}: {}", function_name, e))
+                    }
+                })
+                .await;
+        }
+
+        let contracts = self.contracts();
+        let outputs = futures::future::join_all(
+            contracts
+                .iter()
+                .map(|contract| async { contract.provider().call(&request).await }),
+        )
+        .await;
+        let mut votes: Vec<(Bytes, usize)> = Vec::new();
+        for (index, output) in outputs.into_iter().enumerate() {
+            match output {
+                Ok(output) => match votes.iter_mut().find(|(voted, _)| *voted == output) {
+                    Some((_, count)) => *count += 1,
+                    None => votes.push((output, 1)),
+                },
+                Err(e) => warn!(
+                    "{} failed on an EVM endpoint {}: {}",
+                    function_name, index, e
+                ),
+            }
+        }
+        votes
+            .into_iter()
+            .find(|(_, count)| *count >= self.quorum)
+            .map(|(output, _)| output)
+            .ok_or_else(|| {
+                hyper_err!(
+                    Evm,
+                    "Fewer than {} of {} EVM endpoints agreed on {}",
+                    self.quorum,
+                    contracts.len(),
+                    function_name
+                )
+            })
+    }
+
+    // runs the read against the active endpoint and, if it fails, against every other endpoint that
+    // passed its last health check in the configured order, returning the last error if none answer
+    pub async fn call<T, F, Fut>(&self, function_name: &str, f: F) -> Result<T>
+    where
+        F: Fn(Arc<RiftExchangeWebsocket>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let active = *self.active.borrow();
+        let fallbacks = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(index, endpoint)| {
+                *index != active && endpoint.healthy.load(Ordering::Relaxed)
+            });
+        let mut last_error = None;
+        for (index, endpoint) in std::iter::once((active, &self.endpoints[active])).chain(fallbacks)
+        {
+            let Some(contract) = endpoint.contract.lock().unwrap().clone() else {
+                continue;
+            };
+            match f(contract).await {
+                Ok(output) => return Ok(output),
+                Err(e) => {
+                    warn!(
+                        "{} failed on EVM endpoint {}, trying the next healthy one: {}",
+                        function_name, index, e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            hyper_err!(Evm, "No EVM endpoint is connected for {}", function_name)
+        }))
+    }
+
+    fn contracts(&self) -> Vec<Arc<RiftExchangeWebsocket>> {
+        self.endpoints
+            .iter()
+            .filter_map(|endpoint| endpoint.contract.lock().unwrap().clone())
+            .collect()
+    }
+
+    async fn health_task(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(self.check_interval).await;
+            self.check_health().await;
+        }
+    }
+
+    // connects endpoints that aren't yet, records every endpoint's height and fails over if the
+    // active endpoint is behind
+    async fn check_health(&self) {
+        let heights = futures::future::join_all(
+            self.endpoints
+                .iter()
+                .enumerate()
+                .map(|(index, endpoint)| self.endpoint_height(index, endpoint)),
+        )
+        .await;
+
+        for (index, height) in heights.iter().enumerate() {
+            if let Some(height) = height {
+                gauge!(EVM_ENDPOINT_HEIGHT, "endpoint" => index.to_string()).set(*height as f64);
+            }
+        }
+
+        let Some(best_height) = heights.iter().flatten().max().copied() else {
+            warn!("None of the EVM endpoints are reachable");
+            return;
+        };
+        let healthy = |height: &Option<u64>| {
+            height.is_some_and(|height| best_height.saturating_sub(height) <= self.max_lag)
+        };
+        for (endpoint, height) in self.endpoints.iter().zip(&heights) {
+            endpoint.healthy.store(healthy(height), Ordering::Relaxed);
+        }
+        let active = *self.active.borrow();
+        // the first healthy endpoint is preferred, so calls move back once an earlier one recovers
+        if let Some(preferred) = heights.iter().position(healthy) {
+            if preferred != active {
+                match heights[active] {
+                    Some(_) if healthy(&heights[active]) => info!(
+                        "EVM endpoint {} recovered, moving calls back from endpoint {}",
+                        preferred, active
+                    ),
+                    Some(height) => warn!(
+                        "EVM endpoint {} is {} blocks behind, failing over to endpoint {}",
+                        active,
+                        best_height - height,
+                        preferred
+                    ),
+                    None => warn!(
+                        "EVM endpoint {} is unreachable, failing over to endpoint {}",
+                        active, preferred
+                    ),
+                }
+                counter!(EVM_ENDPOINT_FAILOVERS).increment(1);
+                self.active.send_replace(preferred);
+            }
+        }
+
+        // a connection that has given up reconnecting never recovers, so unreachable endpoints
+        // other than the active one are connected from scratch on the next check
+        let active = *self.active.borrow();
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            if index != active && heights[index].is_none() {
+                endpoint.contract.lock().unwrap().take();
+            }
+        }
+    }
+
+    async fn endpoint_height(&self, index: usize, endpoint: &Endpoint) -> Option<u64> {
+        let check = async {
+            let connected = endpoint.contract.lock().unwrap().clone();
+            let contract = match connected {
+                Some(contract) => contract,
+                None => {
+                    let contract = self
+                        .connect_endpoint(&endpoint.url)
+                        .await
+                        .inspect_err(|e| {
+                            warn!("Failed to connect to EVM endpoint {}: {}", index, e)
+                        })
+                        .ok()?;
+                    info!("Connected to EVM endpoint {}", index);
+                    *endpoint.contract.lock().unwrap() = Some(Arc::clone(&contract));
+                    contract
+                }
+            };
+            contract
+                .provider()
+                .get_block_number()
+                .await
+                .inspect_err(|e| warn!("EVM endpoint {} failed its health check: {}", index, e))
+                .ok()
+        };
+        tokio::time::timeout(self.check_interval, check)
+            .await
+            .inspect_err(|_| {
+                warn!(
+                    "EVM endpoint {} did not answer its health check within {:?}",
+                    index, self.check_interval
+                )
+            })
+            .ok()
+            .flatten()
+    }
+
+    async fn connect_endpoint(&self, url: &str) -> Result<Arc<RiftExchangeWebsocket>> {
+        let ws = RetryWsConnect {
+            ws: WsConnect::new(url),
+            max_reconnect_time: self.check_interval,
+        };
+        let client = ClientBuilder::default()
+            .pubsub(ws)
+            .await
+            .map_err(|e| hyper_err!(Connection, "Failed to connect to WebSocket: {}", e))?;
+        let provider = Arc::new(
+            ProviderBuilder::new()
+                .with_recommended_fillers()
+                .wallet(self.wallet.clone())
+                .on_client(client),
+        );
+        Ok(Arc::new(RiftExchange::new(
+            self.rift_exchange_address,
+            provider,
+        )))
+    }
+}
@@ -1,103 +1,31 @@
-use alloy::network::eip2718::Encodable2718;
-use alloy::primitives::FixedBytes;
-use alloy::providers::WalletProvider;
 use alloy::{
     eips::{BlockId, BlockNumberOrTag},
-    network::TransactionBuilder,
     primitives::{Bytes, U256},
     providers::Provider,
-    rpc::types::{BlockTransactionsKind, Filter, TransactionInput, TransactionRequest},
+    rpc::types::{BlockTransactionsKind, Filter},
     sol_types::{SolEvent, SolValue},
 };
-use bitcoin::hex::DisplayHex;
 use futures::stream::{self, TryStreamExt};
 use futures_util::StreamExt;
 use log::{error, info};
+use metrics::gauge;
 use std::time::Instant;
 use std::{collections::HashMap, collections::HashSet, sync::Arc};
 use tokio::sync::Mutex;
 use tokio::time::{sleep, Duration};
 
-use crate::core::{EvmHttpProvider, RiftExchangeWebsocket};
+use crate::core::RiftExchangeWebsocket;
+use crate::core::{
+    BlockHeaderAggregator, DepositVaultAggregator, ReservationMetadata,
+    RiftExchange::{self},
+    ThreadSafeStore,
+};
 use crate::error::HypernodeError;
 use crate::evm_block_trigger::{self, EvmBlockTrigger};
-use crate::{
-    constants::HEADER_LOOKBACK_LIMIT,
-    core::{
-        BlockHeaderAggregator, DepositVaultAggregator, ReservationMetadata,
-        RiftExchange::{self},
-        ThreadSafeStore,
-    },
-};
+use crate::evm_endpoints::EvmEndpoints;
+use crate::telemetry::EVM_INDEXED_HEIGHT;
 use crate::{hyper_err, Result};
 
-pub async fn broadcast_transaction_via_flashbots(
-    contract: &Arc<RiftExchangeWebsocket>,
-    flashbots_provider: &EvmHttpProvider,
-    txn_calldata: &[u8],
-) -> Result<FixedBytes<32>> {
-    let provider = contract.provider();
-    let tx = TransactionRequest::default()
-        .to(*contract.address())
-        .input(TransactionInput::new(txn_calldata.to_vec().into()));
-
-    let tx = provider
-        .fill(tx)
-        .await
-        .map_err(|e| hyper_err!(Evm, "Failed to fill transaction: {}", e))?;
-
-    let tx_envelope = tx
-        .as_builder()
-        .unwrap()
-        .clone()
-        .build(&provider.wallet())
-        .await
-        .map_err(|e| hyper_err!(Evm, "Failed to build transaction envelope: {}", e))?;
-
-    let tx_encoded = tx_envelope.encoded_2718();
-    let pending = flashbots_provider
-        .send_raw_transaction(&tx_encoded)
-        .await
-        .map_err(|e| hyper_err!(Evm, "Failed to send raw transaction: {}", e))?
-        .register()
-        .await
-        .map_err(|e| hyper_err!(Evm, "Failed to register transaction: {}", e))?;
-
-    Ok(*pending.tx_hash())
-}
-
-pub async fn broadcast_transaction(
-    contract: &Arc<RiftExchangeWebsocket>,
-    txn_calldata: &[u8],
-    debug_url: &str,
-) -> Result<FixedBytes<32>> {
-    let provider = contract.provider();
-    let tx = TransactionRequest::default()
-        .to(*contract.address())
-        .input(TransactionInput::new(txn_calldata.to_vec().into()));
-
-    match provider.send_transaction(tx.clone()).await {
-        Ok(tx) => Ok(*tx.tx_hash()),
-        Err(e) => {
-            let block_height = provider
-                .get_block_number()
-                .await
-                .map_err(|e| hyper_err!(Evm, "Failed to get block number: {}", e))?;
-
-            let data = txn_calldata.as_hex();
-            let to = contract.address().to_string();
-            info!(
-                    "To debug failed proof broadcast run: cast call {} --data {} --trace --block {} --rpc-url {}",
-                    to,
-                    data,
-                    block_height,
-                    debug_url
-                );
-            Err(hyper_err!(Evm, "Failed to broadcast proof: {}", e))
-        }
-    }
-}
-
 pub async fn fetch_token_decimals(exchange: &RiftExchangeWebsocket) -> Result<u8> {
     exchange
         .tokenDecimals()
@@ -113,29 +41,56 @@ fn decode_vaults(encoded_vaults: Vec<u8>) -> Result<Vec<RiftExchange::DepositVau
     encoded_vaults
         .into_iter()
         .map(|bytes| {
-            RiftExchange::DepositVault::abi_decode(&bytes.to_vec(), false)
+            RiftExchange::DepositVault::abi_decode(&bytes, false)
                 .map_err(|e| hyper_err!(Decode, "Failed to decode deposit vault: {}", e))
         })
         .collect()
 }
 
 pub async fn download_vaults(
-    contract: Arc<RiftExchangeWebsocket>,
+    endpoints: &EvmEndpoints,
     vault_indices: Vec<u32>,
 ) -> Result<Vec<RiftExchange::DepositVault>> {
-    let provider = contract.provider();
-    let encoded_vaults = DepositVaultAggregator::deploy_builder(
-        provider,
-        vault_indices.clone(),
+    let contract = endpoints.contract();
+    let aggregator = DepositVaultAggregator::deploy_builder(
+        contract.provider(),
+        vault_indices,
         *contract.address(),
-    )
-    .call()
-    .await
-    .map_err(|e| hyper_err!(Evm, "Failed to call DepositVaultAggregator: {}", e))?;
+    );
+    let encoded_vaults = endpoints
+        .quorum_call("DepositVaultAggregator", aggregator.as_ref().clone())
+        .await?;
 
     decode_vaults(encoded_vaults.to_vec())
 }
 
+// vaults only hold P2WPKH payout scripts, the contract keeps every other script alongside them
+pub async fn download_payout_scripts(
+    endpoints: &EvmEndpoints,
+    vault_indexes: &[U256],
+) -> Result<Vec<Vec<u8>>> {
+    let contract = endpoints.contract();
+    futures::future::try_join_all(vault_indexes.iter().map(|vault_index| {
+        let call = contract.getBtcPayoutLockingScript(*vault_index);
+        async move {
+            let output = endpoints
+                .quorum_call("getBtcPayoutLockingScript", call.as_ref().clone())
+                .await?;
+            call.decode_output(output, false)
+                .map(|script| script._0.to_vec())
+                .map_err(|e| {
+                    hyper_err!(
+                        Decode,
+                        "Failed to decode payout script of vault {}: {}",
+                        vault_index,
+                        e
+                    )
+                })
+        }
+    }))
+    .await
+}
+
 fn decode_block_hashes(encoded_blocks: Vec<u8>) -> Result<Vec<[u8; 32]>> {
     <Vec<Bytes>>::abi_decode(&encoded_blocks, false)
         .map_err(|e| hyper_err!(Decode, "Failed to decode block hashes: {}", e))?
@@ -153,7 +108,7 @@ pub async fn download_safe_bitcoin_headers(
     contract: Arc<RiftExchangeWebsocket>,
     store: Arc<ThreadSafeStore>,
     end_block_height: Option<u64>,
-    lookback_count: Option<usize>,
+    lookback_count: u64,
 ) -> Result<u64> {
     let provider = contract.provider();
     let current_evm_tip = provider
@@ -176,9 +131,7 @@ pub async fn download_safe_bitcoin_headers(
         ),
     };
 
-    let lookback_limit = lookback_count.unwrap_or(HEADER_LOOKBACK_LIMIT);
-
-    let mut heights = (0..lookback_limit)
+    let mut heights = (0..lookback_count)
         .map(|i| U256::from(stored_tip).saturating_sub(U256::from(i)))
         .collect::<Vec<_>>();
 
@@ -193,37 +146,42 @@ pub async fn download_safe_bitcoin_headers(
     let block_hashes = decode_block_hashes(encoded_blocks.to_vec())?;
 
     store
-        .with_lock(|store_guard| {
+        .with_lock(|store_guard| -> Result<()> {
             for (i, hash) in block_hashes.iter().enumerate() {
                 if hash == &[0u8; 32] {
                     continue;
                 }
-                store_guard.safe_contract_block_hashes.insert(
+                store_guard.insert_safe_contract_block_hash(
                     u64::from_be_bytes(
                         heights[i].to_be_bytes::<32>()[32 - 8..].try_into().unwrap(),
                     ),
                     *hash,
-                );
+                )?;
             }
+            Ok(())
         })
-        .await;
+        .await?;
 
     Ok(current_evm_tip)
 }
 
-// Downloads the actual reservation struct and all utilized deposit vaults
+// Downloads the actual reservation struct and all utilized deposit vaults, both are proven
+// against so they are read from as many evm endpoints as the read quorum asks for
 pub async fn download_reservation(
     reservation_id: U256,
-    contract: Arc<RiftExchangeWebsocket>,
+    endpoints: &EvmEndpoints,
 ) -> Result<(U256, ReservationMetadata)> {
-    let reservation = contract
-        .getReservation(reservation_id)
-        .call()
-        .await
+    let contract = endpoints.contract();
+    let call = contract.getReservation(reservation_id);
+    let output = endpoints
+        .quorum_call("getReservation", call.as_ref().clone())
+        .await?;
+    let reservation = call
+        .decode_output(output, false)
         .map_err(|e| {
             hyper_err!(
-                Evm,
-                "Failed to get reservation with ID {:?}: {}",
+                Decode,
+                "Failed to decode reservation with ID {:?}: {}",
                 reservation_id,
                 e
             )
@@ -236,10 +194,11 @@ pub async fn download_reservation(
         .map(|index| u32::from_be_bytes(index.to_be_bytes::<32>()[32 - 4..].try_into().unwrap()))
         .collect();
 
-    let vaults = download_vaults(contract, vault_indexes).await?;
+    let vaults = download_vaults(endpoints, vault_indexes).await?;
+    let payout_scripts = download_payout_scripts(endpoints, &reservation.vaultIndexes).await?;
     Ok((
         reservation_id,
-        ReservationMetadata::new(reservation, vaults),
+        ReservationMetadata::new(reservation, vaults, payout_scripts),
     ))
 }
 
@@ -283,19 +242,19 @@ pub async fn find_block_height_from_time(
             info!(
                 "Found EVM block height: {}, {:.2} hours from tip in {:?}",
                 check_block,
-                (current_timestamp - block_timestamp) as f64 / 3600 as f64,
+                (current_timestamp - block_timestamp) as f64 / 3600.0,
                 time.elapsed()
             );
-            return Ok(check_block.into());
+            return Ok(check_block);
         }
 
         check_block = check_block.saturating_sub(blocks_per_hour);
     }
 }
 
-// Goes through the last RESERVATION_DURATION_HOURS worth of ethereum blocks and collects all reservations
+// Goes through the last reservation duration worth of ethereum blocks and collects all reservations
 pub async fn sync_reservations(
-    contract: Arc<RiftExchangeWebsocket>,
+    endpoints: Arc<EvmEndpoints>,
     safe_store: Arc<ThreadSafeStore>,
     trigger: Arc<EvmBlockTrigger>,
     start_block: u64,
@@ -303,6 +262,7 @@ pub async fn sync_reservations(
 ) -> Result<u64> {
     let time = Instant::now();
     info!("Syncing reservations from block {}", start_block);
+    let contract = endpoints.contract();
     let provider = contract.provider();
     let latest_block: u64 = provider
         .get_block_number()
@@ -370,9 +330,9 @@ pub async fn sync_reservations(
     let downloaded_reservations: HashMap<U256, ReservationMetadata> =
         stream::iter(reservations_to_download)
             .map(|reservation_id| {
-                let contract: Arc<RiftExchangeWebsocket> = Arc::clone(&contract);
+                let endpoints = Arc::clone(&endpoints);
                 async move {
-                    download_reservation(reservation_id, contract)
+                    download_reservation(reservation_id, &endpoints)
                         .await
                         .map_err(|e| {
                             info!("Failed to download reservation: {}", e);
@@ -393,11 +353,13 @@ pub async fn sync_reservations(
 
     let total_reservations = &reservations.len();
 
-    // Update active_reservations
+    // Update active_reservations, reservations persisted by a previous run keep their local
+    // progress and anything that is no longer active onchain is dropped
     safe_store
         .with_lock(|reservations_guard| {
+            reservations_guard.retain(&active_reservations_ids)?;
             for (id, metadata) in reservations {
-                reservations_guard.insert(id, metadata);
+                reservations_guard.upsert(id, metadata)?;
             }
             Ok(())
         })
@@ -419,6 +381,7 @@ pub async fn sync_reservations(
             .add(evm_block_trigger::EvmBlockTriggerRequestInput::new(
                 *reservation_id,
                 unlock_timestamp,
+                metadata.reservation.totalSwapOutputAmount,
             ))
             .await?;
     }
@@ -427,7 +390,7 @@ pub async fn sync_reservations(
 }
 
 pub async fn exchange_event_listener(
-    contract: Arc<RiftExchangeWebsocket>,
+    endpoints: Arc<EvmEndpoints>,
     trigger: Arc<EvmBlockTrigger>,
     mut start_index_block_height: u64,
     mut start_block_header_height: u64,
@@ -437,7 +400,7 @@ pub async fn exchange_event_listener(
 
     loop {
         let result = try_exchange_event_listener(
-            Arc::clone(&contract),
+            Arc::clone(&endpoints),
             Arc::clone(&trigger),
             start_index_block_height,
             start_block_header_height,
@@ -464,12 +427,17 @@ pub async fn exchange_event_listener(
 }
 
 async fn try_exchange_event_listener(
-    contract: Arc<RiftExchangeWebsocket>,
+    endpoints: Arc<EvmEndpoints>,
     trigger: Arc<EvmBlockTrigger>,
     start_index_block_height: u64,
     start_block_header_height: u64,
     active_reservations: Arc<ThreadSafeStore>,
 ) -> Result<(u64, u64)> {
+    // filters only live on the endpoint they were created on, so they are recreated on the new one
+    // after a failover
+    let mut active_endpoint = endpoints.watch_active();
+    active_endpoint.mark_unchanged();
+    let contract = endpoints.contract();
     info!(
         "Rift deployed at: {} on chain ID: {}",
         contract.address(),
@@ -520,7 +488,11 @@ async fn try_exchange_event_listener(
 
     loop {
         tokio::select! {
-            Some(log) = swap_complete_stream.next() => {
+            log = swap_complete_stream.next() => {
+                let Some(log) = log else {
+                    info!("SwapComplete stream closed. Returning current block heights.");
+                    return Ok((current_index_block_height, current_block_header_height));
+                };
                 let log_data = log.clone().map_err(|e| hyper_err!(Evm, "Failed to clone SwapComplete log: {}", e))?;
                 let log_identifier = (log_data.1.block_number, log_data.1.transaction_index, log_data.1.log_index);
 
@@ -532,12 +504,16 @@ async fn try_exchange_event_listener(
                     let swap_reservation_index = log_data.0.swapReservationIndex;
                     info!("SwapComplete with reservation index: {:?}", &swap_reservation_index);
                     active_reservations.with_lock(|reservations_guard| {
-                        reservations_guard.remove(swap_reservation_index);
-                    }).await;
+                        reservations_guard.remove(swap_reservation_index)
+                    }).await?;
                 }
                 current_index_block_height = log_data.1.block_number.unwrap();
             }
-            Some(log) = liquidity_reserved_stream.next() => {
+            log = liquidity_reserved_stream.next() => {
+                let Some(log) = log else {
+                    info!("LiquidityReserved stream closed. Returning current block heights.");
+                    return Ok((current_index_block_height, current_block_header_height));
+                };
                 let log_data = log.clone().map_err(|e| hyper_err!(Evm, "Failed to clone LiquidityReserved log: {}", e))?;
                 let log_identifier = (log_data.1.block_number, log_data.1.transaction_index, log_data.1.log_index);
 
@@ -548,14 +524,18 @@ async fn try_exchange_event_listener(
 
                     info!("LiquidityReserved w/ reservation index: {:?}", &log_data.0.swapReservationIndex);
                     let swap_reservation_index = log_data.0.swapReservationIndex;
-                    let reservation = download_reservation(swap_reservation_index, Arc::clone(&contract)).await?;
+                    let reservation = download_reservation(swap_reservation_index, &endpoints).await?;
                     active_reservations.with_lock(|reservations_guard| {
-                        reservations_guard.insert(swap_reservation_index, reservation.1.clone());
-                    }).await;
+                        reservations_guard.insert(swap_reservation_index, reservation.1.clone())
+                    }).await?;
                 }
                 current_index_block_height = log_data.1.block_number.unwrap();
             }
-            Some(log) = proof_proposed_stream.next() => {
+            log = proof_proposed_stream.next() => {
+                let Some(log) = log else {
+                    info!("ProofSubmitted stream closed. Returning current block heights.");
+                    return Ok((current_index_block_height, current_block_header_height));
+                };
                 let log_data = log.clone().map_err(|e| hyper_err!(Evm, "Failed to clone ProofSubmitted log: {}", e))?;
                 let log_identifier = (log_data.1.block_number, log_data.1.transaction_index, log_data.1.log_index);
 
@@ -566,18 +546,23 @@ async fn try_exchange_event_listener(
 
                     info!("ProofSubmitted w/ reservation index: {:?}", &log_data.0.swapReservationIndex);
                     let swap_reservation_index = log_data.0.swapReservationIndex;
-                    let reservation_metadata = download_reservation(swap_reservation_index, Arc::clone(&contract)).await?;
+                    let reservation_metadata = download_reservation(swap_reservation_index, &endpoints).await?;
                     active_reservations.with_lock(|reservations_guard| {
-                        reservations_guard.insert(swap_reservation_index, reservation_metadata.1.clone());
-                    }).await;
+                        reservations_guard.upsert(swap_reservation_index, reservation_metadata.1.clone())
+                    }).await?;
                     trigger.add(evm_block_trigger::EvmBlockTriggerRequestInput::new(
                         swap_reservation_index,
-                        reservation_metadata.1.reservation.liquidityUnlockedTimestamp
+                        reservation_metadata.1.reservation.liquidityUnlockedTimestamp,
+                        reservation_metadata.1.reservation.totalSwapOutputAmount
                     )).await?;
                 }
                 current_index_block_height = log_data.1.block_number.unwrap();
             }
-            Some(log) = blocks_added_stream.next() => {
+            log = blocks_added_stream.next() => {
+                let Some(log) = log else {
+                    info!("BlocksAdded stream closed. Returning current block heights.");
+                    return Ok((current_index_block_height, current_block_header_height));
+                };
                 let log_data = log.clone().map_err(|e| hyper_err!(Evm, "Failed to clone BlocksAdded log: {}", e))?;
                 let log_identifier = (log_data.1.block_number, log_data.1.transaction_index, log_data.1.log_index);
 
@@ -594,14 +579,17 @@ async fn try_exchange_event_listener(
                         Arc::clone(&contract),
                         Arc::clone(&active_reservations),
                         Some(end_block_height),
-                        Some(u64::from_be_bytes(blocks_added.count.to_be_bytes::<32>()[32-8..].try_into().unwrap()) as usize)).await?;
+                        u64::from_be_bytes(blocks_added.count.to_be_bytes::<32>()[32-8..].try_into().unwrap())).await?;
                 }
                 current_block_header_height = log_data.1.block_number.unwrap();
             }
-            else => {
-                info!("All event streams have closed. Returning current block heights.");
+            // a closed stream or a failover has every filter recreated from the current heights
+            _ = active_endpoint.changed() => {
+                info!("EVM endpoint changed. Returning current block heights.");
                 return Ok((current_index_block_height, current_block_header_height));
             }
         }
+        gauge!(EVM_INDEXED_HEIGHT)
+            .set(current_index_block_height.max(current_block_header_height) as f64);
     }
 }
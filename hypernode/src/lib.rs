@@ -1,32 +1,100 @@
+pub mod admin;
+pub mod batcher;
+pub mod btc_electrum;
+pub mod btc_esplora;
 pub mod btc_indexer;
+pub mod btc_mempool;
 pub mod btc_rpc;
+pub mod btc_source;
+pub mod btc_zmq;
+pub mod config;
 pub mod constants;
 pub mod core;
 pub mod error;
 pub mod evm_block_trigger;
+pub mod evm_endpoints;
 pub mod evm_indexer;
 pub mod node;
+pub mod profitability;
 pub mod proof_broadcast;
 pub mod proof_builder;
+pub mod prover;
+pub mod remote_prover;
+pub mod retry;
+pub mod signer;
+pub mod telemetry;
+pub mod tx_manager;
 
 use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 pub type Result<T> = std::result::Result<T, error::HypernodeError>;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct HypernodeArgs {
-    /// Ethereum RPC websocket URL for indexing and proposing proofs onchain
-    #[arg(short, long, env)]
-    pub evm_ws_rpc: String,
+    /// Ethereum RPC websocket URLs for indexing and proposing proofs onchain, comma separated in
+    /// order of preference, calls fail over to the next healthy one
+    #[arg(short, long, env, value_delimiter = ',', required = true)]
+    pub evm_ws_rpc: Vec<String>,
 
-    /// Bitcoin RPC URL for indexing
+    /// URL of the Bitcoin data source used for indexing: a bitcoind RPC URL, an Esplora API URL
+    /// or an Electrum server as tcp://host:port
     #[arg(short, long, env)]
     pub btc_rpc: String,
 
-    /// Ethereum private key for signing hypernode initiated transactions
+    /// Kind of Bitcoin data source btc_rpc points to: bitcoind, esplora or electrum
+    #[arg(long, env, default_value = "bitcoind")]
+    pub btc_source: String,
+
+    /// Username for the bitcoind RPC, credentials in the btc_rpc URL are used if unset
+    #[arg(long, env, requires = "btc_rpc_password")]
+    pub btc_rpc_user: Option<String>,
+
+    /// Password for the bitcoind RPC
+    #[arg(long, env, requires = "btc_rpc_user")]
+    pub btc_rpc_password: Option<String>,
+
+    /// bitcoind's .cookie file to authenticate to the RPC with, used instead of a username and
+    /// password
+    #[arg(long, env, conflicts_with = "btc_rpc_user")]
+    pub btc_rpc_cookie_file: Option<PathBuf>,
+
+    /// Seconds a single Bitcoin RPC request can take before it is abandoned
+    #[arg(long, env, default_value = "30")]
+    pub btc_rpc_timeout: u64,
+
+    /// Seconds a Bitcoin RPC request is retried for when bitcoind can't be reached or is still
+    /// warming up, errors returned by bitcoind otherwise fail right away
+    #[arg(long, env, default_value = "60")]
+    pub btc_rpc_retry_time: u64,
+
+    /// Number of blocks fetched per batch request when downloading ranges of Bitcoin blocks
+    #[arg(long, env, default_value = "10")]
+    pub btc_rpc_batch_size: u64,
+
+    /// Ethereum private key for signing hypernode initiated transactions, prefer a keystore or
+    /// remote signer so the key isn't kept in the environment
     #[arg(short, long, env)]
-    pub private_key: String,
+    pub private_key: Option<String>,
+
+    /// Encrypted JSON keystore holding the Ethereum key, used instead of the private key
+    #[arg(long, env, requires = "keystore_password_file")]
+    pub keystore_path: Option<PathBuf>,
+
+    /// File containing the keystore's password
+    #[arg(long, env)]
+    pub keystore_password_file: Option<PathBuf>,
+
+    /// URL of a Web3Signer compatible remote signer holding the Ethereum key, used instead of the
+    /// private key
+    #[arg(long, env, requires = "remote_signer_address")]
+    pub remote_signer_url: Option<String>,
+
+    /// Address of the remote signer's key to sign with
+    #[arg(long, env)]
+    pub remote_signer_address: Option<String>,
 
     /// Rift Exchange contract address
     #[arg(short, long, env)]
@@ -44,6 +112,16 @@ pub struct HypernodeArgs {
     #[arg(short, long, env, default_value = "30")]
     pub btc_polling_interval: u64,
 
+    /// bitcoind's zmqpubhashblock or zmqpubrawblock endpoint, e.g. tcp://127.0.0.1:28332, new
+    /// blocks are then picked up as soon as they are announced, with polling kept as a fallback
+    #[arg(long, env)]
+    pub btc_zmq_url: Option<String>,
+
+    /// Seconds between scans of bitcoind's mempool for reservation payments that aren't mined yet,
+    /// only supported with the bitcoind source. The mempool isn't watched when unset
+    #[arg(long, env)]
+    pub btc_mempool_polling_interval: Option<u64>,
+
     /// Average time between EVM blocks in seconds
     #[arg(short, long, env, default_value = "12")]
     pub evm_block_time: u64,
@@ -69,4 +147,143 @@ pub struct HypernodeArgs {
     /// broadcasting transactions
     #[arg(short, long, env)]
     pub flashbots_relay_rpc: Option<String>,
+
+    /// Path to the database used to persist reservation and indexer state across restarts, state
+    /// is only kept in memory if unset
+    #[arg(long, env)]
+    pub db_path: Option<String>,
+
+    /// Bitcoin network whose consensus rules are proven: mainnet, testnet4, signet or regtest,
+    /// must match the chain params hash the Rift Exchange contract was deployed with
+    #[arg(long, env, default_value = "mainnet")]
+    pub btc_network: String,
+
+    /// Hex encoded signet block challenge, only used when btc_network is signet, defaults to the
+    /// default signet's challenge
+    #[arg(long, env)]
+    pub signet_challenge: Option<String>,
+
+    /// Address to serve Prometheus metrics on, e.g. 0.0.0.0:9090, scraped at /metrics, metrics
+    /// are not exported if unset
+    #[arg(long, env)]
+    pub metrics_address: Option<SocketAddr>,
+
+    /// Address to serve the operator admin API on, e.g. 127.0.0.1:9091, disabled if unset
+    #[arg(long, env, requires = "admin_token")]
+    pub admin_address: Option<SocketAddr>,
+
+    /// Shared token the admin API expects as a bearer token, keep it in the environment
+    #[arg(long, env)]
+    pub admin_token: Option<String>,
+
+    /// Address to serve the prover worker API on, e.g. 0.0.0.0:9092, proofs are then generated by
+    /// the prover-worker processes polling it instead of on this host
+    #[arg(long, env, requires = "prover_worker_token")]
+    pub prover_worker_address: Option<SocketAddr>,
+
+    /// Shared token prover workers present to the worker API, keep it in the environment
+    #[arg(long, env)]
+    pub prover_worker_token: Option<String>,
+
+    /// Seconds a prover worker can go without a heartbeat before its job is offered to another
+    /// worker
+    #[arg(long, env, default_value = "60")]
+    pub prover_lease_duration: u64,
+
+    /// Seconds to wait for the prover workers to deliver a proof
+    #[arg(long, env, default_value = "3600")]
+    pub prover_job_timeout: u64,
+
+    /// Number of times a proof job is leased to prover workers before it is failed
+    #[arg(long, env, default_value = "3")]
+    pub prover_max_attempts: u32,
+
+    /// Number of times a failed proof generation or broadcast job is attempted before it is dead
+    /// lettered
+    #[arg(long, env, default_value = "5")]
+    pub job_max_attempts: u32,
+
+    /// Delay in seconds before the first retry of a failed job, doubled on every further attempt
+    #[arg(long, env, default_value = "10")]
+    pub job_retry_delay: u64,
+
+    /// Maximum delay in seconds between retries of a failed job
+    #[arg(long, env, default_value = "600")]
+    pub job_retry_max_delay: u64,
+
+    /// Number of blocks, including the one it was mined in, before a transaction is considered
+    /// final
+    #[arg(long, env, default_value = "1")]
+    pub tx_confirmations: u64,
+
+    /// Seconds a transaction can stay pending before it is replaced with higher fees
+    #[arg(long, env, default_value = "60")]
+    pub tx_bump_interval: u64,
+
+    /// Percentage both EIP-1559 fees are raised by when replacing a pending transaction
+    #[arg(long, env, default_value = "20")]
+    pub tx_fee_bump_percent: u64,
+
+    /// Max fee per gas in gwei that fee bumps won't go past
+    #[arg(long, env)]
+    pub tx_max_fee_per_gas_gwei: Option<u64>,
+
+    /// What to do with swap proofs and releases that cost more in gas than the protocol fee they
+    /// earn: always (submit anyway), skip or delay
+    #[arg(long, env, default_value = "always")]
+    pub profitability_policy: String,
+
+    /// Value in wei of the smallest unit of the deposit token, used to compare protocol fees to
+    /// gas costs, required unless the profitability policy is always
+    #[arg(long, env)]
+    pub wei_per_token_unit: Option<String>,
+
+    /// Seconds an unprofitable call is delayed for before it is submitted anyway, counted from the
+    /// reservation for swap proofs and from the unlock for releases
+    #[arg(long, env, default_value = "3600")]
+    pub profitability_max_delay: u64,
+
+    /// Address of a RiftCallBatcher contract to send swap proofs and releases through in batches,
+    /// it must be deployed from the hypernode's key and approved as a hypernode on the exchange
+    #[arg(long, env)]
+    pub batcher_address: Option<String>,
+
+    /// EVM blocks an endpoint can fall behind the most synced one before calls fail over from it
+    #[arg(long, env, default_value = "3")]
+    pub evm_max_lag_blocks: u64,
+
+    /// Number of EVM endpoints that must return the same result for reservations and public
+    /// inputs read before proving or broadcasting
+    #[arg(long, env, default_value = "1")]
+    pub evm_read_quorum: usize,
+
+    /// Hours a swap reservation stays open for, must match the Rift Exchange contract
+    #[arg(long, env, default_value = "8")]
+    pub reservation_duration_hours: u64,
+
+    /// Bitcoin blocks mined on top of a swap's block before it is proven, must match the Rift
+    /// Exchange contract
+    #[arg(long, env, default_value = "1")]
+    pub confirmation_height_delta: u64,
+
+    /// Bitcoin blocks the contract's light client can fall behind the tip before proveBlocks is
+    /// called
+    #[arg(long, env, default_value = "72")]
+    pub checkpoint_block_interval: u64,
+
+    /// Minimum seconds between proveBlocks calls made because the light client fell behind
+    #[arg(long, env, default_value = "43200")]
+    pub prove_blocks_interval: u64,
+
+    /// Safe Bitcoin block hashes read back from the contract's light client at startup
+    #[arg(long, env, default_value = "200")]
+    pub header_lookback_limit: u64,
+
+    /// TOML file of named flag profiles, the command line and environment override its values
+    #[arg(long, env = "HYPERNODE_CONFIG", requires = "profile")]
+    pub config: Option<PathBuf>,
+
+    /// Profile in the config file to run with, e.g. mainnet, testnet or devnet
+    #[arg(long, env = "HYPERNODE_PROFILE", requires = "config")]
+    pub profile: Option<String>,
 }
@@ -1,73 +1,221 @@
-use crate::constants::RESERVATION_DURATION_HOURS;
-use crate::core::{
-    EvmHttpProvider, EvmWebsocketProvider, RiftExchange, RiftExchangeWebsocket, ThreadSafeStore,
-};
+use crate::batcher::CallBatcher;
+use crate::btc_rpc::{BitcoinRpcAuth, BitcoinRpcClient, BitcoinRpcConfig};
+use crate::core::{EvmHttpProvider, ThreadSafeStore};
 use crate::error::HypernodeError;
-use crate::{btc_indexer, btc_rpc, evm_indexer, proof_broadcast, proof_builder};
+use crate::evm_endpoints::EvmEndpoints;
+use crate::profitability::ProfitabilityGate;
+use crate::prover::{LocalProver, MockProver, Prover};
+use crate::remote_prover::{ExchangeProofVerifier, RemoteProver, RemoteProverConfig};
+use crate::retry::{DeadLetters, RetryPolicy};
+use crate::signer::SignerBackend;
+use crate::tx_manager::{TxManager, TxManagerConfig};
+use crate::{
+    admin, btc_indexer, btc_mempool, btc_source, btc_zmq, config, evm_indexer, proof_broadcast,
+    proof_builder, telemetry,
+};
 use crate::{evm_block_trigger, HypernodeArgs};
 use crate::{hyper_err, Result};
-use alloy::rpc::client::ClientBuilder;
-use alloy::{
-    network::EthereumWallet,
-    providers::{ProviderBuilder, WsConnect},
-    signers::local::PrivateKeySigner,
-};
+use alloy::network::EthereumWallet;
+use alloy::primitives::U256;
+use alloy::providers::ProviderBuilder;
+use log::info;
+use rift_core::chain_params::ChainParams;
+use rift_core::constants::MAX_SIGNET_CHALLENGE_SIZE;
+use std::time::Duration;
 use std::{str::FromStr, sync::Arc};
+use tokio::sync::Notify;
 
 pub async fn run(args: HypernodeArgs) -> Result<()> {
+    config::validate(&args)?;
+
+    if let Some(metrics_address) = args.metrics_address {
+        telemetry::install_exporter(metrics_address)?;
+        info!("Serving metrics on {}", metrics_address);
+    }
+
     let rift_exchange_address =
         alloy::primitives::Address::from_str(&args.rift_exchange_address)
             .map_err(|e| hyper_err!(Parse, "Failed to parse Rift exchange address: {}", e))?;
 
-    let safe_store = Arc::new(ThreadSafeStore::new());
+    let safe_store = Arc::new(match &args.db_path {
+        Some(db_path) => ThreadSafeStore::open(db_path)?,
+        None => ThreadSafeStore::new(),
+    });
 
-    let (contract, flashbots_provider) = create_providers_and_contract(
-        &args.evm_ws_rpc,
-        &args.private_key,
-        rift_exchange_address,
+    let wallet = signer_backend(&args)?.wallet().await?;
+    let flashbots_provider = create_flashbots_provider(
+        wallet.clone(),
         args.flashbots,
         args.flashbots_relay_rpc.as_deref(),
+    )?;
+    // endpoint health is checked about once per evm block
+    let endpoints = EvmEndpoints::connect(
+        args.evm_ws_rpc.clone(),
+        wallet,
+        rift_exchange_address,
+        args.evm_max_lag_blocks,
+        args.evm_read_quorum,
+        Duration::from_secs(args.evm_block_time),
     )
     .await?;
 
-    let btc_rpc = Arc::new(btc_rpc::BitcoinRpcClient::new(&args.btc_rpc));
+    let btc_rpc_config = BitcoinRpcConfig {
+        auth: btc_rpc_auth(&args),
+        timeout: Duration::from_secs(args.btc_rpc_timeout),
+        max_retry_time: Duration::from_secs(args.btc_rpc_retry_time),
+    };
+    let btc_rpc = btc_source::connect(
+        args.btc_source.parse()?,
+        &args.btc_rpc,
+        btc_rpc_config.clone(),
+    )?;
+
+    let new_block = match &args.btc_zmq_url {
+        Some(btc_zmq_url) => btc_zmq::spawn_block_notifier(btc_zmq_url)?,
+        None => Arc::new(Notify::new()),
+    };
+
+    let chain_params = parse_chain_params(&args.btc_network, args.signet_challenge.as_deref())?;
+
+    let retry_policy = RetryPolicy {
+        max_attempts: args.job_max_attempts,
+        initial_delay: Duration::from_secs(args.job_retry_delay),
+        max_delay: Duration::from_secs(args.job_retry_max_delay),
+    };
+    let dead_letters = Arc::new(DeadLetters::new(Arc::clone(&safe_store)));
+
+    let tx_manager = Arc::new(TxManager::new(
+        Arc::clone(&endpoints),
+        flashbots_provider,
+        TxManagerConfig {
+            confirmations: args.tx_confirmations,
+            bump_interval: Duration::from_secs(args.tx_bump_interval),
+            fee_bump_percent: args.tx_fee_bump_percent,
+            max_fee_per_gas: args
+                .tx_max_fee_per_gas_gwei
+                .map(|gwei| gwei as u128 * 1_000_000_000),
+        },
+    ));
+
+    let batcher_address = args
+        .batcher_address
+        .as_deref()
+        .map(alloy::primitives::Address::from_str)
+        .transpose()
+        .map_err(|e| hyper_err!(Parse, "Failed to parse batcher address: {}", e))?;
+    // calls ready within an evm block of each other are sent in one transaction
+    let batcher = Arc::new(CallBatcher::new(
+        Arc::clone(&tx_manager),
+        rift_exchange_address,
+        batcher_address,
+        Duration::from_secs(args.evm_block_time),
+    ));
+
+    let profitability = Arc::new(ProfitabilityGate::new(
+        args.profitability_policy.parse()?,
+        parse_wei_per_token_unit(&args)?,
+        args.profitability_max_delay,
+        Arc::clone(&batcher),
+        Arc::clone(&endpoints),
+    ));
 
     let proof_broadcast_queue = Arc::new(proof_broadcast::ProofBroadcastQueue::new(
         Arc::clone(&safe_store),
-        Arc::clone(&flashbots_provider),
-        Arc::clone(&contract),
-        args.evm_ws_rpc.as_ref(),
+        Arc::clone(&batcher),
+        Arc::clone(&profitability),
+        Arc::clone(&endpoints),
+        retry_policy,
+        Arc::clone(&dead_letters),
     ));
 
+    let prover: Arc<dyn Prover> = if let Some(prover_worker_address) = args.prover_worker_address {
+        let remote_prover = RemoteProver::serve(
+            prover_worker_address,
+            RemoteProverConfig {
+                lease_duration: Duration::from_secs(args.prover_lease_duration),
+                job_timeout: Duration::from_secs(args.prover_job_timeout),
+                max_attempts: args.prover_max_attempts,
+            },
+            args.prover_worker_token.as_deref().ok_or_else(|| {
+                hyper_err!(
+                    Config,
+                    "A prover worker token is required to serve the worker API"
+                )
+            })?,
+            Arc::new(ExchangeProofVerifier::new(Arc::clone(&endpoints))),
+        )
+        .await?;
+        info!("Serving prover worker API on {}", remote_prover.address());
+        Arc::new(remote_prover)
+    } else if args.mock_proof {
+        Arc::new(MockProver)
+    } else {
+        Arc::new(LocalProver)
+    };
+
     let proof_gen_queue = Arc::new(proof_builder::ProofGenerationQueue::new(
         Arc::clone(&safe_store),
         Arc::clone(&proof_broadcast_queue),
-        args.mock_proof,
+        prover,
         args.proof_gen_concurrency,
+        chain_params,
+        retry_policy,
+        Arc::clone(&dead_letters),
     ));
 
     let trigger = Arc::new(evm_block_trigger::EvmBlockTrigger::new(
-        Arc::clone(&flashbots_provider),
-        Arc::clone(&contract),
-        args.evm_ws_rpc.as_ref(),
+        Arc::clone(&batcher),
+        Arc::clone(&profitability),
+        Arc::clone(&endpoints),
     ));
 
+    let prove_blocks_request = Arc::new(btc_indexer::ProveBlocksRequest::default());
+
+    if let Some(admin_address) = args.admin_address {
+        admin::serve(
+            admin_address,
+            admin::AdminState {
+                store: Arc::clone(&safe_store),
+                proof_gen_queue: Arc::clone(&proof_gen_queue),
+                proof_broadcast_queue: Arc::clone(&proof_broadcast_queue),
+                trigger: Arc::clone(&trigger),
+                prove_blocks_request: Arc::clone(&prove_blocks_request),
+                dead_letters: Arc::clone(&dead_letters),
+                tx_manager: Arc::clone(&tx_manager),
+            },
+            args.admin_token.as_deref().ok_or_else(|| {
+                hyper_err!(Config, "An admin token is required to serve the admin API")
+            })?,
+        )
+        .await?;
+        info!("Serving admin API on {}", admin_address);
+    }
+
+    let contract = endpoints.contract();
     let (start_evm_block_height, start_btc_block_height) = tokio::try_join!(
         evm_indexer::find_block_height_from_time(
             &contract,
-            RESERVATION_DURATION_HOURS,
+            args.reservation_duration_hours,
             args.evm_block_time
         ),
         btc_indexer::find_block_height_from_time(
-            &args.btc_rpc,
-            RESERVATION_DURATION_HOURS,
+            btc_rpc.as_ref(),
+            args.reservation_duration_hours,
             args.btc_block_time
         )
     )
     .map_err(|e| hyper_err!(Indexer, "Failed to find starting block heights: {}", e))?;
 
+    // pick up from the last analyzed bitcoin block if a previous run got further than the lookback
+    let start_btc_block_height = safe_store
+        .with_lock(|store| store.btc_analyzed_height())
+        .await
+        .map_or(start_btc_block_height, |analyzed_height| {
+            start_btc_block_height.max(analyzed_height + 1)
+        });
+
     let synced_reservation_evm_height = evm_indexer::sync_reservations(
-        Arc::clone(&contract),
+        Arc::clone(&endpoints),
         Arc::clone(&safe_store),
         Arc::clone(&trigger),
         start_evm_block_height,
@@ -76,18 +224,29 @@ pub async fn run(args: HypernodeArgs) -> Result<()> {
     .await
     .map_err(|e| hyper_err!(Indexer, "Failed to sync reservations: {}", e))?;
 
+    resume_pending_work(&safe_store, &proof_gen_queue, &proof_broadcast_queue).await?;
+
     let synced_block_header_evm_height = evm_indexer::download_safe_bitcoin_headers(
-        Arc::clone(&contract),
+        endpoints.contract(),
         Arc::clone(&safe_store),
         None,
-        None,
+        args.header_lookback_limit,
     )
     .await
     .map_err(|e| hyper_err!(Indexer, "Failed to download safe Bitcoin headers: {}", e))?;
 
+    // config::validate only allows this with the bitcoind source
+    if let Some(polling_interval) = args.btc_mempool_polling_interval {
+        tokio::spawn(btc_mempool::mempool_listener(
+            Arc::new(BitcoinRpcClient::with_config(&args.btc_rpc, btc_rpc_config)),
+            Arc::clone(&safe_store),
+            polling_interval,
+        ));
+    }
+
     tokio::try_join!(
         evm_indexer::exchange_event_listener(
-            Arc::clone(&contract),
+            Arc::clone(&endpoints),
             Arc::clone(&trigger),
             synced_reservation_evm_height,
             synced_block_header_evm_height,
@@ -96,10 +255,19 @@ pub async fn run(args: HypernodeArgs) -> Result<()> {
         btc_indexer::block_listener(
             Arc::clone(&btc_rpc),
             start_btc_block_height,
-            args.btc_polling_interval,
             Arc::clone(&safe_store),
             Arc::clone(&proof_gen_queue),
-            args.btc_rpc_concurrency
+            Arc::clone(&prove_blocks_request),
+            new_block,
+            btc_indexer::BlockListenerConfig {
+                polling_interval: args.btc_polling_interval,
+                reservation_duration: args.reservation_duration_hours * 3600,
+                confirmation_height_delta: args.confirmation_height_delta,
+                checkpoint_block_interval: args.checkpoint_block_interval,
+                prove_blocks_interval: Duration::from_secs(args.prove_blocks_interval),
+                block_batch_size: args.btc_rpc_batch_size,
+                max_concurrent_requests: args.btc_rpc_concurrency,
+            }
         )
     )
     .map_err(|e| hyper_err!(Listener, "Event listener or block listener failed: {}", e))?;
@@ -107,38 +275,139 @@ pub async fn run(args: HypernodeArgs) -> Result<()> {
     Ok(())
 }
 
-async fn create_providers_and_contract(
-    evm_ws_rpc: &str,
-    private_key_hex: &str,
-    rift_exchange_address: alloy::primitives::Address,
-    flashbots_enabled: bool,
-    flashbots_relay_rpc: Option<&str>,
-) -> Result<(Arc<RiftExchangeWebsocket>, Arc<Option<EvmHttpProvider>>)> {
-    let private_key: [u8; 32] = hex::decode(private_key_hex.trim_start_matches("0x"))
-        .map_err(|e| hyper_err!(Parse, "Failed to decode private key: {}", e))?
-        .get(..32)
-        .and_then(|slice| slice.try_into().ok())
-        .ok_or_else(|| hyper_err!(Parse, "Invalid private key length"))?;
-
-    let ws = WsConnect::new(evm_ws_rpc);
-    let ws = crate::core::RetryWsConnect(ws);
-    let client = ClientBuilder::default()
-        .pubsub(ws)
-        .await
-        .map_err(|e| hyper_err!(Connection, "Failed to connect to WebSocket: {}", e))?;
+// clap already rejects a username without a password and a username alongside a cookie file
+fn btc_rpc_auth(args: &HypernodeArgs) -> BitcoinRpcAuth {
+    match (
+        &args.btc_rpc_user,
+        &args.btc_rpc_password,
+        &args.btc_rpc_cookie_file,
+    ) {
+        (Some(user), Some(password), _) => BitcoinRpcAuth::UserPass {
+            user: user.clone(),
+            password: password.clone(),
+        },
+        (_, _, Some(cookie_file)) => BitcoinRpcAuth::CookieFile(cookie_file.clone()),
+        _ => BitcoinRpcAuth::None,
+    }
+}
+
+// exactly one place to get the Ethereum key from has to be configured
+fn signer_backend(args: &HypernodeArgs) -> Result<SignerBackend<'_>> {
+    match (
+        args.private_key.as_deref(),
+        args.keystore_path.as_deref(),
+        args.remote_signer_url.as_deref(),
+    ) {
+        (Some(private_key), None, None) => Ok(SignerBackend::PrivateKey(private_key)),
+        (None, Some(path), None) => Ok(SignerBackend::Keystore {
+            path,
+            password_file: args.keystore_password_file.as_deref().ok_or_else(|| {
+                hyper_err!(
+                    Config,
+                    "A keystore password file is required with a keystore"
+                )
+            })?,
+        }),
+        (None, None, Some(url)) => Ok(SignerBackend::Remote {
+            url,
+            address: args.remote_signer_address.as_deref().ok_or_else(|| {
+                hyper_err!(
+                    Config,
+                    "A remote signer address is required with a remote signer"
+                )
+            })?,
+        }),
+        _ => Err(hyper_err!(
+            Config,
+            "Exactly one of a private key, keystore or remote signer has to be configured"
+        )),
+    }
+}
 
-    let provider: Arc<EvmWebsocketProvider> = Arc::new(
-        ProviderBuilder::new()
-            .with_recommended_fillers()
-            .wallet(EthereumWallet::from(
-                PrivateKeySigner::from_bytes(&private_key.into()).unwrap(),
-            ))
-            .on_client(client),
-    );
+fn parse_wei_per_token_unit(args: &HypernodeArgs) -> Result<U256> {
+    match args.wei_per_token_unit.as_deref() {
+        Some(wei_per_token_unit) => U256::from_str(wei_per_token_unit)
+            .map_err(|e| hyper_err!(Parse, "Failed to parse wei per token unit: {}", e)),
+        None if args.profitability_policy == "always" => Ok(U256::ZERO),
+        None => Err(hyper_err!(
+            Config,
+            "Wei per token unit is required for the {} profitability policy",
+            args.profitability_policy
+        )),
+    }
+}
+
+fn parse_chain_params(btc_network: &str, signet_challenge: Option<&str>) -> Result<ChainParams> {
+    match btc_network {
+        "mainnet" => Ok(ChainParams::mainnet()),
+        "testnet4" => Ok(ChainParams::testnet4()),
+        "regtest" => Ok(ChainParams::regtest()),
+        "signet" => match signet_challenge {
+            Some(challenge) => {
+                let challenge = hex::decode(challenge.trim_start_matches("0x"))
+                    .map_err(|e| hyper_err!(Parse, "Failed to decode signet challenge: {}", e))?;
+                if challenge.is_empty() || challenge.len() > MAX_SIGNET_CHALLENGE_SIZE {
+                    return Err(hyper_err!(
+                        Config,
+                        "Signet challenge must be between 1 and {} bytes",
+                        MAX_SIGNET_CHALLENGE_SIZE
+                    ));
+                }
+                Ok(ChainParams::signet(&challenge))
+            }
+            None => Ok(ChainParams::default_signet()),
+        },
+        _ => Err(hyper_err!(
+            Config,
+            "Unsupported bitcoin network: {}",
+            btc_network
+        )),
+    }
+}
 
-    let contract: Arc<RiftExchangeWebsocket> =
-        Arc::new(RiftExchange::new(rift_exchange_address, provider.clone()));
+// re-queues work interrupted by a restart: finalized payments that still need a proof, and
+// proofs that were generated but never broadcast
+async fn resume_pending_work(
+    store: &Arc<ThreadSafeStore>,
+    proof_gen_queue: &Arc<proof_builder::ProofGenerationQueue>,
+    proof_broadcast_queue: &Arc<proof_broadcast::ProofBroadcastQueue>,
+) -> Result<()> {
+    let (unproven, unbroadcasted) = store
+        .with_lock(|store| {
+            let unproven: Vec<U256> = store
+                .reservations()
+                .iter()
+                .filter(|(_, metadata)| metadata.btc_final.is_some() && metadata.proof.is_none())
+                .map(|(id, _)| *id)
+                .collect();
+            let unbroadcasted: Vec<U256> = store
+                .reservations()
+                .iter()
+                .filter(|(_, metadata)| {
+                    metadata.proof.is_some() && metadata.proof_tx_hash.is_none()
+                })
+                .map(|(id, _)| *id)
+                .collect();
+            (unproven, unbroadcasted)
+        })
+        .await;
 
+    for id in unproven {
+        info!("Resuming proof generation for reservation: {}", id);
+        proof_gen_queue.add(proof_builder::ProofGenerationInput::new_reservation(id))?;
+    }
+    for id in unbroadcasted {
+        info!("Resuming proof broadcast for reservation: {}", id);
+        proof_broadcast_queue.add(proof_broadcast::ProofBroadcastInput::new_reservation(id))?;
+    }
+    Ok(())
+}
+
+fn create_flashbots_provider(
+    wallet: EthereumWallet,
+    flashbots_enabled: bool,
+    flashbots_relay_rpc: Option<&str>,
+) -> Result<Arc<Option<EvmHttpProvider>>> {
     let flashbots_provider: Arc<Option<EvmHttpProvider>> = Arc::new(if flashbots_enabled {
         let url = flashbots_relay_rpc.ok_or_else(|| {
             hyper_err!(
@@ -149,9 +418,7 @@ async fn create_providers_and_contract(
         Some(
             ProviderBuilder::new()
                 .with_recommended_fillers()
-                .wallet(EthereumWallet::from(
-                    PrivateKeySigner::from_bytes(&private_key.into()).unwrap(),
-                ))
+                .wallet(wallet)
                 .on_http(
                     url.parse()
                         .map_err(|e| hyper_err!(Parse, "Failed to parse Flashbots URL: {}", e))?,
@@ -161,5 +428,5 @@ async fn create_providers_and_contract(
         None
     });
 
-    Ok((contract, flashbots_provider))
+    Ok(flashbots_provider)
 }
@@ -0,0 +1,162 @@
+// Weighs the gas a swap proof or release costs against the protocol fee the swap pays out
+use crate::batcher::CallBatcher;
+use crate::error::HypernodeError;
+use crate::evm_endpoints::EvmEndpoints;
+use crate::telemetry::UNPROFITABLE_CALLS;
+use crate::{hyper_err, Result};
+use alloy::primitives::U256;
+use log::info;
+use metrics::counter;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// how often a held back proof is checked again
+pub const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProfitabilityPolicy {
+    // submit every call regardless of its cost
+    Always,
+    // drop calls that cost more than they earn
+    Skip,
+    // hold calls back until they are profitable, or until they have been held for the max delay
+    Delay,
+}
+
+impl FromStr for ProfitabilityPolicy {
+    type Err = HypernodeError;
+
+    fn from_str(policy: &str) -> Result<Self> {
+        match policy {
+            "always" => Ok(ProfitabilityPolicy::Always),
+            "skip" => Ok(ProfitabilityPolicy::Skip),
+            "delay" => Ok(ProfitabilityPolicy::Delay),
+            _ => Err(hyper_err!(
+                Config,
+                "Unknown profitability policy {}, expected always, skip or delay",
+                policy
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProfitabilityDecision {
+    Submit,
+    Skip,
+    Delay,
+}
+
+impl ProfitabilityDecision {
+    fn label(&self) -> &'static str {
+        match self {
+            ProfitabilityDecision::Submit => "submit",
+            ProfitabilityDecision::Skip => "skip",
+            ProfitabilityDecision::Delay => "delay",
+        }
+    }
+}
+
+pub struct ProfitabilityGate {
+    policy: ProfitabilityPolicy,
+    // value of the smallest unit of the deposit token in wei, protocol fees are converted with it
+    wei_per_token_unit: U256,
+    // seconds a call is held back for under the delay policy
+    max_delay: u64,
+    batcher: Arc<CallBatcher>,
+    endpoints: Arc<EvmEndpoints>,
+}
+
+impl ProfitabilityGate {
+    pub fn new(
+        policy: ProfitabilityPolicy,
+        wei_per_token_unit: U256,
+        max_delay: u64,
+        batcher: Arc<CallBatcher>,
+        endpoints: Arc<EvmEndpoints>,
+    ) -> Self {
+        ProfitabilityGate {
+            policy,
+            wei_per_token_unit,
+            max_delay,
+            batcher,
+            endpoints,
+        }
+    }
+
+    // `held_since` is the timestamp the max delay counts from
+    pub async fn check(
+        &self,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+        total_swap_output_amount: U256,
+        held_since: u64,
+    ) -> Result<ProfitabilityDecision> {
+        if self.policy == ProfitabilityPolicy::Always {
+            return Ok(ProfitabilityDecision::Submit);
+        }
+
+        let reward = self.protocol_fee(total_swap_output_amount).await? * self.wei_per_token_unit;
+        let cost = self
+            .batcher
+            .estimate_cost(function_name, txn_calldata)
+            .await?;
+        if reward >= cost {
+            return Ok(ProfitabilityDecision::Submit);
+        }
+
+        let decision = match self.policy {
+            ProfitabilityPolicy::Always => ProfitabilityDecision::Submit,
+            ProfitabilityPolicy::Skip => ProfitabilityDecision::Skip,
+            ProfitabilityPolicy::Delay
+                if chrono::Utc::now().timestamp() as u64 >= held_since + self.max_delay =>
+            {
+                ProfitabilityDecision::Submit
+            }
+            ProfitabilityPolicy::Delay => ProfitabilityDecision::Delay,
+        };
+        info!(
+            "{} costs {} wei in gas for a {} wei protocol fee, decided to {}",
+            function_name,
+            cost,
+            reward,
+            decision.label()
+        );
+        counter!(UNPROFITABLE_CALLS, "function" => function_name, "decision" => decision.label())
+            .increment(1);
+        Ok(decision)
+    }
+
+    async fn protocol_fee(&self, total_swap_output_amount: U256) -> Result<U256> {
+        let (protocol_fee_bp, bp_scale, min_protocol_fee) = self
+            .endpoints
+            .call("protocol fee parameters", |contract| async move {
+                let protocol_fee_bp = contract
+                    .protocolFeeBP()
+                    .call()
+                    .await
+                    .map_err(|e| hyper_err!(Evm, "Failed to call protocolFeeBP: {}", e))?
+                    ._0;
+                let bp_scale = contract
+                    .bpScale()
+                    .call()
+                    .await
+                    .map_err(|e| hyper_err!(Evm, "Failed to call bpScale: {}", e))?
+                    ._0;
+                let min_protocol_fee = contract
+                    .minProtocolFee()
+                    .call()
+                    .await
+                    .map_err(|e| hyper_err!(Evm, "Failed to call minProtocolFee: {}", e))?
+                    ._0;
+                Ok((protocol_fee_bp, bp_scale, min_protocol_fee))
+            })
+            .await?;
+        // mirrors the fee releaseLiquidity pays out
+        Ok(
+            (total_swap_output_amount * U256::from(protocol_fee_bp) / bp_scale)
+                .max(U256::from(min_protocol_fee)),
+        )
+    }
+}
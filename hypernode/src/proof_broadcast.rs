@@ -1,7 +1,10 @@
-use crate::core::{EvmHttpProvider, RiftExchangeWebsocket};
+use crate::batcher::CallBatcher;
 use crate::core::{RiftExchange, ThreadSafeStore};
 use crate::error::HypernodeError;
-use crate::evm_indexer;
+use crate::evm_endpoints::EvmEndpoints;
+use crate::profitability::{self, ProfitabilityDecision, ProfitabilityGate};
+use crate::retry::{DeadLetters, RetryPolicy};
+use crate::telemetry::{BROADCASTS, BROADCAST_FAILURES, JOB_RETRIES, PROOF_BROADCAST_QUEUE_DEPTH};
 use crate::{hyper_err, Result};
 use alloy::primitives::{FixedBytes, Uint, U256};
 use alloy::providers::WalletProvider;
@@ -15,25 +18,31 @@ use bitcoin::hashes::Hash;
 use bitcoin::hex::DisplayHex;
 use crypto_bigint::{Encoding, U256 as SP1OptimizedU256};
 use json_patch::diff;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use metrics::{counter, gauge};
 use rift_lib::{self, AsRiftOptimizedBlock};
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ProofBroadcastInput {
-    Reservation {
-        reservation_id: U256,
-    },
-    BlockProof {
-        safe_chainwork: U256,
-        safe_block_height: u64,
-        blocks: Vec<Block>,
-        retarget_block: Block,
-        retarget_block_height: u64,
-        solidity_proof: Vec<u8>,
-        public_inputs: Vec<u8>,
-    },
+    Reservation { reservation_id: U256 },
+    BlockProof(BlockProofBroadcast),
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockProofBroadcast {
+    pub safe_chainwork: U256,
+    pub safe_block_height: u64,
+    pub blocks: Vec<Block>,
+    pub retarget_block: Block,
+    pub retarget_block_height: u64,
+    pub solidity_proof: Vec<u8>,
+    pub public_inputs: Vec<u8>,
+    // proven by aggregating chunk block proofs, see proveBlocksAggregated
+    pub is_aggregated: bool,
 }
 
 impl ProofBroadcastInput {
@@ -41,138 +50,235 @@ impl ProofBroadcastInput {
         ProofBroadcastInput::Reservation { reservation_id }
     }
 
-    pub fn new_block_proof(
-        safe_chainwork: U256,
-        safe_block_height: u64,
-        blocks: Vec<Block>,
-        retarget_block: Block,
-        retarget_block_height: u64,
-        solidity_proof: Vec<u8>,
-        public_inputs: Vec<u8>,
-    ) -> Self {
-        ProofBroadcastInput::BlockProof {
-            safe_chainwork,
-            safe_block_height,
-            blocks,
-            retarget_block,
-            retarget_block_height,
-            solidity_proof,
-            public_inputs,
+    pub fn new_block_proof(block_proof: BlockProofBroadcast) -> Self {
+        ProofBroadcastInput::BlockProof(block_proof)
+    }
+
+    fn job_key(&self) -> Option<U256> {
+        match self {
+            ProofBroadcastInput::Reservation { reservation_id } => Some(*reservation_id),
+            ProofBroadcastInput::BlockProof(_) => None,
         }
     }
 }
 
+// a queued item with the number of attempts already made
+type QueuedItem = (ProofBroadcastInput, u32);
+
+// what every broadcast runs against
+#[derive(Clone)]
+struct BroadcastContext {
+    store: Arc<ThreadSafeStore>,
+    batcher: Arc<CallBatcher>,
+    profitability: Arc<ProfitabilityGate>,
+    endpoints: Arc<EvmEndpoints>,
+}
+
+// the inputs the contract builds its public inputs from, block proofs leave the swap fields unset
+struct PublicInputsQuery<'a> {
+    swap_reservation_index: Uint<256, 4>,
+    bitcoin_tx_id: FixedBytes<32>,
+    merkle_root: FixedBytes<32>,
+    safe_block_height: u32,
+    proposed_block_height: u64,
+    confirmation_block_height: u64,
+    block_hashes: &'a [FixedBytes<32>],
+    block_chainworks: &'a [Uint<256, 4>],
+    is_transaction_proof: bool,
+}
+
+enum Outcome {
+    Done,
+    // held back by the profitability gate, the item is queued again later
+    Held,
+}
+
 pub struct ProofBroadcastQueue {
-    sender: mpsc::UnboundedSender<ProofBroadcastInput>,
+    sender: mpsc::UnboundedSender<QueuedItem>,
+    // keys of the queued items in queue order, block proofs have no key
+    pending: Arc<Mutex<VecDeque<Option<U256>>>>,
 }
 
 impl ProofBroadcastQueue {
     pub fn new(
         store: Arc<ThreadSafeStore>,
-        flashbots_provider: Arc<Option<EvmHttpProvider>>,
-        contract: Arc<RiftExchangeWebsocket>,
-        debug_url: &str,
+        batcher: Arc<CallBatcher>,
+        profitability: Arc<ProfitabilityGate>,
+        endpoints: Arc<EvmEndpoints>,
+        retry_policy: RetryPolicy,
+        dead_letters: Arc<DeadLetters>,
     ) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
-        let queue = ProofBroadcastQueue { sender };
+        let pending = Arc::new(Mutex::new(VecDeque::new()));
+        let queue = ProofBroadcastQueue {
+            sender: sender.clone(),
+            pending: Arc::clone(&pending),
+        };
         tokio::spawn(ProofBroadcastQueue::consume_task(
             receiver,
-            store,
-            flashbots_provider,
-            contract,
-            debug_url.to_string(),
+            sender,
+            pending,
+            BroadcastContext {
+                store,
+                batcher,
+                profitability,
+                endpoints,
+            },
+            retry_policy,
+            dead_letters,
         ));
         queue
     }
 
     pub fn add(&self, proof_args: ProofBroadcastInput) -> Result<()> {
-        self.sender
-            .send(proof_args)
-            .map_err(|e| hyper_err!(Queue, "Failed to add to proof broadcast queue: {}", e))
+        Self::enqueue(&self.sender, &self.pending, (proof_args, 0))
+    }
+
+    fn enqueue(
+        sender: &mpsc::UnboundedSender<QueuedItem>,
+        pending: &Mutex<VecDeque<Option<U256>>>,
+        item: QueuedItem,
+    ) -> Result<()> {
+        // tracked before sending so the consumer never sees an item it can't pop
+        pending.lock().unwrap().push_back(item.0.job_key());
+        sender
+            .send(item)
+            .map_err(|e| hyper_err!(Queue, "Failed to add to proof broadcast queue: {}", e))?;
+        gauge!(PROOF_BROADCAST_QUEUE_DEPTH).increment(1.0);
+        Ok(())
+    }
+
+    pub fn pending(&self) -> Vec<Option<U256>> {
+        self.pending.lock().unwrap().iter().cloned().collect()
     }
 
     async fn consume_task(
-        mut receiver: mpsc::UnboundedReceiver<ProofBroadcastInput>,
-        store: Arc<ThreadSafeStore>,
-        flashbots_provider: Arc<Option<EvmHttpProvider>>,
-        contract: Arc<RiftExchangeWebsocket>,
-        debug_url: String,
+        mut receiver: mpsc::UnboundedReceiver<QueuedItem>,
+        sender: mpsc::UnboundedSender<QueuedItem>,
+        pending: Arc<Mutex<VecDeque<Option<U256>>>>,
+        context: BroadcastContext,
+        retry_policy: RetryPolicy,
+        dead_letters: Arc<DeadLetters>,
     ) {
-        let provider = contract.provider();
-
         info!(
             "Hypernode address: {}",
-            provider.wallet().default_signer().address()
+            context
+                .endpoints
+                .contract()
+                .provider()
+                .wallet()
+                .default_signer()
+                .address()
         );
 
-        while let Some(item) = receiver.recv().await {
-            if let Err(e) =
-                Self::process_item(item, &store, &flashbots_provider, &contract, &debug_url).await
-            {
-                error!("Failed to process proof broadcast item: {}", e);
-            }
+        while let Some((item, attempts)) = receiver.recv().await {
+            pending.lock().unwrap().pop_front();
+            gauge!(PROOF_BROADCAST_QUEUE_DEPTH).decrement(1.0);
+            // items are broadcast concurrently so swap proofs that are ready together can share a
+            // batch
+            let sender = sender.clone();
+            let pending = Arc::clone(&pending);
+            let context = context.clone();
+            let dead_letters = Arc::clone(&dead_letters);
+            tokio::spawn(async move {
+                let job_key = item.job_key();
+                let result = Self::process_item(item.clone(), &context).await;
+                let e = match result {
+                    Ok(Outcome::Done) => return,
+                    // doesn't use up an attempt
+                    Ok(Outcome::Held) => {
+                        Self::requeue_after(
+                            &sender,
+                            &pending,
+                            (item, attempts),
+                            profitability::RECHECK_INTERVAL,
+                        );
+                        return;
+                    }
+                    Err(e) => e,
+                };
+                let attempts = attempts + 1;
+                if retry_policy.should_retry(&e, attempts) {
+                    let delay = retry_policy.delay(attempts);
+                    warn!(
+                        "Proof broadcast {:?} failed on attempt {}, retrying in {:?}: {}",
+                        job_key, attempts, delay, e
+                    );
+                    counter!(JOB_RETRIES, "queue" => "proof_broadcast").increment(1);
+                    Self::requeue_after(&sender, &pending, (item, attempts), delay);
+                } else {
+                    error!(
+                        "Proof broadcast {:?} failed after {} attempts: {}",
+                        job_key, attempts, e
+                    );
+                    dead_letters
+                        .push("proof_broadcast", job_key, attempts, &e)
+                        .await;
+                }
+            });
         }
     }
 
+    // queued again in the background so the rest of the queue isn't held up
+    fn requeue_after(
+        sender: &mpsc::UnboundedSender<QueuedItem>,
+        pending: &Arc<Mutex<VecDeque<Option<U256>>>>,
+        item: QueuedItem,
+        delay: Duration,
+    ) {
+        let sender = sender.clone();
+        let pending = Arc::clone(pending);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = Self::enqueue(&sender, &pending, item) {
+                error!("Failed to requeue proof broadcast: {}", e);
+            }
+        });
+    }
+
     async fn process_item(
         item: ProofBroadcastInput,
-        store: &Arc<ThreadSafeStore>,
-        flashbots_provider: &Arc<Option<EvmHttpProvider>>,
-        contract: &Arc<RiftExchangeWebsocket>,
-        debug_url: &str,
-    ) -> Result<()> {
+        context: &BroadcastContext,
+    ) -> Result<Outcome> {
         match item {
             ProofBroadcastInput::Reservation { reservation_id } => {
                 info!("Processing proof broadcast item: {}", reservation_id);
-                Self::process_reservation(
-                    reservation_id,
-                    store,
-                    flashbots_provider,
-                    contract,
-                    debug_url,
-                )
-                .await
+                Self::process_reservation(reservation_id, context).await
             }
-            ProofBroadcastInput::BlockProof {
-                safe_chainwork,
-                safe_block_height,
-                blocks,
-                retarget_block,
-                retarget_block_height,
-                solidity_proof,
-                public_inputs,
-            } => {
+            ProofBroadcastInput::BlockProof(block_proof) => {
                 info!("Processing block proof broadcast");
-                Self::process_block_proof(
-                    safe_chainwork,
-                    safe_block_height,
-                    blocks,
-                    retarget_block,
-                    retarget_block_height,
-                    solidity_proof,
-                    public_inputs,
-                    flashbots_provider,
-                    contract,
-                    debug_url,
-                )
-                .await
+                Self::process_block_proof(block_proof, &context.batcher, &context.endpoints)
+                    .await?;
+                Ok(Outcome::Done)
             }
         }
     }
 
     async fn process_reservation(
         reservation_id: U256,
-        store: &Arc<ThreadSafeStore>,
-        flashbots_provider: &Arc<Option<EvmHttpProvider>>,
-        contract: &Arc<RiftExchangeWebsocket>,
-        debug_url: &str,
-    ) -> Result<()> {
+        context: &BroadcastContext,
+    ) -> Result<Outcome> {
+        let BroadcastContext {
+            store,
+            batcher,
+            profitability,
+            endpoints,
+        } = context;
         info!("Processing proof broadcast item: {}", reservation_id);
         let reservation_metadata = store
             .with_lock(|store| store.get(reservation_id).cloned())
             .await
             .ok_or_else(|| hyper_err!(Store, "Reservation not found: {}", reservation_id))?;
 
+        if let Some(proof_tx_hash) = reservation_metadata.proof_tx_hash {
+            info!(
+                "Proof for reservation: {} already broadcasted with evm tx hash: {}",
+                reservation_id,
+                FixedBytes(proof_tx_hash)
+            );
+            return Ok(Outcome::Done);
+        }
+
         let solidity_proof = reservation_metadata.proof.ok_or_else(|| {
             hyper_err!(
                 ProofBroadcast,
@@ -216,29 +322,34 @@ impl ProofBroadcastQueue {
         )?;
 
         Self::validate_public_inputs(
-            reservation_id,
-            bitcoin_tx_id.into(),
-            FixedBytes(
-                btc_final
-                    .blocks
-                    .index(((proposed_block_height as u64) - (safe_block_height as u64)) as usize)
-                    .header
-                    .merkle_root
-                    .to_byte_array()
-                    .to_little_endian(),
-            ),
-            safe_block_height as u32,
-            proposed_block_height,
-            confirmation_block_height,
-            &block_hashes,
-            &chainworks,
-            Arc::clone(contract),
+            PublicInputsQuery {
+                swap_reservation_index: reservation_id,
+                bitcoin_tx_id: bitcoin_tx_id.into(),
+                merkle_root: FixedBytes(
+                    btc_final
+                        .blocks
+                        .index(
+                            ((proposed_block_height as u64) - (safe_block_height as u64)) as usize,
+                        )
+                        .header
+                        .merkle_root
+                        .to_byte_array()
+                        .to_little_endian(),
+                ),
+                safe_block_height: safe_block_height as u32,
+                proposed_block_height,
+                confirmation_block_height,
+                block_hashes: &block_hashes,
+                block_chainworks: &chainworks,
+                is_transaction_proof: true,
+            },
+            endpoints,
             &public_inputs_encoded,
-            true,
         )
         .await?;
 
-        let txn_calldata = contract
+        let txn_calldata = endpoints
+            .contract()
             .submitSwapProof(
                 reservation_id,
                 bitcoin_tx_id.into(),
@@ -263,28 +374,83 @@ impl ProofBroadcastQueue {
             .calldata()
             .to_owned();
 
-        Self::broadcast_transaction(
-            contract,
-            flashbots_provider,
-            &txn_calldata,
-            debug_url,
-            "submitSwapProof",
-        )
-        .await
+        // the max delay counts from the reservation, so a held proof still lands before it expires
+        let decision = profitability
+            .check(
+                "submitSwapProof",
+                &txn_calldata,
+                reservation_metadata.reservation.totalSwapOutputAmount,
+                reservation_metadata.reservation.reservationTimestamp,
+            )
+            .await?;
+        match decision {
+            ProfitabilityDecision::Submit => {}
+            ProfitabilityDecision::Skip => {
+                info!(
+                    "Skipping unprofitable proof for reservation: {}",
+                    reservation_id
+                );
+                return Ok(Outcome::Done);
+            }
+            ProfitabilityDecision::Delay => return Ok(Outcome::Held),
+        }
+
+        let tx_hash =
+            Self::broadcast_transaction(batcher, &txn_calldata, "submitSwapProof", true).await?;
+
+        store
+            .with_lock(|store| store.update_proof_tx_hash(reservation_id, tx_hash.0))
+            .await?;
+        Ok(Outcome::Done)
     }
 
     async fn process_block_proof(
-        safe_chainwork: U256,
-        safe_block_height: u64,
-        blocks: Vec<Block>,
-        _retarget_block: Block,
-        _retarget_block_height: u64,
-        solidity_proof: Vec<u8>,
-        public_inputs: Vec<u8>,
-        flashbots_provider: &Arc<Option<EvmHttpProvider>>,
-        contract: &Arc<RiftExchangeWebsocket>,
-        debug_url: &str,
+        block_proof: BlockProofBroadcast,
+        batcher: &Arc<CallBatcher>,
+        endpoints: &Arc<EvmEndpoints>,
     ) -> Result<()> {
+        let BlockProofBroadcast {
+            safe_chainwork,
+            safe_block_height,
+            blocks,
+            retarget_block: _,
+            retarget_block_height: _,
+            solidity_proof,
+            public_inputs,
+            is_aggregated,
+        } = block_proof;
+        let contract = endpoints.contract();
+        // an aggregated proof commits the public inputs of the whole range alongside the digest
+        // its chunks were verified against, which the contract pins
+        let public_inputs = if is_aggregated {
+            let (public_inputs, chunk_verification_key_digest) =
+                rift_lib::proof::decode_aggregated_block_proof_public_values(&public_inputs)
+                    .map_err(|e| {
+                        hyper_err!(
+                            ProofBroadcast,
+                            "Failed to decode aggregated public inputs: {}",
+                            e
+                        )
+                    })?;
+            let contract_verification_key_digest = contract
+                .circuitVerificationKeyDigest()
+                .call()
+                .await
+                .map_err(|e| hyper_err!(Evm, "Failed to call circuitVerificationKeyDigest: {}", e))?
+                ._0;
+            if contract_verification_key_digest != FixedBytes(chunk_verification_key_digest) {
+                return Err(hyper_err!(
+                    ProofBroadcast,
+                    "Chunk verification key digest {} does not match the contract's {}",
+                    FixedBytes(chunk_verification_key_digest),
+                    contract_verification_key_digest
+                ));
+            }
+            public_inputs
+        } else {
+            public_inputs
+        };
+
         let (block_hashes, chainworks) = Self::prepare_block_data(
             &blocks,
             safe_block_height,
@@ -301,39 +467,56 @@ impl ProofBroadcastQueue {
 
         // Validate public inputs
         Self::validate_public_inputs(
-            Uint::<256, 4>::ZERO, // Placeholder for swap_reservation_index (not used for block proofs)
-            FixedBytes::default(), // Placeholder for bitcoin_tx_id (not used for block proofs)
-            FixedBytes::default(), // Placeholder for merkle_root (not used for block proofs)
-            safe_block_height as u32,
-            proposed_block_height,
-            confirmation_block_height,
-            &block_hashes,
-            &chainworks,
-            Arc::clone(contract),
+            PublicInputsQuery {
+                swap_reservation_index: Uint::<256, 4>::ZERO,
+                bitcoin_tx_id: FixedBytes::default(),
+                merkle_root: FixedBytes::default(),
+                safe_block_height: safe_block_height as u32,
+                proposed_block_height,
+                confirmation_block_height,
+                block_hashes: &block_hashes,
+                block_chainworks: &chainworks,
+                is_transaction_proof: false,
+            },
+            endpoints,
             &public_inputs,
-            false,
         )
         .await?;
 
-        let txn_calldata = contract
-            .proveBlocks(
-                safe_block_height as u32,
-                confirmation_block_height,
-                block_hashes,
-                chainworks,
-                solidity_proof.into(),
+        let (txn_calldata, function_name) = if is_aggregated {
+            (
+                contract
+                    .proveBlocksAggregated(
+                        safe_block_height as u32,
+                        confirmation_block_height,
+                        block_hashes,
+                        chainworks,
+                        solidity_proof.into(),
+                    )
+                    .calldata()
+                    .to_owned(),
+                "proveBlocksAggregated",
             )
-            .calldata()
-            .to_owned();
+        } else {
+            (
+                contract
+                    .proveBlocks(
+                        safe_block_height as u32,
+                        confirmation_block_height,
+                        block_hashes,
+                        chainworks,
+                        solidity_proof.into(),
+                    )
+                    .calldata()
+                    .to_owned(),
+                "proveBlocks",
+            )
+        };
 
-        Self::broadcast_transaction(
-            contract,
-            flashbots_provider,
-            &txn_calldata,
-            debug_url,
-            "proveBlocks",
-        )
-        .await
+        // only approved hypernodes can submit swap proofs, so block proofs are sent from the
+        // hypernode's own address
+        Self::broadcast_transaction(batcher, &txn_calldata, function_name, false).await?;
+        Ok(())
     }
 
     fn prepare_block_data(
@@ -366,77 +549,82 @@ impl ProofBroadcastQueue {
         Ok((block_hashes, chainworks))
     }
 
+    // returns the hash of the transaction once it is confirmed
     async fn broadcast_transaction(
-        contract: &Arc<RiftExchangeWebsocket>,
-        flashbots_provider: &Arc<Option<EvmHttpProvider>>,
+        batcher: &Arc<CallBatcher>,
         txn_calldata: &[u8],
-        debug_url: &str,
-        function_name: &str,
-    ) -> Result<()> {
+        function_name: &'static str,
+        batched: bool,
+    ) -> Result<FixedBytes<32>> {
         debug!("{} calldata: {}", function_name, txn_calldata.as_hex());
 
-        let tx_hash = if let Some(flashbots_provider) = flashbots_provider.as_ref() {
-            evm_indexer::broadcast_transaction_via_flashbots(
-                contract,
-                flashbots_provider,
-                txn_calldata,
-            )
-            .await?
+        let result = if batched {
+            batcher.send(function_name, txn_calldata).await
         } else {
-            evm_indexer::broadcast_transaction(contract, txn_calldata, debug_url).await?
+            batcher.send_unbatched(function_name, txn_calldata).await
         };
-
-        info!(
-            "{} broadcasted with evm tx hash: {}",
-            function_name,
-            tx_hash.to_string()
-        );
-        Ok(())
+        counter!(BROADCASTS, "function" => function_name).increment(1);
+        result.inspect_err(|_| {
+            counter!(BROADCAST_FAILURES, "function" => function_name).increment(1);
+        })
     }
 
-    // validate that circuit generated public inputs match what the contract will generate
+    // validate that circuit generated public inputs match what the contract will generate, read
+    // from as many evm endpoints as the read quorum asks for
     async fn validate_public_inputs(
-        swap_reservation_index: Uint<256, 4>,
-        bitcoin_tx_id: FixedBytes<32>,
-        merkle_root: FixedBytes<32>,
-        safe_block_height: u32,
-        proposed_block_height: u64,
-        confirmation_block_height: u64,
-        block_hashes: &[FixedBytes<32>],
-        block_chainworks: &[Uint<256, 4>],
-        contract: Arc<RiftExchangeWebsocket>,
+        query: PublicInputsQuery<'_>,
+        endpoints: &EvmEndpoints,
         circuit_generated_public_inputs_encoded: &[u8],
-        is_transaction_proof: bool,
     ) -> Result<()> {
+        let PublicInputsQuery {
+            swap_reservation_index,
+            bitcoin_tx_id,
+            merkle_root,
+            safe_block_height,
+            proposed_block_height,
+            confirmation_block_height,
+            block_hashes,
+            block_chainworks,
+            is_transaction_proof,
+        } = query;
+        let contract = endpoints.contract();
         // call the buildPublicInputs function in the contract
         let contract_generated_public_inputs_decoded = if is_transaction_proof {
-            contract
-                .buildPublicInputs(
-                    swap_reservation_index,
-                    bitcoin_tx_id,
-                    merkle_root,
-                    safe_block_height,
-                    proposed_block_height,
-                    confirmation_block_height,
-                    block_hashes.to_vec(),
-                    block_chainworks.to_vec(),
-                    is_transaction_proof,
-                )
-                .call()
-                .await
-                .map_err(|e| hyper_err!(Evm, "Failed to call buildPublicInputs: {}", e))?
+            let call = contract.buildPublicInputs(
+                swap_reservation_index,
+                bitcoin_tx_id,
+                merkle_root,
+                safe_block_height,
+                proposed_block_height,
+                confirmation_block_height,
+                block_hashes.to_vec(),
+                block_chainworks.to_vec(),
+                is_transaction_proof,
+            );
+            let output = endpoints
+                .quorum_call("buildPublicInputs", call.as_ref().clone())
+                .await?;
+            call.decode_output(output, false)
+                .map_err(|e| hyper_err!(Decode, "Failed to decode buildPublicInputs: {}", e))?
                 ._0
         } else {
-            contract
-                .buildBlockProofPublicInputs(
-                    safe_block_height,
-                    confirmation_block_height,
-                    block_hashes.to_vec(),
-                    block_chainworks.to_vec(),
-                )
-                .call()
-                .await
-                .map_err(|e| hyper_err!(Evm, "Failed to call buildBlockProofPublicInputs: {}", e))?
+            let call = contract.buildBlockProofPublicInputs(
+                safe_block_height,
+                confirmation_block_height,
+                block_hashes.to_vec(),
+                block_chainworks.to_vec(),
+            );
+            let output = endpoints
+                .quorum_call("buildBlockProofPublicInputs", call.as_ref().clone())
+                .await?;
+            call.decode_output(output, false)
+                .map_err(|e| {
+                    hyper_err!(
+                        Decode,
+                        "Failed to decode buildBlockProofPublicInputs: {}",
+                        e
+                    )
+                })?
                 ._0
         };
 
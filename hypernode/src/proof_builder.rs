@@ -1,17 +1,25 @@
 use alloy::primitives::U256;
+use bitcoin::block::Header;
+use bitcoin::hashes::Hash;
 use bitcoin::Block;
-use log::{error, info};
+use log::{error, info, warn};
 use rift_core::btc_light_client::AsLittleEndianBytes;
-use rift_core::lp::LiquidityReservation;
+use rift_core::chain_params::ChainParams;
 use rift_lib;
-use std::sync::Arc;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, Semaphore};
 
 use crate::core::ThreadSafeStore;
 use crate::error::HypernodeError;
 use crate::proof_broadcast::{self, ProofBroadcastQueue};
+use crate::prover::{decode_public_values, ProofRequest, Prover};
+use crate::retry::{DeadLetters, RetryPolicy};
+use crate::telemetry::{JOB_RETRIES, PROOF_CYCLES, PROOF_DURATION, PROOF_GENERATION_QUEUE_DEPTH};
 use crate::{hyper_err, Result};
 use crypto_bigint::U256 as SP1OptimizedU256;
+use metrics::{counter, gauge, histogram};
 
 pub fn buffer_to_18_decimals(amount: U256, token_decimals: u8) -> U256 {
     if token_decimals < 18 {
@@ -39,16 +47,20 @@ pub fn sats_to_wei(sats_amount: U256, wei_sats_exchange_rate: U256) -> U256 {
 
 #[derive(Debug, Clone)]
 pub enum ProofGenerationInput {
-    Reservation {
-        reservation_id: U256,
-    },
-    BlockProof {
-        safe_chainwork: U256,
-        safe_block_height: u64,
-        blocks: Vec<Block>,
-        retarget_block: Block,
-        retarget_block_height: u64,
-    },
+    Reservation { reservation_id: U256 },
+    BlockProof(BlockProofInput),
+}
+
+// the blocks a block proof extends the contract's light client with
+#[derive(Debug, Clone)]
+pub struct BlockProofInput {
+    pub safe_chainwork: U256,
+    pub safe_block_height: u64,
+    pub blocks: Vec<Block>,
+    pub retarget_block: Block,
+    pub retarget_block_height: u64,
+    // headers preceding the safe block, needed for the median time past check
+    pub ancestor_headers: Vec<Header>,
 }
 
 impl ProofGenerationInput {
@@ -56,65 +68,176 @@ impl ProofGenerationInput {
         ProofGenerationInput::Reservation { reservation_id }
     }
 
-    pub fn new_block_proof(
-        safe_chainwork: U256,
-        safe_block_height: u64,
-        blocks: Vec<Block>,
-        retarget_block: Block,
-        retarget_block_height: u64,
-    ) -> Self {
-        ProofGenerationInput::BlockProof {
-            safe_chainwork,
-            safe_block_height,
-            blocks,
-            retarget_block,
-            retarget_block_height,
+    pub fn new_block_proof(block_proof: BlockProofInput) -> Self {
+        ProofGenerationInput::BlockProof(block_proof)
+    }
+
+    // block proofs share a single key, so a newer block proof (or a reorg) supersedes older ones
+    fn job_key(&self) -> Option<U256> {
+        match self {
+            ProofGenerationInput::Reservation { reservation_id } => Some(*reservation_id),
+            ProofGenerationInput::BlockProof(_) => None,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofJobStatus {
+    Queued,
+    Proving,
+}
+
+// Every queued job is tagged with a generation, cancelling (or re-queueing) a job bumps the
+// generation for its key so stale jobs are skipped when they come up, and stale results are
+// discarded if the job was already running. The status of the current job, if any, is kept
+// alongside its generation
+#[derive(Default)]
+struct JobGenerations(Mutex<HashMap<Option<U256>, JobGeneration>>);
+
+// the current generation for a job key and the status of its job
+type JobGeneration = (u64, Option<ProofJobStatus>);
+
+impl JobGenerations {
+    fn bump(&self, key: Option<U256>, status: Option<ProofJobStatus>) -> u64 {
+        let mut generations = self.0.lock().unwrap();
+        let job = generations.entry(key).or_insert((0, None));
+        job.0 += 1;
+        job.1 = status;
+        job.0
+    }
+
+    fn is_current(&self, key: Option<U256>, generation: u64) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&key)
+            .is_some_and(|job| job.0 == generation)
+    }
+
+    // only the current job's status is tracked, stale jobs are ignored
+    fn set_status(&self, key: Option<U256>, generation: u64, status: Option<ProofJobStatus>) {
+        if let Some(job) = self.0.lock().unwrap().get_mut(&key) {
+            if job.0 == generation {
+                job.1 = status;
+            }
+        }
+    }
+
+    fn statuses(&self) -> Vec<(Option<U256>, ProofJobStatus)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(key, job)| job.1.map(|status| (*key, status)))
+            .collect()
+    }
+}
+
+// a queued job with its generation and the number of attempts already made
+type QueuedJob = (ProofGenerationInput, u64, u32);
+
+// what every proof generation job runs against
+#[derive(Clone)]
+struct GenerationContext {
+    generations: Arc<JobGenerations>,
+    store: Arc<ThreadSafeStore>,
+    proof_broadcast_queue: Arc<ProofBroadcastQueue>,
+    prover: Arc<dyn Prover>,
+    chain_params: ChainParams,
+}
+
 pub struct ProofGenerationQueue {
-    sender: mpsc::UnboundedSender<ProofGenerationInput>,
+    sender: mpsc::UnboundedSender<QueuedJob>,
+    generations: Arc<JobGenerations>,
 }
 
 impl ProofGenerationQueue {
     pub fn new(
         store: Arc<ThreadSafeStore>,
         proof_broadcast_queue: Arc<ProofBroadcastQueue>,
-        mock_proof_gen: bool,
+        prover: Arc<dyn Prover>,
         concurrency_limit: usize,
+        chain_params: ChainParams,
+        retry_policy: RetryPolicy,
+        dead_letters: Arc<DeadLetters>,
     ) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
+        let generations = Arc::new(JobGenerations::default());
 
-        let queue = ProofGenerationQueue { sender };
+        let queue = ProofGenerationQueue {
+            sender: sender.clone(),
+            generations: Arc::clone(&generations),
+        };
 
         tokio::spawn(ProofGenerationQueue::consume_task(
             receiver,
-            store,
-            proof_broadcast_queue,
-            mock_proof_gen,
+            sender,
+            GenerationContext {
+                generations,
+                store,
+                proof_broadcast_queue,
+                prover,
+                chain_params,
+            },
             concurrency_limit,
+            retry_policy,
+            dead_letters,
         ));
 
         queue
     }
 
     pub fn add(&self, proof_args: ProofGenerationInput) -> Result<()> {
-        self.sender
-            .send(proof_args)
-            .map_err(|e| hyper_err!(Queue, "Failed to add to proof generation queue: {}", e))
+        let generation = self
+            .generations
+            .bump(proof_args.job_key(), Some(ProofJobStatus::Queued));
+        Self::enqueue(&self.sender, (proof_args, generation, 0))
+    }
+
+    fn enqueue(sender: &mpsc::UnboundedSender<QueuedJob>, job: QueuedJob) -> Result<()> {
+        sender
+            .send(job)
+            .map_err(|e| hyper_err!(Queue, "Failed to add to proof generation queue: {}", e))?;
+        gauge!(PROOF_GENERATION_QUEUE_DEPTH).increment(1.0);
+        Ok(())
+    }
+
+    // cancels any queued or in progress proof for the reservation
+    pub fn cancel_reservation(&self, reservation_id: U256) {
+        self.generations.bump(Some(reservation_id), None);
+    }
+
+    // cancels all queued or in progress block proofs
+    pub fn cancel_block_proofs(&self) {
+        self.generations.bump(None, None);
+    }
+
+    // queued and in progress jobs, keyed by reservation id, block proofs have no key
+    pub fn jobs(&self) -> Vec<(Option<U256>, ProofJobStatus)> {
+        self.generations.statuses()
     }
 
     async fn consume_task(
-        mut receiver: mpsc::UnboundedReceiver<ProofGenerationInput>,
-        store: Arc<ThreadSafeStore>,
-        proof_broadcast_queue: Arc<ProofBroadcastQueue>,
-        mock_proof_gen: bool,
+        mut receiver: mpsc::UnboundedReceiver<QueuedJob>,
+        sender: mpsc::UnboundedSender<QueuedJob>,
+        context: GenerationContext,
         concurrency_limit: usize,
+        retry_policy: RetryPolicy,
+        dead_letters: Arc<DeadLetters>,
     ) {
+        let generations = Arc::clone(&context.generations);
         let semaphore = Arc::new(Semaphore::new(concurrency_limit));
 
-        while let Some(item) = receiver.recv().await {
+        while let Some((item, generation, attempts)) = receiver.recv().await {
+            gauge!(PROOF_GENERATION_QUEUE_DEPTH).decrement(1.0);
+            if !generations.is_current(item.job_key(), generation) {
+                info!(
+                    "Skipping cancelled proof generation job: {:?}",
+                    item.job_key()
+                );
+                continue;
+            }
             let permit = match semaphore.clone().acquire_owned().await {
                 Ok(permit) => permit,
                 Err(e) => {
@@ -122,76 +245,81 @@ impl ProofGenerationQueue {
                     continue;
                 }
             };
-            let store_clone = store.clone();
-            let proof_broadcast_queue_clone = proof_broadcast_queue.clone();
-            let mock_proof_gen_clone = mock_proof_gen;
-            let item_clone = item.clone();
+            generations.set_status(item.job_key(), generation, Some(ProofJobStatus::Proving));
+            let context = context.clone();
+            let generations_clone = generations.clone();
+            let sender_clone = sender.clone();
+            let dead_letters_clone = dead_letters.clone();
 
             tokio::spawn(async move {
-                if let Err(e) = Self::process_item(
-                    item_clone,
-                    store_clone,
-                    proof_broadcast_queue_clone,
-                    mock_proof_gen_clone,
-                )
-                .await
-                {
-                    error!("Error processing proof generation item: {}", e);
-                }
+                let job_key = item.job_key();
+                let result = Self::process_item(item.clone(), generation, &context).await;
                 drop(permit);
+
+                let e = match result {
+                    Ok(()) => {
+                        generations_clone.set_status(job_key, generation, None);
+                        return;
+                    }
+                    Err(e) => e,
+                };
+                let attempts = attempts + 1;
+                if !generations_clone.is_current(job_key, generation) {
+                    info!("Cancelled proof generation job {:?} failed: {}", job_key, e);
+                } else if retry_policy.should_retry(&e, attempts) {
+                    let delay = retry_policy.delay(attempts);
+                    warn!(
+                        "Proof generation job {:?} failed on attempt {}, retrying in {:?}: {}",
+                        job_key, attempts, delay, e
+                    );
+                    counter!(JOB_RETRIES, "queue" => "proof_generation").increment(1);
+                    generations_clone.set_status(job_key, generation, Some(ProofJobStatus::Queued));
+                    // keeps the generation, so cancelling the job also cancels the retry
+                    tokio::time::sleep(delay).await;
+                    if let Err(e) = Self::enqueue(&sender_clone, (item, generation, attempts)) {
+                        error!("Failed to retry proof generation job: {}", e);
+                    }
+                } else {
+                    error!(
+                        "Proof generation job {:?} failed after {} attempts: {}",
+                        job_key, attempts, e
+                    );
+                    generations_clone.set_status(job_key, generation, None);
+                    dead_letters_clone
+                        .push("proof_generation", job_key, attempts, &e)
+                        .await;
+                }
             });
         }
     }
 
     async fn process_item(
         item: ProofGenerationInput,
-        store: Arc<ThreadSafeStore>,
-        proof_broadcast_queue: Arc<ProofBroadcastQueue>,
-        mock_proof_gen: bool,
+        generation: u64,
+        context: &GenerationContext,
     ) -> Result<()> {
         match item {
             ProofGenerationInput::Reservation { reservation_id } => {
-                Self::process_reservation(
-                    reservation_id,
-                    mock_proof_gen,
-                    store,
-                    proof_broadcast_queue,
-                )
-                .await
+                Self::process_reservation(reservation_id, generation, context).await
             }
-            ProofGenerationInput::BlockProof {
-                safe_chainwork,
-                safe_block_height,
-                blocks,
-                retarget_block,
-                retarget_block_height,
-            } => {
-                Self::process_block_proof(
-                    safe_chainwork,
-                    safe_block_height,
-                    blocks,
-                    retarget_block,
-                    retarget_block_height,
-                    mock_proof_gen,
-                    proof_broadcast_queue,
-                )
-                .await
+            ProofGenerationInput::BlockProof(block_proof) => {
+                Self::process_block_proof(block_proof, generation, context).await
             }
         }
     }
 
     async fn process_reservation(
         reservation_id: U256,
-        mock_proof_gen: bool,
-        store: Arc<ThreadSafeStore>,
-        proof_broadcast_queue: Arc<ProofBroadcastQueue>,
+        generation: u64,
+        context: &GenerationContext,
     ) -> Result<()> {
-        let reservation_metadata = store
+        let reservation_metadata = context
+            .store
             .with_lock(|store| store.get(reservation_id).cloned())
             .await
             .ok_or_else(|| hyper_err!(Store, "Reservation not found: {}", reservation_id))?;
 
-        let order_nonce = reservation_metadata
+        let order_nonce: &[u8; 32] = reservation_metadata
             .reservation
             .nonce
             .0
@@ -200,16 +328,7 @@ impl ProofGenerationQueue {
             .and_then(|slice| slice.try_into().ok())
             .ok_or_else(|| hyper_err!(ProofGeneration, "Invalid order nonce"))?;
 
-        let reserved_vaults = reservation_metadata.reserved_vaults;
-        let expected_sats_per_lp = reservation_metadata.reservation.expectedSatsOutput;
-        let liquidity_reservations = reserved_vaults
-            .iter()
-            .zip(expected_sats_per_lp.iter())
-            .map(|(vault, sats)| LiquidityReservation {
-                expected_sats: *sats,
-                script_pub_key: *vault.btcPayoutLockingScript,
-            })
-            .collect::<Vec<_>>();
+        let liquidity_reservations = reservation_metadata.liquidity_reservations();
 
         let btc_final = reservation_metadata
             .btc_final
@@ -223,6 +342,23 @@ impl ProofGenerationQueue {
         let proposed_block_index = btc_initial.proposed_block_height - btc_final.safe_block_height;
         let retarget_block = btc_final.retarget_block;
 
+        // payments recorded before they were validated on discovery would only fail in the circuit
+        let proposed_transaction = blocks
+            .get(proposed_block_index as usize)
+            .and_then(|block| {
+                block
+                    .txdata
+                    .iter()
+                    .find(|tx| *tx.compute_txid().as_byte_array() == proposed_txid)
+            })
+            .ok_or_else(|| hyper_err!(ProofGeneration, "Payment not found in its block"))?;
+        rift_lib::payment::validate_payment(
+            proposed_transaction,
+            &liquidity_reservations,
+            *order_nonce,
+        )
+        .map_err(|e| hyper_err!(ProofGeneration, "Payment can't be proven: {}", e))?;
+
         let circuit_input = rift_lib::proof::build_transaction_proof_input(
             order_nonce,
             &liquidity_reservations,
@@ -233,114 +369,200 @@ impl ProofGenerationQueue {
             &proposed_txid.to_little_endian(),
             &retarget_block,
             btc_final.retarget_block_height,
+            &btc_final.ancestor_headers,
+            &context.chain_params,
         );
 
         let proof_gen_timer = std::time::Instant::now();
-        let result = tokio::task::spawn_blocking(move || {
-            let (public_values_string, execution_report) = rift_lib::proof::execute(circuit_input);
-            info!(
-                "Reservation {} executed with {} cycles",
-                reservation_id,
-                execution_report.total_instruction_count()
-            );
-            if mock_proof_gen {
-                (Vec::new(), public_values_string)
-            } else {
-                let proof = rift_lib::proof::generate_plonk_proof(circuit_input, Some(true));
-                let solidity_proof_bytes = proof.bytes();
-                (solidity_proof_bytes, public_values_string)
+        // executing first reports the cycle count and catches invalid inputs before proving
+        let executed_public_inputs = tokio::task::spawn_blocking({
+            let circuit_input = circuit_input.clone();
+            move || {
+                let (public_values_string, execution_report) =
+                    rift_lib::proof::execute(circuit_input);
+                info!(
+                    "Reservation {} executed with {} cycles",
+                    reservation_id,
+                    execution_report.total_instruction_count()
+                );
+                histogram!(PROOF_CYCLES, "kind" => "reservation")
+                    .record(execution_report.total_instruction_count() as f64);
+                decode_public_values(&public_values_string)
             }
         })
         .await
-        .map_err(|e| hyper_err!(ProofGeneration, "Proof generation task panicked: {}", e))?;
+        .map_err(|e| hyper_err!(ProofGeneration, "Proof execution task panicked: {}", e))??;
+
+        let proof = context
+            .prover
+            .prove(ProofRequest::Rift(Box::new(circuit_input)))
+            .await?;
+        check_public_values(&executed_public_inputs, &proof.public_values)?;
 
-        let (solidity_proof_bytes, public_values_string) = result;
         info!(
             "Proof generation for reservation_id: {:?} took: {:?}",
             reservation_id,
             proof_gen_timer.elapsed()
         );
+        histogram!(PROOF_DURATION, "kind" => "reservation")
+            .record(proof_gen_timer.elapsed().as_secs_f64());
 
-        info!("Public Inputs Encoded: {:?}", public_values_string);
+        info!(
+            "Public Inputs Encoded: 0x{}",
+            hex::encode(&proof.public_values)
+        );
 
-        let public_inputs = hex::decode(public_values_string.clone().trim_start_matches("0x"))
-            .map_err(|e| hyper_err!(ProofGeneration, "Failed to decode public inputs: {}", e))?;
+        if !context
+            .generations
+            .is_current(Some(reservation_id), generation)
+        {
+            info!(
+                "Discarding proof for reservation_id: {:?}, it was cancelled during generation",
+                reservation_id
+            );
+            return Ok(());
+        }
 
-        store
+        context
+            .store
             .with_lock(|store| {
-                store.update_proof_data(reservation_id, solidity_proof_bytes, public_inputs)
+                store.update_proof_data(reservation_id, proof.solidity_proof, proof.public_values)
             })
-            .await;
+            .await?;
 
-        proof_broadcast_queue.add(proof_broadcast::ProofBroadcastInput::new_reservation(
-            reservation_id,
-        ))?;
+        context.proof_broadcast_queue.add(
+            proof_broadcast::ProofBroadcastInput::new_reservation(reservation_id),
+        )?;
 
         info!("Finished processing reservation_id: {:?}", reservation_id);
         Ok(())
     }
 
     async fn process_block_proof(
-        safe_chainwork: U256,
-        safe_block_height: u64,
-        blocks: Vec<Block>,
-        retarget_block: Block,
-        retarget_block_height: u64,
-        mock_proof_gen: bool,
-        proof_broadcast_queue: Arc<ProofBroadcastQueue>,
+        block_proof: BlockProofInput,
+        generation: u64,
+        context: &GenerationContext,
     ) -> Result<()> {
-        let circuit_input = rift_lib::proof::build_block_proof_input(
-            SP1OptimizedU256::from_be_slice(&safe_chainwork.to_be_bytes::<32>()),
-            safe_block_height,
-            &blocks,
-            &retarget_block,
-            retarget_block_height,
+        // ranges longer than a single proof allows are proven in chunks and aggregated
+        let mut chunk_inputs = rift_lib::proof::build_block_proof_chunk_inputs(
+            SP1OptimizedU256::from_be_slice(&block_proof.safe_chainwork.to_be_bytes::<32>()),
+            block_proof.safe_block_height,
+            &block_proof.blocks,
+            &block_proof.retarget_block,
+            block_proof.retarget_block_height,
+            &block_proof.ancestor_headers,
+            &context.chain_params,
         );
+        let is_aggregated = chunk_inputs.len() > 1;
 
         let proof_gen_timer = std::time::Instant::now();
-        let result = tokio::task::spawn_blocking(move || {
-            let (public_values_string, execution_report) = rift_lib::proof::execute(circuit_input);
-            info!(
-                "Block proof executed with {} cycles",
-                execution_report.total_instruction_count()
-            );
-            if mock_proof_gen {
-                (Vec::new(), public_values_string)
-            } else {
-                let proof = rift_lib::proof::generate_plonk_proof(circuit_input, Some(true));
-                let solidity_proof_bytes = proof.bytes();
-                (solidity_proof_bytes, public_values_string)
+        let chunk_public_inputs = tokio::task::spawn_blocking({
+            let chunk_inputs = chunk_inputs.clone();
+            move || {
+                chunk_inputs
+                    .into_iter()
+                    .map(|chunk_input| {
+                        let (public_values_string, execution_report) =
+                            rift_lib::proof::execute(chunk_input);
+                        info!(
+                            "Block proof executed with {} cycles",
+                            execution_report.total_instruction_count()
+                        );
+                        histogram!(PROOF_CYCLES, "kind" => "block")
+                            .record(execution_report.total_instruction_count() as f64);
+                        decode_public_values(&public_values_string)
+                    })
+                    .collect::<Result<Vec<_>>>()
             }
         })
         .await
         .map_err(|e| {
             hyper_err!(
                 ProofGeneration,
-                "Block proof generation task panicked: {}",
+                "Block proof execution task panicked: {}",
                 e
             )
-        })?;
+        })??;
+
+        let proof = if is_aggregated {
+            info!(
+                "Aggregating block proofs of {} chunks",
+                chunk_public_inputs.len()
+            );
+            let proof = context
+                .prover
+                .prove(ProofRequest::AggregatedBlockProof(chunk_inputs))
+                .await?;
+            let (_, chunk_verification_key_digest) =
+                rift_lib::proof::decode_aggregated_block_proof_public_values(&proof.public_values)
+                    .map_err(|e| {
+                        hyper_err!(
+                            ProofGeneration,
+                            "Failed to decode aggregated public inputs: {}",
+                            e
+                        )
+                    })?;
+            check_public_values(
+                &rift_lib::proof::build_aggregated_block_proof_public_values(
+                    &chunk_public_inputs,
+                    chunk_verification_key_digest,
+                ),
+                &proof.public_values,
+            )?;
+            proof
+        } else {
+            let proof = context
+                .prover
+                .prove(ProofRequest::Rift(Box::new(chunk_inputs.remove(0))))
+                .await?;
+            check_public_values(&chunk_public_inputs[0], &proof.public_values)?;
+            proof
+        };
 
-        let (solidity_proof_bytes, public_values_string) = result;
         info!(
             "Block proof generation took: {:?}",
             proof_gen_timer.elapsed()
         );
+        histogram!(PROOF_DURATION, "kind" => "block")
+            .record(proof_gen_timer.elapsed().as_secs_f64());
 
-        let public_inputs = hex::decode(public_values_string.clone().trim_start_matches("0x"))
-            .map_err(|e| hyper_err!(ProofGeneration, "Failed to decode public inputs: {}", e))?;
+        info!(
+            "Public Inputs Encoded: 0x{}",
+            hex::encode(&proof.public_values)
+        );
 
-        info!("Public Inputs Encoded: {:?}", public_values_string);
+        if !context.generations.is_current(None, generation) {
+            info!("Discarding block proof, it was cancelled during generation");
+            return Ok(());
+        }
 
-        proof_broadcast_queue.add(proof_broadcast::ProofBroadcastInput::new_block_proof(
-            safe_chainwork,
-            safe_block_height,
-            blocks,
-            retarget_block,
-            retarget_block_height,
-            solidity_proof_bytes,
-            public_inputs,
-        ))?;
+        context.proof_broadcast_queue.add(
+            proof_broadcast::ProofBroadcastInput::new_block_proof(
+                proof_broadcast::BlockProofBroadcast {
+                    safe_chainwork: block_proof.safe_chainwork,
+                    safe_block_height: block_proof.safe_block_height,
+                    blocks: block_proof.blocks,
+                    retarget_block: block_proof.retarget_block,
+                    retarget_block_height: block_proof.retarget_block_height,
+                    solidity_proof: proof.solidity_proof,
+                    public_inputs: proof.public_values,
+                    is_aggregated,
+                },
+            ),
+        )?;
         Ok(())
     }
 }
+
+// the prover may be a remote worker, so what it proved is checked against the local execution
+fn check_public_values(executed_public_inputs: &[u8], proven_public_inputs: &[u8]) -> Result<()> {
+    if executed_public_inputs != proven_public_inputs {
+        return Err(hyper_err!(
+            ProofGeneration,
+            "Proven public inputs 0x{} do not match the executed public inputs 0x{}",
+            hex::encode(proven_public_inputs),
+            hex::encode(executed_public_inputs)
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// Backends that turn circuit inputs into proofs the contract can verify
+use crate::error::HypernodeError;
+use crate::{hyper_err, Result};
+use async_trait::async_trait;
+use log::info;
+use rift_core::CircuitInput;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ProofRequest {
+    // a rift transaction or a block proof that fits in a single proof
+    Rift(Box<CircuitInput>),
+    // chunked block proofs that are proven individually and then aggregated
+    AggregatedBlockProof(Vec<CircuitInput>),
+}
+
+impl ProofRequest {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProofRequest::Rift(_) => "rift",
+            ProofRequest::AggregatedBlockProof(_) => "aggregated_block_proof",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Proof {
+    pub solidity_proof: Vec<u8>,
+    pub public_values: Vec<u8>,
+}
+
+#[async_trait]
+pub trait Prover: Send + Sync {
+    async fn prove(&self, request: ProofRequest) -> Result<Proof>;
+}
+
+// Proves with the SP1 prover on this host
+pub struct LocalProver;
+
+#[async_trait]
+impl Prover for LocalProver {
+    async fn prove(&self, request: ProofRequest) -> Result<Proof> {
+        tokio::task::spawn_blocking(move || {
+            let proof = match request {
+                ProofRequest::Rift(circuit_input) => {
+                    rift_lib::proof::generate_plonk_proof(*circuit_input, Some(true))
+                }
+                ProofRequest::AggregatedBlockProof(chunk_inputs) => {
+                    rift_lib::proof::generate_aggregated_block_proof(chunk_inputs, Some(true))
+                }
+            };
+            Proof {
+                solidity_proof: proof.bytes(),
+                public_values: proof.public_values.to_vec(),
+            }
+        })
+        .await
+        .map_err(|e| hyper_err!(ProofGeneration, "Proof generation task panicked: {}", e))
+    }
+}
+
+// Executes the program without proving and returns an empty proof, only useful against a contract
+// deployed with a mock verifier
+pub struct MockProver;
+
+#[async_trait]
+impl Prover for MockProver {
+    async fn prove(&self, request: ProofRequest) -> Result<Proof> {
+        tokio::task::spawn_blocking(move || -> Result<Proof> {
+            let public_values = match request {
+                ProofRequest::Rift(circuit_input) => {
+                    decode_public_values(&rift_lib::proof::execute(*circuit_input).0)?
+                }
+                ProofRequest::AggregatedBlockProof(chunk_inputs) => {
+                    let chunk_public_values = chunk_inputs
+                        .into_iter()
+                        .map(|chunk_input| {
+                            decode_public_values(&rift_lib::proof::execute(chunk_input).0)
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    rift_lib::proof::build_aggregated_block_proof_public_values(
+                        &chunk_public_values,
+                        rift_lib::proof::compute_circuit_vkey_digest(),
+                    )
+                }
+            };
+            info!("Mock proof generated");
+            Ok(Proof {
+                solidity_proof: Vec::new(),
+                public_values,
+            })
+        })
+        .await
+        .map_err(|e| hyper_err!(ProofGeneration, "Mock proof task panicked: {}", e))?
+    }
+}
+
+pub fn decode_public_values(public_values_string: &str) -> Result<Vec<u8>> {
+    hex::decode(public_values_string.trim_start_matches("0x"))
+        .map_err(|e| hyper_err!(ProofGeneration, "Failed to decode public inputs: {}", e))
+}
@@ -0,0 +1,708 @@
+// Hands proof requests to a pool of prover workers that pull jobs over http:
+//   POST /jobs/lease               -> 200 with a LeasedJob, or 204 if there is nothing to prove
+//   POST /leases/{id}/heartbeat    -> extends the lease, 410 if it was lost
+//   POST /leases/{id}/result       -> completes the job with a WorkerResult, 410 if the lease was lost
+// Every request carries the shared token as a bearer token, 401 otherwise. A job whose lease
+// expires, whose worker reports a failure or whose proof doesn't verify is offered again until it
+// has been leased max_attempts times
+use crate::core::ISP1Verifier;
+use crate::error::HypernodeError;
+use crate::evm_endpoints::EvmEndpoints;
+use crate::prover::{Proof, ProofRequest, Prover};
+use crate::{hyper_err, Result};
+use alloy::transports::RpcError;
+use async_trait::async_trait;
+use axum::extract::{Path, Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use backoff::ExponentialBackoff;
+use log::{error, info, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+#[derive(Serialize, Deserialize)]
+pub struct LeasedJob {
+    pub lease_id: u64,
+    // the worker has to heartbeat or submit a result within this long to keep the job
+    pub lease_duration_secs: u64,
+    pub request: ProofRequest,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum WorkerResult {
+    Proved(Proof),
+    Failed(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteProverConfig {
+    pub lease_duration: Duration,
+    pub job_timeout: Duration,
+    pub max_attempts: u32,
+}
+
+struct Lease {
+    id: u64,
+    expires_at: Instant,
+}
+
+struct Job {
+    request: ProofRequest,
+    attempts: u32,
+    lease: Option<Lease>,
+    responder: oneshot::Sender<Result<Proof>>,
+}
+
+// jobs are keyed by submission order so the oldest job is leased first
+#[derive(Default)]
+struct JobBoard {
+    next_job_id: u64,
+    next_lease_id: u64,
+    jobs: BTreeMap<u64, Job>,
+}
+
+impl JobBoard {
+    fn submit(&mut self, request: ProofRequest) -> (u64, oneshot::Receiver<Result<Proof>>) {
+        let (responder, receiver) = oneshot::channel();
+        self.next_job_id += 1;
+        self.jobs.insert(
+            self.next_job_id,
+            Job {
+                request,
+                attempts: 0,
+                lease: None,
+                responder,
+            },
+        );
+        (self.next_job_id, receiver)
+    }
+
+    fn lease(&mut self, now: Instant, config: &RemoteProverConfig) -> Option<LeasedJob> {
+        self.expire_leases(now, config);
+        let (_, job) = self.jobs.iter_mut().find(|(_, job)| job.lease.is_none())?;
+        self.next_lease_id += 1;
+        job.attempts += 1;
+        job.lease = Some(Lease {
+            id: self.next_lease_id,
+            expires_at: now + config.lease_duration,
+        });
+        Some(LeasedJob {
+            lease_id: self.next_lease_id,
+            lease_duration_secs: config.lease_duration.as_secs(),
+            request: job.request.clone(),
+        })
+    }
+
+    fn find_lease(&self, lease_id: u64) -> Option<u64> {
+        self.jobs
+            .iter()
+            .find(|(_, job)| job.lease.as_ref().is_some_and(|lease| lease.id == lease_id))
+            .map(|(job_id, _)| *job_id)
+    }
+
+    fn heartbeat(&mut self, lease_id: u64, now: Instant, config: &RemoteProverConfig) -> bool {
+        self.expire_leases(now, config);
+        let Some(job_id) = self.find_lease(lease_id) else {
+            return false;
+        };
+        if let Some(lease) = self
+            .jobs
+            .get_mut(&job_id)
+            .and_then(|job| job.lease.as_mut())
+        {
+            lease.expires_at = now + config.lease_duration;
+        }
+        true
+    }
+
+    fn complete(
+        &mut self,
+        lease_id: u64,
+        result: WorkerResult,
+        now: Instant,
+        config: &RemoteProverConfig,
+    ) -> bool {
+        self.expire_leases(now, config);
+        let Some(job_id) = self.find_lease(lease_id) else {
+            return false;
+        };
+        match result {
+            WorkerResult::Proved(proof) => {
+                if let Some(job) = self.jobs.remove(&job_id) {
+                    info!("Proof job {} proved by lease {}", job_id, lease_id);
+                    let _ = job.responder.send(Ok(proof));
+                }
+            }
+            WorkerResult::Failed(reason) => self.retry(job_id, &reason, config),
+        }
+        true
+    }
+
+    fn expire_leases(&mut self, now: Instant, config: &RemoteProverConfig) {
+        let expired_job_ids: Vec<u64> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| {
+                job.lease
+                    .as_ref()
+                    .is_some_and(|lease| lease.expires_at <= now)
+            })
+            .map(|(job_id, _)| *job_id)
+            .collect();
+        for job_id in expired_job_ids {
+            self.retry(job_id, "lease expired", config);
+        }
+    }
+
+    // offers the job to another worker, or fails it once it's out of attempts
+    fn retry(&mut self, job_id: u64, reason: &str, config: &RemoteProverConfig) {
+        let Some(job) = self.jobs.get_mut(&job_id) else {
+            return;
+        };
+        job.lease = None;
+        if job.attempts < config.max_attempts {
+            warn!(
+                "Proof job {} attempt {} failed: {}, retrying",
+                job_id, job.attempts, reason
+            );
+            return;
+        }
+        if let Some(job) = self.jobs.remove(&job_id) {
+            error!(
+                "Proof job {} failed after {} attempts: {}",
+                job_id, job.attempts, reason
+            );
+            let _ = job.responder.send(Err(hyper_err!(
+                ProofGeneration,
+                "Proof job failed after {} attempts: {}",
+                job.attempts,
+                reason
+            )));
+        }
+    }
+}
+
+// Checks a worker's proof before it is accepted
+#[async_trait]
+pub trait ProofVerifier: Send + Sync {
+    // false if the proof doesn't verify, an error if it couldn't be checked
+    async fn verify(&self, proof: &Proof) -> Result<bool>;
+}
+
+// Verifies proofs with the exchange's SP1 verifier contract and the circuit verification key the
+// exchange pins, the same check the proof has to pass once it is broadcast
+pub struct ExchangeProofVerifier {
+    endpoints: Arc<EvmEndpoints>,
+}
+
+impl ExchangeProofVerifier {
+    pub fn new(endpoints: Arc<EvmEndpoints>) -> Self {
+        ExchangeProofVerifier { endpoints }
+    }
+}
+
+#[async_trait]
+impl ProofVerifier for ExchangeProofVerifier {
+    async fn verify(&self, proof: &Proof) -> Result<bool> {
+        self.endpoints
+            .call("verifyProof", |contract| async move {
+                let verification_key = contract
+                    .circuitVerificationKey()
+                    .call()
+                    .await
+                    .map_err(|e| hyper_err!(Evm, "Failed to call circuitVerificationKey: {}", e))?
+                    ._0;
+                let verifier_address = contract
+                    .verifierContract()
+                    .call()
+                    .await
+                    .map_err(|e| hyper_err!(Evm, "Failed to call verifierContract: {}", e))?
+                    ._0;
+                let verifier = ISP1Verifier::new(verifier_address, contract.provider());
+                match verifier
+                    .verifyProof(
+                        verification_key,
+                        proof.public_values.clone().into(),
+                        proof.solidity_proof.clone().into(),
+                    )
+                    .call()
+                    .await
+                {
+                    Ok(_) => Ok(true),
+                    // the verifier reverts on an invalid proof
+                    Err(alloy::contract::Error::TransportError(RpcError::ErrorResp(_))) => {
+                        Ok(false)
+                    }
+                    Err(e) => Err(hyper_err!(Evm, "Failed to call verifyProof: {}", e)),
+                }
+            })
+            .await
+    }
+}
+
+#[derive(Clone)]
+struct WorkerApiState {
+    board: Arc<Mutex<JobBoard>>,
+    config: RemoteProverConfig,
+    verifier: Arc<dyn ProofVerifier>,
+}
+
+// rejects requests that don't carry the expected bearer token, the admin API is guarded by it too
+pub(crate) async fn require_token(
+    State(expected): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if tokens_match(token, &expected) => next.run(request).await,
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+// compares every byte so the time taken doesn't leak how much of the token was right
+fn tokens_match(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn lease_job(State(state): State<WorkerApiState>) -> Response {
+    let leased = state
+        .board
+        .lock()
+        .unwrap()
+        .lease(Instant::now(), &state.config);
+    match leased {
+        Some(leased) => {
+            info!(
+                "Leased {} proof job with lease {}",
+                leased.request.kind(),
+                leased.lease_id
+            );
+            Json(leased).into_response()
+        }
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+async fn heartbeat(State(state): State<WorkerApiState>, Path(lease_id): Path<u64>) -> StatusCode {
+    let held = state
+        .board
+        .lock()
+        .unwrap()
+        .heartbeat(lease_id, Instant::now(), &state.config);
+    if held {
+        StatusCode::OK
+    } else {
+        StatusCode::GONE
+    }
+}
+
+async fn submit_result(
+    State(state): State<WorkerApiState>,
+    Path(lease_id): Path<u64>,
+    Json(mut result): Json<WorkerResult>,
+) -> StatusCode {
+    if let WorkerResult::Proved(proof) = &result {
+        match state.verifier.verify(proof).await {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Proof submitted with lease {} does not verify", lease_id);
+                result = WorkerResult::Failed("proof does not verify".to_string());
+            }
+            // the worker retries the submission
+            Err(e) => {
+                warn!("Failed to verify proof for lease {}: {}", lease_id, e);
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+        }
+    }
+    let held =
+        state
+            .board
+            .lock()
+            .unwrap()
+            .complete(lease_id, result, Instant::now(), &state.config);
+    if held {
+        StatusCode::OK
+    } else {
+        StatusCode::GONE
+    }
+}
+
+// Proves by queueing jobs for the workers polling this node's worker API
+pub struct RemoteProver {
+    board: Arc<Mutex<JobBoard>>,
+    config: RemoteProverConfig,
+    address: SocketAddr,
+}
+
+impl RemoteProver {
+    // binds `address` and serves the worker API in the background, workers have to present `token`
+    pub async fn serve(
+        address: SocketAddr,
+        config: RemoteProverConfig,
+        token: &str,
+        verifier: Arc<dyn ProofVerifier>,
+    ) -> Result<Self> {
+        let board = Arc::new(Mutex::new(JobBoard::default()));
+        let state = WorkerApiState {
+            board: Arc::clone(&board),
+            config,
+            verifier,
+        };
+        let router = Router::new()
+            .route("/jobs/lease", post(lease_job))
+            .route("/leases/:lease_id/heartbeat", post(heartbeat))
+            .route("/leases/:lease_id/result", post(submit_result))
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::<str>::from(token),
+                require_token,
+            ))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind(address).await.map_err(|e| {
+            hyper_err!(
+                Config,
+                "Failed to bind prover worker API to {}: {}",
+                address,
+                e
+            )
+        })?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| hyper_err!(Config, "Failed to read prover worker API address: {}", e))?;
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                error!("Prover worker API stopped: {}", e);
+            }
+        });
+        Ok(RemoteProver {
+            board,
+            config,
+            address,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+#[async_trait]
+impl Prover for RemoteProver {
+    async fn prove(&self, request: ProofRequest) -> Result<Proof> {
+        let kind = request.kind();
+        let (job_id, receiver) = self.board.lock().unwrap().submit(request);
+        info!("Queued {} proof job {} for prover workers", kind, job_id);
+
+        match tokio::time::timeout(self.config.job_timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(hyper_err!(
+                ProofGeneration,
+                "Proof job {} was dropped",
+                job_id
+            )),
+            Err(_) => {
+                self.board.lock().unwrap().jobs.remove(&job_id);
+                Err(hyper_err!(
+                    ProofGeneration,
+                    "Proof job {} timed out after {:?}",
+                    job_id,
+                    self.config.job_timeout
+                ))
+            }
+        }
+    }
+}
+
+// Pulls jobs from the worker API at `coordinator_url` and proves them with `prover`, forever
+pub async fn run_worker(
+    coordinator_url: &str,
+    token: &str,
+    prover: Arc<dyn Prover>,
+    poll_interval: Duration,
+) -> Result<()> {
+    let client = Client::new();
+    let coordinator_url = coordinator_url.trim_end_matches('/').to_string();
+
+    loop {
+        let leased = match request_lease(&client, &coordinator_url, token).await {
+            Ok(Some(leased)) => leased,
+            Ok(None) => {
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to lease a proof job: {}", e);
+                tokio::time::sleep(poll_interval).await;
+                continue;
+            }
+        };
+        let lease_id = leased.lease_id;
+        let lease_duration = Duration::from_secs(leased.lease_duration_secs);
+        info!(
+            "Proving {} job with lease {}",
+            leased.request.kind(),
+            lease_id
+        );
+
+        let heartbeats = tokio::spawn(send_heartbeats(
+            client.clone(),
+            coordinator_url.clone(),
+            token.to_string(),
+            lease_id,
+            (lease_duration / 3).max(Duration::from_secs(1)),
+        ));
+        let result = match prover.prove(leased.request).await {
+            Ok(proof) => WorkerResult::Proved(proof),
+            Err(e) => {
+                error!("Failed to prove job with lease {}: {}", lease_id, e);
+                WorkerResult::Failed(e.to_string())
+            }
+        };
+        heartbeats.abort();
+
+        let backoff = ExponentialBackoff {
+            max_elapsed_time: Some(lease_duration),
+            ..Default::default()
+        };
+        let submitted = backoff::future::retry(backoff, || async {
+            let response = client
+                .post(format!("{}/leases/{}/result", coordinator_url, lease_id))
+                .bearer_auth(token)
+                .json(&result)
+                .send()
+                .await
+                .map_err(backoff::Error::transient)?;
+            if response.status() == StatusCode::GONE {
+                return Err(backoff::Error::permanent(
+                    response.error_for_status().unwrap_err(),
+                ));
+            }
+            response
+                .error_for_status()
+                .map_err(backoff::Error::transient)?;
+            Ok(())
+        })
+        .await;
+        match submitted {
+            Ok(()) => info!("Submitted result for lease {}", lease_id),
+            Err(e) => error!("Failed to submit result for lease {}: {}", lease_id, e),
+        }
+    }
+}
+
+async fn request_lease(
+    client: &Client,
+    coordinator_url: &str,
+    token: &str,
+) -> Result<Option<LeasedJob>> {
+    let response = client
+        .post(format!("{}/jobs/lease", coordinator_url))
+        .bearer_auth(token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| hyper_err!(Connection, "Failed to request a lease: {}", e))?;
+    if response.status() == StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+    let leased = response
+        .json::<LeasedJob>()
+        .await
+        .map_err(|e| hyper_err!(Decode, "Failed to decode leased job: {}", e))?;
+    Ok(Some(leased))
+}
+
+async fn send_heartbeats(
+    client: Client,
+    coordinator_url: String,
+    token: String,
+    lease_id: u64,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match client
+            .post(format!("{}/leases/{}/heartbeat", coordinator_url, lease_id))
+            .bearer_auth(&token)
+            .send()
+            .await
+        {
+            Ok(response) if response.status() == StatusCode::GONE => {
+                warn!("Lease {} was lost", lease_id);
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to send heartbeat for lease {}: {}", lease_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: RemoteProverConfig = RemoteProverConfig {
+        lease_duration: Duration::from_secs(60),
+        job_timeout: Duration::from_secs(30),
+        max_attempts: 2,
+    };
+
+    fn test_request() -> ProofRequest {
+        ProofRequest::AggregatedBlockProof(vec![])
+    }
+
+    fn test_proof(solidity_proof: &[u8]) -> Proof {
+        Proof {
+            solidity_proof: solidity_proof.to_vec(),
+            public_values: vec![1, 2, 3],
+        }
+    }
+
+    // proves every job with the same proof bytes
+    struct MockWorkerProver(&'static [u8]);
+
+    #[async_trait]
+    impl Prover for MockWorkerProver {
+        async fn prove(&self, _request: ProofRequest) -> Result<Proof> {
+            Ok(test_proof(self.0))
+        }
+    }
+
+    // only "bad" proofs fail to verify
+    struct MockVerifier;
+
+    #[async_trait]
+    impl ProofVerifier for MockVerifier {
+        async fn verify(&self, proof: &Proof) -> Result<bool> {
+            Ok(proof.solidity_proof != b"bad")
+        }
+    }
+
+    #[test]
+    fn test_job_board_lease() {
+        let mut board = JobBoard::default();
+        let now = Instant::now();
+        assert!(board.lease(now, &CONFIG).is_none());
+        let (first_job_id, mut receiver) = board.submit(test_request());
+        let (second_job_id, _) = board.submit(test_request());
+
+        // the oldest job is leased first, and a leased job isn't leased again
+        let first = board.lease(now, &CONFIG).unwrap();
+        assert_eq!(board.find_lease(first.lease_id), Some(first_job_id));
+        let second = board.lease(now, &CONFIG).unwrap();
+        assert_eq!(board.find_lease(second.lease_id), Some(second_job_id));
+        assert!(board.lease(now, &CONFIG).is_none());
+
+        let proved = WorkerResult::Proved(test_proof(b"proof"));
+        assert!(board.complete(first.lease_id, proved, now, &CONFIG));
+        assert!(matches!(receiver.try_recv(), Ok(Ok(proof)) if proof.solidity_proof == b"proof"));
+        // a lease completes its job once
+        let proved = WorkerResult::Proved(test_proof(b"proof"));
+        assert!(!board.complete(first.lease_id, proved, now, &CONFIG));
+    }
+
+    #[test]
+    fn test_job_board_heartbeat_expiry() {
+        let mut board = JobBoard::default();
+        let now = Instant::now();
+        board.submit(test_request());
+        let lease = board.lease(now, &CONFIG).unwrap();
+
+        // a heartbeat keeps the lease past its original expiry
+        assert!(board.heartbeat(lease.lease_id, now + CONFIG.lease_duration / 2, &CONFIG));
+        assert!(board.lease(now + CONFIG.lease_duration, &CONFIG).is_none());
+
+        // once the worker goes silent for a lease duration the job is leased to another worker
+        let expired = now + CONFIG.lease_duration * 2;
+        let re_lease = board.lease(expired, &CONFIG).unwrap();
+        assert_ne!(re_lease.lease_id, lease.lease_id);
+        assert!(!board.heartbeat(lease.lease_id, expired, &CONFIG));
+        let proved = WorkerResult::Proved(test_proof(b"proof"));
+        assert!(!board.complete(lease.lease_id, proved, expired, &CONFIG));
+        assert!(board.heartbeat(re_lease.lease_id, expired, &CONFIG));
+    }
+
+    #[test]
+    fn test_job_board_max_attempts() {
+        let mut board = JobBoard::default();
+        let now = Instant::now();
+        let (_, mut receiver) = board.submit(test_request());
+
+        let lease = board.lease(now, &CONFIG).unwrap();
+        let failed = WorkerResult::Failed("out of memory".to_string());
+        assert!(board.complete(lease.lease_id, failed, now, &CONFIG));
+        assert!(receiver.try_recv().is_err());
+
+        // the last attempt expiring fails the job
+        board.lease(now, &CONFIG).unwrap();
+        assert!(board.lease(now + CONFIG.lease_duration, &CONFIG).is_none());
+        assert!(matches!(receiver.try_recv(), Ok(Err(_))));
+        assert!(board.jobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remote_prover() {
+        let prover = RemoteProver::serve(
+            "127.0.0.1:0".parse().unwrap(),
+            CONFIG,
+            "secret",
+            Arc::new(MockVerifier),
+        )
+        .await
+        .unwrap();
+        let url = format!("http://{}", prover.address());
+
+        // workers without the token are turned away
+        let client = Client::new();
+        for token in [None, Some("wrong")] {
+            let mut request = client.post(format!("{}/jobs/lease", url));
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let spawn_worker = |solidity_proof: &'static [u8]| {
+            let url = url.clone();
+            tokio::spawn(async move {
+                run_worker(
+                    &url,
+                    "secret",
+                    Arc::new(MockWorkerProver(solidity_proof)),
+                    Duration::from_millis(10),
+                )
+                .await
+            })
+        };
+
+        // proofs that don't verify use up the job's attempts
+        let bad_worker = spawn_worker(b"bad");
+        assert!(prover.prove(test_request()).await.is_err());
+        bad_worker.abort();
+
+        let worker = spawn_worker(b"proof");
+        let proof = prover.prove(test_request()).await.unwrap();
+        assert_eq!(proof.solidity_proof, b"proof");
+        worker.abort();
+    }
+}
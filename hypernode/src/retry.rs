@@ -0,0 +1,133 @@
+// Retries for failed proof generation and broadcast jobs, jobs that fail permanently or run out of
+// attempts end up in the dead letters for an operator to look at
+use crate::core::ThreadSafeStore;
+use crate::error::HypernodeError;
+use crate::telemetry::DEAD_LETTERS;
+use crate::Result;
+use alloy::primitives::U256;
+use log::error;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn should_retry(&self, error: &HypernodeError, attempts: u32) -> bool {
+        error.is_transient() && attempts < self.max_attempts
+    }
+
+    // exponential backoff after `attempts` failed attempts, jittered between half and the full
+    // delay so jobs that failed together don't retry together
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_delay);
+        delay / 2 + (delay / 2).mul_f64(rand::random::<f64>())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub queue: String,
+    // unset for block proofs
+    pub reservation_id: Option<U256>,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: i64,
+}
+
+// kept in the store, so they survive a restart when it is backed by a database
+pub struct DeadLetters(Arc<ThreadSafeStore>);
+
+impl DeadLetters {
+    pub fn new(store: Arc<ThreadSafeStore>) -> Self {
+        DeadLetters(store)
+    }
+
+    pub async fn push(
+        &self,
+        queue: &'static str,
+        reservation_id: Option<U256>,
+        attempts: u32,
+        error: &HypernodeError,
+    ) {
+        counter!(DEAD_LETTERS, "queue" => queue).increment(1);
+        let dead_letter = DeadLetter {
+            queue: queue.to_string(),
+            reservation_id,
+            attempts,
+            error: error.to_string(),
+            failed_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = self
+            .0
+            .with_lock(|store| store.push_dead_letter(dead_letter))
+            .await
+        {
+            error!("Failed to store dead letter: {}", e);
+        }
+    }
+
+    pub async fn list(&self) -> Vec<DeadLetter> {
+        self.0
+            .with_lock(|store| store.dead_letters().to_vec())
+            .await
+    }
+
+    pub async fn clear(&self) -> Result<()> {
+        self.0.with_lock(|store| store.clear_dead_letters()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyper_err;
+
+    fn test_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = test_policy();
+        for (attempts, full_delay) in [(1, 10), (2, 20), (3, 40), (4, 60), (40, 60)] {
+            let full_delay = Duration::from_secs(full_delay);
+            for _ in 0..100 {
+                let delay = policy.delay(attempts);
+                assert!(delay >= full_delay / 2 && delay <= full_delay);
+            }
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = test_policy();
+        let transient = hyper_err!(Connection, "connection reset");
+        assert!(policy.should_retry(&transient, 1));
+        assert!(policy.should_retry(&hyper_err!(Evm, "nonce too low"), 2));
+        assert!(!policy.should_retry(&transient, 3));
+
+        for permanent in [
+            hyper_err!(Reverted, "NotApprovedHypernode"),
+            hyper_err!(ProofGeneration, "Payment can't be proven"),
+            hyper_err!(Decode, "invalid public inputs"),
+            hyper_err!(Indexer, "reservation not found"),
+            hyper_err!(Unknown, "unexpected"),
+        ] {
+            assert!(!policy.should_retry(&permanent, 1));
+        }
+    }
+}
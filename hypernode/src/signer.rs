@@ -0,0 +1,246 @@
+// Where the hypernode's EVM key is held: a raw private key, an encrypted JSON keystore, or a remote
+// signer speaking the Web3Signer eth1 API:
+//   GET  /api/v1/eth1/publicKeys          -> hex encoded public keys the signer holds
+//   POST /api/v1/eth1/sign/{address}      -> hex encoded signature over keccak256 of {"data": hex}
+// Every provider signs through the wallet built here
+use crate::error::HypernodeError;
+use crate::{hyper_err, Result};
+use alloy::consensus::SignableTransaction;
+use alloy::network::{EthereumWallet, TxSigner};
+use alloy::primitives::{Address, Signature};
+use alloy::signers::local::PrivateKeySigner;
+use async_trait::async_trait;
+use log::info;
+use reqwest::Client;
+use std::path::Path;
+use std::str::FromStr;
+
+pub enum SignerBackend<'a> {
+    PrivateKey(&'a str),
+    Keystore {
+        path: &'a Path,
+        password_file: &'a Path,
+    },
+    Remote {
+        url: &'a str,
+        address: &'a str,
+    },
+}
+
+impl SignerBackend<'_> {
+    pub async fn wallet(&self) -> Result<EthereumWallet> {
+        match self {
+            SignerBackend::PrivateKey(private_key_hex) => {
+                let signer = PrivateKeySigner::from_str(private_key_hex)
+                    .map_err(|e| hyper_err!(Parse, "Failed to decode private key: {}", e))?;
+                Ok(EthereumWallet::from(signer))
+            }
+            SignerBackend::Keystore {
+                path,
+                password_file,
+            } => {
+                let password = std::fs::read_to_string(password_file).map_err(|e| {
+                    hyper_err!(
+                        Config,
+                        "Failed to read keystore password file {}: {}",
+                        password_file.display(),
+                        e
+                    )
+                })?;
+                let signer = PrivateKeySigner::decrypt_keystore(path, password.trim_end())
+                    .map_err(|e| {
+                        hyper_err!(
+                            Config,
+                            "Failed to decrypt keystore {}: {}",
+                            path.display(),
+                            e
+                        )
+                    })?;
+                info!("Loaded signer {} from keystore", signer.address());
+                Ok(EthereumWallet::from(signer))
+            }
+            SignerBackend::Remote { url, address } => {
+                let address = Address::from_str(address).map_err(|e| {
+                    hyper_err!(Parse, "Failed to parse remote signer address: {}", e)
+                })?;
+                let signer = RemoteSigner::connect(url, address).await?;
+                info!("Using remote signer {} at {}", address, url);
+                Ok(EthereumWallet::new(signer))
+            }
+        }
+    }
+}
+
+pub struct RemoteSigner {
+    client: Client,
+    url: String,
+    address: Address,
+}
+
+impl RemoteSigner {
+    // fails unless the signer holds the key for `address`
+    pub async fn connect(url: &str, address: Address) -> Result<Self> {
+        let url = url.trim_end_matches('/').to_string();
+        let client = Client::new();
+        let public_keys = client
+            .get(format!("{}/api/v1/eth1/publicKeys", url))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| hyper_err!(Connection, "Failed to reach remote signer: {}", e))?
+            .json::<Vec<String>>()
+            .await
+            .map_err(|e| hyper_err!(Decode, "Failed to decode remote signer keys: {}", e))?;
+
+        let held = public_keys.iter().any(|public_key| {
+            hex::decode(public_key.trim_start_matches("0x"))
+                .ok()
+                .and_then(|public_key| {
+                    // uncompressed keys may come with or without the 0x04 prefix
+                    let public_key = match public_key.len() {
+                        65 => public_key[1..].to_vec(),
+                        _ => public_key,
+                    };
+                    (public_key.len() == 64).then(|| Address::from_raw_public_key(&public_key))
+                })
+                .is_some_and(|key_address| key_address == address)
+        });
+        if !held {
+            return Err(hyper_err!(
+                Config,
+                "Remote signer at {} does not hold a key for {}",
+                url,
+                address
+            ));
+        }
+
+        Ok(RemoteSigner {
+            client,
+            url,
+            address,
+        })
+    }
+
+    async fn sign(&self, data: &[u8]) -> alloy::signers::Result<Signature> {
+        let signature = self
+            .client
+            .post(format!("{}/api/v1/eth1/sign/{}", self.url, self.address))
+            .json(&serde_json::json!({ "data": format!("0x{}", hex::encode(data)) }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(alloy::signers::Error::other)?
+            .text()
+            .await
+            .map_err(alloy::signers::Error::other)?;
+        let signature = hex::decode(signature.trim().trim_matches('"').trim_start_matches("0x"))
+            .map_err(alloy::signers::Error::other)?;
+        Signature::try_from(signature.as_slice()).map_err(alloy::signers::Error::other)
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        let mut signature = self.sign(&tx.encoded_for_signing()).await?;
+        // a signer that signed something other than this transaction would get it rejected onchain
+        let signed_by = signature
+            .recover_address_from_prehash(&tx.signature_hash())
+            .map_err(alloy::signers::Error::other)?;
+        if signed_by != self.address {
+            return Err(alloy::signers::Error::other(format!(
+                "Remote signer signed with {} instead of {}",
+                signed_by, self.address
+            )));
+        }
+        // legacy transactions carry the chain id in v
+        if let Some(chain_id) = tx.chain_id().filter(|_| tx.use_eip155()) {
+            signature = signature.with_chain_id(chain_id);
+        }
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::consensus::TxEip1559;
+    use alloy::primitives::keccak256;
+    use alloy::signers::SignerSync;
+    use axum::extract::State;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::sync::Arc;
+
+    // serves the Web3Signer eth1 endpoints for one local key
+    async fn serve_mock_signer(signer: PrivateKeySigner) -> String {
+        let signer = Arc::new(signer);
+        let app = Router::new()
+            .route(
+                "/api/v1/eth1/publicKeys",
+                get(|State(signer): State<Arc<PrivateKeySigner>>| async move {
+                    let public_key = signer.credential().verifying_key().to_encoded_point(false);
+                    Json(vec![format!("0x{}", hex::encode(public_key.as_bytes()))])
+                }),
+            )
+            .route(
+                "/api/v1/eth1/sign/:address",
+                post(
+                    |State(signer): State<Arc<PrivateKeySigner>>,
+                     Json(body): Json<serde_json::Value>| async move {
+                        let data =
+                            hex::decode(body["data"].as_str().unwrap().trim_start_matches("0x"))
+                                .unwrap();
+                        let signature = signer.sign_hash_sync(&keccak256(data)).unwrap();
+                        format!("0x{}", hex::encode(signature.as_bytes()))
+                    },
+                ),
+            )
+            .with_state(signer);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_signs_transactions() {
+        let local_signer = PrivateKeySigner::random();
+        let address = local_signer.address();
+        let url = serve_mock_signer(local_signer).await;
+
+        let remote_signer = RemoteSigner::connect(&url, address).await.unwrap();
+        let mut tx = TxEip1559 {
+            chain_id: 1,
+            nonce: 7,
+            gas_limit: 21000,
+            max_fee_per_gas: 20_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            ..Default::default()
+        };
+        let signature = remote_signer.sign_transaction(&mut tx).await.unwrap();
+        assert_eq!(
+            signature
+                .recover_address_from_prehash(&tx.signature_hash())
+                .unwrap(),
+            address
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_without_key() {
+        let url = serve_mock_signer(PrivateKeySigner::random()).await;
+        assert!(
+            RemoteSigner::connect(&url, PrivateKeySigner::random().address())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// Prometheus metrics, served over http when the hypernode is started with a metrics address.
+// Without an installed exporter the metric macros are no-ops.
+use crate::core::Store;
+use crate::error::HypernodeError;
+use crate::{hyper_err, Result};
+use metrics::{describe_counter, describe_gauge, describe_histogram, gauge, Unit};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use std::net::SocketAddr;
+
+pub const BTC_TIP_HEIGHT: &str = "hypernode_btc_tip_height";
+pub const BTC_INDEXED_HEIGHT: &str = "hypernode_btc_indexed_height";
+pub const BTC_BLOCK_NOTIFICATIONS: &str = "hypernode_btc_block_notifications_total";
+pub const BTC_PENDING_PAYMENTS: &str = "hypernode_btc_pending_payments";
+pub const BTC_REJECTED_PAYMENTS: &str = "hypernode_btc_rejected_payments_total";
+pub const EVM_INDEXED_HEIGHT: &str = "hypernode_evm_indexed_height";
+// labelled by the `endpoint`'s index in the configured list
+pub const EVM_ENDPOINT_HEIGHT: &str = "hypernode_evm_endpoint_height";
+pub const EVM_ENDPOINT_FAILOVERS: &str = "hypernode_evm_endpoint_failovers_total";
+pub const CONTRACT_BTC_HEIGHT: &str = "hypernode_contract_btc_height";
+pub const CONTRACT_BTC_LAG: &str = "hypernode_contract_btc_lag_blocks";
+pub const PROOF_GENERATION_QUEUE_DEPTH: &str = "hypernode_proof_generation_queue_depth";
+pub const PROOF_BROADCAST_QUEUE_DEPTH: &str = "hypernode_proof_broadcast_queue_depth";
+pub const EVM_BLOCK_TRIGGER_QUEUE_DEPTH: &str = "hypernode_evm_block_trigger_queue_depth";
+// labelled by `kind`, either reservation or block
+pub const PROOF_DURATION: &str = "hypernode_proof_duration_seconds";
+pub const PROOF_CYCLES: &str = "hypernode_proof_cycles";
+// labelled by the contract `function` called
+pub const BROADCASTS: &str = "hypernode_broadcasts_total";
+pub const BROADCAST_FAILURES: &str = "hypernode_broadcast_failures_total";
+pub const TX_FEE_BUMPS: &str = "hypernode_tx_fee_bumps_total";
+pub const TX_REVERTS: &str = "hypernode_tx_reverts_total";
+pub const PENDING_TRANSACTIONS: &str = "hypernode_pending_transactions";
+pub const BATCH_SIZE: &str = "hypernode_batch_size";
+// also labelled by the `decision` taken
+pub const UNPROFITABLE_CALLS: &str = "hypernode_unprofitable_calls_total";
+// labelled by the `queue` the job was in
+pub const JOB_RETRIES: &str = "hypernode_job_retries_total";
+pub const DEAD_LETTERS: &str = "hypernode_dead_letters_total";
+pub const STORE_RESERVATIONS: &str = "hypernode_store_reservations";
+pub const STORE_SAFE_BLOCK_HASHES: &str = "hypernode_store_safe_block_hashes";
+pub const STORE_BTC_BLOCK_HASHES: &str = "hypernode_store_btc_block_hashes";
+
+pub fn install_exporter(address: SocketAddr) -> Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(address)
+        .install()
+        .map_err(|e| hyper_err!(Config, "Failed to start metrics exporter: {}", e))?;
+
+    describe_gauge!(BTC_TIP_HEIGHT, "Height of the bitcoin node's best chain");
+    describe_gauge!(BTC_INDEXED_HEIGHT, "Last bitcoin block analyzed");
+    describe_counter!(
+        BTC_BLOCK_NOTIFICATIONS,
+        "New block notifications received over zmq"
+    );
+    describe_gauge!(
+        BTC_PENDING_PAYMENTS,
+        "Reservation payments in the mempool that aren't mined yet"
+    );
+    describe_counter!(
+        BTC_REJECTED_PAYMENTS,
+        "Mined reservation payments that would fail the circuit's checks"
+    );
+    describe_gauge!(
+        EVM_INDEXED_HEIGHT,
+        "Last evm block with a processed exchange event"
+    );
+    describe_gauge!(EVM_ENDPOINT_HEIGHT, "Latest block of each evm endpoint");
+    describe_counter!(
+        EVM_ENDPOINT_FAILOVERS,
+        "Times calls were moved to another evm endpoint"
+    );
+    describe_gauge!(
+        CONTRACT_BTC_HEIGHT,
+        "Last bitcoin block known to the contract"
+    );
+    describe_gauge!(
+        CONTRACT_BTC_LAG,
+        "Bitcoin blocks the contract is behind the bitcoin node's tip"
+    );
+    describe_gauge!(
+        PROOF_GENERATION_QUEUE_DEPTH,
+        "Proofs waiting to be generated"
+    );
+    describe_gauge!(
+        PROOF_BROADCAST_QUEUE_DEPTH,
+        "Proofs waiting to be broadcast"
+    );
+    describe_gauge!(
+        EVM_BLOCK_TRIGGER_QUEUE_DEPTH,
+        "Reservations waiting for their liquidity to unlock"
+    );
+    describe_histogram!(PROOF_DURATION, Unit::Seconds, "Time to generate a proof");
+    describe_histogram!(PROOF_CYCLES, Unit::Count, "Cycles executed by the circuit");
+    describe_counter!(BROADCASTS, "Contract calls broadcast");
+    describe_counter!(
+        BROADCAST_FAILURES,
+        "Contract calls that failed to broadcast"
+    );
+    describe_counter!(
+        TX_FEE_BUMPS,
+        "Pending transactions replaced with higher fees"
+    );
+    describe_counter!(TX_REVERTS, "Contract calls that reverted");
+    describe_counter!(
+        UNPROFITABLE_CALLS,
+        "Contract calls that would cost more in gas than the protocol fee they earn"
+    );
+    describe_gauge!(
+        PENDING_TRANSACTIONS,
+        "Transactions broadcast but not yet confirmed"
+    );
+    describe_histogram!(BATCH_SIZE, Unit::Count, "Contract calls sent in one batch");
+    describe_counter!(JOB_RETRIES, "Failed jobs scheduled for another attempt");
+    describe_counter!(
+        DEAD_LETTERS,
+        "Jobs given up on after a permanent failure or running out of attempts"
+    );
+    describe_gauge!(STORE_RESERVATIONS, "Reservations tracked in the store");
+    describe_gauge!(
+        STORE_SAFE_BLOCK_HASHES,
+        "Contract block hashes tracked in the store"
+    );
+    describe_gauge!(
+        STORE_BTC_BLOCK_HASHES,
+        "Analyzed bitcoin block hashes tracked in the store"
+    );
+    Ok(())
+}
+
+pub fn record_store_sizes(store: &Store) {
+    gauge!(STORE_RESERVATIONS).set(store.reservations().len() as f64);
+    gauge!(STORE_SAFE_BLOCK_HASHES).set(store.safe_contract_block_hashes().len() as f64);
+    gauge!(STORE_BTC_BLOCK_HASHES).set(store.btc_block_hashes().len() as f64);
+}
@@ -0,0 +1,501 @@
+// Sends the hypernode's transactions and follows them until they are confirmed. Nonces are handed
+// out here so submitSwapProof, proveBlocks and releaseLiquidity never race for one, and
+// transactions that stay pending are replaced with higher EIP-1559 fees
+use crate::core::EvmHttpProvider;
+use crate::error::HypernodeError;
+use crate::evm_endpoints::EvmEndpoints;
+use crate::telemetry::{PENDING_TRANSACTIONS, TX_FEE_BUMPS, TX_REVERTS};
+use crate::{hyper_err, Result};
+use alloy::eips::BlockId;
+use alloy::network::eip2718::Encodable2718;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{keccak256, Address, Bytes, FixedBytes, U256};
+use alloy::providers::{Provider, WalletProvider};
+use alloy::rpc::types::{TransactionInput, TransactionReceipt, TransactionRequest};
+use alloy::transports::{RpcError, TransportError};
+use bitcoin::hex::DisplayHex;
+use log::{info, warn};
+use metrics::{counter, gauge};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug)]
+pub struct TxManagerConfig {
+    // blocks on top of (and including) the one a transaction was mined in before it is final
+    pub confirmations: u64,
+    // how long a transaction can stay pending before it is replaced with higher fees
+    pub bump_interval: Duration,
+    pub fee_bump_percent: u64,
+    pub max_fee_per_gas: Option<u128>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PendingTransaction {
+    pub function_name: &'static str,
+    pub nonce: u64,
+    // every version of the transaction that was broadcast, the last one has the highest fees
+    pub tx_hashes: Vec<FixedBytes<32>>,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+pub struct TxManager {
+    endpoints: Arc<EvmEndpoints>,
+    flashbots_provider: Arc<Option<EvmHttpProvider>>,
+    config: TxManagerConfig,
+    // fetched from the chain when unset, and held while a transaction is first broadcast so a
+    // nonce is only used up by a transaction that made it to the mempool
+    next_nonce: tokio::sync::Mutex<Option<u64>>,
+    pending: Mutex<BTreeMap<u64, PendingTransaction>>,
+}
+
+impl TxManager {
+    pub fn new(
+        endpoints: Arc<EvmEndpoints>,
+        flashbots_provider: Arc<Option<EvmHttpProvider>>,
+        config: TxManagerConfig,
+    ) -> Self {
+        TxManager {
+            endpoints,
+            flashbots_provider,
+            config,
+            next_nonce: tokio::sync::Mutex::new(None),
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // transactions broadcast but not yet confirmed, in nonce order
+    pub fn pending(&self) -> Vec<PendingTransaction> {
+        self.pending.lock().unwrap().values().cloned().collect()
+    }
+
+    fn address(&self) -> Address {
+        self.endpoints
+            .contract()
+            .provider()
+            .wallet()
+            .default_signer()
+            .address()
+    }
+
+    // gas cost in wei of a call at the current gas price
+    pub async fn estimate_cost(
+        &self,
+        to: Address,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+    ) -> Result<U256> {
+        let contract = self.endpoints.contract();
+        let provider = contract.provider();
+        let gas = match provider.estimate_gas(&self.call(to, txn_calldata)).await {
+            Ok(gas) => gas,
+            Err(e) => return Err(self.call_failed(to, function_name, txn_calldata, e).await),
+        };
+        let gas_price = provider
+            .get_gas_price()
+            .await
+            .map_err(|e| hyper_err!(Evm, "Failed to get gas price: {}", e))?;
+        Ok(U256::from(gas) * U256::from(gas_price))
+    }
+
+    fn call(&self, to: Address, txn_calldata: &[u8]) -> TransactionRequest {
+        TransactionRequest::default()
+            .from(self.address())
+            .to(to)
+            .input(TransactionInput::new(txn_calldata.to_vec().into()))
+    }
+
+    // sends a call and waits until it is confirmed, a call that reverts (when estimating gas or
+    // once mined) fails with the decoded revert reason
+    pub async fn send(
+        &self,
+        to: Address,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+    ) -> Result<TransactionReceipt> {
+        let contract = self.endpoints.contract();
+        let provider = contract.provider();
+        let call = self.call(to, txn_calldata);
+
+        let gas_limit = match provider.estimate_gas(&call).await {
+            Ok(gas_limit) => gas_limit,
+            Err(e) => return Err(self.call_failed(to, function_name, txn_calldata, e).await),
+        };
+        let fees = provider
+            .estimate_eip1559_fees(None)
+            .await
+            .map_err(|e| hyper_err!(Evm, "Failed to estimate fees: {}", e))?;
+        let max_fee_per_gas = self.cap_fee(fees.max_fee_per_gas);
+        let max_priority_fee_per_gas = fees.max_priority_fee_per_gas.min(max_fee_per_gas);
+        let tx = call.clone().with_gas_limit(gas_limit);
+
+        let (nonce, tx_hash) = {
+            let mut next_nonce = self.next_nonce.lock().await;
+            let nonce = match *next_nonce {
+                Some(nonce) => nonce,
+                None => provider
+                    .get_transaction_count(self.address())
+                    .pending()
+                    .await
+                    .map_err(|e| hyper_err!(Evm, "Failed to get transaction count: {}", e))?,
+            };
+            let result = self
+                .broadcast(
+                    tx.clone()
+                        .with_nonce(nonce)
+                        .with_max_fee_per_gas(max_fee_per_gas)
+                        .with_max_priority_fee_per_gas(max_priority_fee_per_gas),
+                )
+                .await;
+            match result {
+                Ok(tx_hash) => {
+                    *next_nonce = Some(nonce + 1);
+                    (nonce, tx_hash)
+                }
+                Err(e) => {
+                    // refetched for the next transaction in case our nonce drifted from the chain
+                    *next_nonce = None;
+                    return Err(e);
+                }
+            }
+        };
+        info!(
+            "{} broadcast with nonce {} and evm tx hash: {}",
+            function_name, nonce, tx_hash
+        );
+
+        self.pending.lock().unwrap().insert(
+            nonce,
+            PendingTransaction {
+                function_name,
+                nonce,
+                tx_hashes: vec![tx_hash],
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            },
+        );
+        gauge!(PENDING_TRANSACTIONS).increment(1.0);
+        let result = self.track(function_name, &call, tx, nonce).await;
+        self.pending.lock().unwrap().remove(&nonce);
+        gauge!(PENDING_TRANSACTIONS).decrement(1.0);
+        result
+    }
+
+    // polls for a receipt of any version of the transaction until it has enough confirmations,
+    // replacing it with higher fees whenever it stays pending for the bump interval
+    async fn track(
+        &self,
+        function_name: &'static str,
+        call: &TransactionRequest,
+        tx: TransactionRequest,
+        nonce: u64,
+    ) -> Result<TransactionReceipt> {
+        let mut last_broadcast = Instant::now();
+        loop {
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+            let contract = self.endpoints.contract();
+            let provider = contract.provider();
+
+            if let Some(receipt) = self.find_receipt(nonce).await {
+                let Some(block_number) = receipt.block_number else {
+                    continue;
+                };
+                let tip = match provider.get_block_number().await {
+                    Ok(tip) => tip,
+                    Err(e) => {
+                        warn!("Failed to get block number: {}", e);
+                        continue;
+                    }
+                };
+                if tip + 1 < block_number + self.config.confirmations {
+                    continue;
+                }
+                if !receipt.status() {
+                    counter!(TX_REVERTS, "function" => function_name).increment(1);
+                    let reason = self.replay_revert_reason(call, block_number).await;
+                    return Err(hyper_err!(
+                        Reverted,
+                        "{} {} reverted: {}",
+                        function_name,
+                        receipt.transaction_hash,
+                        reason
+                    ));
+                }
+                info!(
+                    "{} {} confirmed in block {}",
+                    function_name, receipt.transaction_hash, block_number
+                );
+                return Ok(receipt);
+            }
+
+            if last_broadcast.elapsed() < self.config.bump_interval {
+                continue;
+            }
+            last_broadcast = Instant::now();
+
+            match provider
+                .get_transaction_count(self.address())
+                .latest()
+                .await
+            {
+                // one of our versions was mined since the receipt check, or something else used
+                // the nonce
+                Ok(mined_nonce) if mined_nonce > nonce => {
+                    if self.find_receipt(nonce).await.is_some() {
+                        continue;
+                    }
+                    *self.next_nonce.lock().await = None;
+                    return Err(hyper_err!(
+                        Evm,
+                        "Nonce {} of {} was used by another transaction",
+                        nonce,
+                        function_name
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to get transaction count: {}", e),
+            }
+            self.bump_fees(function_name, &tx, nonce).await;
+        }
+    }
+
+    async fn find_receipt(&self, nonce: u64) -> Option<TransactionReceipt> {
+        let tx_hashes = self
+            .pending
+            .lock()
+            .unwrap()
+            .get(&nonce)
+            .map(|pending| pending.tx_hashes.clone())
+            .unwrap_or_default();
+        let contract = self.endpoints.contract();
+        for tx_hash in tx_hashes.into_iter().rev() {
+            match contract.provider().get_transaction_receipt(tx_hash).await {
+                Ok(Some(receipt)) => return Some(receipt),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to get receipt for {}: {}", tx_hash, e),
+            }
+        }
+        None
+    }
+
+    async fn bump_fees(&self, function_name: &'static str, tx: &TransactionRequest, nonce: u64) {
+        let Some((max_fee_per_gas, max_priority_fee_per_gas)) = self
+            .pending
+            .lock()
+            .unwrap()
+            .get(&nonce)
+            .map(|pending| (pending.max_fee_per_gas, pending.max_priority_fee_per_gas))
+        else {
+            return;
+        };
+        if self
+            .config
+            .max_fee_per_gas
+            .is_some_and(|max_fee| max_fee_per_gas >= max_fee)
+        {
+            warn!(
+                "{} with nonce {} is pending at the max fee per gas, waiting for it to be mined",
+                function_name, nonce
+            );
+            return;
+        }
+
+        // nodes only accept a replacement that raises both fees, by 10% for geth
+        let bump = |fee: u128| fee * (100 + self.config.fee_bump_percent as u128) / 100 + 1;
+        let contract = self.endpoints.contract();
+        let (estimated_max_fee, estimated_priority_fee) =
+            match contract.provider().estimate_eip1559_fees(None).await {
+                Ok(fees) => (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+                Err(e) => {
+                    warn!("Failed to estimate fees: {}", e);
+                    (0, 0)
+                }
+            };
+        let max_fee_per_gas = self.cap_fee(bump(max_fee_per_gas).max(estimated_max_fee));
+        let max_priority_fee_per_gas = bump(max_priority_fee_per_gas)
+            .max(estimated_priority_fee)
+            .min(max_fee_per_gas);
+
+        let result = self
+            .broadcast(
+                tx.clone()
+                    .with_nonce(nonce)
+                    .with_max_fee_per_gas(max_fee_per_gas)
+                    .with_max_priority_fee_per_gas(max_priority_fee_per_gas),
+            )
+            .await;
+        match result {
+            Ok(tx_hash) => {
+                counter!(TX_FEE_BUMPS, "function" => function_name).increment(1);
+                info!(
+                    "{} with nonce {} replaced with max fee per gas {}: {}",
+                    function_name, nonce, max_fee_per_gas, tx_hash
+                );
+                if let Some(pending) = self.pending.lock().unwrap().get_mut(&nonce) {
+                    pending.tx_hashes.push(tx_hash);
+                    pending.max_fee_per_gas = max_fee_per_gas;
+                    pending.max_priority_fee_per_gas = max_priority_fee_per_gas;
+                }
+            }
+            // the previous version may have been mined in the meantime, which is picked up by the
+            // next receipt check
+            Err(e) => warn!(
+                "Failed to replace {} with nonce {}: {}",
+                function_name, nonce, e
+            ),
+        }
+    }
+
+    fn cap_fee(&self, max_fee_per_gas: u128) -> u128 {
+        self.config
+            .max_fee_per_gas
+            .map_or(max_fee_per_gas, |max_fee| max_fee_per_gas.min(max_fee))
+    }
+
+    async fn broadcast(&self, tx: TransactionRequest) -> Result<FixedBytes<32>> {
+        let contract = self.endpoints.contract();
+        let provider = contract.provider();
+        let Some(flashbots_provider) = self.flashbots_provider.as_ref() else {
+            return provider
+                .send_transaction(tx)
+                .await
+                .map(|pending| *pending.tx_hash())
+                .map_err(|e| hyper_err!(Evm, "Failed to send transaction: {}", e));
+        };
+
+        let tx = provider
+            .fill(tx)
+            .await
+            .map_err(|e| hyper_err!(Evm, "Failed to fill transaction: {}", e))?;
+
+        let tx_envelope = tx
+            .as_builder()
+            .unwrap()
+            .clone()
+            .build(&provider.wallet())
+            .await
+            .map_err(|e| hyper_err!(Evm, "Failed to build transaction envelope: {}", e))?;
+
+        let tx_encoded = tx_envelope.encoded_2718();
+        let pending = flashbots_provider
+            .send_raw_transaction(&tx_encoded)
+            .await
+            .map_err(|e| hyper_err!(Evm, "Failed to send raw transaction: {}", e))?
+            .register()
+            .await
+            .map_err(|e| hyper_err!(Evm, "Failed to register transaction: {}", e))?;
+
+        Ok(*pending.tx_hash())
+    }
+
+    async fn call_failed(
+        &self,
+        to: Address,
+        function_name: &'static str,
+        txn_calldata: &[u8],
+        error: TransportError,
+    ) -> HypernodeError {
+        let Some(revert_data) = revert_data(&error) else {
+            return hyper_err!(
+                Evm,
+                "Failed to estimate gas for {}: {}",
+                function_name,
+                error
+            );
+        };
+        counter!(TX_REVERTS, "function" => function_name).increment(1);
+        let contract = self.endpoints.contract();
+        if let Ok(block_height) = contract.provider().get_block_number().await {
+            info!(
+                "To debug failed {} run: cast call {} --data {} --trace --block {} --rpc-url {}",
+                function_name,
+                to,
+                txn_calldata.as_hex(),
+                block_height,
+                self.endpoints.active_url()
+            );
+        }
+        hyper_err!(
+            Reverted,
+            "{} reverted: {}",
+            function_name,
+            decode_revert(&revert_data)
+        )
+    }
+
+    // mined transactions don't carry a revert reason, so the call is replayed on the state it was
+    // mined on top of
+    async fn replay_revert_reason(&self, call: &TransactionRequest, block_number: u64) -> String {
+        match self
+            .endpoints
+            .contract()
+            .provider()
+            .call(call)
+            .block(BlockId::number(block_number.saturating_sub(1)))
+            .await
+        {
+            Ok(_) => "no revert when replayed".to_string(),
+            Err(e) => revert_data(&e)
+                .map(|revert_data| decode_revert(&revert_data))
+                .unwrap_or_else(|| e.to_string()),
+        }
+    }
+}
+
+fn revert_data(error: &TransportError) -> Option<Bytes> {
+    match error {
+        RpcError::ErrorResp(payload) => payload
+            .data
+            .as_ref()
+            .and_then(|data| serde_json::from_str(data.get()).ok()),
+        _ => None,
+    }
+}
+
+// names custom errors of the exchange contract, falling back to Error(string) and Panic(uint256)
+pub fn decode_revert(revert_data: &[u8]) -> String {
+    let custom_error = revert_data
+        .get(..4)
+        .and_then(|selector| <[u8; 4]>::try_from(selector).ok())
+        .and_then(|selector| exchange_errors().get(&selector))
+        .map(|signature| match &revert_data[4..] {
+            [] => signature.clone(),
+            args => format!("{} {}", signature, args.as_hex()),
+        });
+    custom_error
+        .or_else(|| alloy::sol_types::decode_revert_reason(revert_data))
+        .unwrap_or_else(|| format!("unknown revert data {}", revert_data.as_hex()))
+}
+
+// error signatures by selector, read from the same artifact the contract bindings are built from
+fn exchange_errors() -> &'static HashMap<[u8; 4], String> {
+    static EXCHANGE_ERRORS: OnceLock<HashMap<[u8; 4], String>> = OnceLock::new();
+    EXCHANGE_ERRORS.get_or_init(|| {
+        let artifact: serde_json::Value =
+            serde_json::from_str(include_str!("../artifacts/RiftExchange.json"))
+                .expect("RiftExchange artifact is valid json");
+        artifact["abi"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|item| item["type"] == "error")
+            .map(|error| {
+                let types = error["inputs"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|input| input["type"].as_str())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let signature = format!("{}({})", error["name"].as_str().unwrap_or(""), types);
+                let mut selector = [0u8; 4];
+                selector.copy_from_slice(&keccak256(signature.as_bytes())[..4]);
+                (selector, signature)
+            })
+            .collect()
+    })
+}